@@ -1,3 +1,130 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Section type of the ELF symbol table
+const SHT_SYMTAB: u32 = 2;
+/// Symbol type of functions
+const STT_FUNC: u8 = 2;
+
 fn main() {
     built::write_built_file().expect("Failed to acquire build-time information");
+    write_symbol_table();
+}
+
+/// Write a sorted table of all function symbols to 'symbols.rs' in the output directory (used to resolve backtraces).
+/// Symbols are read from the kernel ELF file given by `KERNEL_SYMBOLS`, which is produced by a first link pass (see 'Makefile.toml').
+/// Since the table is placed after the code, adding it does not move any functions in the second link pass.
+/// Without `KERNEL_SYMBOLS`, the table is empty.
+fn write_symbol_table() {
+    println!("cargo:rerun-if-env-changed=KERNEL_SYMBOLS");
+
+    let mut symbols = match env::var("KERNEL_SYMBOLS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            match fs::read(&path) {
+                Ok(elf) => read_function_symbols(&elf),
+                Err(_) => Vec::new()
+            }
+        }
+        _ => Vec::new()
+    };
+
+    symbols.sort_by_key(|symbol| symbol.0);
+    symbols.dedup_by_key(|symbol| symbol.0);
+
+    let mut table = String::from("pub static SYMBOLS: &[(u64, &str)] = &[\n");
+    for (address, name) in symbols {
+        table.push_str(&format!("    (0x{:x}, {:?}),\n", address, name));
+    }
+    table.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set");
+    fs::write(Path::new(&out_dir).join("symbols.rs"), table).expect("Failed to write symbol table");
+}
+
+/// Parse the symbol table of a 64-bit little endian ELF file and return address and (demangled) name of each function.
+fn read_function_symbols(elf: &[u8]) -> Vec<(u64, String)> {
+    let mut symbols = Vec::new();
+    if elf.len() < 64 || &elf[0..4] != b"\x7fELF" || elf[4] != 2 || elf[5] != 1 {
+        return symbols;
+    }
+
+    let section_offset = read_u64(elf, 0x28) as usize;
+    let section_size = read_u16(elf, 0x3a) as usize;
+    let section_count = read_u16(elf, 0x3c) as usize;
+    let section = |index: usize| &elf[section_offset + index * section_size..section_offset + (index + 1) * section_size];
+
+    for index in 0..section_count {
+        let header = section(index);
+        if read_u32(header, 0x04) != SHT_SYMTAB {
+            continue;
+        }
+
+        let symbol_data = &elf[read_u64(header, 0x18) as usize..(read_u64(header, 0x18) + read_u64(header, 0x20)) as usize];
+        let symbol_size = read_u64(header, 0x38) as usize;
+        let string_header = section(read_u32(header, 0x28) as usize);
+        let strings = &elf[read_u64(string_header, 0x18) as usize..(read_u64(string_header, 0x18) + read_u64(string_header, 0x20)) as usize];
+
+        for symbol in symbol_data.chunks_exact(symbol_size) {
+            let address = read_u64(symbol, 0x08);
+            if symbol[4] & 0x0f != STT_FUNC || address == 0 {
+                continue;
+            }
+
+            let name_start = read_u32(symbol, 0x00) as usize;
+            let name_end = name_start + strings[name_start..].iter().position(|&byte| byte == 0).unwrap_or(0);
+            let name = String::from_utf8_lossy(&strings[name_start..name_end]);
+            symbols.push((address, demangle(&name)));
+        }
+    }
+
+    return symbols;
+}
+
+/// Demangle legacy Rust symbols (e.g. '_ZN6kernel4boot4main17h0123456789abcdefE' -> 'kernel::boot::main').
+/// Other symbols are returned unchanged.
+fn demangle(symbol: &str) -> String {
+    let mut rest = match symbol.strip_prefix("_ZN").and_then(|rest| rest.strip_suffix('E')) {
+        Some(rest) => rest,
+        None => return symbol.to_string()
+    };
+
+    let mut path = Vec::new();
+    while !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let length = match rest[..digits].parse::<usize>() {
+            Ok(length) if digits + length <= rest.len() => length,
+            _ => return symbol.to_string()
+        };
+
+        let element = &rest[digits..digits + length];
+        rest = &rest[digits + length..];
+
+        // The last element is a hash of the crate and its dependencies
+        if rest.is_empty() && element.len() == 17 && element.starts_with('h') && element[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            break;
+        }
+
+        path.push(element.strip_prefix('_').filter(|element| element.starts_with('$')).unwrap_or(element)
+            .replace("$LT$", "<").replace("$GT$", ">").replace("$RF$", "&").replace("$BP$", "*")
+            .replace("$C$", ",").replace("$SP$", "@").replace("$u20$", " ").replace("$u27$", "'")
+            .replace("$u5b$", "[").replace("$u5d$", "]").replace("$u7b$", "{").replace("$u7d$", "}")
+            .replace("$u3b$", ";").replace("$u7e$", "~").replace("$u2b$", "+").replace("$u22$", "\"")
+            .replace("..", "::"));
+    }
+
+    return path.join("::");
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    return u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
 }
@@ -0,0 +1,131 @@
+use alloc::vec::Vec;
+use acpi::sdt::{SdtHeader, Signature};
+use acpi::AcpiTable;
+use core::mem::size_of;
+use core::ptr;
+use x86_64::PhysAddr;
+
+/// DMA Remapping Reporting table, as described in the Intel VT-d specification (chapter 8).
+/// It is followed by a variable number of remapping structures.
+#[repr(C, packed)]
+pub struct Dmar {
+    header: SdtHeader,
+    host_address_width: u8,
+    flags: u8,
+    reserved: [u8; 10]
+}
+
+#[repr(C, packed)]
+struct RemappingStructureHeader {
+    typ: u16,
+    length: u16
+}
+
+#[repr(C, packed)]
+struct HardwareUnitDefinition {
+    header: RemappingStructureHeader,
+    flags: u8,
+    size: u8,
+    segment: u16,
+    register_base: u64
+}
+
+#[repr(C, packed)]
+struct DeviceScopeHeader {
+    typ: u8,
+    length: u8,
+    reserved: u16,
+    enumeration_id: u8,
+    start_bus: u8
+}
+
+const STRUCTURE_TYPE_HARDWARE_UNIT: u16 = 0;
+const HARDWARE_UNIT_FLAG_INCLUDE_PCI_ALL: u8 = 0x01;
+const DEVICE_SCOPE_TYPE_PCI_ENDPOINT: u8 = 0x01;
+
+/// A PCI endpoint device (bus, device, function), which is located directly on the start bus of a device scope.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PciEndpoint {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8
+}
+
+/// A DMA Remapping Hardware Unit (DRHD), parsed from the DMAR table.
+pub struct RemappingUnit {
+    pub segment: u16,
+    pub register_base: PhysAddr,
+    /// If set, this unit is responsible for all devices on its segment, that are not covered by any other unit.
+    pub include_pci_all: bool,
+    pub endpoints: Vec<PciEndpoint>
+}
+
+unsafe impl AcpiTable for Dmar {
+    const SIGNATURE: Signature = Signature::DMAR;
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
+
+impl Dmar {
+    /// Maximum DMA physical address width supported by the platform.
+    pub fn host_address_width(&self) -> u8 {
+        return self.host_address_width + 1;
+    }
+
+    pub fn remapping_units(&self) -> Vec<RemappingUnit> {
+        let mut units = Vec::new();
+        let table_start = ptr::from_ref(self) as usize;
+        let table_end = table_start + self.header.length as usize;
+        let mut current = table_start + size_of::<Dmar>();
+
+        while current + size_of::<RemappingStructureHeader>() <= table_end {
+            let header = unsafe { (current as *const RemappingStructureHeader).read_unaligned() };
+            if header.length == 0 {
+                break; // Malformed table -> Stop parsing to avoid an endless loop
+            }
+
+            if header.typ == STRUCTURE_TYPE_HARDWARE_UNIT {
+                let unit = unsafe { (current as *const HardwareUnitDefinition).read_unaligned() };
+                let scopes_start = current + size_of::<HardwareUnitDefinition>();
+                let scopes_end = current + header.length as usize;
+
+                units.push(RemappingUnit {
+                    segment: unit.segment,
+                    register_base: PhysAddr::new(unit.register_base),
+                    include_pci_all: unit.flags & HARDWARE_UNIT_FLAG_INCLUDE_PCI_ALL != 0,
+                    endpoints: Dmar::parse_endpoints(scopes_start, scopes_end)
+                });
+            }
+
+            current += header.length as usize;
+        }
+
+        return units;
+    }
+
+    fn parse_endpoints(mut current: usize, end: usize) -> Vec<PciEndpoint> {
+        let mut endpoints = Vec::new();
+
+        while current + size_of::<DeviceScopeHeader>() <= end {
+            let scope = unsafe { (current as *const DeviceScopeHeader).read_unaligned() };
+            if scope.length == 0 {
+                break;
+            }
+
+            // Devices behind bridges are described by a path with multiple entries.
+            // Resolving these requires walking the PCI bus, so only devices directly located on the start bus are supported.
+            let path_length = (scope.length as usize - size_of::<DeviceScopeHeader>()) / 2;
+            if scope.typ == DEVICE_SCOPE_TYPE_PCI_ENDPOINT && path_length == 1 {
+                let path = (current + size_of::<DeviceScopeHeader>()) as *const u8;
+                let (device, function) = unsafe { (path.read(), path.add(1).read()) };
+                endpoints.push(PciEndpoint { bus: scope.start_bus, device, function });
+            }
+
+            current += scope.length as usize;
+        }
+
+        return endpoints;
+    }
+}
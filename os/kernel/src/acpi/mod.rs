@@ -0,0 +1,2 @@
+pub mod dmar;
+pub mod power;
@@ -0,0 +1,163 @@
+use acpi::address::{AddressSpace, GenericAddress};
+use acpi::fadt::Fadt;
+use core::ptr;
+use core::slice;
+use log::{info, warn};
+use x86_64::instructions::{hlt, interrupts};
+use x86_64::instructions::port::Port;
+use x86_64::structures::idt::InterruptDescriptorTable;
+use crate::{acpi_tables, vfs};
+
+/// Bits in the PM1 control registers, that put the system into the sleep state given by 'SLP_TYP'.
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_TYP_MASK: u16 = 0x1c00;
+const SLP_EN: u16 = 0x2000;
+
+/// AML opcodes, used to find the '_S5_' package (see 's5_sleep_types()').
+const NAME_OP: u8 = 0x08;
+const ROOT_CHAR: u8 = b'\\';
+const PACKAGE_OP: u8 = 0x12;
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const BYTE_PREFIX: u8 = 0x0a;
+
+/// Fallback for systems without a reset register: Pulse the CPU reset line via the PS/2 controller.
+const PS2_COMMAND_PORT: u16 = 0x64;
+const PS2_PULSE_RESET: u8 = 0xfe;
+
+/// Enter the soft-off state S5 by writing its sleep type ('SLP_TYPa'/'SLP_TYPb' from the DSDT) and 'SLP_EN'
+/// into the PM1a (and PM1b) control register of the FADT. Filesystems are synchronized first.
+pub fn shutdown() -> ! {
+    info!("Powering off");
+    sync_filesystems();
+    interrupts::disable();
+
+    let tables = match acpi_tables() {
+        Some(tables) => tables.lock(),
+        None => panic!("ACPI: Tables not available -> Cannot power off!")
+    };
+    let sleep_types = tables.dsdt().ok().and_then(|dsdt| {
+        let aml = unsafe { slice::from_raw_parts(dsdt.address as *const u8, dsdt.length as usize) };
+        s5_sleep_types(aml)
+    });
+
+    match (tables.find_table::<Fadt>(), sleep_types) {
+        (Ok(fadt), Some((sleep_type_a, sleep_type_b))) => {
+            if let Ok(pm1a_control) = fadt.pm1a_control_block() {
+                enter_sleep_state(&pm1a_control, sleep_type_a);
+            }
+            if let Ok(Some(pm1b_control)) = fadt.pm1b_control_block() {
+                enter_sleep_state(&pm1b_control, sleep_type_b);
+            }
+        }
+        _ => warn!("'_S5_' object or FADT not found")
+    }
+
+    panic!("ACPI: Failed to power off!");
+}
+
+/// Restart the system by writing the reset value to the reset register of the FADT. Filesystems are synchronized first.
+/// If the FADT does not support a reset (or ACPI is disabled), the PS/2 controller is used and as last resort, a triple fault is caused.
+pub fn reboot() -> ! {
+    info!("Rebooting");
+    sync_filesystems();
+    interrupts::disable();
+
+    if let Some(Ok(fadt)) = acpi_tables().map(|tables| tables.lock().find_table::<Fadt>()) {
+        let flags = fadt.flags;
+        if flags.supports_system_reset_via_fadt() {
+            match fadt.reset_register() {
+                Ok(reset_register) => write_register(&reset_register, fadt.reset_value as u16),
+                Err(err) => warn!("Invalid reset register in FADT (Error: {:?})", err)
+            }
+        }
+    }
+
+    unsafe { Port::<u8>::new(PS2_COMMAND_PORT).write(PS2_PULSE_RESET); }
+    for _ in 0..1000 {
+        hlt();
+    }
+
+    // An exception without a valid IDT causes a triple fault, which resets the CPU
+    let empty_idt = InterruptDescriptorTable::new();
+    unsafe {
+        empty_idt.load_unsafe();
+        core::arch::asm!("int3", options(nomem, nostack));
+    }
+
+    panic!("ACPI: Failed to reboot!");
+}
+
+/// Filesystems write through to their devices, but this gives filesystems with caches a chance to write them back.
+/// Skipped, if the VFS is locked (e.g. when the calling thread has been interrupted while accessing a file).
+fn sync_filesystems() {
+    match vfs().try_read() {
+        Some(vfs) => {
+            if let Err(err) = vfs.sync() {
+                warn!("Failed to synchronize filesystems (Error: {:?})", err);
+            }
+        }
+        None => warn!("VFS is locked -> Filesystems are not synchronized")
+    }
+}
+
+fn enter_sleep_state(control_register: &GenericAddress, sleep_type: u16) {
+    let value = read_register(control_register) & !SLP_TYP_MASK;
+    write_register(control_register, value | (sleep_type << SLP_TYP_SHIFT) | SLP_EN);
+}
+
+fn read_register(register: &GenericAddress) -> u16 {
+    return match register.address_space {
+        AddressSpace::SystemIo => unsafe { Port::<u16>::new(register.address as u16).read() },
+        AddressSpace::SystemMemory => unsafe { ptr::read_volatile(register.address as *const u16) },
+        _ => 0
+    };
+}
+
+/// Write `value` with the register's width (only I/O ports and memory are supported).
+fn write_register(register: &GenericAddress, value: u16) {
+    match (register.address_space, register.bit_width) {
+        (AddressSpace::SystemIo, 8) => unsafe { Port::<u8>::new(register.address as u16).write(value as u8) },
+        (AddressSpace::SystemIo, _) => unsafe { Port::<u16>::new(register.address as u16).write(value) },
+        (AddressSpace::SystemMemory, 8) => unsafe { ptr::write_volatile(register.address as *mut u8, value as u8) },
+        (AddressSpace::SystemMemory, _) => unsafe { ptr::write_volatile(register.address as *mut u16, value) },
+        (address_space, _) => warn!("Unsupported address space [{:?}] for ACPI register", address_space)
+    }
+}
+
+/// Find the '_S5_' object in the AML of the DSDT and return the sleep types for PM1a and PM1b.
+/// This does not interpret AML, but only matches the usual encoding of 'Name (_S5, Package () { a, b, ... })'.
+fn s5_sleep_types(aml: &[u8]) -> Option<(u16, u16)> {
+    let index = aml.windows(4).position(|window| window == b"_S5_")?;
+    let name_op = match index.checked_sub(1).map(|prefix| aml[prefix]) {
+        Some(ROOT_CHAR) => index.checked_sub(2)?,
+        _ => index.checked_sub(1)?
+    };
+
+    if aml[name_op] != NAME_OP || *aml.get(index + 4)? != PACKAGE_OP {
+        return None;
+    }
+
+    // The number of bytes of the package length is encoded in its two most significant bits (followed by the number of elements)
+    let package_length_bytes = (*aml.get(index + 5)? >> 6) as usize + 1;
+    let mut position = index + 5 + package_length_bytes + 1;
+
+    let sleep_type_a = read_aml_integer(aml, &mut position)?;
+    let sleep_type_b = read_aml_integer(aml, &mut position)?;
+    return Some((sleep_type_a, sleep_type_b));
+}
+
+fn read_aml_integer(aml: &[u8], position: &mut usize) -> Option<u16> {
+    let value = match *aml.get(*position)? {
+        ZERO_OP => 0,
+        ONE_OP => 1,
+        BYTE_PREFIX => {
+            *position += 1;
+            *aml.get(*position)? as u16
+        }
+        _ => return None
+    };
+
+    *position += 1;
+    return Some(value);
+}
@@ -0,0 +1,88 @@
+use core::arch::x86_64::__cpuid_count;
+use spin::Once;
+
+// Feature bits in the registers returned by the CPUID leaves 1 ('ECX', 'EDX'), 7 ('EBX') and 0xD ('EAX')
+const LEAF1_ECX_TSC_DEADLINE: u32 = 1 << 24;
+const LEAF1_ECX_XSAVE: u32 = 1 << 26;
+const LEAF1_ECX_AVX: u32 = 1 << 28;
+const LEAF1_EDX_FXSR: u32 = 1 << 24;
+const LEAF7_EBX_FSGSBASE: u32 = 1 << 0;
+const LEAF7_EBX_AVX512F: u32 = 1 << 16;
+/// State components in XCR0, supported by 'XSAVE' (AVX registers and the three AVX-512 components).
+const LEAF_D_EAX_AVX_STATE: u32 = 1 << 2;
+const LEAF_D_EAX_AVX512_STATE: u32 = 0b111 << 5;
+
+static CPU_FEATURES: Once<CpuFeatures> = Once::new();
+
+/// Processor features, that the kernel uses (or could use) if they are available.
+/// All processors of the system are assumed to support the same features as the bootstrap processor.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CpuFeatures {
+    leaf1_ecx: u32,
+    leaf1_edx: u32,
+    leaf7_ebx: u32,
+    leaf_d_eax: u32
+}
+
+/// Detect the features of the executing processor and cache them for 'features()'.
+/// Must be called once during boot, before any feature-specific code runs.
+pub fn init() {
+    CPU_FEATURES.call_once(detect);
+}
+
+/// Features detected by 'init()'.
+pub fn features() -> &'static CpuFeatures {
+    return CPU_FEATURES.get().expect("CPUID: Features accessed before initialization!");
+}
+
+/// Query the CPUID leaves 1, 7 and 0xD (leaves above the highest supported one are treated as reporting no features).
+pub fn detect() -> CpuFeatures {
+    let max_leaf = unsafe { __cpuid_count(0, 0) }.eax;
+    let mut features = CpuFeatures::default();
+
+    if max_leaf >= 1 {
+        let leaf1 = unsafe { __cpuid_count(1, 0) };
+        features.leaf1_ecx = leaf1.ecx;
+        features.leaf1_edx = leaf1.edx;
+    }
+    if max_leaf >= 7 {
+        features.leaf7_ebx = unsafe { __cpuid_count(7, 0) }.ebx;
+    }
+    if max_leaf >= 0xd && features.leaf1_ecx & LEAF1_ECX_XSAVE != 0 {
+        features.leaf_d_eax = unsafe { __cpuid_count(0xd, 0) }.eax;
+    }
+
+    return features;
+}
+
+impl CpuFeatures {
+    /// 'FXSAVE'/'FXRSTOR' for the x87 and SSE state.
+    pub fn has_fxsave(&self) -> bool {
+        return self.leaf1_edx & LEAF1_EDX_FXSR != 0;
+    }
+
+    /// 'XSAVE'/'XRSTOR' and the 'XCR0' register.
+    pub fn has_xsave(&self) -> bool {
+        return self.leaf1_ecx & LEAF1_ECX_XSAVE != 0;
+    }
+
+    /// 'RDFSBASE'/'WRFSBASE' (and the GS counterparts).
+    pub fn has_fsgsbase(&self) -> bool {
+        return self.leaf7_ebx & LEAF7_EBX_FSGSBASE != 0;
+    }
+
+    /// TSC deadline mode of the local APIC timer.
+    pub fn has_tsc_deadline(&self) -> bool {
+        return self.leaf1_ecx & LEAF1_ECX_TSC_DEADLINE != 0;
+    }
+
+    /// AVX instructions, including support for saving their registers with 'XSAVE'.
+    pub fn has_avx(&self) -> bool {
+        return self.leaf1_ecx & LEAF1_ECX_AVX != 0 && self.leaf_d_eax & LEAF_D_EAX_AVX_STATE != 0;
+    }
+
+    /// AVX-512 foundation instructions, including support for saving their registers with 'XSAVE'.
+    pub fn has_avx512f(&self) -> bool {
+        return self.has_avx() && self.leaf7_ebx & LEAF7_EBX_AVX512F != 0 && self.leaf_d_eax & LEAF_D_EAX_AVX512_STATE == LEAF_D_EAX_AVX512_STATE;
+    }
+}
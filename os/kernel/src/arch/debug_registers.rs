@@ -0,0 +1,186 @@
+use core::arch::asm;
+use library_syscall::{SIGTRAP, WATCHPOINT_COUNT};
+use x86_64::registers::debug::{BreakpointCondition, BreakpointSize, DebugAddressRegister, DebugAddressRegisterNumber, Dr0, Dr1, Dr2, Dr3, Dr6, Dr6Flags, Dr7, Dr7Flags, Dr7Value};
+use x86_64::VirtAddr;
+use crate::arch::exception::ExceptionState;
+use crate::scheduler;
+use crate::thread::signal;
+
+/// Trap flag in rflags (single step).
+const TRAP_FLAG: u64 = 0x100;
+
+/// Resume flag in rflags (suppresses instruction breakpoints for the next instruction).
+const RESUME_FLAG: u64 = 0x10000;
+
+/// Value of DR6 without any debug condition (reserved bits are set).
+const DR6_CLEAR: u64 = 0xffff0ff0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchCondition {
+    Execute,
+    Write,
+    ReadWrite
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchSize {
+    Byte = 1,
+    Word = 2,
+    DoubleWord = 4,
+    QuadWord = 8
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DbgError {
+    InvalidIndex,
+    /// The address must be aligned to the watched size
+    MisalignedAddress,
+    /// Instruction breakpoints must watch a single byte
+    InvalidSize
+}
+
+/// Decision of a 'WatchHandler', how to continue after a watchpoint has been hit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchAction {
+    Resume,
+    /// Execute a single instruction and call the handler again (with `None` as watchpoint index)
+    Step
+}
+
+/// Called with the index of the watchpoint, that has been hit (or `None` after a single step), and the interrupted state.
+pub type WatchHandler = fn(Option<usize>, &mut ExceptionState) -> WatchAction;
+
+/// Hardware breakpoints of a thread, which are loaded into the debug registers, when the thread is switched to.
+#[derive(Copy, Clone)]
+pub struct Watchpoints {
+    addresses: [u64; WATCHPOINT_COUNT],
+    dr7: Dr7Value,
+    handler: Option<WatchHandler>,
+    stepping: bool
+}
+
+impl Watchpoints {
+    pub const fn new() -> Self {
+        Self { addresses: [0; WATCHPOINT_COUNT], dr7: Dr7Value::from_bits_truncate(0), handler: None, stepping: false }
+    }
+
+    pub fn is_active(&self) -> bool {
+        return !self.dr7.flags().is_empty();
+    }
+
+    fn is_enabled(&self, index: usize) -> bool {
+        return self.dr7.flags().contains(Dr7Flags::local_breakpoint_enable(register_number(index)));
+    }
+
+    /// Write the watchpoints into the debug registers.
+    pub fn load(&self) {
+        Dr0::write(self.addresses[0]);
+        Dr1::write(self.addresses[1]);
+        Dr2::write(self.addresses[2]);
+        Dr3::write(self.addresses[3]);
+        Dr7::write(self.dr7);
+    }
+}
+
+/// Watch `size` bytes at `addr` in the current thread (replacing the watchpoint at `index`).
+/// Data watchpoints trigger after the access, instruction watchpoints ('Execute') before the instruction at `addr` is executed.
+pub fn set_watchpoint(index: usize, addr: VirtAddr, condition: WatchCondition, size: WatchSize) -> Result<(), DbgError> {
+    if index >= WATCHPOINT_COUNT {
+        return Err(DbgError::InvalidIndex);
+    }
+    if condition == WatchCondition::Execute && size != WatchSize::Byte {
+        return Err(DbgError::InvalidSize);
+    }
+    if addr.as_u64() % size as u64 != 0 {
+        return Err(DbgError::MisalignedAddress);
+    }
+
+    let number = register_number(index);
+    let condition = match condition {
+        WatchCondition::Execute => BreakpointCondition::InstructionExecution,
+        WatchCondition::Write => BreakpointCondition::DataWrites,
+        WatchCondition::ReadWrite => BreakpointCondition::DataReadsWrites
+    };
+
+    let thread = scheduler().current_thread();
+    let mut watchpoints = thread.watchpoints().lock();
+    watchpoints.addresses[index] = addr.as_u64();
+    watchpoints.dr7.set_condition(number, condition);
+    watchpoints.dr7.set_size(number, BreakpointSize::new(size as usize).unwrap());
+    watchpoints.dr7.insert_flags(Dr7Flags::local_breakpoint_enable(number));
+    watchpoints.load();
+
+    return Ok(());
+}
+
+/// Disable the watchpoint at `index` in the current thread (does nothing, if `index` is invalid).
+pub fn clear_watchpoint(index: usize) {
+    if index >= WATCHPOINT_COUNT {
+        return;
+    }
+
+    let thread = scheduler().current_thread();
+    let mut watchpoints = thread.watchpoints().lock();
+    watchpoints.addresses[index] = 0;
+    watchpoints.dr7.remove_flags(Dr7Flags::local_breakpoint_enable(register_number(index)));
+    watchpoints.load();
+}
+
+/// Register `handler` for the watchpoints of the current thread (`None` restores the default behaviour, see 'handle_exception()').
+pub fn set_watch_handler(handler: Option<WatchHandler>) {
+    scheduler().current_thread().watchpoints().lock().handler = handler;
+}
+
+/// Handle a debug exception, that has been caused by a watchpoint of the current thread (or by a single step, requested by its handler).
+/// Without a handler, user threads receive 'SIGTRAP' and hits in kernel mode (e.g. while copying from/to user space) are ignored.
+/// Returns `false`, if the exception has another cause (e.g. a single step of the GDB stub) or the signal cannot be delivered.
+pub fn handle_exception(state: &mut ExceptionState) -> bool {
+    let status = Dr6::read();
+    unsafe { asm!("mov dr6, {}", in(reg) DR6_CLEAR, options(nomem, nostack, preserves_flags)); }
+
+    let thread = match scheduler().try_current_thread() {
+        Some(thread) => thread,
+        None => return false
+    };
+
+    let (triggered, handler, address) = {
+        let mut watchpoints = match thread.watchpoints().try_lock() {
+            Some(watchpoints) => watchpoints,
+            None => return false
+        };
+
+        let triggered = (0..WATCHPOINT_COUNT).find(|&index| status.contains(Dr6Flags::trap(register_number(index))) && watchpoints.is_enabled(index));
+        if triggered.is_none() && !(watchpoints.stepping && status.contains(Dr6Flags::STEP)) {
+            return false;
+        }
+
+        watchpoints.stepping = false;
+        (triggered, watchpoints.handler, triggered.map_or(0, |index| watchpoints.addresses[index]))
+    };
+
+    state.rflags &= !TRAP_FLAG;
+    if triggered.is_some() {
+        // Instruction breakpoints trigger before the instruction is executed, so they must be suppressed to continue
+        state.rflags |= RESUME_FLAG;
+    }
+
+    match handler {
+        Some(handler) => {
+            if handler(triggered, state) == WatchAction::Step {
+                thread.watchpoints().lock().stepping = true;
+                state.rflags |= TRAP_FLAG;
+            }
+        }
+        None => {
+            if state.is_user_mode() {
+                return signal::deliver(state, SIGTRAP, address);
+            }
+        }
+    }
+
+    return true;
+}
+
+fn register_number(index: usize) -> DebugAddressRegisterNumber {
+    return DebugAddressRegisterNumber::new(index as u8).expect("Debug registers: Invalid watchpoint index!");
+}
@@ -0,0 +1,285 @@
+use core::arch::asm;
+use core::ptr;
+use x86_64::registers::control::Cr2;
+use x86_64::structures::idt::InterruptDescriptorTable;
+use x86_64::VirtAddr;
+use library_syscall::{SIGBUS, SIGFPE, SIGILL, SIGSEGV, SIGTRAP};
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::memory::PAGE_SIZE;
+use crate::syscall::copy_user;
+use crate::debug::backtrace::Backtrace;
+use crate::debug::gdb_stub;
+use crate::arch::debug_registers;
+use crate::thread::signal;
+use log::warn;
+use crate::{scheduler, tss};
+
+/// Index of rbp (frame pointer) in 'ExceptionState::registers'.
+const RBP_INDEX: usize = 5;
+
+/// Set in the page fault error code, if the page was present (otherwise the page was not present).
+const PAGE_FAULT_PROTECTION_VIOLATION: u64 = 0x01;
+
+// A kernel stack overflow causes a double fault, since the CPU cannot push the page fault's stack frame onto the guard page.
+// Thus, the double fault handler runs on its own stack (interrupt stack table entry).
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const DOUBLE_FAULT_STACK_SIZE: usize = 8 * PAGE_SIZE;
+
+static mut DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] = [0; DOUBLE_FAULT_STACK_SIZE];
+
+/// Registers saved by the exception entry stubs, followed by the interrupt stack frame.
+/// `registers` is indexed by the register numbers used in instruction encoding (rax = 0, rcx = 1, ..., r15 = 15).
+/// The slot for rsp (4) is unused, since rsp is part of the interrupt stack frame.
+/// For exceptions without an error code, `error_code` is 0.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ExceptionState {
+    pub registers: [u64; 16],
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64
+}
+
+impl ExceptionState {
+    pub fn is_user_mode(&self) -> bool {
+        return (self.cs & 0x03) == 3;
+    }
+}
+
+/// Replace the general handlers for exceptions, that can be caused by user threads and translated into signals.
+/// These need access to all registers, to save them in a signal frame.
+pub fn install(idt: &mut InterruptDescriptorTable) {
+    unsafe {
+        idt.divide_error.set_handler_addr(VirtAddr::new(divide_error_entry as u64));
+        idt.invalid_opcode.set_handler_addr(VirtAddr::new(invalid_opcode_entry as u64));
+        idt.general_protection_fault.set_handler_addr(VirtAddr::new(general_protection_fault_entry as u64));
+        idt.page_fault.set_handler_addr(VirtAddr::new(page_fault_entry as u64));
+        idt.x87_floating_point.set_handler_addr(VirtAddr::new(x87_floating_point_entry as u64));
+        idt.alignment_check.set_handler_addr(VirtAddr::new(alignment_check_entry as u64));
+        idt.simd_floating_point.set_handler_addr(VirtAddr::new(simd_floating_point_entry as u64));
+        idt.debug.set_handler_addr(VirtAddr::new(debug_entry as u64));
+        idt.breakpoint.set_handler_addr(VirtAddr::new(breakpoint_entry as u64));
+
+        let double_fault_stack_end = ptr::addr_of!(DOUBLE_FAULT_STACK) as u64 + DOUBLE_FAULT_STACK_SIZE as u64;
+        tss().lock().interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = VirtAddr::new(double_fault_stack_end);
+        idt.double_fault.set_handler_addr(VirtAddr::new(double_fault_entry as u64)).set_stack_index(DOUBLE_FAULT_IST_INDEX);
+    }
+}
+
+/// Page faults on lazily mapped pages are resolved and faults in 'copy_user' are recovered.
+/// Otherwise, exceptions raised in user mode deliver a signal to the current thread, which terminates it, if no handler is registered.
+/// Exceptions in kernel mode are fatal (reporting kernel stack overflows and null pointer dereferences explicitly).
+pub extern "C" fn handle_exception(state: &mut ExceptionState, vector: u64) {
+    // Faults on not present pages may be resolved by demand paging, in user mode as well as while copying from/to user space
+    if vector == InterruptVector::PageFault as u64 && state.error_code & PAGE_FAULT_PROTECTION_VIOLATION == 0 && handle_lazy_fault(Cr2::read()) {
+        return;
+    }
+
+    // Faults while copying from/to user space (see 'copy_user') are recovered by continuing at a fixup address
+    if !state.is_user_mode() && (vector == InterruptVector::PageFault as u64 || vector == InterruptVector::GeneralProtectionFault as u64) {
+        if let Some(fixup) = copy_user::fixup(state.rip) {
+            state.rip = fixup;
+            return;
+        }
+    }
+
+    let signum = match InterruptVector::try_from(vector as u8) {
+        Ok(InterruptVector::DivisionByZero) | Ok(InterruptVector::X87FloatingPointException) | Ok(InterruptVector::SimdFloatingPointException) => SIGFPE,
+        Ok(InterruptVector::InvalidOpcode) | Ok(InterruptVector::DeviceNotAvailable) => SIGILL,
+        Ok(InterruptVector::AlignmentCheck) => SIGBUS,
+        Ok(InterruptVector::Debug) | Ok(InterruptVector::Breakpoint) => SIGTRAP,
+        _ => SIGSEGV
+    };
+
+    let fault_address = if vector == InterruptVector::PageFault as u64 { Cr2::read().as_u64() } else { 0 };
+    if state.is_user_mode() {
+        if signal::deliver(state, signum, fault_address) {
+            return;
+        }
+
+        // Without a handler, the signal terminates the thread (it cannot hold any kernel locks while running in user mode)
+        let thread = scheduler().current_thread();
+        warn!("Thread [{}] terminated by signal [{}] (Exception: [{}], Address: [{:0>16x}], rip: [{:0>16x}])", thread.id(), signum, vector, fault_address, state.rip);
+        drop(thread);
+        scheduler().exit();
+    }
+
+    // Kernel faults are reported to the debugger, which may inspect and modify the state before continuing
+    if gdb_stub::is_active() && gdb_stub::enter(state, vector) {
+        return;
+    }
+
+    let stack = scheduler().try_current_thread().map_or(0..0, |thread| thread.kernel_stack_range());
+    let backtrace = Backtrace::new(state.rip, state.registers[RBP_INDEX], stack);
+
+    if vector == InterruptVector::PageFault as u64 {
+        check_kernel_stack_overflow(fault_address);
+
+        if fault_address < PAGE_SIZE as u64 {
+            panic!("Null pointer dereference!\nError code: [{:?}]\nAddress: [{:0>16x}]\n{:?}\n{}", state.error_code, fault_address, state, backtrace);
+        }
+
+        panic!("Page Fault!\nError code: [{:?}]\nAddress: [{:0>16x}]\n{:?}\n{}", state.error_code, fault_address, state, backtrace);
+    }
+
+    panic!("CPU Exception: [{} - {:?}]\nError code: [{:?}]\n{:?}\n{}", vector, InterruptVector::try_from(vector as u8).unwrap(), state.error_code, state, backtrace);
+}
+
+/// Watchpoints are reported to the current thread (see 'debug_registers').
+/// Other breakpoints and single steps are handled by the GDB stub, if it is active. Otherwise, they are treated like other exceptions.
+pub extern "C" fn handle_debug_exception(state: &mut ExceptionState, vector: u64) {
+    if vector == InterruptVector::Debug as u64 && debug_registers::handle_exception(state) {
+        return;
+    }
+
+    if gdb_stub::is_active() && gdb_stub::enter(state, vector) {
+        return;
+    }
+
+    handle_exception(state, vector);
+}
+
+/// Map a page of the current thread's address space, that has been registered for demand paging (see 'AddressSpace::map_lazy()').
+/// The address space must not be locked while accessing such pages, since the fault cannot be resolved otherwise.
+/// Also called by 'copy_user::user_page_flags()' to fault in user pages before the kernel accesses them.
+pub fn handle_lazy_fault(fault_address: VirtAddr) -> bool {
+    let thread = match scheduler().try_current_thread() {
+        Some(thread) => thread,
+        None => return false
+    };
+
+    let resolved = match thread.address_space().try_write() {
+        Some(mut address_space) => address_space.handle_lazy_fault(fault_address),
+        None => false
+    };
+
+    if resolved {
+        thread.resource_usage().minor_fault();
+    }
+
+    return resolved;
+}
+
+/// Double faults are always fatal. Runs on its own stack, so that kernel stack overflows can be reported.
+pub extern "C" fn handle_double_fault(state: &mut ExceptionState, _vector: u64) {
+    // After a page fault during delivery of a page fault, CR2 contains the address of the second fault
+    check_kernel_stack_overflow(Cr2::read().as_u64());
+
+    let stack = scheduler().try_current_thread().map_or(0..0, |thread| thread.kernel_stack_range());
+    let backtrace = Backtrace::new(state.rip, state.registers[RBP_INDEX], stack);
+    panic!("Double Fault!\n{:?}\n{}", state, backtrace);
+}
+
+/// Panic with a clear message, if `fault_address` is in the guard page below the current thread's kernel stack.
+fn check_kernel_stack_overflow(fault_address: u64) {
+    if let Some(thread) = scheduler().try_current_thread() {
+        if thread.is_kernel_stack_guard(fault_address) {
+            panic!("Kernel stack overflow in thread [{}] (Address: [{:0>16x}])!", thread.id(), fault_address);
+        }
+    }
+}
+
+/// Load all registers from `state` and return from the exception via `iretq` (switching to the user's GS base, if `state` is in user mode).
+///
+/// # Safety
+/// `state` must point to an 'ExceptionState' on the kernel stack, directly followed by the interrupt frame (as pushed by 'exception_entry!').
+#[naked]
+pub unsafe extern "C" fn restore_state(state: *const ExceptionState) -> ! {
+    asm!(
+    "mov rsp, rdi",
+    "pop rax",
+    "pop rcx",
+    "pop rdx",
+    "pop rbx",
+    "add rsp, 8", // Skip placeholder for rsp
+    "pop rbp",
+    "pop rsi",
+    "pop rdi",
+    "pop r8",
+    "pop r9",
+    "pop r10",
+    "pop r11",
+    "pop r12",
+    "pop r13",
+    "pop r14",
+    "pop r15",
+    "add rsp, 8", // Skip error code
+
+    // Switch back to the user's GS base, if returning to user mode (cs is located behind rip)
+    "test byte ptr [rsp + 8], 3",
+    "jz 2f",
+    "swapgs",
+    "2:",
+    "iretq",
+    options(noreturn)
+    );
+}
+
+// Entry stubs: Save all general purpose registers (in encoding order) and call `$handler` with a pointer to the 'ExceptionState'.
+// For exceptions without error code, a 0 is pushed instead, so that the layout is always the same.
+// Exceptions from user mode switch to the kernel's GS base (see 'percpu') before saving the registers.
+macro_rules! exception_entry {
+    ($name:ident, $handler:ident, $vector:expr) => {
+        exception_entry!(@entry $name, $handler, $vector, "push 0");
+    };
+    ($name:ident, $handler:ident, $vector:expr, error_code) => {
+        exception_entry!(@entry $name, $handler, $vector);
+    };
+    (@entry $name:ident, $handler:ident, $vector:expr $(, $push_error_code:literal)?) => {
+        #[naked]
+        unsafe extern "C" fn $name() {
+            core::arch::asm!(
+            $($push_error_code,)?
+            "test byte ptr [rsp + 16], 3", // cs is located behind the error code and rip
+            "jz 2f",
+            "swapgs",
+            "2:",
+            "push r15",
+            "push r14",
+            "push r13",
+            "push r12",
+            "push r11",
+            "push r10",
+            "push r9",
+            "push r8",
+            "push rdi",
+            "push rsi",
+            "push rbp",
+            "push rax", // Placeholder for rsp
+            "push rbx",
+            "push rdx",
+            "push rcx",
+            "push rax",
+
+            // The CPU aligns the stack to 16 bytes before pushing the interrupt frame.
+            // Together with the error code, that frame is 6 quadwords, and 16 registers were pushed above, so rsp is still aligned here.
+            "mov rdi, rsp", // Pointer to 'ExceptionState'
+            "mov rsi, {vector}",
+            "call {handler}",
+
+            "mov rdi, rsp",
+            "jmp {restore}",
+            vector = const $vector,
+            handler = sym $handler,
+            restore = sym $crate::arch::exception::restore_state,
+            options(noreturn)
+            );
+        }
+    };
+}
+
+pub(crate) use exception_entry;
+
+exception_entry!(double_fault_entry, handle_double_fault, InterruptVector::DoubleFault as u8, error_code);
+exception_entry!(divide_error_entry, handle_exception, InterruptVector::DivisionByZero as u8);
+exception_entry!(invalid_opcode_entry, handle_exception, InterruptVector::InvalidOpcode as u8);
+exception_entry!(general_protection_fault_entry, handle_exception, InterruptVector::GeneralProtectionFault as u8, error_code);
+exception_entry!(page_fault_entry, handle_exception, InterruptVector::PageFault as u8, error_code);
+exception_entry!(x87_floating_point_entry, handle_exception, InterruptVector::X87FloatingPointException as u8);
+exception_entry!(alignment_check_entry, handle_exception, InterruptVector::AlignmentCheck as u8, error_code);
+exception_entry!(simd_floating_point_entry, handle_exception, InterruptVector::SimdFloatingPointException as u8);
+exception_entry!(debug_entry, handle_debug_exception, InterruptVector::Debug as u8);
+exception_entry!(breakpoint_entry, handle_debug_exception, InterruptVector::Breakpoint as u8);
@@ -0,0 +1,319 @@
+use core::ptr;
+use x86_64::registers::control::{Cr0, Cr0Flags};
+use x86_64::structures::idt::InterruptDescriptorTable;
+use x86_64::VirtAddr;
+use crate::arch::exception;
+use crate::arch::exception::{exception_entry, ExceptionState};
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::scheduler;
+
+// Software emulation of a subset of x87 and SSE instructions for CPUs with a disabled FPU.
+// With CR0.EM set, x87 instructions raise #NM (device not available) and SSE instructions raise #UD (invalid opcode).
+// Both exceptions are caught here, the faulting instruction is decoded and executed on the thread's software FPU state.
+// x87 registers are emulated with 64-bit precision (instead of 80-bit).
+
+/// Software FPU state of a thread.
+pub struct FpuState {
+    st: [f64; 8],
+    top: usize,
+    xmm: [[u32; 4]; 16]
+}
+
+enum Operand {
+    Register(usize),
+    Memory(u64)
+}
+
+struct Decoder<'a> {
+    state: &'a ExceptionState,
+    rip: u64,
+    rex: u8
+}
+
+impl FpuState {
+    pub const fn new() -> Self {
+        Self { st: [0.0; 8], top: 0, xmm: [[0; 4]; 16] }
+    }
+
+    fn st(&self, index: usize) -> f64 {
+        return self.st[(self.top + index) % 8];
+    }
+
+    fn set_st(&mut self, index: usize, value: f64) {
+        self.st[(self.top + index) % 8] = value;
+    }
+
+    fn push(&mut self, value: f64) {
+        self.top = (self.top + 7) % 8;
+        self.st[self.top] = value;
+    }
+
+    fn pop(&mut self) -> f64 {
+        let value = self.st[self.top];
+        self.top = (self.top + 1) % 8;
+        return value;
+    }
+}
+
+/// Install the exception handlers for #NM and #UD and disable the hardware FPU.
+/// Must be called after `exception::install()`, since it replaces the handler for #UD.
+pub fn install(idt: &mut InterruptDescriptorTable) {
+    unsafe {
+        idt.device_not_available.set_handler_addr(VirtAddr::new(device_not_available_entry as u64));
+        idt.invalid_opcode.set_handler_addr(VirtAddr::new(invalid_opcode_entry as u64));
+        Cr0::update(|flags| flags.insert(Cr0Flags::EMULATE_COPROCESSOR));
+    }
+}
+
+/// Emulate the faulting instruction. Unsupported instructions are handled like any other exception.
+extern "C" fn handle_fpu_exception(state: &mut ExceptionState, vector: u64) {
+    let next_rip = {
+        let thread = scheduler().current_thread();
+        let mut fpu = thread.fpu_state().lock();
+        let mut decoder = Decoder { state, rip: state.rip, rex: 0 };
+
+        if emulate(&mut decoder, &mut fpu) { Some(decoder.rip) } else { None }
+    };
+
+    match next_rip {
+        Some(rip) => state.rip = rip,
+        None => exception::handle_exception(state, vector)
+    }
+}
+
+fn emulate(decoder: &mut Decoder, fpu: &mut FpuState) -> bool {
+    let mut scalar_single = false;
+
+    // Parse prefixes (a REX prefix must directly precede the opcode)
+    let mut opcode = decoder.byte();
+    loop {
+        match opcode {
+            0xf3 => scalar_single = true,
+            0x2e | 0x36 | 0x3e | 0x26 => {}, // Segment overrides are ignored in 64-bit mode
+            0x40..=0x4f => {
+                decoder.rex = opcode;
+                opcode = decoder.byte();
+                break;
+            },
+            _ => break
+        }
+
+        opcode = decoder.byte();
+    }
+
+    return match opcode {
+        0xd8..=0xdf => emulate_x87(opcode, decoder, fpu),
+        0x0f if scalar_single => emulate_sse(decoder, fpu),
+        _ => false
+    };
+}
+
+fn emulate_x87(opcode: u8, decoder: &mut Decoder, fpu: &mut FpuState) -> bool {
+    let (operation, operand) = decoder.modrm();
+    let operation = operation & 0x07; // REX.R is not used by x87 instructions
+
+    match (opcode, operand) {
+        // Arithmetic with ST(0) and a 32-bit or 64-bit memory operand
+        (0xd8, Operand::Memory(addr)) | (0xdc, Operand::Memory(addr)) => {
+            let value = if opcode == 0xd8 { read_f32(addr) as f64 } else { read_f64(addr) };
+            match arithmetic(operation, fpu.st(0), value) {
+                Some(result) => fpu.set_st(0, result),
+                None => return false
+            }
+        },
+        // ST(0) = ST(0) op ST(i)
+        (0xd8, Operand::Register(index)) => {
+            match arithmetic(operation, fpu.st(0), fpu.st(index & 0x07)) {
+                Some(result) => fpu.set_st(0, result),
+                None => return false
+            }
+        },
+        // ST(i) = ST(i) op ST(0), optionally followed by a pop (0xde)
+        (0xdc, Operand::Register(index)) | (0xde, Operand::Register(index)) => {
+            // Encodings of reversed and non-reversed subtraction/division are swapped compared to 0xd8
+            let operation = match operation {
+                4 => 5,
+                5 => 4,
+                6 => 7,
+                7 => 6,
+                other => other
+            };
+
+            match arithmetic(operation, fpu.st(index & 0x07), fpu.st(0)) {
+                Some(result) => fpu.set_st(index & 0x07, result),
+                None => return false
+            }
+
+            if opcode == 0xde {
+                fpu.pop();
+            }
+        },
+        // FLD/FST/FSTP with 32-bit memory operand
+        (0xd9, Operand::Memory(addr)) => match operation {
+            0 => fpu.push(read_f32(addr) as f64),
+            2 => write_f32(addr, fpu.st(0) as f32),
+            3 => write_f32(addr, fpu.pop() as f32),
+            _ => return false
+        },
+        (0xd9, Operand::Register(index)) => match (operation, index & 0x07) {
+            (0, index) => fpu.push(fpu.st(index)), // FLD ST(i)
+            (5, 0) => fpu.push(1.0), // FLD1
+            (5, 6) => fpu.push(0.0), // FLDZ
+            _ => return false
+        },
+        // FLD/FST/FSTP with 64-bit memory operand
+        (0xdd, Operand::Memory(addr)) => match operation {
+            0 => fpu.push(read_f64(addr)),
+            2 => write_f64(addr, fpu.st(0)),
+            3 => write_f64(addr, fpu.pop()),
+            _ => return false
+        },
+        (0xdd, Operand::Register(index)) => match operation {
+            2 => fpu.set_st(index & 0x07, fpu.st(0)), // FST ST(i)
+            3 => { // FSTP ST(i)
+                fpu.set_st(index & 0x07, fpu.st(0));
+                fpu.pop();
+            },
+            _ => return false
+        },
+        // FNINIT
+        (0xdb, Operand::Register(3)) if operation == 4 => {
+            fpu.st = [0.0; 8];
+            fpu.top = 0;
+        },
+        _ => return false
+    }
+
+    return true;
+}
+
+// Operation numbers as encoded in the reg field of 0xd8 instructions
+fn arithmetic(operation: usize, destination: f64, source: f64) -> Option<f64> {
+    return match operation {
+        0 => Some(destination + source), // FADD
+        1 => Some(destination * source), // FMUL
+        4 => Some(destination - source), // FSUB
+        5 => Some(source - destination), // FSUBR
+        6 => Some(destination / source), // FDIV
+        7 => Some(source / destination), // FDIVR
+        _ => None // FCOM/FCOMP are not supported
+    };
+}
+
+// Scalar single precision SSE instructions (prefix 0xf3, opcode 0x0f xx)
+fn emulate_sse(decoder: &mut Decoder, fpu: &mut FpuState) -> bool {
+    let opcode = decoder.byte();
+    let (register, operand) = decoder.modrm();
+
+    let source = match operand {
+        Operand::Register(index) => f32::from_bits(fpu.xmm[index][0]),
+        Operand::Memory(addr) if opcode != 0x11 => read_f32(addr),
+        Operand::Memory(_) => 0.0
+    };
+    let destination = f32::from_bits(fpu.xmm[register][0]);
+
+    let result = match opcode {
+        0x10 => { // MOVSS xmm, xmm/m32 (upper lanes are cleared when loading from memory)
+            if let Operand::Memory(_) = operand {
+                fpu.xmm[register] = [0; 4];
+            }
+            source
+        },
+        0x11 => { // MOVSS xmm/m32, xmm
+            match operand {
+                Operand::Register(index) => fpu.xmm[index][0] = destination.to_bits(),
+                Operand::Memory(addr) => write_f32(addr, destination)
+            }
+            return true;
+        },
+        0x58 => destination + source, // ADDSS
+        0x59 => destination * source, // MULSS
+        0x5c => destination - source, // SUBSS
+        0x5e => destination / source, // DIVSS
+        _ => return false
+    };
+
+    fpu.xmm[register][0] = result.to_bits();
+    return true;
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> u8 {
+        let value = unsafe { ptr::read_volatile(self.rip as *const u8) };
+        self.rip += 1;
+        return value;
+    }
+
+    fn dword(&mut self) -> i32 {
+        let mut bytes = [0u8; 4];
+        for byte in bytes.iter_mut() {
+            *byte = self.byte();
+        }
+
+        return i32::from_le_bytes(bytes);
+    }
+
+    fn register(&self, index: usize) -> u64 {
+        return if index == 4 { self.state.rsp } else { self.state.registers[index] };
+    }
+
+    /// Decode a ModRM byte (and SIB byte and displacement, if present). Returns the reg field and the r/m operand.
+    /// None of the supported instructions has an immediate, so RIP-relative addresses are relative to the end of the displacement.
+    fn modrm(&mut self) -> (usize, Operand) {
+        let modrm = self.byte();
+        let mode = modrm >> 6;
+        let reg = ((modrm >> 3) & 0x07 | (self.rex & 0x04) << 1) as usize;
+        let rm = (modrm & 0x07) as usize;
+        let rex_b = ((self.rex & 0x01) << 3) as usize;
+
+        if mode == 3 {
+            return (reg, Operand::Register(rm | rex_b));
+        }
+
+        let mut addr;
+        if rm == 4 { // SIB byte follows
+            let sib = self.byte();
+            let index = ((sib >> 3) & 0x07 | (self.rex & 0x02) << 2) as usize;
+            let base = (sib & 0x07) as usize;
+
+            addr = if index == 4 { 0 } else { self.register(index) << (sib >> 6) };
+            if base == 5 && mode == 0 {
+                addr = addr.wrapping_add(self.dword() as i64 as u64);
+            } else {
+                addr = addr.wrapping_add(self.register(base | rex_b));
+            }
+        } else if rm == 5 && mode == 0 { // RIP-relative
+            let displacement = self.dword() as i64 as u64;
+            addr = self.rip.wrapping_add(displacement);
+        } else {
+            addr = self.register(rm | rex_b);
+        }
+
+        if mode == 1 {
+            addr = addr.wrapping_add(self.byte() as i8 as i64 as u64);
+        } else if mode == 2 {
+            addr = addr.wrapping_add(self.dword() as i64 as u64);
+        }
+
+        return (reg, Operand::Memory(addr));
+    }
+}
+
+fn read_f32(addr: u64) -> f32 {
+    return f32::from_bits(unsafe { ptr::read_unaligned(addr as *const u32) });
+}
+
+fn read_f64(addr: u64) -> f64 {
+    return f64::from_bits(unsafe { ptr::read_unaligned(addr as *const u64) });
+}
+
+fn write_f32(addr: u64, value: f32) {
+    unsafe { ptr::write_unaligned(addr as *mut u32, value.to_bits()); }
+}
+
+fn write_f64(addr: u64, value: f64) {
+    unsafe { ptr::write_unaligned(addr as *mut u64, value.to_bits()); }
+}
+
+exception_entry!(device_not_available_entry, handle_fpu_exception, InterruptVector::DeviceNotAvailable as u8);
+exception_entry!(invalid_opcode_entry, handle_fpu_exception, InterruptVector::InvalidOpcode as u8);
@@ -0,0 +1,40 @@
+use spin::Once;
+use x86_64::instructions::segmentation::{Segment64, FS};
+use x86_64::registers::control::{Cr4, Cr4Flags};
+use x86_64::registers::model_specific::FsBase;
+use x86_64::VirtAddr;
+use crate::arch::cpuid;
+
+static FSGSBASE_AVAILABLE: Once<bool> = Once::new();
+
+/// Check for the 'RDFSBASE'/'WRFSBASE' instructions.
+/// Without them, the 'IA32_FS_BASE' MSR is used, which is slower to access.
+/// Must be called once during boot, before the first thread switch (which saves and restores the FS base).
+pub fn init() {
+    FSGSBASE_AVAILABLE.call_once(|| cpuid::features().has_fsgsbase());
+}
+
+/// Enable the instructions in CR4 of the executing CPU (see 'boot::init_cpu()').
+pub fn enable() {
+    if FSGSBASE_AVAILABLE.get().copied().unwrap_or(false) {
+        unsafe { Cr4::update(|flags| flags.insert(Cr4Flags::FSGSBASE)); }
+    }
+}
+
+/// Base address of the FS segment, used by user space as thread-local storage pointer.
+pub fn read_fs_base() -> u64 {
+    if FSGSBASE_AVAILABLE.get().copied().unwrap_or(false) {
+        return FS::read_base().as_u64();
+    }
+
+    return FsBase::read().as_u64();
+}
+
+/// `base` must be a canonical address.
+pub fn write_fs_base(base: u64) {
+    if FSGSBASE_AVAILABLE.get().copied().unwrap_or(false) {
+        unsafe { FS::write_base(VirtAddr::new(base)); }
+    } else {
+        FsBase::write(VirtAddr::new(base));
+    }
+}
@@ -0,0 +1,122 @@
+use core::mem::size_of;
+use core::ops::{Deref, DerefMut, RangeInclusive};
+use x86_64::structures::gdt::Descriptor;
+use x86_64::structures::tss::TaskStateSegment;
+
+/// One bit per I/O port, followed by a byte with all bits set (the CPU may read one byte past the bit of port 0xffff).
+const IOPB_SIZE: usize = 65536 / 8 + 1;
+
+/// Ports used by the kernel's own drivers (PIC, PIT, PS/2 controller, CMOS/RTC, PCI configuration space and COM1-COM4).
+const RESERVED_PORTS: [RangeInclusive<u16>; 9] = [0x20..=0x21, 0x40..=0x43, 0x60..=0x64, 0x70..=0x71, 0xa0..=0xa1, 0xcf8..=0xcff, 0x2e8..=0x2ef, 0x2f8..=0x2ff, 0x3e8..=0x3ff];
+
+/// Maximum number of ports, that a single process may request.
+pub const MAX_PROCESS_PORTS: usize = 16;
+
+/// Task state segment, followed by the I/O permission bitmap. A set bit denies user mode access to the corresponding port.
+/// Each CPU has its own TSS (see 'smp::alloc_ap_info()'). Its bitmap allows the ports of the process, that is running on the CPU (see 'load_ports()').
+#[repr(C)]
+pub struct TssWithIopb {
+    tss: TaskStateSegment,
+    iopb: [u8; IOPB_SIZE],
+    /// Ports, that are currently allowed in 'iopb' (not part of the segment)
+    loaded: IoPorts
+}
+
+/// I/O ports granted to a process (see 'sys_request_ioport()').
+#[derive(Copy, Clone, PartialEq)]
+pub struct IoPorts {
+    ports: [u16; MAX_PROCESS_PORTS],
+    count: usize
+}
+
+impl TssWithIopb {
+    /// Create a TSS, that denies access to all ports.
+    pub const fn new() -> Self {
+        let mut tss = TaskStateSegment::new();
+        tss.iomap_base = size_of::<TaskStateSegment>() as u16;
+
+        Self { tss, iopb: [0xff; IOPB_SIZE], loaded: IoPorts::new() }
+    }
+
+    /// Same as 'Descriptor::tss_segment()', but with the segment limit covering the I/O permission bitmap.
+    /// Without this, the CPU treats all ports as denied, since the bitmap lies beyond the limit.
+    pub fn descriptor(&'static self) -> Descriptor {
+        return match Descriptor::tss_segment(&self.tss) {
+            Descriptor::SystemSegment(low, high) => {
+                let limit = (size_of::<TaskStateSegment>() + IOPB_SIZE - 1) as u64;
+                Descriptor::SystemSegment((low & !0xffff) | limit, high)
+            }
+            Descriptor::UserSegment(_) => panic!("IOPB: Invalid TSS descriptor!")
+        };
+    }
+
+    /// Allow exactly `ports` in the bitmap (called on thread switches, see 'percpu::tss_load_io_ports()').
+    /// Only the bits of the previously loaded ports are changed, so switching between processes without ports costs nothing.
+    pub fn load_ports(&mut self, ports: &IoPorts) {
+        if self.loaded == *ports {
+            return;
+        }
+
+        for port in self.loaded.iter() {
+            let (index, mask) = bit_position(*port);
+            self.iopb[index] |= mask;
+        }
+        for port in ports.iter() {
+            let (index, mask) = bit_position(*port);
+            self.iopb[index] &= !mask;
+        }
+
+        self.loaded = *ports;
+    }
+}
+
+impl Deref for TssWithIopb {
+    type Target = TaskStateSegment;
+
+    fn deref(&self) -> &Self::Target {
+        return &self.tss;
+    }
+}
+
+impl DerefMut for TssWithIopb {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        return &mut self.tss;
+    }
+}
+
+impl IoPorts {
+    pub const fn new() -> Self {
+        Self { ports: [0; MAX_PROCESS_PORTS], count: 0 }
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        return self.iter().any(|allowed| *allowed == port);
+    }
+
+    /// Add `port` and return `false`, if 'MAX_PROCESS_PORTS' have already been added.
+    pub fn insert(&mut self, port: u16) -> bool {
+        if self.contains(port) {
+            return true;
+        }
+        if self.count == MAX_PROCESS_PORTS {
+            return false;
+        }
+
+        self.ports[self.count] = port;
+        self.count += 1;
+        return true;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &u16> {
+        return self.ports[..self.count].iter();
+    }
+}
+
+/// Check if `port` is used by a kernel driver and must never be accessed by user mode.
+pub fn is_reserved(port: u16) -> bool {
+    return RESERVED_PORTS.iter().any(|range| range.contains(&port));
+}
+
+fn bit_position(port: u16) -> (usize, u8) {
+    return (port as usize / 8, 1 << (port % 8));
+}
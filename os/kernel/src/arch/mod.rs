@@ -0,0 +1,10 @@
+pub mod cpuid;
+pub mod debug_registers;
+pub mod exception;
+#[cfg(feature = "fpu_emulate")]
+pub mod fpu;
+pub mod fsbase;
+pub mod iopb;
+pub mod pkey;
+pub mod tsc;
+pub mod xsave;
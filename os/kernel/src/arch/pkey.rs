@@ -0,0 +1,45 @@
+use core::arch::asm;
+use raw_cpuid::CpuId;
+use spin::Once;
+use x86_64::registers::control::{Cr4, Cr4Flags};
+
+/// Number of protection keys, that can be stored in the page table entry bits 59 to 62.
+pub const PKEY_COUNT: usize = 16;
+
+static PKEYS_AVAILABLE: Once<bool> = Once::new();
+
+/// Check for Memory Protection Keys (PKU).
+/// Must be called once during boot, before the first thread switch (which saves and restores 'PKRU').
+pub fn init() {
+    PKEYS_AVAILABLE.call_once(|| {
+        return match CpuId::new().get_extended_feature_info() {
+            Some(features) => features.has_pku(),
+            None => false
+        };
+    });
+}
+
+/// Enable protection keys in CR4 of the executing CPU (see 'boot::init_cpu()').
+pub fn enable() {
+    if pkeys_available() {
+        unsafe { Cr4::update(|flags| flags.insert(Cr4Flags::PROTECTION_KEY_USER)); }
+    }
+}
+
+pub fn pkeys_available() -> bool {
+    return PKEYS_AVAILABLE.get().copied().unwrap_or(false);
+}
+
+/// Read the 'PKRU' register (two bits per key: access disable and write disable).
+/// Must only be called, if protection keys are available ('RDPKRU' raises #UD otherwise).
+pub fn read_pkru() -> u32 {
+    let pkru: u32;
+    unsafe { asm!("rdpkru", out("eax") pkru, in("ecx") 0, out("edx") _, options(nomem, nostack, preserves_flags)); }
+
+    return pkru;
+}
+
+/// Must only be called, if protection keys are available ('WRPKRU' raises #UD otherwise).
+pub fn write_pkru(pkru: u32) {
+    unsafe { asm!("wrpkru", in("eax") pkru, in("ecx") 0, in("edx") 0, options(nostack, preserves_flags)); }
+}
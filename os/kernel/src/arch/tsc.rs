@@ -0,0 +1,94 @@
+use core::arch::x86_64::_rdtsc;
+use core::hint::spin_loop;
+use raw_cpuid::CpuId;
+use spin::Once;
+use x86_64::registers::model_specific::Msr;
+use crate::arch::cpuid;
+use crate::hpet;
+
+const IA32_TSC_DEADLINE: u32 = 0x6e0;
+
+/// Time, during which the TSC is calibrated against the HPET (if CPUID does not report its frequency).
+const CALIBRATION_NS: u64 = 10000000;
+
+/// TSC frequency in Hz (0, if the TSC is not invariant or its frequency is unknown).
+static TSC_FREQUENCY: Once<u64> = Once::new();
+static DEADLINE_AVAILABLE: Once<bool> = Once::new();
+
+/// Determine the TSC frequency (via CPUID or by calibrating it against the HPET) and check for TSC deadline mode support.
+/// Only an invariant TSC is used, since the frequency of other TSCs may change with the CPU's power state.
+/// Must be called once during boot, after the HPET has been initialized.
+pub fn init() {
+    let cpuid = CpuId::new();
+    let invariant = match cpuid.get_advanced_power_mgmt_info() {
+        Some(info) => info.has_invariant_tsc(),
+        None => false
+    };
+
+    let frequency = *TSC_FREQUENCY.call_once(|| {
+        if !invariant {
+            return 0;
+        }
+
+        return match cpuid.get_tsc_info().and_then(|info| info.tsc_frequency()) {
+            Some(frequency) => frequency,
+            None => calibrate()
+        };
+    });
+
+    DEADLINE_AVAILABLE.call_once(|| cpuid::features().has_tsc_deadline() && frequency != 0);
+}
+
+/// TSC frequency in Hz, if the TSC can be used as a clock source.
+pub fn frequency() -> Option<u64> {
+    return match TSC_FREQUENCY.get().copied().unwrap_or(0) {
+        0 => None,
+        frequency => Some(frequency)
+    };
+}
+
+/// Check if the local APIC timer can be used in TSC deadline mode.
+pub fn deadline_available() -> bool {
+    return DEADLINE_AVAILABLE.get().copied().unwrap_or(false);
+}
+
+/// Nanoseconds since the TSC has been reset (usually at power on).
+pub fn time_ns() -> Option<u64> {
+    let frequency = frequency()?;
+    let ticks = unsafe { _rdtsc() };
+
+    return Some((ticks as u128 * 1000000000 / frequency as u128) as u64);
+}
+
+/// Program the local APIC timer (which must be in TSC deadline mode) to fire at `deadline_ns` (see 'time_ns()').
+/// A deadline in the past fires immediately, while a deadline of 0 disarms the timer.
+pub fn set_deadline_ns(deadline_ns: u64) {
+    let frequency = frequency().expect("TSC: Frequency unknown!");
+    let ticks = if deadline_ns == 0 { 0 } else { max_ticks(deadline_ns as u128 * frequency as u128 / 1000000000) };
+
+    unsafe { Msr::new(IA32_TSC_DEADLINE).write(ticks); }
+}
+
+fn max_ticks(ticks: u128) -> u64 {
+    return if ticks > u64::MAX as u128 { u64::MAX } else { ticks as u64 };
+}
+
+/// Count TSC ticks, while the HPET advances by 'CALIBRATION_NS' (returns 0, if no HPET is available).
+fn calibrate() -> u64 {
+    let hpet = match hpet() {
+        Some(hpet) => hpet,
+        None => return 0
+    };
+
+    let start_ns = hpet.time_ns();
+    let start_ticks = unsafe { _rdtsc() };
+
+    let mut end_ns = hpet.time_ns();
+    while end_ns - start_ns < CALIBRATION_NS {
+        spin_loop();
+        end_ns = hpet.time_ns();
+    }
+
+    let end_ticks = unsafe { _rdtsc() };
+    return ((end_ticks - start_ticks) as u128 * 1000000000 / (end_ns - start_ns) as u128) as u64;
+}
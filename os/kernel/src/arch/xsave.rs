@@ -0,0 +1,149 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::slice;
+use raw_cpuid::CpuId;
+use spin::Once;
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+use x86_64::registers::xcontrol::{XCr0, XCr0Flags};
+use crate::arch::cpuid;
+
+/// Size of the legacy area (x87 and SSE registers), used by 'FXSAVE' and at the start of the 'XSAVE' area.
+const FXSAVE_AREA_SIZE: usize = 512;
+const FCW_OFFSET: usize = 0;
+const MXCSR_OFFSET: usize = 24;
+
+// Values after processor reset (like after 'FINIT', with all SIMD exceptions masked)
+const FCW_DEFAULT: u16 = 0x037f;
+const MXCSR_DEFAULT: u32 = 0x1f80;
+
+#[derive(Copy, Clone, PartialEq)]
+enum SaveMode {
+    None,
+    FxSave,
+    XSave
+}
+
+/// Instructions used to save the FPU state and size of the save area in bytes.
+static SAVE_MODE: Once<(SaveMode, usize)> = Once::new();
+
+#[repr(C, align(64))]
+#[derive(Copy, Clone)]
+struct Chunk([u8; 64]);
+
+/// FPU/SSE/AVX registers of a thread, saved on thread switches.
+/// The area is 64-byte aligned, as required by 'XSAVE'.
+pub struct FpuArea {
+    chunks: Vec<Chunk>
+}
+
+/// Enable the FPU and SSE (and AVX, if supported by 'XSAVE') and choose the instructions to save their state.
+/// With the FPU emulator, the FPU must stay disabled, so no state is saved (the emulator keeps its own state per thread).
+/// Must be called once during boot, before the first thread is created.
+pub fn init() {
+    SAVE_MODE.call_once(|| {
+        if cfg!(feature = "fpu_emulate") {
+            return (SaveMode::None, 0);
+        }
+
+        let features = cpuid::features();
+        let mode = if features.has_fxsave() && features.has_xsave() {
+            SaveMode::XSave
+        } else if features.has_fxsave() {
+            SaveMode::FxSave
+        } else {
+            return (SaveMode::None, 0);
+        };
+
+        enable_mode(mode);
+        if mode == SaveMode::FxSave {
+            return (SaveMode::FxSave, FXSAVE_AREA_SIZE);
+        }
+
+        // The area size depends on the components enabled in XCR0, so it must be queried after writing XCR0
+        let size = CpuId::new().get_extended_state_info().map_or(FXSAVE_AREA_SIZE + 64, |info| info.xsave_area_size_enabled_features() as usize);
+        return (SaveMode::XSave, size);
+    });
+}
+
+/// Enable the FPU in the same configuration on the executing CPU (see 'boot::init_cpu()').
+pub fn enable() {
+    enable_mode(save_mode().0);
+}
+
+/// Check if SSE has been enabled by 'init()' (and thus by 'enable()' on each CPU), so that XMM registers may be used.
+pub fn sse_enabled() -> bool {
+    return SAVE_MODE.get().map_or(false, |(mode, _)| *mode != SaveMode::None);
+}
+
+fn enable_mode(mode: SaveMode) {
+    if mode == SaveMode::None {
+        return;
+    }
+
+    unsafe {
+        Cr0::update(|flags| {
+            flags.remove(Cr0Flags::EMULATE_COPROCESSOR | Cr0Flags::TASK_SWITCHED);
+            flags.insert(Cr0Flags::MONITOR_COPROCESSOR);
+        });
+        Cr4::update(|flags| flags.insert(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE));
+    }
+
+    if mode == SaveMode::XSave {
+        let mut components = XCr0Flags::X87 | XCr0Flags::SSE;
+        if cpuid::features().has_avx() {
+            components |= XCr0Flags::AVX;
+        }
+
+        unsafe {
+            Cr4::update(|flags| flags.insert(Cr4Flags::OSXSAVE));
+            XCr0::write(components);
+        }
+    }
+}
+
+fn save_mode() -> (SaveMode, usize) {
+    return *SAVE_MODE.get().expect("FPU: Save mode accessed before initialization!");
+}
+
+impl FpuArea {
+    /// Create an area in the processor's reset state.
+    /// For 'XSAVE', the zeroed header marks all components as being in their initial state,
+    /// but the control registers are loaded from the legacy area in both formats.
+    pub fn new() -> Self {
+        let size = save_mode().1;
+        let mut area = Self { chunks: vec![Chunk([0; 64]); size.div_ceil(64)] };
+
+        if size > 0 {
+            let bytes = area.as_bytes_mut();
+            bytes[FCW_OFFSET..FCW_OFFSET + 2].copy_from_slice(&FCW_DEFAULT.to_le_bytes());
+            bytes[MXCSR_OFFSET..MXCSR_OFFSET + 4].copy_from_slice(&MXCSR_DEFAULT.to_le_bytes());
+        }
+
+        return area;
+    }
+
+    /// Save the current FPU registers into this area.
+    pub fn save(&mut self) {
+        let area = self.chunks.as_mut_ptr();
+        match save_mode().0 {
+            SaveMode::XSave => unsafe { asm!("xsave64 [{}]", in(reg) area, in("eax") u32::MAX, in("edx") u32::MAX, options(nostack, preserves_flags)); },
+            SaveMode::FxSave => unsafe { asm!("fxsave64 [{}]", in(reg) area, options(nostack, preserves_flags)); },
+            SaveMode::None => {}
+        }
+    }
+
+    /// Load the FPU registers from this area.
+    pub fn restore(&self) {
+        let area = self.chunks.as_ptr();
+        match save_mode().0 {
+            SaveMode::XSave => unsafe { asm!("xrstor64 [{}]", in(reg) area, in("eax") u32::MAX, in("edx") u32::MAX, options(nostack, preserves_flags)); },
+            SaveMode::FxSave => unsafe { asm!("fxrstor64 [{}]", in(reg) area, options(nostack, preserves_flags)); },
+            SaveMode::None => {}
+        }
+    }
+
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        return unsafe { slice::from_raw_parts_mut(self.chunks.as_mut_ptr() as *mut u8, self.chunks.len() * 64) };
+    }
+}
@@ -0,0 +1,178 @@
+use alloc::alloc::{alloc, dealloc};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::alloc::Layout;
+use core::arch::x86_64::_rdtsc;
+use core::hint::{black_box, spin_loop};
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::{Acquire, Release};
+use library_graphic::buffered_lfb::BufferedLFB;
+use library_graphic::color;
+use library_graphic::lfb::{CHAR_HEIGHT, CHAR_WIDTH, LFB};
+use library_thread::usr_thread_yield;
+use crate::memory::{MemorySpace, PAGE_SIZE};
+use crate::thread::thread::Thread;
+use crate::{allocator, memory, scheduler, timer};
+
+/// Number of operations, each benchmark is averaged over.
+const ITERATIONS: u64 = 10000;
+
+/// Time (measured with the system timer), during which TSC ticks are counted to convert them to nanoseconds.
+const CALIBRATION_MS: usize = 10;
+
+/// Sizes used by 'run_benchmarks()' for 'bench_alloc_free()'.
+const ALLOC_SIZES: [usize; 4] = [16, 256, 4096, 65536];
+
+/// Resolution of the memory-backed framebuffer used by 'bench_lfb_flush()'.
+/// Back buffer and shadow copy are allocated on the kernel heap, which is too small for two 1920x1080 buffers.
+const LFB_WIDTH: u32 = 640;
+const LFB_HEIGHT: u32 = 480;
+const LFB_BPP: u8 = 16;
+
+/// Number of flushes, 'bench_lfb_flush()' is averaged over (a full flush copies the whole screen, so fewer than 'ITERATIONS').
+const LFB_ITERATIONS: u64 = 100;
+
+/// Run all benchmarks and print their results to the terminal. Used as entry of a kernel thread (see 'boot.rs').
+pub fn run_benchmarks() {
+    println!("Running benchmarks ([{}] iterations each)", ITERATIONS);
+    println!("System call round trip: [{}] ns", bench_syscall_roundtrip());
+    println!("Thread switch: [{}] ns", bench_thread_switch());
+    for size in ALLOC_SIZES {
+        println!("Allocation and free of [{}] bytes: [{}] ns", size, bench_alloc_free(size));
+    }
+
+    let (full_ns, full_bytes) = bench_lfb_flush(false);
+    let (diff_ns, diff_bytes) = bench_lfb_flush(true);
+    println!("Framebuffer flush ([{}x{}], one changed cursor cell): [{}] ns, [{}] bytes written", LFB_WIDTH, LFB_HEIGHT, full_ns, full_bytes);
+    println!("Framebuffer diff flush ([{}x{}], one changed cursor cell): [{}] ns, [{}] bytes written", LFB_WIDTH, LFB_HEIGHT, diff_ns, diff_bytes);
+    println!("Lock contentions: heap [{}], scheduler state [{}]", allocator().lock_contentions(), scheduler().state_contentions());
+}
+
+/// Average duration of a 'ThreadYield' system call, issued by a user thread (without another thread to switch to, it returns immediately).
+pub fn bench_syscall_roundtrip() -> u64 {
+    let elapsed = Arc::new(AtomicU64::new(0));
+    let result = Arc::clone(&elapsed);
+
+    // System calls can only be issued from user mode (kernel code is accessible by user threads)
+    let thread = Thread::new_user_thread(Box::new(move || {
+        let start = unsafe { _rdtsc() };
+        for _ in 0..ITERATIONS {
+            usr_thread_yield();
+        }
+
+        result.store(unsafe { _rdtsc() } - start, Release);
+    }), None);
+
+    let id = thread.id();
+    scheduler().ready(thread);
+    scheduler().try_join(id);
+
+    return ticks_to_ns(elapsed.load(Acquire)) / ITERATIONS;
+}
+
+/// Average duration of a context switch, forced alternately by two kernel threads, that are pinned to the same CPU.
+pub fn bench_thread_switch() -> u64 {
+    let start = Arc::new(AtomicU64::new(0));
+    let end = Arc::new(AtomicU64::new(0));
+
+    let first = Thread::new_kernel_thread(switch_loop(Arc::clone(&start), Arc::clone(&end)), None);
+    let second = Thread::new_kernel_thread(switch_loop(Arc::clone(&start), Arc::clone(&end)), None);
+    first.set_affinity_mask(1);
+    second.set_affinity_mask(1);
+
+    let first_id = first.id();
+    let second_id = second.id();
+    scheduler().ready(first);
+    scheduler().ready(second);
+    scheduler().try_join(first_id);
+    scheduler().try_join(second_id);
+
+    return ticks_to_ns(end.load(Acquire) - start.load(Acquire)) / ITERATIONS;
+}
+
+/// Average duration of allocating and freeing `size` bytes on the kernel heap.
+pub fn bench_alloc_free(size: usize) -> u64 {
+    let layout = Layout::from_size_align(size, 8).expect("Bench: Invalid allocation size!");
+
+    let start = unsafe { _rdtsc() };
+    for _ in 0..ITERATIONS {
+        unsafe {
+            let ptr = black_box(alloc(layout));
+            if ptr.is_null() {
+                panic!("Bench: Out of memory!");
+            }
+
+            dealloc(ptr, layout);
+        }
+    }
+
+    return ticks_to_ns(unsafe { _rdtsc() } - start) / ITERATIONS;
+}
+
+/// Average duration and number of bytes written to the framebuffer of 'BufferedLFB::diff_flush()' (if `diff` is set) or 'BufferedLFB::flush()',
+/// when only a single cursor cell changes between two flushes. The framebuffer is backed by ordinary memory, so the screen is not touched.
+pub fn bench_lfb_flush(diff: bool) -> (u64, usize) {
+    let pitch = LFB_WIDTH * ((LFB_BPP as u32 + 7) / 8);
+    let size = (pitch * LFB_HEIGHT) as usize;
+    let frames = memory::physical::alloc(size.div_ceil(PAGE_SIZE), MemorySpace::Kernel);
+    let mut lfb = BufferedLFB::new(LFB::new(frames.start.start_address().as_u64() as *mut u8, pitch, LFB_WIDTH, LFB_HEIGHT, LFB_BPP));
+
+    // The first diff flush always copies the whole screen (and creates the shadow copy), so it is not measured
+    lfb.lfb().clear();
+    if diff { lfb.diff_flush(); } else { lfb.flush(); }
+
+    let mut written = 0;
+    let start = unsafe { _rdtsc() };
+    for i in 0..LFB_ITERATIONS {
+        let cursor_color = if i % 2 == 0 { &color::WHITE } else { &color::BLACK };
+        lfb.region_lfb(0, 0, CHAR_WIDTH, CHAR_HEIGHT).fill_rect(0, 0, CHAR_WIDTH, CHAR_HEIGHT, cursor_color);
+
+        if diff {
+            written += lfb.diff_flush();
+        } else {
+            lfb.flush();
+            written += size;
+        }
+    }
+
+    let elapsed = unsafe { _rdtsc() } - start;
+    drop(lfb);
+    unsafe { memory::physical::free(frames); }
+
+    return (ticks_to_ns(elapsed) / LFB_ITERATIONS, written / LFB_ITERATIONS as usize);
+}
+
+/// Each of the two threads forces half of the switches. The first one to start records the start time, the last one to finish the end time.
+fn switch_loop(start: Arc<AtomicU64>, end: Arc<AtomicU64>) -> Box<dyn FnMut()> {
+    return Box::new(move || {
+        let _ = start.compare_exchange(0, unsafe { _rdtsc() }, Release, Acquire);
+        for _ in 0..ITERATIONS / 2 {
+            scheduler().switch_thread();
+        }
+
+        end.store(unsafe { _rdtsc() }, Release);
+    });
+}
+
+/// Convert TSC ticks to nanoseconds, using the system time (driven by the calibrated local APIC timer) as reference.
+fn ticks_to_ns(ticks: u64) -> u64 {
+    let ticks_per_ms = tsc_ticks_per_ms();
+    return (ticks as u128 * 1000000 / ticks_per_ms as u128) as u64;
+}
+
+/// Count the TSC ticks during 'CALIBRATION_MS' milliseconds of system time.
+fn tsc_ticks_per_ms() -> u64 {
+    // Start at the beginning of a timer tick, to avoid measuring a fraction of a tick
+    let tick = timer().read().systime_ms();
+    while timer().read().systime_ms() == tick {
+        spin_loop();
+    }
+
+    let start_ms = timer().read().systime_ms();
+    let start = unsafe { _rdtsc() };
+    while timer().read().systime_ms() - start_ms < CALIBRATION_MS {
+        spin_loop();
+    }
+
+    return (unsafe { _rdtsc() } - start) / CALIBRATION_MS as u64;
+}
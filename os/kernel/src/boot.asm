@@ -7,6 +7,12 @@
 ; Kernel constants
 STACK_SIZE equ 0x10000
 
+; Application processor constants (see 'smp.rs')
+TRAMPOLINE_ADDRESS equ 0x8000
+IA32_EFER equ 0xc0000080
+CR4_PAE equ 0x20
+CR0_PG_WP_NE_ET_MP_PE equ 0x80010033
+
 ; Multiboot2 constants
 MULTIBOOT2_HEADER_MAGIC equ 0xe85250d6
 MULTIBOOT2_HEADER_ARCHITECTURE equ 0
@@ -155,6 +161,85 @@ clear_bss:
     mov esi, ebx
     call start
 
+; Entry point of application processors, which start in real mode at TRAMPOLINE_ADDRESS after receiving a startup IPI.
+; The code is copied there by 'smp::init()', so all addresses are relative to the copy.
+; It switches directly to long mode with the kernel page tables (which identity map the trampoline)
+; and calls the entry function with the prepared stack and the processor's startup information as parameter.
+[BITS 16]
+global ap_trampoline
+ap_trampoline:
+    cli
+    cld
+    xor ax, ax
+    mov ds, ax
+
+    lgdt [TRAMPOLINE_ADDRESS + (ap_trampoline.gdt_descriptor - ap_trampoline)]
+
+    ; Enable physical address extension and load the kernel page tables (must be located below 4 GiB)
+    mov eax, cr4
+    or eax, CR4_PAE
+    mov cr4, eax
+    mov eax, [TRAMPOLINE_ADDRESS + (ap_trampoline_params.cr3 - ap_trampoline)]
+    mov cr3, eax
+
+    ; Enable long mode (and the no-execute bit, if used by the bootstrap processor)
+    mov ecx, IA32_EFER
+    rdmsr
+    or eax, [TRAMPOLINE_ADDRESS + (ap_trampoline_params.efer - ap_trampoline)]
+    wrmsr
+
+    ; Enable protected mode and paging at once (this also clears the cache disable bits, set after INIT)
+    mov eax, CR0_PG_WP_NE_ET_MP_PE
+    mov cr0, eax
+    jmp dword 0x08:(TRAMPOLINE_ADDRESS + (ap_trampoline.long_mode - ap_trampoline))
+
+[BITS 64]
+.long_mode:
+    mov ax, 0x10
+    mov ds, ax
+    mov es, ax
+    mov ss, ax
+    xor ax, ax
+    mov fs, ax
+    mov gs, ax
+
+    mov rsp, [TRAMPOLINE_ADDRESS + (ap_trampoline_params.stack - ap_trampoline)]
+    mov rdi, [TRAMPOLINE_ADDRESS + (ap_trampoline_params.cpu - ap_trampoline)]
+    mov rax, [TRAMPOLINE_ADDRESS + (ap_trampoline_params.entry - ap_trampoline)]
+    call rax
+
+.halt:
+    hlt
+    jmp .halt
+
+    ; Temporary GDT with a 64-bit code segment (selector 0x08) and a data segment (selector 0x10)
+    align 8
+.gdt:
+    dq 0x0000000000000000
+    dq 0x00af9a000000ffff
+    dq 0x00cf92000000ffff
+.gdt_descriptor:
+    dw .gdt_descriptor - .gdt - 1
+    dd TRAMPOLINE_ADDRESS + (.gdt - ap_trampoline)
+
+    ; Parameters, written by 'smp::init()' before each startup IPI (layout must match 'TrampolineParams')
+    align 8
+global ap_trampoline_params
+ap_trampoline_params:
+.cr3:
+    dq 0
+.efer:
+    dq 0
+.stack:
+    dq 0
+.entry:
+    dq 0
+.cpu:
+    dq 0
+
+global ap_trampoline_end
+ap_trampoline_end:
+
 [SECTION .bss]
 
 global init_stack:data (init_stack.end - init_stack)
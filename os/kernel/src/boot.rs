@@ -1,21 +1,28 @@
 use crate::interrupt::interrupt_dispatcher;
 use crate::syscall::syscall_dispatcher;
 use crate::thread::thread::Thread;
+use crate::arch::{cpuid, fsbase, pkey, tsc, xsave};
+use crate::arch::iopb::TssWithIopb;
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::cmp::max;
 use core::ffi::c_void;
 use core::fmt::Arguments;
 use core::mem::size_of;
-use core::ops::Deref;
+use core::ops::{Deref, DerefMut};
 use core::panic::PanicInfo;
 use core::ptr;
-use chrono::DateTime;
+use chrono::{DateTime, NaiveDate};
 use log::{debug, error, info, Level, Log, Record};
+use library_io::stream::OutputStream;
 use multiboot2::{BootInformation, BootInformationHeader, EFIMemoryMapTag, MemoryAreaType, MemoryMapTag, Tag};
 use uefi::prelude::*;
+use uefi::proto::rng::Rng;
 use uefi::table::boot::{MemoryMap, PAGE_SIZE};
+use uefi::table::runtime::Time;
 use uefi::table::Runtime;
 use uefi_raw::table::boot::MemoryType;
 use x86_64::instructions::interrupts;
@@ -23,19 +30,37 @@ use x86_64::instructions::segmentation::{Segment, CS, DS, ES, FS, GS, SS};
 use x86_64::instructions::tables::load_tss;
 use x86_64::{PhysAddr, VirtAddr};
 use x86_64::registers::segmentation::SegmentSelector;
-use x86_64::structures::gdt::Descriptor;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable};
 use x86_64::structures::paging::{Page, PageTableFlags, PhysFrame};
 use x86_64::PrivilegeLevel::Ring0;
 use x86_64::registers::control::{Cr3, Cr3Flags};
 use x86_64::structures::paging::frame::PhysFrameRange;
 use x86_64::structures::paging::page::PageRange;
-use crate::{allocator, efi_system_table, gdt, init_acpi_tables, init_apic, init_efi_system_table, init_keyboard, init_serial_port, init_terminal, logger, memory, ps2_devices, scheduler, serial_port, terminal, terminal_initialized, timer, tss};
+use crate::{allocator, cmdline, efi_system_table, entropy_pool, gdt, hpet, init_acpi_tables, init_apic, init_cmdline, init_hpet, init_iommu, init_efi_system_table, init_keyboard, init_modules, init_serial_port, init_terminal, init_virtio_blk, iommu, logger, memory, module, modules, ps2_devices, scheduler, serial_port, terminal, timer, tss, vfs, virtio_blk};
+use crate::crypto::entropy::SEED_BITS;
 use crate::memory::MemorySpace;
+use crate::debug::backtrace::Backtrace;
+use crate::debug::gdb_stub;
+use crate::debug::panic_screen;
+use crate::debug::panic_screen::CrashDump;
+use crate::module::KernelModule;
+use crate::fs::fat32::Fat32Volume;
+use crate::fs::tmpfs::RamFs;
+use crate::device::{keyboard_layout, pit, vbe};
+use crate::device::vbe::FbInfo;
+use crate::{bench, percpu, smp};
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    if terminal_initialized() {
-        println!("Panic: {}", info);
+    interrupts::disable();
+
+    // The crash dump is drawn over the terminal's content (or directly to the framebuffer, if the terminal has not been initialized yet)
+    let dump = CrashDump::new(info, &Backtrace::current());
+    panic_screen::draw(&dump);
+
+    // While the GDB stub is active, the serial port is reserved for the debugger
+    if let Some(serial) = serial_port().filter(|_| !gdb_stub::is_active()) {
+        serial.write_str(dump.as_str());
     } else {
         let record = Record::builder()
             .level(Level::Error)
@@ -43,7 +68,10 @@ fn panic(info: &PanicInfo) -> ! {
             .args(*info.message().unwrap_or(&Arguments::new_const(&["A panic occurred!"])))
             .build();
 
-        unsafe { logger().force_unlock() };
+        unsafe {
+            logger().force_unlock();
+            crate::log::force_unlock_early_log();
+        }
         let log = logger().lock();
         unsafe { logger().force_unlock() }; // log() also calls logger().lock()
         log.log(&record);
@@ -81,6 +109,11 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
 
     let multiboot = unsafe { BootInformation::load(multiboot2_addr).expect("Failed to get Multiboot2 information!") };
 
+    // The kernel heap must be placed behind the kernel image and all modules, loaded by the bootloader
+    let reserved_end = multiboot.module_tags()
+        .map(|tag| module::frame_range(tag).end)
+        .fold(kernel_image_region().end, max);
+
     let mut heap_region = PhysFrameRange { start: PhysFrame::from_start_address(PhysAddr::zero()).unwrap(), end: PhysFrame::from_start_address(PhysAddr::zero()).unwrap() };
     let bootloader_memory_regions: Vec<PhysFrameRange>;
 
@@ -100,38 +133,64 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
             system_table.boot_services().set_image_handle(image_handle);
         }
 
+        // The EFI random number generator is only accessible via boot services, so we need to use it now
+        {
+            let mut efi_seed = [0u8; SEED_BITS / 8];
+            let rng = system_table.boot_services().get_handle_for_protocol::<Rng>()
+                .and_then(|handle| system_table.boot_services().open_protocol_exclusive::<Rng>(handle));
+            if let Ok(mut rng) = rng {
+                if rng.get_rng(None, &mut efi_seed).is_ok() {
+                    info!("Seeding entropy pool with EFI random number generator");
+                    entropy_pool().lock().add(&efi_seed, SEED_BITS);
+                }
+            }
+        }
+
         info!("Exiting EFI boot services to obtain runtime system table and memory map");
         let (runtime_table, memory_map) = system_table.exit_boot_services(MemoryType::LOADER_DATA);
 
-        bootloader_memory_regions = scan_efi_memory_map(&memory_map, &mut heap_region);
+        bootloader_memory_regions = scan_efi_memory_map(&memory_map, reserved_end, &mut heap_region);
         init_efi_system_table(runtime_table);
     } else {
         info!("EFI boot services have been exited");
         if let Some(memory_map) = multiboot.efi_memory_map_tag() {
             // EFI services have been exited, but the bootloader has provided us with the EFI memory map
             info!("Bootloader provides EFI memory map");
-            bootloader_memory_regions = scan_efi_multiboot2_memory_map(memory_map, &mut heap_region);
+            bootloader_memory_regions = scan_efi_multiboot2_memory_map(memory_map, reserved_end, &mut heap_region);
         } else if let Some(memory_map) = multiboot.memory_map_tag() {
             // EFI services have been exited, but the bootloader has provided us with a Multiboot2 memory map
             info!("Bootloader provides Multiboot2 memory map");
-            bootloader_memory_regions = scan_multiboot2_memory_map(memory_map, &mut heap_region);
+            bootloader_memory_regions = scan_multiboot2_memory_map(memory_map, reserved_end, &mut heap_region);
         } else {
             panic!("No memory information available!");
         }
     }
 
+    // Detect CPU features once, so that all feature-specific code paths can check them
+    info!("Detecting CPU features");
+    cpuid::init();
+    info!("CPU features: {:?}", cpuid::features());
+
     // Setup global descriptor table
     // Has to be done after EFI boot services have been exited, since they rely on their own GDT
     info!("Initializing GDT");
     init_gdt();
 
-    // The bootloader marks the kernel image region as available, so we need to check for regions overlapping
-    // with the kernel image and temporary heap and build a new memory map with the kernel image and heap cut out.
+    // Enable FPU/SSE and determine the size of the FPU save area, which is needed to create threads
+    info!("Initializing FPU");
+    xsave::init();
+
+    // The bootloader marks the kernel image region (and modules) as available, so we need to check for regions overlapping
+    // with the kernel image, modules, temporary heap and the trampoline for application processors and build a new memory map with them cut out.
     // Furthermore, we need to make sure, that no region starts at address 0, to avoid null pointer panics.
     let null_region = PhysFrameRange { start: PhysFrame::from_start_address(PhysAddr::zero()).unwrap(), end: PhysFrame::from_start_address(PhysAddr::new(PAGE_SIZE as u64)).unwrap() };
     let mut available_memory_regions = cut_region(bootloader_memory_regions, null_region);
+    available_memory_regions = cut_region(available_memory_regions, smp::trampoline_frames());
     available_memory_regions = cut_region(available_memory_regions, kernel_image_region());
     available_memory_regions = cut_region(available_memory_regions, heap_region);
+    for tag in multiboot.module_tags() {
+        available_memory_regions = cut_region(available_memory_regions, module::frame_range(tag));
+    }
 
     // Initialize physical memory management
     info!("Initializing page frame allocator");
@@ -142,6 +201,9 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
     let address_space = memory::r#virtual::create_address_space();
     unsafe { Cr3::write(address_space.read().page_table_address(), Cr3Flags::empty()) };
 
+    // Map modules, loaded by the bootloader, so that they can be accessed by the kernel and via system calls
+    init_modules(multiboot.module_tags().map(|tag| KernelModule::map(tag)).collect());
+
     // Initialize serial port and enable serial logging
     init_serial_port();
     if let Some(serial) = serial_port() {
@@ -149,15 +211,21 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
     }
 
     // Initialize terminal and enable terminal logging
-    let fb_info = multiboot.framebuffer_tag()
-        .expect("No framebuffer information provided by bootloader!")
-        .expect("Unknown framebuffer type!");
+    // On BIOS systems, the VBE mode information is preferred over the framebuffer tag (without EFI GOP, the bootloader has set the mode via VBE)
+    let fb_info = vbe::vbe_probe(&multiboot).unwrap_or_else(|| {
+        let tag = multiboot.framebuffer_tag()
+            .expect("No framebuffer information provided by bootloader!")
+            .expect("Unknown framebuffer type!");
+
+        FbInfo::from_framebuffer_tag(tag)
+    });
 
-    let fb_start_page = Page::from_start_address(VirtAddr::new(fb_info.address())).expect("Framebuffer address is not page aligned!");
-    let fb_end_page = Page::from_start_address(VirtAddr::new(fb_info.address() + (fb_info.height() * fb_info.pitch()) as u64).align_up(PAGE_SIZE as u64)).unwrap();
+    let fb_start_page = Page::from_start_address(VirtAddr::new(fb_info.address)).expect("Framebuffer address is not page aligned!");
+    let fb_end_page = Page::from_start_address(VirtAddr::new(fb_info.address + fb_info.size()).align_up(PAGE_SIZE as u64)).unwrap();
     address_space.write().map(PageRange { start: fb_start_page, end: fb_end_page }, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::NO_CACHE);
 
-    init_terminal(fb_info.address() as *mut u8, fb_info.pitch(), fb_info.width(), fb_info.height(), fb_info.bpp());
+    panic_screen::init(fb_info.address as *mut u8, fb_info.pitch, fb_info.width, fb_info.height, fb_info.bpp);
+    init_terminal(fb_info.address as *mut u8, fb_info.pitch, fb_info.width, fb_info.height, fb_info.bpp);
     logger().lock().register(terminal());
 
     info!("Welcome to hhuTOSr!");
@@ -173,42 +241,102 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
         None => "Unknown",
     };
 
+    // Parse kernel command line and apply logger and serial port settings
+    let cmdline_str = multiboot.command_line_tag().and_then(|tag| tag.cmdline().ok()).unwrap_or("");
+    init_cmdline(cmdline::parse(cmdline_str));
+    if let Some(level) = cmdline().log_level() {
+        logger().lock().set_level(level);
+    }
+    if let Some(baud_rate) = cmdline().serial_baud_rate() {
+        if let Some(serial) = serial_port() {
+            serial.speed(baud_rate);
+        }
+    }
+
     info!("OS Version: [{}]", version);
     info!("Git Version: [{} - {}]", built_info::GIT_HEAD_REF.unwrap_or_else(|| "Unknown"), git_commit);
     info!("Build Date: [{}]", build_date);
     info!("Compiler: [{}]", built_info::RUSTC_VERSION);
     info!("Bootloader: [{}]", bootloader_name);
+    info!("Command line: [{}]", cmdline_str);
 
-    // Initialize ACPI tables
-    let rsdp_addr: usize = if let Some(rsdp_tag) = multiboot.rsdp_v2_tag() {
-        ptr::from_ref(rsdp_tag) as usize + size_of::<Tag>()
-    } else if let Some(rsdp_tag) = multiboot.rsdp_v1_tag() {
-        ptr::from_ref(rsdp_tag) as usize + size_of::<Tag>()
+    // Initialize ACPI tables (unless disabled on the kernel command line)
+    if cmdline().no_acpi() {
+        info!("ACPI disabled on kernel command line -> IOMMU, HPET, application processors and ACPI power management are not available");
     } else {
-        panic!("ACPI not available!");
-    };
+        let rsdp_addr: usize = if let Some(rsdp_tag) = multiboot.rsdp_v2_tag() {
+            ptr::from_ref(rsdp_tag) as usize + size_of::<Tag>()
+        } else if let Some(rsdp_tag) = multiboot.rsdp_v1_tag() {
+            ptr::from_ref(rsdp_tag) as usize + size_of::<Tag>()
+        } else {
+            panic!("ACPI not available!");
+        };
 
-    init_acpi_tables(rsdp_addr);
+        init_acpi_tables(rsdp_addr);
+    }
+
+    // Initialize DMA remapping (if available and enabled on the kernel command line)
+    if cmdline().iommu() {
+        info!("Initializing IOMMU");
+        init_iommu();
+        if iommu().is_none() {
+            info!("No IOMMU available -> DMA remapping disabled");
+        }
+    } else {
+        info!("IOMMU not enabled on kernel command line -> DMA remapping disabled");
+    }
 
-    // Initialize interrupts
-    info!("Initializing IDT");
+    // Initialize interrupts and system calls
+    info!("Initializing IDT and system calls");
     interrupt_dispatcher::setup_idt();
-    info!("Initializing system calls");
-    syscall_dispatcher::init();
+    fsbase::init();
+    pkey::init();
+    // The global GDT and TSS have a static lifetime (see 'init_gdt()')
+    init_cpu(0, unsafe { ptr::from_ref(gdt().lock().deref()).as_ref().unwrap() }, ptr::from_mut(tss().lock().deref_mut()));
+    if !pkey::pkeys_available() {
+        info!("CPU does not support protection keys -> pkey system calls disabled");
+    }
+
+    // Wait for GDB to connect, if requested on the kernel command line
+    if cmdline().flag("gdb") {
+        gdb_stub::init();
+    }
+
     init_apic();
+    init_hpet();
+    tsc::init();
 
     // Initialize timer
     {
         info!("Initializing timer");
         let mut timer = timer().write();
         timer.interrupt_rate(1);
+        info!("Local APIC timer calibrated ([{}] ticks per millisecond)", pit::calibrate_apic_timer());
         timer.plugin();
     }
 
+    if tsc::deadline_available() {
+        info!("Using TSC deadline timer for precise wakeups");
+    } else if hpet().is_some() {
+        info!("Using HPET for precise wakeups");
+    } else if timer().read().precise_time_ns().is_some() {
+        info!("Using local APIC timer for precise wakeups");
+    } else {
+        info!("Neither TSC deadline timer, HPET nor calibrated local APIC timer available -> Precise sleep falls back to timer ticks");
+    }
+
+    // Collect initial entropy (interrupt timings are added continuously from now on)
+    entropy_pool().lock().add_cpu_randomness();
+    info!("Entropy pool initialized with [{}] bits of entropy", entropy_pool().lock().entropy_bits());
+
     // Enable interrupts
     info!("Enabling interrupts");
     interrupts::enable();
 
+    // Start application processors (waiting for them requires the timer interrupt)
+    info!("Starting application processors");
+    smp::init();
+
     // Initialize EFI runtime service (if available and not done already during memory initialization)
     if efi_system_table().is_none() {
         if let Some(sdt_tag) = multiboot.efi_sdt64_tag() {
@@ -224,19 +352,84 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
 
     if let Some(system_table) = efi_system_table() {
         info!("EFI runtime services available (Vendor: [{}], UEFI version: [{}])", system_table.firmware_vendor(), system_table.uefi_revision());
+
+        // Initialize wall clock
+        match unsafe { system_table.runtime_services() }.get_time() {
+            Ok(time) => match efi_time_to_unix_ns(&time) {
+                Some(unix_time_ns) => {
+                    timer().write().set_wall_clock(unix_time_ns);
+                    info!("Wall clock initialized ([{}-{:0>2}-{:0>2} {:0>2}:{:0>2}:{:0>2}])", time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second());
+                }
+                None => error!("EFI returned an invalid time!")
+            },
+            Err(err) => error!("Failed to read time from EFI runtime services (Error: {:?})", err.status())
+        }
     }
 
-    // Initialize keyboard
+    // Initialize keyboard and mouse
     info!("Initializing PS/2 devices");
     init_keyboard();
+    if let Some(name) = cmdline().keyboard_layout() {
+        match keyboard_layout::from_name(name) {
+            Some(layout) => ps2_devices().keyboard().set_layout(layout),
+            None => error!("Unknown keyboard layout [{}]", name)
+        }
+    }
     ps2_devices().keyboard().plugin();
+    ps2_devices().mouse().plugin();
 
     // Enable serial port interrupts
     if let Some(serial) = serial_port() {
         serial.plugin();
     }
 
+    // Initialize block device (if available)
+    info!("Initializing VirtIO block device");
+    init_virtio_blk();
+    match virtio_blk() {
+        Some(device) => {
+            device.plugin();
+
+            info!("Mounting FAT32 volume at [/]");
+            match Fat32Volume::mount(device.clone()) {
+                Ok(volume) => vfs().write().mount("/", Arc::new(volume)),
+                Err(err) => error!("Failed to mount FAT32 volume (Error: {:?})", err)
+            }
+        }
+        None => info!("No VirtIO block device available")
+    }
+
+    info!("Mounting tmpfs at [/tmp]");
+    vfs().write().mount("/tmp", Arc::new(RamFs::new()));
+
+    for (index, module) in modules().iter().enumerate() {
+        info!("Module [{}]: [{}] ([{}] bytes)", index, module.name(), module.data().len());
+    }
+
     let scheduler = scheduler();
+
+    // The first module is expected to be the initial user program (unless another one is selected on the kernel command line)
+    let init_index = cmdline().init_module().unwrap_or(0);
+    if init_index >= modules().len() && !modules().is_empty() {
+        error!("Module [{}] selected as initial user program does not exist", init_index);
+    }
+
+    if let Some(module) = modules().get(init_index) {
+        scheduler.ready(Thread::new_kernel_thread(Box::new(move || {
+            match Thread::new_user_thread_from_elf(module.data(), None) {
+                Ok(thread) => {
+                    info!("Starting initial user program [{}]", module.name());
+                    scheduler.ready(thread);
+                }
+                Err(err) => error!("Failed to load initial user program [{}] (Error: {:?})", module.name(), err)
+            }
+        }), None));
+    }
+
+    if cmdline().flag("bench") {
+        scheduler.ready(Thread::new_kernel_thread(Box::new(|| bench::run_benchmarks()), None));
+    }
+
     scheduler.ready(Thread::new_kernel_thread(Box::new(|| {
         let terminal = terminal();
         terminal.write_str("> ");
@@ -248,7 +441,7 @@ pub extern "C" fn start(multiboot2_magic: u32, multiboot2_addr: *const BootInfor
                 _ => {}
             }
         }
-    })));
+    }), None));
 
     // Disable terminal logging
     logger().lock().remove(terminal());
@@ -271,20 +464,42 @@ fn init_gdt() {
     let mut gdt = gdt().lock();
     let tss = tss().lock();
 
-    gdt.add_entry(Descriptor::kernel_code_segment());
-    gdt.add_entry(Descriptor::kernel_data_segment());
-    gdt.add_entry(Descriptor::user_data_segment());
-    gdt.add_entry(Descriptor::user_code_segment());
-
     unsafe {
         // We need to obtain a static reference to the TSS and GDT for the following operations.
         // We know, that they have a static lifetime, since they are declared as static variables in 'kernel/mod.rs'.
         // However, since they are hidden behind a Mutex, the borrow checker does not see them with a static lifetime.
         let gdt_ref = ptr::from_ref(gdt.deref()).as_ref().unwrap();
         let tss_ref = ptr::from_ref(tss.deref()).as_ref().unwrap();
-        gdt.add_entry(Descriptor::tss_segment(tss_ref));
-        gdt_ref.load();
+        build_gdt(&mut gdt, tss_ref);
+        load_gdt(gdt_ref);
     }
+}
+
+/// Add the kernel and user segments and the TSS (selector 5) to an empty `gdt`.
+/// The bootstrap processor uses the global GDT and TSS, application processors get their own (see 'smp::init()').
+pub fn build_gdt(gdt: &mut GlobalDescriptorTable, tss: &'static TssWithIopb) {
+    gdt.add_entry(Descriptor::kernel_code_segment());
+    gdt.add_entry(Descriptor::kernel_data_segment());
+    gdt.add_entry(Descriptor::user_data_segment());
+    gdt.add_entry(Descriptor::user_code_segment());
+    gdt.add_entry(tss.descriptor());
+}
+
+/// Per-CPU initialization, executed by the bootstrap processor during boot and by each application processor in 'smp::ap_main()'.
+/// Loads `gdt` (see 'build_gdt()') and the IDT, sets up the per-CPU data (see 'percpu') and the system call registers
+/// and enables the CPU features detected during boot. `tss` must be the TSS referenced by `gdt` (it receives the I/O port permissions of each process, see 'iopb').
+pub fn init_cpu(cpu_id: u32, gdt: &'static GlobalDescriptorTable, tss: *mut TssWithIopb) {
+    load_gdt(gdt);
+    percpu::init(cpu_id, tss);
+    interrupt_dispatcher::load_idt();
+    syscall_dispatcher::init();
+    xsave::enable();
+    fsbase::enable();
+    pkey::enable();
+}
+
+fn load_gdt(gdt: &'static GlobalDescriptorTable) {
+    gdt.load();
 
     unsafe {
         // Load task state segment
@@ -314,13 +529,12 @@ fn kernel_image_region() -> PhysFrameRange {
     return PhysFrameRange { start, end };
 }
 
-fn scan_efi_memory_map(memory_map: &MemoryMap, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
+fn scan_efi_memory_map(memory_map: &MemoryMap, reserved_end: PhysFrame, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
     info!("Searching memory map for region usable for kernel heap");
-    let kernel_region = kernel_image_region();
     let heap_area = memory_map.entries()
         .filter(|area| (area.ty == MemoryType::CONVENTIONAL || area.ty == MemoryType::LOADER_CODE || area.ty == MemoryType::LOADER_DATA
             || area.ty == MemoryType::BOOT_SERVICES_CODE || area.ty == MemoryType::BOOT_SERVICES_DATA)
-            && area.page_count >= INIT_HEAP_PAGES as u64 && area.phys_start >= kernel_region.end.start_address().as_u64())
+            && area.page_count >= INIT_HEAP_PAGES as u64 && area.phys_start >= reserved_end.start_address().as_u64())
         .min_by(|area1, area2| area1.phys_start.cmp(&area2.phys_start))
         .expect("Failed to find memory region usable for kernel heap!");
 
@@ -335,19 +549,18 @@ fn scan_efi_memory_map(memory_map: &MemoryMap, heap_region: &mut PhysFrameRange)
             || area.ty == MemoryType::BOOT_SERVICES_CODE || area.ty == MemoryType::BOOT_SERVICES_DATA)
         .for_each(|area| {
             let start = PhysFrame::from_start_address(PhysAddr::new(area.phys_start).align_up(PAGE_SIZE as u64)).unwrap();
-            regions.push(PhysFrameRange { start, end: start + area.page_count });
+            regions.extend(memory::physical::split_by_zone(PhysFrameRange { start, end: start + area.page_count }));
         });
 
     return regions;
 }
 
-fn scan_efi_multiboot2_memory_map(memory_map: &EFIMemoryMapTag, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
+fn scan_efi_multiboot2_memory_map(memory_map: &EFIMemoryMapTag, reserved_end: PhysFrame, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
     info!("Searching memory map for region usable for kernel heap");
-    let kernel_region = kernel_image_region();
     let heap_area = memory_map.memory_areas().filter(|area|
         (area.ty.0 == MemoryType::CONVENTIONAL.0 || area.ty.0 == MemoryType::LOADER_CODE.0 || area.ty.0 == MemoryType::LOADER_DATA.0
             || area.ty.0 == MemoryType::BOOT_SERVICES_CODE.0 || area.ty.0 == MemoryType::BOOT_SERVICES_DATA.0) // .0 necessary because of different version dependencies to uefi-crate
-            && area.page_count >= INIT_HEAP_PAGES as u64 && area.phys_start >= kernel_region.end.start_address().as_u64())
+            && area.page_count >= INIT_HEAP_PAGES as u64 && area.phys_start >= reserved_end.start_address().as_u64())
         .min_by(|area1, area2| area1.phys_start.cmp(&area2.phys_start))
         .expect("Failed to find memory region usable for kernel heap!");
 
@@ -362,17 +575,16 @@ fn scan_efi_multiboot2_memory_map(memory_map: &EFIMemoryMapTag, heap_region: &mu
             || area.ty.0 == MemoryType::BOOT_SERVICES_CODE.0 || area.ty.0 == MemoryType::BOOT_SERVICES_DATA.0) // .0 necessary because of different version dependencies to uefi-crate
         .for_each(|area| {
             let start = PhysFrame::from_start_address(PhysAddr::new(area.phys_start).align_up(PAGE_SIZE as u64)).unwrap();
-            regions.push(PhysFrameRange { start, end: start + area.page_count });
+            regions.extend(memory::physical::split_by_zone(PhysFrameRange { start, end: start + area.page_count }));
         });
 
     return regions;
 }
 
-fn scan_multiboot2_memory_map(memory_map: &MemoryMapTag, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
+fn scan_multiboot2_memory_map(memory_map: &MemoryMapTag, reserved_end: PhysFrame, heap_region: &mut PhysFrameRange) -> Vec<PhysFrameRange> {
     info!("Searching memory map for region usable for kernel heap");
-    let kernel_region = kernel_image_region();
     let heap_area = memory_map.memory_areas().iter().filter(|area|
-        area.typ() == MemoryAreaType::Available && area.size() / PAGE_SIZE as u64 >= INIT_HEAP_PAGES as u64 && area.start_address() >= kernel_region.end.start_address().as_u64())
+        area.typ() == MemoryAreaType::Available && area.size() / PAGE_SIZE as u64 >= INIT_HEAP_PAGES as u64 && area.start_address() >= reserved_end.start_address().as_u64())
         .min_by(|area1, area2| area1.start_address().cmp(&area2.start_address()))
         .expect("Failed to find memory region usable for kernel heap!");
 
@@ -385,10 +597,10 @@ fn scan_multiboot2_memory_map(memory_map: &MemoryMapTag, heap_region: &mut PhysF
     memory_map.memory_areas().iter()
         .filter(|area| area.typ() == MemoryAreaType::Available)
         .for_each(|area| {
-            regions.push(PhysFrameRange {
+            regions.extend(memory::physical::split_by_zone(PhysFrameRange {
                 start: PhysFrame::from_start_address(PhysAddr::new(area.start_address()).align_up(PAGE_SIZE as u64)).unwrap(),
                 end: PhysFrame::from_start_address(PhysAddr::new(area.end_address()).align_down(PAGE_SIZE as u64)).unwrap()
-            });
+            }));
         });
 
     return regions;
@@ -417,4 +629,14 @@ fn cut_region(regions: Vec<PhysFrameRange>, reserved_region: PhysFrameRange) ->
     }
 
     return new_regions;
-}
\ No newline at end of file
+}
+
+/// Convert an EFI time (local time with optional offset in minutes from UTC) into nanoseconds since 1970-01-01 UTC.
+pub fn efi_time_to_unix_ns(time: &Time) -> Option<u64> {
+    let date_time = NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_nano_opt(time.hour() as u32, time.minute() as u32, time.second() as u32, time.nanosecond())?;
+    let offset_secs = time.time_zone().unwrap_or(0) as i64 * 60;
+    let unix_secs = date_time.and_utc().timestamp() - offset_secs;
+
+    return Some(u64::try_from(unix_secs).ok()? * 1000000000 + time.nanosecond() as u64);
+}
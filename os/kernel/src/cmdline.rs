@@ -0,0 +1,89 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+use log::LevelFilter;
+use crate::device::serial::BaudRate;
+
+/// Arguments passed to the kernel by the bootloader (Multiboot2 command line tag).
+/// Arguments are separated by whitespace and are either bare flags (e.g. 'no_acpi') or key/value pairs (e.g. 'log_level=4').
+#[derive(Default)]
+pub struct CmdlineArgs {
+    flags: Vec<String>,
+    values: Vec<(String, String)>
+}
+
+/// Split `s` on whitespace into flags and key/value pairs. If a key is given multiple times, the last value wins.
+pub fn parse(s: &str) -> CmdlineArgs {
+    let mut args = CmdlineArgs::default();
+
+    for arg in s.split_whitespace() {
+        match arg.split_once('=') {
+            Some((key, value)) => {
+                args.values.retain(|(existing, _)| existing != key);
+                args.values.push((key.to_string(), value.to_string()));
+            }
+            None => args.flags.push(arg.to_string())
+        }
+    }
+
+    return args;
+}
+
+impl CmdlineArgs {
+    /// Check if the bare flag `name` is set.
+    pub fn flag(&self, name: &str) -> bool {
+        return self.flags.iter().any(|flag| flag == name);
+    }
+
+    /// Get the value of the key/value pair `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        return self.values.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str());
+    }
+
+    /// Get the value of `name`, parsed as `T` ('None', if the key is missing or the value is invalid).
+    pub fn parsed_value<T: FromStr>(&self, name: &str) -> Option<T> {
+        return self.value(name).and_then(|value| value.parse().ok());
+    }
+
+    /// Filter level of the logger, given by 'log_level=<n>' (0 = off, 1 = error, ..., 5 = trace).
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        return match self.parsed_value::<usize>("log_level")? {
+            0 => Some(LevelFilter::Off),
+            1 => Some(LevelFilter::Error),
+            2 => Some(LevelFilter::Warn),
+            3 => Some(LevelFilter::Info),
+            4 => Some(LevelFilter::Debug),
+            5 => Some(LevelFilter::Trace),
+            _ => None
+        };
+    }
+
+    /// Baud rate of the serial port, given by 'serial=<baud>' (must be one of the rates in 'BaudRate').
+    pub fn serial_baud_rate(&self) -> Option<BaudRate> {
+        return BaudRate::from_bits_per_second(self.parsed_value("serial")?);
+    }
+
+    /// Skip parsing the ACPI tables, given by the flag 'no_acpi'.
+    pub fn no_acpi(&self) -> bool {
+        return self.flag("no_acpi");
+    }
+
+    /// Enable DMA remapping, given by the flag 'iommu'.
+    /// Off by default, since memory regions reserved for devices by the firmware (DMAR RMRR entries) are not mapped,
+    /// so that devices like an integrated GPU scanning out the framebuffer would lose access to them.
+    pub fn iommu(&self) -> bool {
+        return self.flag("iommu");
+    }
+
+    /// Name of the keyboard layout ('us', 'de' or 'uk'), given by 'kbd=<layout>'.
+    pub fn keyboard_layout(&self) -> Option<&str> {
+        return self.value("kbd");
+    }
+
+    /// Index of the Multiboot2 module, that is started as initial user program, given by 'init=<module_index>'.
+    pub fn init_module(&self) -> Option<usize> {
+        return self.parsed_value("init");
+    }
+}
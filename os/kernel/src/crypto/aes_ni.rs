@@ -0,0 +1,229 @@
+use core::arch::asm;
+use raw_cpuid::CpuId;
+use spin::Once;
+use x86_64::instructions::interrupts;
+use crate::arch::xsave;
+
+// AES-128 with hardware acceleration (AES-NI) and a pure software fallback for CPUs without it.
+
+const ROUNDS: usize = 10;
+const ROUND_CONSTANTS: [u8; ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+static AES_NI_SUPPORTED: Once<bool> = Once::new();
+
+/// Check for AES-NI support. The AES instructions operate on XMM registers, so SSE must have been enabled on all CPUs by 'xsave::init()'
+/// (and 'xsave::enable()'). Before that (e.g. while the entropy pool is seeded early during boot) and with the FPU emulator, the software implementation is used.
+pub fn aes_ni_available() -> bool {
+    let supported = *AES_NI_SUPPORTED.call_once(|| {
+        return match CpuId::new().get_feature_info() {
+            Some(features) => features.has_aesni() && features.has_sse2(),
+            None => false
+        };
+    });
+
+    return supported && xsave::sse_enabled();
+}
+
+pub fn aes128_encrypt_block(key: &[u8; 16], block: &mut [u8; 16]) {
+    Aes128::new(key).encrypt_block(block);
+}
+
+/// AES-128 cipher with expanded round keys.
+/// The round keys have the same layout for AES-NI and the software implementation, so only encryption differs.
+pub struct Aes128 {
+    round_keys: [[u8; 16]; ROUNDS + 1]
+}
+
+impl Aes128 {
+    pub fn new(key: &[u8; 16]) -> Self {
+        let mut round_keys = [[0u8; 16]; ROUNDS + 1];
+        round_keys[0] = *key;
+
+        for round in 1..=ROUNDS {
+            let previous = round_keys[round - 1];
+            let mut word = [previous[13], previous[14], previous[15], previous[12]]; // RotWord
+            for byte in word.iter_mut() {
+                *byte = SBOX[*byte as usize]; // SubWord
+            }
+            word[0] ^= ROUND_CONSTANTS[round - 1];
+
+            for i in 0..16 {
+                let value = previous[i] ^ if i < 4 { word[i] } else { round_keys[round][i - 4] };
+                round_keys[round][i] = value;
+            }
+        }
+
+        return Self { round_keys };
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        if aes_ni_available() {
+            self.encrypt_block_hardware(block);
+        } else {
+            self.encrypt_block_software(block);
+        }
+    }
+
+    fn encrypt_block_hardware(&self, block: &mut [u8; 16]) {
+        // The kernel runs with the FPU/SSE registers of the interrupted user thread (they are only saved on thread switches),
+        // so the used XMM registers are saved and restored (which also removes the round keys from them).
+        // Interrupts are disabled, so that no thread switch happens in between.
+        let mut saved = [0u8; 32];
+        interrupts::without_interrupts(|| unsafe {
+            asm!(
+                "movdqu [{saved}], xmm0",
+                "movdqu [{saved} + 16], xmm1",
+                "movdqu xmm0, [{block}]",
+                "movdqu xmm1, [{keys}]",
+                "pxor xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 16]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 32]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 48]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 64]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 80]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 96]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 112]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 128]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 144]",
+                "aesenc xmm0, xmm1",
+                "movdqu xmm1, [{keys} + 160]",
+                "aesenclast xmm0, xmm1",
+                "movdqu [{block}], xmm0",
+                "movdqu xmm0, [{saved}]",
+                "movdqu xmm1, [{saved} + 16]",
+                block = in(reg) block.as_mut_ptr(),
+                keys = in(reg) self.round_keys.as_ptr(),
+                saved = in(reg) saved.as_mut_ptr(),
+                options(nostack)
+            );
+        });
+    }
+
+    fn encrypt_block_software(&self, block: &mut [u8; 16]) {
+        add_round_key(block, &self.round_keys[0]);
+
+        for round in 1..=ROUNDS {
+            for byte in block.iter_mut() {
+                *byte = SBOX[*byte as usize];
+            }
+            shift_rows(block);
+            if round != ROUNDS {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+}
+
+/// Random number generator based on AES-128 in counter mode (with fast key erasure, like `ChaCha20Rng`).
+/// With AES-NI, this is considerably faster than ChaCha20, which has no vectorized implementation in the kernel.
+pub struct AesCtrRng {
+    cipher: Aes128,
+    key: [u8; 16],
+    counter: u128
+}
+
+impl AesCtrRng {
+    pub fn new(seed: [u32; 8]) -> Self {
+        let (key, counter) = split_seed(&seed);
+        Self { cipher: Aes128::new(&key), key, counter }
+    }
+
+    pub fn reseed(&mut self, seed: [u32; 8]) {
+        let (key, counter) = split_seed(&seed);
+        for (byte, seed_byte) in self.key.iter_mut().zip(key.iter()) {
+            *byte ^= *seed_byte;
+        }
+
+        self.counter ^= counter;
+        self.cipher = Aes128::new(&self.key);
+    }
+
+    pub fn fill(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(16) {
+            let keystream = self.next_block();
+            chunk.copy_from_slice(&keystream[..chunk.len()]);
+        }
+
+        // Replace the key, so that previous outputs cannot be reconstructed
+        self.key = self.next_block();
+        self.cipher = Aes128::new(&self.key);
+    }
+
+    fn next_block(&mut self) -> [u8; 16] {
+        let mut block = self.counter.to_le_bytes();
+        self.cipher.encrypt_block(&mut block);
+        self.counter = self.counter.wrapping_add(1);
+
+        return block;
+    }
+}
+
+fn split_seed(seed: &[u32; 8]) -> ([u8; 16], u128) {
+    let mut key = [0u8; 16];
+    let mut counter = [0u8; 16];
+    for i in 0..4 {
+        key[i * 4..(i + 1) * 4].copy_from_slice(&seed[i].to_le_bytes());
+        counter[i * 4..(i + 1) * 4].copy_from_slice(&seed[i + 4].to_le_bytes());
+    }
+
+    return (key, u128::from_le_bytes(counter));
+}
+
+fn add_round_key(block: &mut [u8; 16], round_key: &[u8; 16]) {
+    for (byte, key_byte) in block.iter_mut().zip(round_key.iter()) {
+        *byte ^= *key_byte;
+    }
+}
+
+// The block is stored column by column, so row r consists of the bytes r, r + 4, r + 8 and r + 12
+fn shift_rows(block: &mut [u8; 16]) {
+    let state = *block;
+    for column in 0..4 {
+        for row in 0..4 {
+            block[column * 4 + row] = state[((column + row) % 4) * 4 + row];
+        }
+    }
+}
+
+fn mix_columns(block: &mut [u8; 16]) {
+    for column in block.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        let all = a[0] ^ a[1] ^ a[2] ^ a[3];
+        for i in 0..4 {
+            column[i] = a[i] ^ all ^ xtime(a[i] ^ a[(i + 1) % 4]);
+        }
+    }
+}
+
+// Multiplication by 2 in GF(2^8)
+fn xtime(value: u8) -> u8 {
+    return (value << 1) ^ if value & 0x80 != 0 { 0x1b } else { 0x00 };
+}
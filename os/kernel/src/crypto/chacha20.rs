@@ -0,0 +1,83 @@
+// ChaCha20 stream cipher (RFC 8439), used as a CSPRNG for the 'GetRandom' system call.
+
+/// "expand 32-byte k"
+pub const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+pub const BLOCK_SIZE: usize = 64;
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]); state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]); state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]); state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]); state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Apply the 20 ChaCha rounds to `state` (without adding the input state afterwards).
+pub fn permute(state: &mut [u32; 16]) {
+    for _ in 0..10 {
+        quarter_round(state, 0, 4, 8, 12);
+        quarter_round(state, 1, 5, 9, 13);
+        quarter_round(state, 2, 6, 10, 14);
+        quarter_round(state, 3, 7, 11, 15);
+        quarter_round(state, 0, 5, 10, 15);
+        quarter_round(state, 1, 6, 11, 12);
+        quarter_round(state, 2, 7, 8, 13);
+        quarter_round(state, 3, 4, 9, 14);
+    }
+}
+
+pub fn block(key: &[u32; 8], counter: u32, nonce: &[u32; 3]) -> [u32; 16] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter;
+    input[13..].copy_from_slice(nonce);
+
+    let mut state = input;
+    permute(&mut state);
+    for (word, input_word) in state.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*input_word);
+    }
+
+    return state;
+}
+
+/// Random number generator based on ChaCha20 with fast key erasure:
+/// After each request, the key is replaced with fresh keystream, so that previous outputs cannot be reconstructed.
+pub struct ChaCha20Rng {
+    key: [u32; 8],
+    nonce: [u32; 3]
+}
+
+impl ChaCha20Rng {
+    pub const fn new(seed: [u32; 8]) -> Self {
+        Self { key: seed, nonce: [0; 3] }
+    }
+
+    /// Mix a fresh seed into the current key.
+    pub fn reseed(&mut self, seed: [u32; 8]) {
+        for (word, seed_word) in self.key.iter_mut().zip(seed.iter()) {
+            *word ^= *seed_word;
+        }
+        self.rekey();
+    }
+
+    pub fn fill(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(BLOCK_SIZE * u16::MAX as usize) {
+            for (counter, block_chunk) in chunk.chunks_mut(BLOCK_SIZE).enumerate() {
+                let keystream = block(&self.key, counter as u32 + 1, &self.nonce);
+                for (i, byte) in block_chunk.iter_mut().enumerate() {
+                    *byte = keystream[i / 4].to_le_bytes()[i % 4];
+                }
+            }
+
+            // Use a new key for every chunk to avoid counter overflows
+            self.rekey();
+        }
+    }
+
+    fn rekey(&mut self) {
+        let keystream = block(&self.key, 0, &self.nonce);
+        self.key.copy_from_slice(&keystream[..8]);
+    }
+}
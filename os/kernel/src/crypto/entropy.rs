@@ -0,0 +1,162 @@
+use core::arch::x86_64::_rdtsc;
+use crate::crypto::chacha20;
+use crate::crypto::aes_ni::{aes_ni_available, AesCtrRng};
+use crate::crypto::chacha20::ChaCha20Rng;
+use crate::crypto::rdrand::{rdrand64, rdseed64};
+
+/// Amount of estimated entropy, that needs to be collected before the CSPRNG is seeded.
+pub const SEED_BITS: usize = 256;
+
+// The pool is a sponge based on the ChaCha permutation: Input is absorbed into the first 8 words,
+// while the remaining 8 words are never directly exposed.
+const POOL_RATE: usize = 8;
+
+/// Collects randomness from various sources and keeps an estimate of the gathered entropy.
+/// Once at least `SEED_BITS` bits have been collected, the pool seeds a CSPRNG (AES-CTR or ChaCha20), which serves all requests.
+/// Whenever enough entropy has been collected again, the CSPRNG is reseeded.
+pub struct EntropyPool {
+    state: [u32; 16],
+    position: usize,
+    entropy_bits: usize,
+    last_time: u64,
+    last_delta: i64,
+    last_delta2: i64,
+    rng: Option<Csprng>
+}
+
+/// AES in counter mode is used, if the CPU supports AES-NI. Otherwise, ChaCha20 is faster than a software AES.
+enum Csprng {
+    ChaCha20(ChaCha20Rng),
+    AesCtr(AesCtrRng)
+}
+
+impl EntropyPool {
+    pub const fn new() -> Self {
+        let c = chacha20::CONSTANTS;
+        Self { state: [c[0], c[1], c[2], c[3], 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], position: 0, entropy_bits: 0, last_time: 0, last_delta: 0, last_delta2: 0, rng: None }
+    }
+
+    /// Mix `data` into the pool, crediting it with `entropy_bits` bits of entropy.
+    pub fn add(&mut self, data: &[u8], entropy_bits: usize) {
+        for chunk in data.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.absorb(u32::from_le_bytes(word));
+        }
+
+        self.entropy_bits = (self.entropy_bits + entropy_bits).min(SEED_BITS * 2);
+    }
+
+    /// Mix the current time stamp counter into the pool. Called on every interrupt.
+    /// Timings are only credited with one bit of entropy, if they are not predictable from the previous ones
+    /// (i.e. the first, second and third order deltas are all non-zero).
+    pub fn add_interrupt_timing(&mut self, vector: u8) {
+        let time = unsafe { _rdtsc() };
+        let delta = time.wrapping_sub(self.last_time) as i64;
+        let delta2 = delta.wrapping_sub(self.last_delta);
+        let delta3 = delta2.wrapping_sub(self.last_delta2);
+        self.last_time = time;
+        self.last_delta = delta;
+        self.last_delta2 = delta2;
+
+        let credit = if delta != 0 && delta2 != 0 && delta3 != 0 { 1 } else { 0 };
+        let data = time ^ (vector as u64) << 56;
+        self.add(&data.to_le_bytes(), credit);
+    }
+
+    /// Mix a keyboard scancode together with the time of its arrival into the pool.
+    pub fn add_input_event(&mut self, scancode: u8) {
+        let data = unsafe { _rdtsc() } ^ (scancode as u64) << 56;
+        self.add(&data.to_le_bytes(), 1);
+    }
+
+    /// Mix output of the CPU's entropy source into the pool (used at boot time).
+    /// RDSEED is preferred, with RDRAND as fallback for CPUs not supporting it.
+    pub fn add_cpu_randomness(&mut self) {
+        for _ in 0..(SEED_BITS / 64) {
+            if let Some(value) = rdseed64().or_else(|| rdrand64()) {
+                self.add(&value.to_le_bytes(), 64);
+            }
+        }
+    }
+
+    pub fn entropy_bits(&self) -> usize {
+        return self.entropy_bits;
+    }
+
+    /// Check if the CSPRNG has been seeded and can serve requests.
+    pub fn is_ready(&mut self) -> bool {
+        if self.entropy_bits >= SEED_BITS {
+            let seed = self.extract_seed();
+            match self.rng.as_mut() {
+                Some(rng) => rng.reseed(seed),
+                None => self.rng = Some(Csprng::new(seed))
+            }
+        }
+
+        return self.rng.is_some();
+    }
+
+    /// Fill `buffer` with random bytes. Returns false, if the pool has not yet collected enough entropy.
+    pub fn fill(&mut self, buffer: &mut [u8]) -> bool {
+        if !self.is_ready() {
+            return false;
+        }
+
+        // Additionally mix RDRAND output into the CSPRNG for every request (not credited as entropy)
+        let rng = self.rng.as_mut().unwrap();
+        if let Some(value) = rdrand64() {
+            rng.reseed([value as u32, (value >> 32) as u32, 0, 0, 0, 0, 0, 0]);
+        }
+
+        rng.fill(buffer);
+        return true;
+    }
+
+    fn absorb(&mut self, word: u32) {
+        self.state[self.position] ^= word;
+        self.position += 1;
+
+        if self.position == POOL_RATE {
+            chacha20::permute(&mut self.state);
+            self.position = 0;
+        }
+    }
+
+    fn extract_seed(&mut self) -> [u32; 8] {
+        chacha20::permute(&mut self.state);
+        let mut seed = [0u32; 8];
+        seed.copy_from_slice(&self.state[..8]);
+
+        // Permute again, so that the extracted seed cannot be reconstructed from the pool state
+        chacha20::permute(&mut self.state);
+        self.position = 0;
+        self.entropy_bits = 0;
+
+        return seed;
+    }
+}
+
+impl Csprng {
+    fn new(seed: [u32; 8]) -> Self {
+        return if aes_ni_available() {
+            Csprng::AesCtr(AesCtrRng::new(seed))
+        } else {
+            Csprng::ChaCha20(ChaCha20Rng::new(seed))
+        };
+    }
+
+    fn reseed(&mut self, seed: [u32; 8]) {
+        match self {
+            Csprng::ChaCha20(rng) => rng.reseed(seed),
+            Csprng::AesCtr(rng) => rng.reseed(seed)
+        }
+    }
+
+    fn fill(&mut self, buffer: &mut [u8]) {
+        match self {
+            Csprng::ChaCha20(rng) => rng.fill(buffer),
+            Csprng::AesCtr(rng) => rng.fill(buffer)
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod aes_ni;
+pub mod chacha20;
+pub mod entropy;
+pub mod rdrand;
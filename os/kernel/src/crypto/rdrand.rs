@@ -0,0 +1,60 @@
+use core::arch::asm;
+use raw_cpuid::CpuId;
+use spin::Once;
+
+// Both instructions may fail temporarily (CF=0), if the hardware generator has not yet produced new data
+const RETRIES: usize = 10;
+
+static RDRAND_AVAILABLE: Once<bool> = Once::new();
+static RDSEED_AVAILABLE: Once<bool> = Once::new();
+
+pub fn rdrand_available() -> bool {
+    return *RDRAND_AVAILABLE.call_once(|| match CpuId::new().get_feature_info() {
+        Some(features) => features.has_rdrand(),
+        None => false
+    });
+}
+
+pub fn rdseed_available() -> bool {
+    return *RDSEED_AVAILABLE.call_once(|| match CpuId::new().get_extended_feature_info() {
+        Some(features) => features.has_rdseed(),
+        None => false
+    });
+}
+
+/// Read a random number from the CPU's DRBG (RDRAND). Returns `None`, if RDRAND is not supported or did not deliver data.
+pub fn rdrand64() -> Option<u64> {
+    if !rdrand_available() {
+        return None;
+    }
+
+    for _ in 0..RETRIES {
+        let value: u64;
+        let success: u8;
+        unsafe { asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) success, options(nomem, nostack)); }
+        if success != 0 {
+            return Some(value);
+        }
+    }
+
+    return None;
+}
+
+/// Read a random number directly from the CPU's entropy source (RDSEED).
+/// It is better suited for seeding than RDRAND, but slower and more likely to fail.
+pub fn rdseed64() -> Option<u64> {
+    if !rdseed_available() {
+        return None;
+    }
+
+    for _ in 0..RETRIES {
+        let value: u64;
+        let success: u8;
+        unsafe { asm!("rdseed {}", "setc {}", out(reg) value, out(reg_byte) success, options(nomem, nostack)); }
+        if success != 0 {
+            return Some(value);
+        }
+    }
+
+    return None;
+}
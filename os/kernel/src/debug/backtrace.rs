@@ -0,0 +1,88 @@
+use core::arch::asm;
+use core::fmt;
+use core::ops::Range;
+use log::info;
+use crate::debug::symbols;
+use crate::memory::PAGE_SIZE;
+use crate::{allocator, scheduler};
+
+/// Maximum number of return addresses, that are printed.
+const MAX_FRAMES: usize = 32;
+
+/// Part of the stack, that is walked, if the current thread is unknown (e.g. during boot).
+const UNKNOWN_STACK_SIZE: u64 = 4 * PAGE_SIZE as u64;
+
+/// Return addresses, found by following the chain of saved frame pointers (the kernel is built with frame pointers, see 'hhu_tosr.json').
+/// Only frames inside `stack` are followed, so that a corrupted chain cannot cause another fault while printing.
+pub struct Backtrace {
+    rip: u64,
+    rbp: u64,
+    stack: Range<u64>
+}
+
+impl Backtrace {
+    pub fn new(rip: u64, rbp: u64, stack: Range<u64>) -> Self {
+        Self { rip, rbp, stack }
+    }
+
+    /// Backtrace of the calling function, walking the current thread's kernel stack.
+    #[inline(always)]
+    pub fn current() -> Self {
+        let rip: u64;
+        let rbp: u64;
+        let rsp: u64;
+        unsafe { asm!("lea {}, [rip]", "mov {}, rbp", "mov {}, rsp", out(reg) rip, out(reg) rbp, out(reg) rsp); }
+
+        // The scheduler cannot be accessed before heap initialization
+        let thread = if allocator().is_initialized() { scheduler().try_current_thread() } else { None };
+        let stack = match thread {
+            Some(thread) if thread.kernel_stack_range().contains(&rsp) => thread.kernel_stack_range(),
+            _ => rsp..rsp.saturating_add(UNKNOWN_STACK_SIZE)
+        };
+
+        return Self::new(rip, rbp, stack);
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Backtrace:")?;
+        write_frame(f, 0, self.rip)?;
+
+        // Each frame starts with the caller's frame pointer, followed by the return address
+        let mut rbp = self.rbp;
+        for index in 1..=MAX_FRAMES {
+            if rbp % 8 != 0 || !self.stack.contains(&rbp) || !self.stack.contains(&(rbp + 8)) {
+                break;
+            }
+
+            let (next_rbp, return_address) = unsafe { (*(rbp as *const u64), *((rbp + 8) as *const u64)) };
+            if return_address == 0 {
+                break;
+            }
+
+            write_frame(f, index, return_address)?;
+
+            // The stack grows downwards, so callers' frames are always at higher addresses
+            if next_rbp <= rbp {
+                break;
+            }
+            rbp = next_rbp;
+        }
+
+        Ok(())
+    }
+}
+
+/// Print a backtrace of the calling function to the log (e.g. to find out, how a code path has been reached, without panicking).
+#[inline(always)]
+pub fn print() {
+    info!("{}", Backtrace::current());
+}
+
+fn write_frame(f: &mut fmt::Formatter<'_>, index: usize, address: u64) -> fmt::Result {
+    return match symbols::resolve(address) {
+        Some((name, offset)) => write!(f, "\n  #{:<2} 0x{:0>16x} {}+0x{:x}", index, address, name, offset),
+        None => write!(f, "\n  #{:<2} 0x{:0>16x}", index, address)
+    };
+}
@@ -0,0 +1,27 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use spin::Mutex;
+
+// Directory entry cookies, used by profilers to map sampled instruction addresses back to the binary they belong to.
+// The loader registers each user binary with its load address as cookie.
+
+static DCOOKIES: Mutex<BTreeMap<u64, Dcookie>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Debug)]
+pub struct Dcookie {
+    pub inode: u64,
+    pub path: String
+}
+
+/// Register `path` under `cookie`, replacing a previous entry with the same cookie.
+pub fn register(cookie: u64, inode: u64, path: &str) {
+    DCOOKIES.lock().insert(cookie, Dcookie { inode, path: String::from(path) });
+}
+
+pub fn unregister(cookie: u64) {
+    DCOOKIES.lock().remove(&cookie);
+}
+
+pub fn lookup(cookie: u64) -> Option<Dcookie> {
+    return DCOOKIES.lock().get(&cookie).cloned();
+}
@@ -0,0 +1,630 @@
+use core::fmt;
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering::{Acquire, Release};
+use log::{error, info};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+use x86_64::instructions::segmentation::{Segment, DS, ES, FS, GS};
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::VirtAddr;
+use crate::arch::exception::ExceptionState;
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::memory::PAGE_SIZE;
+use crate::memory::r#virtual::active_page_flags;
+use crate::thread::thread::Thread;
+use crate::{logger, scheduler, serial_port};
+
+// GDB remote serial protocol stub, talking to the debugger via polled I/O on the serial port.
+// While the stub is running, interrupts are disabled, so the executing CPU is halted (application processors keep running their threads).
+// The stub does not allocate memory, since it may be entered while the heap is locked or corrupted.
+
+/// Size of the packet buffers (advertised to GDB as 'PacketSize').
+const BUFFER_SIZE: usize = 4096;
+
+/// Registers in the order of GDB's x86_64 target description: 17 registers of 8 bytes (rax - r15, rip),
+/// followed by 7 registers of 4 bytes (eflags, cs, ss, ds, es, fs, gs).
+const REGISTER_COUNT: usize = 24;
+const LONG_REGISTER_COUNT: usize = 17;
+const RSP: usize = 7;
+const RIP: usize = 16;
+const EFLAGS: usize = 17;
+
+/// Slot for rsp in 'ExceptionState::registers' (unused, since rsp is part of the interrupt stack frame).
+const RSP_PLACEHOLDER: usize = 4;
+
+/// Position of the registers (in GDB order) in 'ExceptionState::registers'.
+const EXCEPTION_STATE_INDICES: [usize; 16] = [0, 3, 1, 2, 6, 7, 5, 4, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Position of the registers (in GDB order) in the frame, that 'thread_switch' saves on the kernel stack of a thread, that is not running
+/// (rbp, rdi, rsi, rdx, rcx, rbx, rax, r15 - r8, rflags and the return address as rip). rsp is not saved in the frame.
+const SAVED_FRAME_INDICES: [usize; 16] = [6, 5, 4, 3, 2, 1, 0, UNUSED, 14, 13, 12, 11, 10, 9, 8, 7];
+const SAVED_FRAME_FLAGS: usize = 15;
+const SAVED_FRAME_RIP: usize = 16;
+const SAVED_FRAME_SIZE: u64 = 17 * 8;
+const UNUSED: usize = usize::MAX;
+
+const KERNEL_CODE_SEGMENT: u64 = 0x08;
+const KERNEL_DATA_SEGMENT: u64 = 0x10;
+
+/// Trap flag in rflags (single step).
+const TRAP_FLAG: u64 = 0x100;
+
+/// Byte sent by GDB to interrupt the running system (Ctrl-C).
+pub const BREAK_BYTE: u8 = 0x03;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static STUB: Mutex<Stub> = Mutex::new(Stub::new());
+
+struct Stub {
+    port: u16,
+    packet: [u8; BUFFER_SIZE],
+    response: Response,
+    /// Thread selected for register access via 'Hg' (0 = the thread, that has been stopped)
+    thread: usize,
+    /// Set after 'c' or 's', so that GDB is notified with a stop reply on the next entry
+    resumed: bool
+}
+
+/// Response packet payload, formatted into a fixed size buffer (longer responses are truncated).
+struct Response {
+    data: [u8; BUFFER_SIZE],
+    len: usize
+}
+
+/// Register state of the thread, that has been selected via 'Hg'.
+enum Target {
+    /// The thread, that has been stopped by the exception
+    Stopped,
+    /// Another thread, whose registers have been saved on its kernel stack at the given address
+    Saved(u64)
+}
+
+pub fn is_active() -> bool {
+    return ACTIVE.load(Acquire);
+}
+
+/// Hand the serial port over to the stub (it is removed from the logger) and stop at a breakpoint, waiting for GDB to connect.
+pub fn init() {
+    let serial = match serial_port() {
+        Some(serial) => serial,
+        None => {
+            error!("GDB stub: No serial port available!");
+            return;
+        }
+    };
+
+    info!("Waiting for GDB on serial port [{:?}]", serial.port());
+    logger().lock().detach_serial(serial);
+    STUB.lock().port = serial.port() as u16;
+    ACTIVE.store(true, Release);
+
+    interrupts::int3();
+}
+
+/// Talk to GDB, until it continues or steps. Returns `false`, if the stub is already running (e.g. after a fault inside the stub).
+pub fn enter(state: &mut ExceptionState, vector: u64) -> bool {
+    let mut stub = match STUB.try_lock() {
+        Some(stub) => stub,
+        None => return false
+    };
+
+    stub.run(state, vector);
+    return true;
+}
+
+impl Stub {
+    const fn new() -> Self {
+        Self { port: 0, packet: [0; BUFFER_SIZE], response: Response::new(), thread: 0, resumed: false }
+    }
+
+    fn run(&mut self, state: &mut ExceptionState, vector: u64) {
+        let stopped_id = scheduler().try_current_thread().map_or(0, |thread| thread.id());
+        self.thread = 0;
+
+        if self.resumed {
+            self.response.clear();
+            self.write_stop_reply(vector, stopped_id);
+            self.send_response();
+            self.resumed = false;
+        }
+
+        loop {
+            let len = self.receive_packet();
+            let command = if len > 0 { self.packet[0] } else { 0 };
+            self.response.clear();
+
+            match command {
+                b'?' => self.write_stop_reply(vector, stopped_id),
+                b'g' => self.read_registers(state),
+                b'G' => self.write_registers(state, len),
+                b'm' => self.read_memory(len),
+                b'M' => self.write_memory(len),
+                b'H' => self.select_thread(len, stopped_id),
+                b'T' => {
+                    let alive = parse_thread_id(&self.packet[1..len]).is_some_and(|id| id == 0 || id == stopped_id || find_thread(id, |_| ()).is_some());
+                    self.response.write(if alive { "OK" } else { "E01" });
+                }
+                b'q' => self.query(len, stopped_id),
+                b'c' | b's' => {
+                    if let Some(address) = parse_hex(&self.packet[1..len]) {
+                        state.rip = address;
+                    }
+
+                    if command == b's' {
+                        state.rflags |= TRAP_FLAG;
+                    } else {
+                        state.rflags &= !TRAP_FLAG;
+                    }
+
+                    self.resumed = true;
+                    return;
+                }
+                b'D' => {
+                    state.rflags &= !TRAP_FLAG;
+                    self.response.write("OK");
+                    self.send_response();
+                    return;
+                }
+                b'k' => {
+                    state.rflags &= !TRAP_FLAG;
+                    return;
+                }
+                _ => {} // Unsupported packets are answered with an empty response
+            }
+
+            self.send_response();
+        }
+    }
+
+    fn write_stop_reply(&mut self, vector: u64, stopped_id: usize) {
+        let _ = write!(self.response, "T{:02x}", signal(vector));
+        if stopped_id != 0 {
+            let _ = write!(self.response, "thread:{:x};", stopped_id);
+        }
+    }
+
+    fn query(&mut self, len: usize, stopped_id: usize) {
+        let query = &self.packet[1..len];
+
+        if query.starts_with(b"Supported") {
+            let _ = write!(self.response, "PacketSize={:x}", BUFFER_SIZE);
+        } else if query == b"C" && stopped_id != 0 {
+            let _ = write!(self.response, "QC{:x}", stopped_id);
+        } else if query == b"fThreadInfo" {
+            let response = &mut self.response;
+            let mut first = true;
+            scheduler().for_each_thread(|thread| {
+                let _ = write!(response, "{}{:x}", if first { "m" } else { "," }, thread.id());
+                first = false;
+            });
+
+            if first {
+                response.write("l");
+            }
+        } else if query == b"sThreadInfo" {
+            self.response.write("l");
+        } else if query == b"Attached" {
+            self.response.write("1");
+        }
+    }
+
+    /// 'Hg<id>' selects the thread for 'g' and 'G'.
+    fn select_thread(&mut self, len: usize, stopped_id: usize) {
+        if len < 2 {
+            return;
+        }
+        let operation = self.packet[1];
+        let id = parse_thread_id(&self.packet[2..len]);
+
+        // Execution control ('Hc') always applies to all threads, since single threads cannot be resumed on their own
+        if operation != b'g' {
+            self.response.write("OK");
+            return;
+        }
+
+        match id {
+            Some(id) if id == 0 || id == stopped_id => {
+                self.thread = 0;
+                self.response.write("OK");
+            }
+            Some(id) if find_thread(id, |_| ()).is_some() => {
+                self.thread = id;
+                self.response.write("OK");
+            }
+            _ => self.response.write("E01")
+        }
+    }
+
+    fn target(&self) -> Option<Target> {
+        if self.thread == 0 {
+            return Some(Target::Stopped);
+        }
+
+        return find_thread(self.thread, |thread| Target::Saved(thread.saved_stack_pointer()));
+    }
+
+    fn read_registers(&mut self, state: &ExceptionState) {
+        let registers = match self.target() {
+            Some(target) => load_registers(state, &target),
+            None => {
+                self.response.write("E01");
+                return;
+            }
+        };
+
+        for (index, value) in registers.iter().enumerate() {
+            let size = if index < LONG_REGISTER_COUNT { 8 } else { 4 };
+            for byte in &value.to_le_bytes()[..size] {
+                let _ = write!(self.response, "{:02x}", byte);
+            }
+        }
+    }
+
+    fn write_registers(&mut self, state: &mut ExceptionState, len: usize) {
+        let target = match self.target() {
+            Some(target) => target,
+            None => {
+                self.response.write("E01");
+                return;
+            }
+        };
+
+        let mut registers = load_registers(state, &target);
+        let mut data = &self.packet[1..len];
+        for (index, register) in registers.iter_mut().enumerate() {
+            let size = if index < LONG_REGISTER_COUNT { 8 } else { 4 };
+            if data.len() < 2 * size {
+                break;
+            }
+
+            match parse_le_hex(&data[..2 * size]) {
+                Some(value) => *register = value,
+                None => {
+                    self.response.write("E01");
+                    return;
+                }
+            }
+            data = &data[2 * size..];
+        }
+
+        store_registers(state, &target, &registers);
+        self.response.write("OK");
+    }
+
+    /// 'm addr,length': Read `length` bytes, stopping at the first page, that is not mapped.
+    fn read_memory(&mut self, len: usize) {
+        let (address, len) = match parse_address_length(&self.packet[1..len]) {
+            Some((address, len)) => (address, len.min(BUFFER_SIZE as u64 / 2)),
+            None => {
+                self.response.write("E01");
+                return;
+            }
+        };
+
+        let readable = accessible_length(address, len, PageTableFlags::PRESENT);
+        if readable == 0 && len > 0 {
+            self.response.write("E14");
+            return;
+        }
+
+        for offset in 0..readable {
+            let byte = unsafe { ptr::read_volatile((address + offset) as *const u8) };
+            let _ = write!(self.response, "{:02x}", byte);
+        }
+    }
+
+    /// 'M addr,length:XX...': Write `length` bytes (used by GDB for software breakpoints as well).
+    fn write_memory(&mut self, len: usize) {
+        let args = &self.packet[1..len];
+        let separator = match args.iter().position(|&byte| byte == b':') {
+            Some(separator) => separator,
+            None => {
+                self.response.write("E01");
+                return;
+            }
+        };
+
+        let (address, len) = match parse_address_length(&args[..separator]) {
+            Some((address, len)) => (address, len),
+            None => {
+                self.response.write("E01");
+                return;
+            }
+        };
+
+        let data = &args[separator + 1..];
+        if data.len() as u64 != 2 * len {
+            self.response.write("E01");
+            return;
+        }
+
+        if accessible_length(address, len, PageTableFlags::PRESENT | PageTableFlags::WRITABLE) != len {
+            self.response.write("E14");
+            return;
+        }
+
+        for (offset, pair) in data.chunks(2).enumerate() {
+            match parse_hex(pair) {
+                Some(byte) => unsafe { ptr::write_volatile((address + offset as u64) as *mut u8, byte as u8) },
+                None => {
+                    self.response.write("E01");
+                    return;
+                }
+            }
+        }
+
+        self.response.write("OK");
+    }
+
+    /// Wait for a packet ('$data#checksum'), acknowledge it and return the length of its data, which is stored in `packet`.
+    fn receive_packet(&mut self) -> usize {
+        loop {
+            // Everything before the start of a packet is ignored (e.g. acknowledgements and break requests)
+            while self.receive_byte() != b'$' {}
+
+            let mut len = 0;
+            let mut checksum: u8 = 0;
+            let mut complete = true;
+            loop {
+                let byte = self.receive_byte();
+                if byte == b'#' {
+                    break;
+                }
+                if byte == b'$' {
+                    // GDB restarted the packet
+                    len = 0;
+                    checksum = 0;
+                    continue;
+                }
+
+                checksum = checksum.wrapping_add(byte);
+                if len < BUFFER_SIZE {
+                    self.packet[len] = byte;
+                    len += 1;
+                } else {
+                    complete = false;
+                }
+            }
+
+            let expected = [self.receive_byte(), self.receive_byte()];
+            if complete && parse_hex(&expected) == Some(checksum as u64) {
+                self.send_byte(b'+');
+                return len;
+            }
+
+            self.send_byte(b'-');
+        }
+    }
+
+    /// Send `response` as packet and repeat it, until GDB acknowledges it.
+    fn send_response(&mut self) {
+        let checksum = self.response.as_bytes().iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+        loop {
+            self.send_byte(b'$');
+            for index in 0..self.response.len {
+                self.send_byte(self.response.data[index]);
+            }
+            self.send_byte(b'#');
+            self.send_byte(HEX_DIGITS[(checksum >> 4) as usize]);
+            self.send_byte(HEX_DIGITS[(checksum & 0x0f) as usize]);
+
+            loop {
+                match self.receive_byte() {
+                    b'+' => return,
+                    b'-' => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn receive_byte(&self) -> u8 {
+        let mut data_reg = Port::<u8>::new(self.port);
+        let mut line_status_reg = Port::<u8>::new(self.port + 5);
+
+        unsafe {
+            while (line_status_reg.read() & 0x01) != 0x01 {
+                core::hint::spin_loop();
+            }
+
+            return data_reg.read();
+        }
+    }
+
+    fn send_byte(&self, byte: u8) {
+        let mut data_reg = Port::<u8>::new(self.port);
+        let mut line_status_reg = Port::<u8>::new(self.port + 5);
+
+        unsafe {
+            while (line_status_reg.read() & 0x20) != 0x20 {
+                core::hint::spin_loop();
+            }
+
+            data_reg.write(byte);
+        }
+    }
+}
+
+impl Response {
+    const fn new() -> Self {
+        Self { data: [0; BUFFER_SIZE], len: 0 }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn write(&mut self, string: &str) {
+        let _ = self.write_str(string);
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        return &self.data[..self.len];
+    }
+}
+
+impl fmt::Write for Response {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for &byte in string.as_bytes() {
+            // '$', '#' and '}' would need to be escaped, but never occur in responses of this stub
+            if self.len == BUFFER_SIZE {
+                return Err(fmt::Error);
+            }
+
+            self.data[self.len] = byte;
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+}
+
+const HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
+
+/// Registers of `target` in GDB order.
+fn load_registers(state: &ExceptionState, target: &Target) -> [u64; REGISTER_COUNT] {
+    let mut registers = [0; REGISTER_COUNT];
+
+    match *target {
+        Target::Stopped => {
+            for (register, &index) in registers.iter_mut().zip(EXCEPTION_STATE_INDICES.iter()) {
+                *register = state.registers[index];
+            }
+
+            registers[RSP] = state.rsp;
+            registers[RIP] = state.rip;
+            registers[EFLAGS] = state.rflags;
+            registers[EFLAGS + 1] = state.cs;
+            registers[EFLAGS + 2] = state.ss;
+        }
+        Target::Saved(rsp) => {
+            let frame = unsafe { &*(rsp as *const [u64; 17]) };
+            for (register, &index) in registers.iter_mut().zip(SAVED_FRAME_INDICES.iter()) {
+                if index != UNUSED {
+                    *register = frame[index];
+                }
+            }
+
+            registers[RSP] = rsp + SAVED_FRAME_SIZE;
+            registers[RIP] = frame[SAVED_FRAME_RIP];
+            registers[EFLAGS] = frame[SAVED_FRAME_FLAGS];
+            registers[EFLAGS + 1] = KERNEL_CODE_SEGMENT;
+            registers[EFLAGS + 2] = KERNEL_DATA_SEGMENT;
+        }
+    }
+
+    registers[EFLAGS + 3] = DS::get_reg().0 as u64;
+    registers[EFLAGS + 4] = ES::get_reg().0 as u64;
+    registers[EFLAGS + 5] = FS::get_reg().0 as u64;
+    registers[EFLAGS + 6] = GS::get_reg().0 as u64;
+
+    return registers;
+}
+
+/// Write back general purpose registers, rip and rflags of `target` (segment registers are left unchanged).
+/// The stack pointer of a thread, that is not running, is given by the location of its saved registers and cannot be changed.
+fn store_registers(state: &mut ExceptionState, target: &Target, registers: &[u64; REGISTER_COUNT]) {
+    match *target {
+        Target::Stopped => {
+            for (register, &index) in registers.iter().zip(EXCEPTION_STATE_INDICES.iter()) {
+                if index != RSP_PLACEHOLDER {
+                    state.registers[index] = *register;
+                }
+            }
+
+            state.rsp = registers[RSP];
+            state.rip = registers[RIP];
+            state.rflags = registers[EFLAGS];
+        }
+        Target::Saved(rsp) => {
+            let frame = unsafe { &mut *(rsp as *mut [u64; 17]) };
+            for (register, &index) in registers.iter().zip(SAVED_FRAME_INDICES.iter()) {
+                if index != UNUSED {
+                    frame[index] = *register;
+                }
+            }
+
+            frame[SAVED_FRAME_RIP] = registers[RIP];
+            frame[SAVED_FRAME_FLAGS] = registers[EFLAGS];
+        }
+    }
+}
+
+/// Call `f` for the thread with the given id (without allocating memory, see 'Scheduler::for_each_thread()').
+fn find_thread<T>(thread_id: usize, f: impl FnOnce(&Thread) -> T) -> Option<T> {
+    let mut f = Some(f);
+    let mut result = None;
+
+    scheduler().for_each_thread(|thread| {
+        if thread.id() == thread_id {
+            if let Some(f) = f.take() {
+                result = Some(f(thread));
+            }
+        }
+    });
+
+    return result;
+}
+
+/// Number of bytes starting at `address` (up to `len`), that are mapped with `flags` in the active address space.
+fn accessible_length(address: u64, len: u64, flags: PageTableFlags) -> u64 {
+    let mut accessible = 0;
+
+    while accessible < len {
+        let page_flags = match VirtAddr::try_new(address.wrapping_add(accessible)).ok().and_then(active_page_flags) {
+            Some(page_flags) => page_flags,
+            None => break
+        };
+        if !page_flags.contains(flags) {
+            break;
+        }
+
+        let page_offset = (address + accessible) % PAGE_SIZE as u64;
+        accessible += PAGE_SIZE as u64 - page_offset;
+    }
+
+    return accessible.min(len);
+}
+
+/// Signal number, that is reported to GDB for an exception vector.
+fn signal(vector: u64) -> u8 {
+    return match InterruptVector::try_from(vector as u8) {
+        Ok(InterruptVector::DivisionByZero) | Ok(InterruptVector::X87FloatingPointException) | Ok(InterruptVector::SimdFloatingPointException) => 8, // SIGFPE
+        Ok(InterruptVector::InvalidOpcode) => 4, // SIGILL
+        Ok(InterruptVector::PageFault) | Ok(InterruptVector::GeneralProtectionFault) => 11, // SIGSEGV
+        Ok(InterruptVector::AlignmentCheck) => 7, // SIGBUS
+        _ => 5 // SIGTRAP
+    };
+}
+
+/// Thread ids are sent as hexadecimal numbers ('-1' means all threads, '0' any thread).
+fn parse_thread_id(bytes: &[u8]) -> Option<usize> {
+    if bytes == b"-1" {
+        return Some(0);
+    }
+
+    return parse_hex(bytes).map(|id| id as usize);
+}
+
+/// Parse 'addr,length'.
+fn parse_address_length(bytes: &[u8]) -> Option<(u64, u64)> {
+    let separator = bytes.iter().position(|&byte| byte == b',')?;
+    return Some((parse_hex(&bytes[..separator])?, parse_hex(&bytes[separator + 1..])?));
+}
+
+fn parse_hex(bytes: &[u8]) -> Option<u64> {
+    if bytes.is_empty() || bytes.len() > 16 {
+        return None;
+    }
+
+    return bytes.iter().try_fold(0, |value, &byte| Some((value << 4) | (byte as char).to_digit(16)? as u64));
+}
+
+/// Parse a value, that is sent as hexadecimal bytes in little endian order (register contents).
+fn parse_le_hex(bytes: &[u8]) -> Option<u64> {
+    return bytes.chunks(2).enumerate().try_fold(0, |value, (index, pair)| Some(value | (parse_hex(pair)? << (8 * index))));
+}
@@ -0,0 +1,5 @@
+pub mod backtrace;
+pub mod dcookie;
+pub mod gdb_stub;
+pub mod panic_screen;
+pub mod symbols;
@@ -0,0 +1,100 @@
+use core::fmt;
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use library_graphic::color;
+use library_graphic::lfb::{LFB, CHAR_HEIGHT, CHAR_WIDTH};
+use spin::Once;
+use crate::debug::backtrace::Backtrace;
+
+/// Size of the buffer, the crash dump is formatted into (longer dumps are truncated).
+const DUMP_SIZE: usize = 4096;
+
+/// Framebuffer, set as soon as it is mapped during boot (independent of the terminal, which needs the heap).
+static FRAMEBUFFER: Once<LFB> = Once::new();
+
+/// Panic message and backtrace, formatted into a fixed size buffer,
+/// so that formatting does not need the heap (which may be uninitialized or locked, when panicking).
+pub struct CrashDump {
+    data: [u8; DUMP_SIZE],
+    len: usize,
+}
+
+pub fn init(buffer: *mut u8, pitch: u32, width: u32, height: u32, bpp: u8) {
+    FRAMEBUFFER.call_once(|| LFB::new(buffer, pitch, width, height, bpp));
+}
+
+/// Draw a red banner with the crash dump across the top of the screen.
+/// Does nothing, if the framebuffer has not been mapped yet.
+pub fn draw(dump: &CrashDump) {
+    let lfb = match FRAMEBUFFER.get() {
+        Some(lfb) => lfb,
+        None => return
+    };
+
+    // The banner has a margin of one character on each side
+    let columns = (lfb.width() / CHAR_WIDTH).saturating_sub(2);
+    let max_rows = (lfb.height() / CHAR_HEIGHT).saturating_sub(2);
+    if columns == 0 || max_rows == 0 {
+        return;
+    }
+
+    let rows = layout(dump.as_str(), columns, max_rows, |_, _, _| {});
+    lfb.fill_rect(0, 0, lfb.width(), (rows + 2) * CHAR_HEIGHT, &color::RED);
+    layout(dump.as_str(), columns, max_rows, |c, column, row| {
+        lfb.draw_char((column + 1) * CHAR_WIDTH, (row + 1) * CHAR_HEIGHT, &color::WHITE, &color::RED, c);
+    });
+}
+
+/// Wrap `text` at `columns` characters and call `draw` for each visible character with its position.
+/// Returns the number of rows (at most `max_rows`).
+fn layout(text: &str, columns: u32, max_rows: u32, mut draw: impl FnMut(char, u32, u32)) -> u32 {
+    let mut column = 0;
+    let mut row = 0;
+
+    for c in text.chars() {
+        if c == '\n' || column == columns {
+            column = 0;
+            row += 1;
+        }
+        if row >= max_rows {
+            return max_rows;
+        }
+
+        if c != '\n' {
+            draw(c, column, row);
+            column += 1;
+        }
+    }
+
+    return row + 1;
+}
+
+impl CrashDump {
+    pub fn new(info: &PanicInfo, backtrace: &Backtrace) -> Self {
+        let mut dump = Self { data: [0; DUMP_SIZE], len: 0 };
+        let _ = write!(dump, "KERNEL PANIC\n\n{}\n\n{}\n", info, backtrace);
+
+        return dump;
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only complete characters are written (see 'write_str()')
+        return unsafe { core::str::from_utf8_unchecked(&self.data[..self.len]) };
+    }
+}
+
+impl fmt::Write for CrashDump {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for c in string.chars() {
+            let len = c.len_utf8();
+            if self.len + len > DUMP_SIZE {
+                return Err(fmt::Error);
+            }
+
+            c.encode_utf8(&mut self.data[self.len..self.len + len]);
+            self.len += len;
+        }
+
+        Ok(())
+    }
+}
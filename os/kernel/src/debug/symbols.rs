@@ -0,0 +1,11 @@
+// Generated by 'build.rs' from the symbol table of the kernel ELF file (sorted by address)
+include!(concat!(env!("OUT_DIR"), "/symbols.rs"));
+
+/// Find the function containing `address` and return its name and the offset of `address` inside it.
+/// Returns `None`, if the symbol table is empty (first link pass) or `address` lies before the first function.
+pub fn resolve(address: u64) -> Option<(&'static str, u64)> {
+    let index = SYMBOLS.partition_point(|symbol| symbol.0 <= address).checked_sub(1)?;
+    let (start, name) = SYMBOLS[index];
+
+    return Some((name, address - start));
+}
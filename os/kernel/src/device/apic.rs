@@ -2,19 +2,24 @@ use crate::memory::alloc::AcpiAllocator;
 use crate::interrupt::interrupt_dispatcher::InterruptVector;
 use acpi::madt::Madt;
 use acpi::platform::interrupt::{InterruptSourceOverride, NmiSource, Polarity, TriggerMode};
-use acpi::InterruptModel;
+use acpi::{AcpiTables, InterruptModel};
 use alloc::vec::Vec;
 use log::info;
 use raw_cpuid::CpuId;
 use spin::Mutex;
 use x2apic::ioapic::{IoApic, IrqFlags, IrqMode, RedirectionTableEntry};
-use x2apic::lapic::{xapic_base, LocalApic, LocalApicBuilder};
+use x2apic::lapic::{xapic_base, IpiAllShorthand, LocalApic, LocalApicBuilder, TimerDivide, TimerMode};
 use x86_64::structures::paging::page::PageRange;
 use x86_64::VirtAddr;
 use x86_64::structures::paging::{Page, PageTableFlags};
 use crate::{acpi_tables, allocator};
+use crate::arch::cpuid;
 use crate::memory::MemorySpace;
 use crate::memory::r#virtual::current_address_space;
+use crate::memory::alloc::AcpiHandler;
+
+/// Physical address of the first IO APIC on PC compatible systems (used, if there is no MADT to read it from).
+const DEFAULT_IO_APIC_ADDRESS: u32 = 0xfec00000;
 
 pub struct Apic {
     local_apic: Mutex<LocalApic>,
@@ -38,14 +43,14 @@ impl Apic {
 
         info!("APIC detected");
 
-        // Find APIC relevant structures in ACPI tables
-        let madt = acpi_tables().lock().find_table::<Madt>().expect("MADT not available!");
-        let int_model = madt.parse_interrupt_model_in(AcpiAllocator::new(allocator())).expect("Interrupt model not found in MADT!");
-
-        if let Some(cpu_info) = int_model.1 {
-            info!("[{}] application {} detected", cpu_info.application_processors.len(), if cpu_info.application_processors.len() == 1 { "processor" } else { "processors" });
-            info!("CPU [{}] is the bootstrap processor", cpu_info.boot_processor.processor_uid);
-        }
+        // Find APIC relevant structures in ACPI tables (without ACPI, a single IO APIC at its default address is assumed)
+        let (io_apic_address, gsi_base, irq_overrides, nmi_sources) = match acpi_tables() {
+            Some(tables) => parse_madt(tables),
+            None => {
+                info!("ACPI tables not available -> Assuming one IO APIC at [{:#x}] without interrupt source overrides", DEFAULT_IO_APIC_ADDRESS);
+                (DEFAULT_IO_APIC_ADDRESS, 0, Vec::new(), Vec::new())
+            }
+        };
 
         // Read physical APIC MMIO base address and map it to the kernel address space
         // Needs to be executed in unsafe block; APIC availability has been checked before, so this should work.
@@ -62,8 +67,6 @@ impl Apic {
         );
 
         let io_apic;
-        let mut irq_overrides = Vec::<InterruptSourceOverride>::new();
-        let mut nmi_sources = Vec::<NmiSource>::new();
 
         {
             let mut local_apic_locked = local_apic.lock();
@@ -71,104 +74,72 @@ impl Apic {
                 local_apic_locked.id()
             });
 
-            match int_model.0 {
-                InterruptModel::Unknown => panic!("No APIC described by MADT!"),
-                InterruptModel::Apic(apic_desc) => {
-                    info!("[{}] IO {} detected", apic_desc.io_apics.len(), if apic_desc.io_apics.len() == 1 { "APIC" } else { "APICs" });
+            info!("Initializing IO APIC");
+            let io_apic_page = Page::from_start_address(VirtAddr::new(io_apic_address as u64)).expect("IO Apic MMIO address is not page aligned!");
+            current_address_space().write().map(PageRange { start: io_apic_page, end: io_apic_page + 1 }, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::NO_CACHE);
+            unsafe { io_apic = Mutex::new(IoApic::new(io_apic_page.start_address().as_u64())); } // Needs to be executed in unsafe block; Since exactly one IO APIC has been detected, this should work
 
-                    if apic_desc.io_apics.len() > 1 {
-                        panic!("More than one IO APIC found!");
-                    }
-
-                    let io_apic_desc = apic_desc.io_apics.get(0).unwrap_or_else(|| panic!("No IO APIC described by MADT!"));
+            let mut io_apic_locked = io_apic.lock();
+            unsafe { io_apic_locked.init(gsi_base as u8); }
 
-                    info!("Initializing IO APIC");
-                    let io_apic_page = Page::from_start_address(VirtAddr::new(io_apic_desc.address as u64)).expect("IO Apic MMIO address is not page aligned!");
-                    current_address_space().write().map(PageRange { start: io_apic_page, end: io_apic_page + 1 }, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::NO_CACHE);
-                    unsafe { io_apic = Mutex::new(IoApic::new(io_apic_page.start_address().as_u64())); } // Needs to be executed in unsafe block; Since exactly one IO APIC has been detected, this should work
+            // Initialize redirection table with regards to IRQ override entries
+            // Needs to be executed in unsafe block; At this point, the IO APIC has been initialized successfully, so we can assume, that reading the MSR works.
+            for i in gsi_base as u8..unsafe { io_apic_locked.max_table_entry() } {
+                let mut entry = RedirectionTableEntry::default();
+                let mut flags = IrqFlags::MASKED;
 
-                    let mut io_apic_locked = io_apic.lock();
-                    unsafe { io_apic_locked.init(io_apic_desc.global_system_interrupt_base as u8); }
+                entry.set_mode(IrqMode::Fixed);
 
-                    // Read and store IRQ override entries
-                    info!(
-                        "[{}] interrupt source {} detected", apic_desc.interrupt_source_overrides.len(), if apic_desc.interrupt_source_overrides.len() == 1 { "override" } else { "overrides" }
-                    );
-
-                    for irq_override in apic_desc.interrupt_source_overrides.iter() {
-                        info!("IRQ override [{}]->[{}], Polarity: [{:?}], Trigger: [{:?}]", irq_override.isa_source, irq_override.global_system_interrupt, irq_override.polarity, irq_override.trigger_mode);
-                        irq_overrides.push(InterruptSourceOverride { isa_source: irq_override.isa_source, global_system_interrupt: irq_override.global_system_interrupt, polarity: irq_override.polarity, trigger_mode: irq_override.trigger_mode, });
-                    }
+                // Needs to be executed in unsafe block; At this point, the APIC has been initialized successfully, so we can assume, that reading the MSR works.
+                entry.set_dest(unsafe { local_apic_locked.id() } as u8);
 
-                    // Read and store non-maskable interrupts sources
-                    info!("[{}] NMI {} detected", apic_desc.interrupt_source_overrides.len(), if apic_desc.interrupt_source_overrides.len() == 1 { "source" } else { "sources" });
+                match override_for_target(&irq_overrides, i) {
+                    None => entry.set_vector(i + InterruptVector::Pit as u8),
+                    Some(irq_override) => {
+                        if irq_override.polarity == Polarity::ActiveLow {
+                            flags |= IrqFlags::LOW_ACTIVE;
+                        }
+                        if irq_override.trigger_mode == TriggerMode::Level {
+                            flags |= IrqFlags::LEVEL_TRIGGERED;
+                        }
 
-                    for nmi_source in apic_desc.nmi_sources.iter() {
-                        info!("NMI source [{}], Polarity: [{:?}], Trigger: [{:?}]", nmi_source.global_system_interrupt, nmi_source.polarity, nmi_source.trigger_mode);
-                        nmi_sources.push(NmiSource { global_system_interrupt: nmi_source.global_system_interrupt, polarity: nmi_source.polarity, trigger_mode: nmi_source.trigger_mode });
+                        entry.set_vector(
+                            irq_override.isa_source + InterruptVector::Pit as u8,
+                        );
                     }
+                }
 
-                    // Initialize redirection table with regards to IRQ override entries
-                    // Needs to be executed in unsafe block; At this point, the IO APIC has been initialized successfully, so we can assume, that reading the MSR works.
-                    for i in io_apic_desc.global_system_interrupt_base as u8..unsafe { io_apic_locked.max_table_entry() } {
-                        let mut entry = RedirectionTableEntry::default();
-                        let mut flags = IrqFlags::MASKED;
-
-                        entry.set_mode(IrqMode::Fixed);
-
-                        // Needs to be executed in unsafe block; At this point, the APIC has been initialized successfully, so we can assume, that reading the MSR works.
-                        entry.set_dest(unsafe { local_apic_locked.id() } as u8);
-
-                        match override_for_target(&irq_overrides, i) {
-                            None => entry.set_vector(i + InterruptVector::Pit as u8),
-                            Some(irq_override) => {
-                                if irq_override.polarity == Polarity::ActiveLow {
-                                    flags |= IrqFlags::LOW_ACTIVE;
-                                }
-                                if irq_override.trigger_mode == TriggerMode::Level {
-                                    flags |= IrqFlags::LEVEL_TRIGGERED;
-                                }
-
-                                entry.set_vector(
-                                    irq_override.isa_source + InterruptVector::Pit as u8,
-                                );
-                            }
-                        }
-
-                        entry.set_flags(flags);
+                entry.set_flags(flags);
 
-                        // Needs to be executed in unsafe block; Tables entries have been initialized in IoApic::init(), so writing them works.
-                        unsafe {
-                            io_apic_locked.set_table_entry(i, entry);
-                        }
-                    }
+                // Needs to be executed in unsafe block; Tables entries have been initialized in IoApic::init(), so writing them works.
+                unsafe {
+                    io_apic_locked.set_table_entry(i, entry);
+                }
+            }
 
-                    // Set entries for non-maskable interrupts
-                    for nmi in nmi_sources.iter() {
-                        let mut entry = RedirectionTableEntry::default();
-                        let mut flags = IrqFlags::empty();
+            // Set entries for non-maskable interrupts
+            for nmi in nmi_sources.iter() {
+                let mut entry = RedirectionTableEntry::default();
+                let mut flags = IrqFlags::empty();
 
-                        if nmi.polarity == Polarity::ActiveLow {
-                            flags |= IrqFlags::LOW_ACTIVE;
-                        }
-                        if nmi.trigger_mode == TriggerMode::Level {
-                            flags |= IrqFlags::LEVEL_TRIGGERED;
-                        }
+                if nmi.polarity == Polarity::ActiveLow {
+                    flags |= IrqFlags::LOW_ACTIVE;
+                }
+                if nmi.trigger_mode == TriggerMode::Level {
+                    flags |= IrqFlags::LEVEL_TRIGGERED;
+                }
 
-                        entry.set_mode(IrqMode::NonMaskable);
-                        entry.set_vector(0);
-                        entry.set_flags(flags);
+                entry.set_mode(IrqMode::NonMaskable);
+                entry.set_vector(0);
+                entry.set_flags(flags);
 
-                        // Needs to be executed in unsafe block; At this point, the APIC has been initialized successfully, so we can assume, that reading the MSR works.
-                        entry.set_dest(unsafe { local_apic_locked.id() } as u8);
+                // Needs to be executed in unsafe block; At this point, the APIC has been initialized successfully, so we can assume, that reading the MSR works.
+                entry.set_dest(unsafe { local_apic_locked.id() } as u8);
 
-                        // Needs to be executed in unsafe block; Tables entries have been initialized in IoApic::init(), so writing them works.
-                        unsafe {
-                            io_apic_locked.set_table_entry(nmi.global_system_interrupt as u8, entry);
-                        }
-                    }
+                // Needs to be executed in unsafe block; Tables entries have been initialized in IoApic::init(), so writing them works.
+                unsafe {
+                    io_apic_locked.set_table_entry(nmi.global_system_interrupt as u8, entry);
                 }
-                _ => panic!("No APIC described by MADT!"),
             }
 
             // Initialization is finished -> Enable Local Apic
@@ -198,6 +169,79 @@ impl Apic {
         unsafe { self.io_apic.lock().enable_irq(target); }
     }
 
+    pub fn id(&self) -> u32 {
+        return unsafe { self.local_apic.lock().id() };
+    }
+
+    /// Switch the local APIC timer to TSC deadline mode (see 'tsc::set_deadline_ns()').
+    /// It raises 'InterruptVector::ApicTimer', when the TSC reaches the programmed deadline.
+    pub fn enable_tsc_deadline_timer(&self) {
+        assert!(cpuid::features().has_tsc_deadline(), "APIC: TSC deadline mode is not supported!");
+
+        let mut local_apic = self.local_apic.lock();
+        unsafe {
+            local_apic.set_timer_mode(TimerMode::TscDeadline);
+            local_apic.enable_timer();
+        }
+    }
+
+    /// Start the local APIC timer in one-shot mode, counting down `count` ticks (see 'pit::APIC_TICKS_PER_MS'). A count of 0 stops it.
+    /// If `masked` is set, no interrupt is raised, when the count reaches 0 (used for calibration).
+    pub fn start_oneshot_timer(&self, count: u32, masked: bool) {
+        let mut local_apic = self.local_apic.lock();
+        unsafe {
+            local_apic.set_timer_mode(TimerMode::OneShot);
+            local_apic.set_timer_divide(TimerDivide::Div16);
+            if masked {
+                local_apic.disable_timer();
+            } else {
+                local_apic.enable_timer();
+            }
+            local_apic.set_timer_initial(count);
+        }
+    }
+
+    /// Start the local APIC timer in periodic mode, raising 'InterruptVector::ApicTimer' every `count` ticks (see 'pit::APIC_TICKS_PER_MS').
+    pub fn start_periodic_timer(&self, count: u32) {
+        let mut local_apic = self.local_apic.lock();
+        unsafe {
+            local_apic.set_timer_mode(TimerMode::Periodic);
+            local_apic.set_timer_divide(TimerDivide::Div16);
+            local_apic.enable_timer();
+            local_apic.set_timer_initial(count);
+        }
+    }
+
+    /// Remaining ticks of the local APIC timer.
+    pub fn timer_count(&self) -> u32 {
+        return unsafe { self.local_apic.lock().timer_current() };
+    }
+
+    /// Send an INIT IPI to the processor with the local APIC `id`, which resets it into the wait-for-SIPI state.
+    pub fn send_init_ipi(&self, id: u32) {
+        unsafe { self.local_apic.lock().send_init_ipi(id); }
+    }
+
+    /// Send a startup IPI to the processor with the local APIC `id`, which starts executing in real mode at `vector` * 4 KiB.
+    pub fn send_startup_ipi(&self, id: u32, vector: u8) {
+        unsafe { self.local_apic.lock().send_sipi(vector, id); }
+    }
+
+    /// Send an IPI with `vector` to all CPUs except the executing one.
+    pub fn send_ipi_to_others(&self, vector: InterruptVector) {
+        unsafe { self.local_apic.lock().send_ipi_all(vector as u8, IpiAllShorthand::AllExcludingSelf); }
+    }
+
+    /// Enable the local APIC of the executing CPU (used by application processors, see 'smp::ap_main()').
+    /// Its timer stays disabled, until it is started as the tick of the processor (see 'Timer::start_local_tick()').
+    pub fn enable_local_apic(&self) {
+        let mut local_apic = self.local_apic.lock();
+        unsafe {
+            local_apic.enable();
+            local_apic.disable_timer();
+        }
+    }
+
     pub fn end_of_interrupt(&self) {
         let mut local_apic = self.local_apic.try_lock();
         while local_apic.is_none() {
@@ -211,6 +255,54 @@ impl Apic {
     }
 }
 
+/// Read the address and interrupt base of the IO APIC, as well as the IRQ overrides and NMI sources from the MADT.
+fn parse_madt(tables: &Mutex<AcpiTables<AcpiHandler>>) -> (u32, u32, Vec<InterruptSourceOverride>, Vec<NmiSource>) {
+    let madt = tables.lock().find_table::<Madt>().expect("MADT not available!");
+    let int_model = madt.parse_interrupt_model_in(AcpiAllocator::new(allocator())).expect("Interrupt model not found in MADT!");
+
+    if let Some(cpu_info) = int_model.1 {
+        info!("[{}] application {} detected", cpu_info.application_processors.len(), if cpu_info.application_processors.len() == 1 { "processor" } else { "processors" });
+        info!("CPU [{}] is the bootstrap processor", cpu_info.boot_processor.processor_uid);
+    }
+
+    let mut irq_overrides = Vec::<InterruptSourceOverride>::new();
+    let mut nmi_sources = Vec::<NmiSource>::new();
+
+    match int_model.0 {
+        InterruptModel::Unknown => panic!("No APIC described by MADT!"),
+        InterruptModel::Apic(apic_desc) => {
+            info!("[{}] IO {} detected", apic_desc.io_apics.len(), if apic_desc.io_apics.len() == 1 { "APIC" } else { "APICs" });
+
+            if apic_desc.io_apics.len() > 1 {
+                panic!("More than one IO APIC found!");
+            }
+
+            let io_apic_desc = apic_desc.io_apics.get(0).unwrap_or_else(|| panic!("No IO APIC described by MADT!"));
+
+            // Read and store IRQ override entries
+            info!(
+                "[{}] interrupt source {} detected", apic_desc.interrupt_source_overrides.len(), if apic_desc.interrupt_source_overrides.len() == 1 { "override" } else { "overrides" }
+            );
+
+            for irq_override in apic_desc.interrupt_source_overrides.iter() {
+                info!("IRQ override [{}]->[{}], Polarity: [{:?}], Trigger: [{:?}]", irq_override.isa_source, irq_override.global_system_interrupt, irq_override.polarity, irq_override.trigger_mode);
+                irq_overrides.push(InterruptSourceOverride { isa_source: irq_override.isa_source, global_system_interrupt: irq_override.global_system_interrupt, polarity: irq_override.polarity, trigger_mode: irq_override.trigger_mode, });
+            }
+
+            // Read and store non-maskable interrupts sources
+            info!("[{}] NMI {} detected", apic_desc.interrupt_source_overrides.len(), if apic_desc.interrupt_source_overrides.len() == 1 { "source" } else { "sources" });
+
+            for nmi_source in apic_desc.nmi_sources.iter() {
+                info!("NMI source [{}], Polarity: [{:?}], Trigger: [{:?}]", nmi_source.global_system_interrupt, nmi_source.polarity, nmi_source.trigger_mode);
+                nmi_sources.push(NmiSource { global_system_interrupt: nmi_source.global_system_interrupt, polarity: nmi_source.polarity, trigger_mode: nmi_source.trigger_mode });
+            }
+
+            return (io_apic_desc.address, io_apic_desc.global_system_interrupt_base, irq_overrides, nmi_sources);
+        }
+        _ => panic!("No APIC described by MADT!"),
+    }
+}
+
 fn target_gsi(irq_overrides: &Vec<InterruptSourceOverride>, source_irq: u8) -> u8 {
     match override_for_source(irq_overrides, source_irq) {
         None => source_irq,
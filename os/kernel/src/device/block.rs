@@ -0,0 +1,19 @@
+pub const BLOCK_SIZE: usize = 512;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlkError {
+    /// The block address lies beyond the device's capacity
+    OutOfRange,
+    /// The device has reported an error for the request
+    IoError,
+}
+
+/// Storage device, that is accessed in blocks of 'BLOCK_SIZE' bytes (e.g. by a filesystem).
+pub trait BlockDevice: Send + Sync {
+    fn read_block(&self, lba: u64, buf: &mut [u8; BLOCK_SIZE]) -> Result<(), BlkError>;
+
+    fn write_block(&self, lba: u64, buf: &[u8; BLOCK_SIZE]) -> Result<(), BlkError>;
+
+    /// Number of blocks.
+    fn capacity(&self) -> u64;
+}
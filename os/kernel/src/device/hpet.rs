@@ -0,0 +1,113 @@
+use acpi::HpetInfo;
+use log::info;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::VirtAddr;
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::memory::MemorySpace;
+use crate::memory::r#virtual::current_address_space;
+use crate::{acpi_tables, apic};
+
+// Register offsets (in bytes)
+const CAPABILITIES: usize = 0x000;
+const CONFIGURATION: usize = 0x010;
+const MAIN_COUNTER: usize = 0x0f0;
+const COMPARATOR_BASE: usize = 0x100;
+const COMPARATOR_STRIDE: usize = 0x20;
+const COMPARATOR_CONFIGURATION: usize = 0x00;
+const COMPARATOR_VALUE: usize = 0x08;
+const COMPARATOR_FSB_ROUTE: usize = 0x10;
+
+// General configuration bits
+const ENABLE: u64 = 1 << 0;
+
+// Comparator configuration and capability bits
+const INTERRUPT_ENABLE: u64 = 1 << 2;
+const SIZE_64_BIT: u64 = 1 << 5;
+const FSB_ENABLE: u64 = 1 << 14;
+const FSB_DELIVERY_CAPABLE: u64 = 1 << 15;
+
+/// Address, that messages to the local APIC are written to (the destination APIC id is stored in bits 12-19).
+const MSI_ADDRESS: u64 = 0xfee00000;
+
+const FEMTOSECONDS_PER_NANOSECOND: u128 = 1000000;
+
+/// High Precision Event Timer, used as a precise clock and (if the local APIC timer does not support TSC deadline mode)
+/// for one-shot wakeups. Only 64-bit main counters and comparators, that can deliver their interrupt
+/// directly to the local APIC (FSB delivery), are supported.
+pub struct Hpet {
+    registers: *mut u64,
+    period_fs: u64,
+    comparator: usize,
+}
+
+unsafe impl Send for Hpet {}
+unsafe impl Sync for Hpet {}
+
+impl Hpet {
+    /// Find the HPET in the ACPI tables and start its main counter.
+    /// The comparator's interrupt is delivered on 'InterruptVector::ApicTimer'.
+    pub fn new() -> Option<Self> {
+        let info = HpetInfo::new(&acpi_tables()?.lock()).ok()?;
+        if !info.main_counter_is_64bits() {
+            info!("HPET main counter is not 64-bit wide -> HPET disabled");
+            return None;
+        }
+
+        let hpet_page = Page::from_start_address(VirtAddr::new(info.base_address as u64)).expect("HPET: MMIO address is not page aligned!");
+        current_address_space().write().map(PageRange { start: hpet_page, end: hpet_page + 1 }, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE);
+
+        let mut hpet = Self { registers: info.base_address as *mut u64, period_fs: 0, comparator: 0 };
+        hpet.period_fs = hpet.read(CAPABILITIES) >> 32;
+
+        let comparator_count = info.num_comparators() as usize;
+        hpet.comparator = (0..comparator_count).find(|&comparator| {
+            let capabilities = hpet.read(Self::comparator_register(comparator, COMPARATOR_CONFIGURATION));
+            capabilities & FSB_DELIVERY_CAPABLE != 0 && capabilities & SIZE_64_BIT != 0
+        })?;
+
+        // Route the comparator's interrupt to the local APIC
+        let address = MSI_ADDRESS | ((apic().id() as u64 & 0xff) << 12);
+        hpet.write(Self::comparator_register(hpet.comparator, COMPARATOR_FSB_ROUTE), address << 32 | InterruptVector::ApicTimer as u64);
+        hpet.write(Self::comparator_register(hpet.comparator, COMPARATOR_CONFIGURATION), FSB_ENABLE);
+
+        let configuration = hpet.read(CONFIGURATION);
+        hpet.write(CONFIGURATION, configuration | ENABLE);
+
+        info!("HPET enabled (Period: [{}fs], Comparator: [{}])", hpet.period_fs, hpet.comparator);
+        return Some(hpet);
+    }
+
+    /// Nanoseconds since the HPET has been enabled.
+    pub fn time_ns(&self) -> u64 {
+        return (self.read(MAIN_COUNTER) as u128 * self.period_fs as u128 / FEMTOSECONDS_PER_NANOSECOND) as u64;
+    }
+
+    /// Program the comparator to fire once at `deadline_ns` (see 'time_ns()'), or disarm it, if `deadline_ns` is 0.
+    /// Returns false, if the deadline has already passed after programming the comparator (no interrupt will be raised in this case).
+    pub fn set_deadline_ns(&self, deadline_ns: u64) -> bool {
+        let configuration = Self::comparator_register(self.comparator, COMPARATOR_CONFIGURATION);
+        if deadline_ns == 0 {
+            self.write(configuration, FSB_ENABLE);
+            return true;
+        }
+
+        let ticks = deadline_ns as u128 * FEMTOSECONDS_PER_NANOSECOND / self.period_fs as u128;
+        self.write(Self::comparator_register(self.comparator, COMPARATOR_VALUE), if ticks > u64::MAX as u128 { u64::MAX } else { ticks as u64 });
+        self.write(configuration, FSB_ENABLE | INTERRUPT_ENABLE);
+
+        return self.time_ns() < deadline_ns;
+    }
+
+    fn comparator_register(comparator: usize, offset: usize) -> usize {
+        return COMPARATOR_BASE + comparator * COMPARATOR_STRIDE + offset;
+    }
+
+    fn read(&self, offset: usize) -> u64 {
+        return unsafe { self.registers.byte_add(offset).read_volatile() };
+    }
+
+    fn write(&self, offset: usize, value: u64) {
+        unsafe { self.registers.byte_add(offset).write_volatile(value); }
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::boxed::Box;
+use library_syscall::{KEYBOARD_LAYOUT_DE, KEYBOARD_LAYOUT_UK, KEYBOARD_LAYOUT_US};
+
+/// Scan code of the additional key left of 'Z'/'Y' on ISO keyboards (missing on US keyboards).
+const ISO_KEY: u8 = 0x56;
+
+/// Translates scan codes (set 1 make codes without the 0xe0 prefix) of character keys into the characters of a keyboard layout.
+/// Keys without a character (modifiers, function keys, keypad, ...) are left to the scan code decoder (see 'LFBTerminal::read_key()').
+pub trait KeyboardLayout: Send {
+    fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char>;
+}
+
+/// US QWERTY (104 keys).
+pub struct LayoutUS;
+
+/// German QWERTZ (105 keys).
+pub struct LayoutDE;
+
+/// British QWERTY (105 keys).
+pub struct LayoutUK;
+
+/// Characters of the main key block, indexed by scan code (0x00 to 0x39, '\0' for keys without a character).
+struct ScancodeTable {
+    normal: &'static str,
+    shifted: &'static str,
+    /// Characters of 'ISO_KEY' (without and with shift)
+    iso_key: Option<(char, char)>,
+    altgr: &'static [(u8, char)]
+}
+
+const US_TABLE: ScancodeTable = ScancodeTable {
+    normal: "\0\x1b1234567890-=\x08\tqwertyuiop[]\n\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ",
+    shifted: "\0\x1b!@#$%^&*()_+\x08\tQWERTYUIOP{}\n\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ",
+    iso_key: None,
+    altgr: &[]
+};
+
+const DE_TABLE: ScancodeTable = ScancodeTable {
+    normal: "\0\x1b1234567890ß´\x08\tqwertzuiopü+\n\0asdfghjklöä^\0#yxcvbnm,.-\0*\0 ",
+    shifted: "\0\x1b!\"§$%&/()=?`\x08\tQWERTZUIOPÜ*\n\0ASDFGHJKLÖÄ°\0'YXCVBNM;:_\0*\0 ",
+    iso_key: Some(('<', '>')),
+    altgr: &[(0x03, '²'), (0x04, '³'), (0x08, '{'), (0x09, '['), (0x0a, ']'), (0x0b, '}'), (0x0c, '\\'), (0x10, '@'), (0x12, '€'), (0x1b, '~'), (0x32, 'µ'), (ISO_KEY, '|')]
+};
+
+const UK_TABLE: ScancodeTable = ScancodeTable {
+    normal: "\0\x1b1234567890-=\x08\tqwertyuiop[]\n\0asdfghjkl;'`\0#zxcvbnm,./\0*\0 ",
+    shifted: "\0\x1b!\"£$%^&*()_+\x08\tQWERTYUIOP{}\n\0ASDFGHJKL:@¬\0~ZXCVBNM<>?\0*\0 ",
+    iso_key: Some(('\\', '|')),
+    altgr: &[(0x05, '€'), (0x29, '¦')]
+};
+
+/// Get the layout with the given id (one of the 'KEYBOARD_LAYOUT_*' constants).
+pub fn from_id(id: u32) -> Option<Box<dyn KeyboardLayout>> {
+    return match id {
+        KEYBOARD_LAYOUT_US => Some(Box::new(LayoutUS)),
+        KEYBOARD_LAYOUT_DE => Some(Box::new(LayoutDE)),
+        KEYBOARD_LAYOUT_UK => Some(Box::new(LayoutUK)),
+        _ => None
+    };
+}
+
+/// Get the layout with the given name ('us', 'de' or 'uk', as used on the kernel command line).
+pub fn from_name(name: &str) -> Option<Box<dyn KeyboardLayout>> {
+    return match name {
+        "us" => from_id(KEYBOARD_LAYOUT_US),
+        "de" => from_id(KEYBOARD_LAYOUT_DE),
+        "uk" => from_id(KEYBOARD_LAYOUT_UK),
+        _ => None
+    };
+}
+
+impl ScancodeTable {
+    fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char> {
+        if altgr {
+            return self.altgr.iter()
+                .find(|(code, _)| *code == scancode)
+                .map(|(_, c)| *c);
+        }
+
+        let c = if scancode == ISO_KEY {
+            let (normal, shifted) = self.iso_key?;
+            if shift { shifted } else { normal }
+        } else {
+            let table = if shift { self.shifted } else { self.normal };
+            table.chars().nth(scancode as usize)?
+        };
+
+        return if c == '\0' { None } else { Some(c) };
+    }
+}
+
+impl KeyboardLayout for LayoutUS {
+    fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char> {
+        return US_TABLE.translate(scancode, shift, altgr);
+    }
+}
+
+impl KeyboardLayout for LayoutDE {
+    fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char> {
+        return DE_TABLE.translate(scancode, shift, altgr);
+    }
+}
+
+impl KeyboardLayout for LayoutUK {
+    fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char> {
+        return UK_TABLE.translate(scancode, shift, altgr);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::device::line_discipline::LineDiscipline;
 use crate::device::terminal::Terminal;
 use library_graphic::ansi::COLOR_TABLE_256;
 use library_graphic::buffered_lfb::BufferedLFB;
@@ -5,19 +6,25 @@ use library_graphic::color::Color;
 use library_graphic::lfb::LFB;
 use library_graphic::{color, lfb};
 use library_io::stream::{InputStream, OutputStream};
+use library_syscall::Termios;
+use alloc::collections::VecDeque;
 use alloc::string::String;
 use alloc::vec::Vec;
 use anstyle_parse::{Params, ParamsIter, Parser, Perform, Utf8Parser};
 use core::cell::RefCell;
 use core::mem::size_of;
 use core::ptr;
-use pc_keyboard::layouts::{AnyLayout, De105Key};
-use pc_keyboard::{DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use core::sync::atomic::{AtomicBool, AtomicUsize};
+use core::sync::atomic::Ordering::Relaxed;
+use pc_keyboard::layouts::{AnyLayout, Us104Key};
+use pc_keyboard::{DecodedKey, HandleControl, KeyCode, KeyState, Keyboard, ScancodeSet1};
 use spin::Mutex;
 use crate::{ps2_devices, scheduler, speaker};
 
 const CURSOR: char = if let Some(cursor) = char::from_u32(0x2588) { cursor } else { '_' };
 const TAB_SPACES: u16 = 8;
+/// Number of rows, that are kept after being scrolled out of the screen (viewable with Shift+PageUp/PageDown).
+const SCROLLBACK_ROWS: usize = 256;
 
 struct CursorState {
     pos: (u16, u16),
@@ -34,12 +41,15 @@ struct ColorState {
     invert: bool,
     bright: bool,
     dim: bool,
+    underline: bool,
 }
 
 struct DisplayState {
     size: (u16, u16),
     lfb: BufferedLFB,
     char_buffer: Vec<Character>,
+    scrollback: VecDeque<Vec<Character>>,
+    view_offset: usize,
 }
 
 pub struct LFBTerminal {
@@ -48,6 +58,14 @@ pub struct LFBTerminal {
     color: Mutex<ColorState>,
     parser: Mutex<RefCell<Parser>>,
     decoder: Mutex<Keyboard<AnyLayout, ScancodeSet1>>,
+    shift_pressed: AtomicBool,
+    ctrl_pressed: AtomicBool,
+    altgr_pressed: AtomicBool,
+    caps_lock: AtomicBool,
+    /// Set after the prefix of an extended scan code (0xe0) has been received
+    extended_scancode: AtomicBool,
+    foreground_group: AtomicUsize,
+    line_discipline: Mutex<LineDiscipline>,
 }
 
 pub struct CursorThread {
@@ -60,6 +78,7 @@ struct Character {
     value: char,
     fg_color: Color,
     bg_color: Color,
+    underline: bool,
 }
 
 impl CursorState {
@@ -71,7 +90,7 @@ impl CursorState {
 impl ColorState {
     pub const fn new() -> Self {
         Self { fg_color: color::WHITE, bg_color: color::BLACK, fg_base_color: color::WHITE, bg_base_color: color::BLACK,
-            fg_bright: false, bg_bright: false, invert: false, bright: false, dim: false, }
+            fg_bright: false, bg_bright: false, invert: false, bright: false, dim: false, underline: false, }
     }
 }
 
@@ -83,13 +102,13 @@ impl DisplayState {
 
         let mut char_buffer = Vec::with_capacity(size.0 as usize * size.1 as usize * size_of::<Character>());
         for _ in 0..char_buffer.capacity() {
-            char_buffer.push(Character { value: ' ', fg_color: color::WHITE, bg_color: color::BLACK });
+            char_buffer.push(Character { value: ' ', fg_color: color::WHITE, bg_color: color::BLACK, underline: false });
         }
 
         lfb.lfb().clear();
         lfb.flush();
 
-        Self { size, lfb, char_buffer }
+        Self { size, lfb, char_buffer, scrollback: VecDeque::with_capacity(SCROLLBACK_ROWS), view_offset: 0 }
     }
 }
 
@@ -106,11 +125,17 @@ impl CursorThread {
             {
                 let mut display = self.terminal.display.lock();
                 let cursor = self.terminal.cursor.lock();
-                let character = display.char_buffer[(cursor.pos.1 * display.size.0 + cursor.pos.0) as usize];
 
-                display.lfb.direct_lfb().draw_char(cursor.pos.0 as u32 * lfb::CHAR_WIDTH, cursor.pos.1 as u32 * lfb::CHAR_HEIGHT,
-                    &character.fg_color, &character.bg_color, if self.visible { character.value } else { CURSOR });
-                self.visible = !self.visible;
+                // The cursor is hidden, while the scrollback buffer is shown
+                if display.view_offset == 0 {
+                    let mut character = display.char_buffer[(cursor.pos.1 * display.size.0 + cursor.pos.0) as usize];
+                    if !self.visible {
+                        character.value = CURSOR;
+                    }
+
+                    LFBTerminal::draw_character(display.lfb.direct_lfb(), &character, cursor.pos);
+                    self.visible = !self.visible;
+                }
             }
 
             scheduler().sleep(250);
@@ -127,6 +152,15 @@ impl OutputStream for LFBTerminal {
     }
 
     fn write_str(&self, string: &str) {
+        // New output always returns to the live view
+        {
+            let mut display = self.display.lock();
+            let offset = display.view_offset as isize;
+            if offset > 0 {
+                LFBTerminal::scroll_view(&mut display, -offset);
+            }
+        }
+
         let parser = self.parser.lock().clone();
         for b in string.bytes() {
             // advance() passes mutable terminal reference to methods in 'Perform' trait,
@@ -141,31 +175,20 @@ impl OutputStream for LFBTerminal {
 
 impl InputStream for LFBTerminal {
     fn read_byte(&self) -> i16 {
-        let keyboard = ps2_devices().keyboard();
-        let read_byte;
-
         loop {
-            let mut decoder = self.decoder.lock();
-            let scancode = keyboard.read_byte();
-            if scancode == -1 {
-                panic!("Keyboard stream closed!");
+            if let Some(byte) = self.line_discipline.lock().read_byte() {
+                return byte as i16;
             }
 
-            if let Ok(Some(event)) = decoder.add_byte(scancode as u8) {
-                if let Some(key) = decoder.process_keyevent(event) {
-                    match key {
-                        DecodedKey::Unicode(c) => {
-                            read_byte = c;
-                            break;
-                        }
-                        _ => {}
-                    }
+            let key = self.read_key();
+            if self.line_discipline.lock().input(key, self) {
+                // Ctrl+C -> Kill foreground process group
+                let pgid = self.foreground_group.load(Relaxed);
+                if pgid != 0 {
+                    scheduler().kill_group(pgid);
                 }
             }
         }
-
-        self.write_byte(read_byte as u8);
-        return read_byte as i16;
     }
 }
 
@@ -178,6 +201,22 @@ impl Terminal for LFBTerminal {
         LFBTerminal::clear_screen(&mut display, &mut color);
         LFBTerminal::position(&mut display, &mut cursor, &mut color, (0, 0));
     }
+
+    fn foreground_group(&self) -> usize {
+        return self.foreground_group.load(Relaxed);
+    }
+
+    fn set_foreground_group(&self, pgid: usize) {
+        self.foreground_group.store(pgid, Relaxed);
+    }
+
+    fn termios(&self) -> Termios {
+        return self.line_discipline.lock().mode();
+    }
+
+    fn set_termios(&self, termios: Termios) {
+        self.line_discipline.lock().set_mode(termios);
+    }
 }
 
 impl LFBTerminal {
@@ -187,10 +226,81 @@ impl LFBTerminal {
             cursor: Mutex::new(CursorState::new()),
             color: Mutex::new(ColorState::new()),
             parser: Mutex::new(RefCell::new(Parser::<Utf8Parser>::new())),
-            decoder: Mutex::new(Keyboard::new(ScancodeSet1::new(), AnyLayout::De105Key(De105Key), HandleControl::Ignore))
+            decoder: Mutex::new(Keyboard::new(ScancodeSet1::new(), AnyLayout::Us104Key(Us104Key), HandleControl::MapLettersToUnicode)),
+            shift_pressed: AtomicBool::new(false),
+            ctrl_pressed: AtomicBool::new(false),
+            altgr_pressed: AtomicBool::new(false),
+            caps_lock: AtomicBool::new(false),
+            extended_scancode: AtomicBool::new(false),
+            foreground_group: AtomicUsize::new(0),
+            line_discipline: Mutex::new(LineDiscipline::new()),
+        }
+    }
+
+    /// Wait for the next key press, that produces a character (without echo).
+    /// Character keys are translated with the keyboard's layout (see 'ps2::Keyboard::set_layout()'),
+    /// while control characters (Ctrl+<letter>) and the keypad are handled by the scan code decoder.
+    /// Shift+PageUp/PageDown scroll through the scrollback buffer by half a screen.
+    fn read_key(&self) -> u8 {
+        let keyboard = ps2_devices().keyboard();
+
+        loop {
+            let mut decoder = self.decoder.lock();
+            let scancode = keyboard.read_byte();
+            if scancode == -1 {
+                panic!("Keyboard stream closed!");
+            }
+
+            let extended = self.extended_scancode.swap(scancode == 0xe0, Relaxed);
+            if let Ok(Some(event)) = decoder.add_byte(scancode as u8) {
+                match event.code {
+                    KeyCode::LShift | KeyCode::RShift => self.shift_pressed.store(event.state != KeyState::Up, Relaxed),
+                    KeyCode::LControl | KeyCode::RControl => self.ctrl_pressed.store(event.state != KeyState::Up, Relaxed),
+                    KeyCode::RAltGr => self.altgr_pressed.store(event.state != KeyState::Up, Relaxed),
+                    KeyCode::CapsLock if event.state == KeyState::Down => { self.caps_lock.fetch_xor(true, Relaxed); }
+                    _ => {}
+                }
+
+                let key_down = event.state == KeyState::Down;
+                let decoded = decoder.process_keyevent(event);
+                if key_down && !extended && !self.ctrl_pressed.load(Relaxed) {
+                    if let Some(c) = self.translate(scancode as u8) {
+                        return c as u8;
+                    }
+                }
+
+                match decoded {
+                    Some(DecodedKey::Unicode(c)) => return c as u8,
+                    Some(DecodedKey::RawKey(KeyCode::PageUp)) if self.shift_pressed.load(Relaxed) => {
+                        let mut display = self.display.lock();
+                        let rows = (display.size.1 / 2) as isize;
+                        LFBTerminal::scroll_view(&mut display, rows);
+                    }
+                    Some(DecodedKey::RawKey(KeyCode::PageDown)) if self.shift_pressed.load(Relaxed) => {
+                        let mut display = self.display.lock();
+                        let rows = (display.size.1 / 2) as isize;
+                        LFBTerminal::scroll_view(&mut display, -rows);
+                    }
+                    _ => {}
+                }
+            }
         }
     }
 
+    /// Translate a character key with the keyboard's layout. Caps Lock inverts shift for letters only.
+    fn translate(&self, scancode: u8) -> Option<char> {
+        let keyboard = ps2_devices().keyboard();
+        let shift = self.shift_pressed.load(Relaxed);
+        let altgr = self.altgr_pressed.load(Relaxed);
+
+        let c = keyboard.translate(scancode, shift, altgr)?;
+        if self.caps_lock.load(Relaxed) && c.is_alphabetic() {
+            return keyboard.translate(scancode, !shift, altgr);
+        }
+
+        return Some(c);
+    }
+
     fn print_char(&self, c: char) {
         let mut display = self.display.lock();
         let mut cursor = self.cursor.lock();
@@ -204,7 +314,7 @@ impl LFBTerminal {
         } else {
             if LFBTerminal::print_char_at(&mut display, &mut color, c, cursor.pos) {
                 let index = (cursor.pos.1 * display.size.0 + cursor.pos.0) as usize;
-                display.char_buffer[index] = Character { value: c, fg_color: color.fg_color, bg_color: color.bg_color };
+                display.char_buffer[index] = Character { value: c, fg_color: color.fg_color, bg_color: color.bg_color, underline: color.underline };
 
                 cursor.pos.0 += 1;
             }
@@ -226,11 +336,37 @@ impl LFBTerminal {
     }
 
     fn print_char_at(display: &mut DisplayState, color: &mut ColorState, c: char, pos: (u16, u16)) -> bool {
-        display.lfb.lfb().draw_char(pos.0 as u32 * lfb::CHAR_WIDTH, pos.1 as u32 * lfb::CHAR_HEIGHT, &color.fg_color, &color.bg_color, c)
-            && display.lfb.direct_lfb().draw_char(pos.0 as u32 * lfb::CHAR_WIDTH, pos.1 as u32 * lfb::CHAR_HEIGHT, &color.fg_color, &color.bg_color, c)
+        let character = Character { value: c, fg_color: color.fg_color, bg_color: color.bg_color, underline: color.underline };
+        LFBTerminal::draw_character(display.lfb.lfb(), &character, pos) && LFBTerminal::draw_character(display.lfb.direct_lfb(), &character, pos)
+    }
+
+    /// Draw `character` in the cell at `pos` (in characters), including its underline.
+    fn draw_character(target: &LFB, character: &Character, pos: (u16, u16)) -> bool {
+        let x = pos.0 as u32 * lfb::CHAR_WIDTH;
+        let y = pos.1 as u32 * lfb::CHAR_HEIGHT;
+        if !target.draw_char(x, y, &character.fg_color, &character.bg_color, character.value) {
+            return false;
+        }
+
+        if character.underline {
+            target.fill_rect(x, y + lfb::CHAR_HEIGHT - 1, lfb::CHAR_WIDTH, 1, &character.fg_color);
+        }
+
+        return true;
     }
 
     fn scroll_up(display: &mut DisplayState, color: &mut ColorState) {
+        // Save the top row in the scrollback buffer (reusing the oldest row, if the buffer is full)
+        let width = display.size.0 as usize;
+        let mut row = if display.scrollback.len() >= SCROLLBACK_ROWS {
+            display.scrollback.pop_front().unwrap()
+        } else {
+            Vec::with_capacity(width)
+        };
+        row.clear();
+        row.extend_from_slice(&display.char_buffer[..width]);
+        display.scrollback.push_back(row);
+
         unsafe {
             let char_ptr = display.char_buffer.as_ptr() as *mut u8;
             char_ptr.copy_from(char_ptr.offset(display.size.0 as isize * size_of::<Character>() as isize),
@@ -242,6 +378,7 @@ impl LFBTerminal {
             item.value = '\0';
             item.fg_color = color.fg_color;
             item.bg_color = color.bg_color;
+            item.underline = false;
         });
 
         let size = display.size;
@@ -250,6 +387,38 @@ impl LFBTerminal {
         display.lfb.flush();
     }
 
+    /// Move the view `rows` rows back into the scrollback buffer (or forward for negative values) and redraw the screen.
+    /// With a view offset of 0, the live screen content is shown.
+    fn scroll_view(display: &mut DisplayState, rows: isize) {
+        let offset = (display.view_offset as isize + rows).clamp(0, display.scrollback.len() as isize) as usize;
+        if offset == display.view_offset {
+            return;
+        }
+        display.view_offset = offset;
+
+        let size = display.size;
+        let history = display.scrollback.len();
+        for y in 0..size.1 as usize {
+            let line = if y < offset {
+                &display.scrollback[history - offset + y][..]
+            } else {
+                let start = (y - offset) * size.0 as usize;
+                &display.char_buffer[start..start + size.0 as usize]
+            };
+
+            for (x, character) in line.iter().enumerate() {
+                let mut character = *character;
+                if character.value == '\0' {
+                    character.value = ' ';
+                }
+
+                LFBTerminal::draw_character(display.lfb.lfb(), &character, (x as u16, y as u16));
+            }
+        }
+
+        display.lfb.flush();
+    }
+
     fn position(display: &mut DisplayState, cursor: &mut CursorState, color: &mut ColorState, pos: (u16, u16)) {
         cursor.pos = pos;
 
@@ -283,6 +452,7 @@ impl LFBTerminal {
             item.value = '\0';
             item.fg_color = color.fg_color;
             item.bg_color = color.bg_color;
+            item.underline = false;
         });
 
         display.lfb.flush();
@@ -305,6 +475,7 @@ impl LFBTerminal {
                 item.1.value = '\0';
                 item.1.fg_color = color.fg_color;
                 item.1.bg_color = color.bg_color;
+                item.1.underline = false;
             });
 
         display.lfb.flush();
@@ -326,6 +497,7 @@ impl LFBTerminal {
                 item.value = '\0';
                 item.fg_color = color.fg_color;
                 item.bg_color = color.bg_color;
+                item.underline = false;
             });
 
         display.lfb.flush();
@@ -344,6 +516,7 @@ impl LFBTerminal {
                 item.1.value = 'a';
                 item.1.fg_color = color.fg_color;
                 item.1.bg_color = color.bg_color;
+                item.1.underline = false;
             });
 
         display.lfb.flush();
@@ -363,6 +536,7 @@ impl LFBTerminal {
                 item.1.value = '\0';
                 item.1.fg_color = color.fg_color;
                 item.1.bg_color = color.bg_color;
+                item.1.underline = false;
             });
 
         display.lfb.flush();
@@ -382,6 +556,7 @@ impl LFBTerminal {
                 item.1.value = '\0';
                 item.1.fg_color = color.fg_color;
                 item.1.bg_color = color.bg_color;
+                item.1.underline = false;
             });
 
         display.lfb.flush();
@@ -461,6 +636,7 @@ impl LFBTerminal {
                 color.invert = false;
                 color.bright = false;
                 color.dim = false;
+                color.underline = false;
             }
             1 => {
                 color.bright = true;
@@ -468,6 +644,9 @@ impl LFBTerminal {
             2 => {
                 color.dim = true;
             }
+            4 => {
+                color.underline = true;
+            }
             7 => {
                 color.invert = true;
             }
@@ -475,6 +654,9 @@ impl LFBTerminal {
                 color.bright = false;
                 color.dim = false;
             }
+            24 => {
+                color.underline = false;
+            }
             27 => {
                 color.invert = false;
             }
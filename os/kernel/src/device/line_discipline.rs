@@ -0,0 +1,120 @@
+use library_io::stream::OutputStream;
+use library_syscall::{Termios, ECHO, ECHOE, ICANON, ISIG, VERASE, VINTR};
+
+const INPUT_BUFFER_SIZE: usize = 256;
+
+/// Processes keyboard input of a terminal, before it is passed on to readers.
+/// In canonical mode ('ICANON'), input is collected and can be edited, until a line is complete.
+/// Otherwise, every byte is passed on directly.
+pub struct LineDiscipline {
+    mode: Termios,
+    input_buf: [u8; INPUT_BUFFER_SIZE],
+    buf_pos: usize,
+    line_end: usize,
+    read_pos: usize
+}
+
+impl LineDiscipline {
+    pub const fn new() -> Self {
+        Self { mode: Termios::new(), input_buf: [0; INPUT_BUFFER_SIZE], buf_pos: 0, line_end: 0, read_pos: 0 }
+    }
+
+    pub fn mode(&self) -> Termios {
+        return self.mode;
+    }
+
+    pub fn set_mode(&mut self, mode: Termios) {
+        self.mode = mode;
+
+        // In raw mode, unfinished input is available immediately
+        if self.mode.lflag & ICANON == 0 {
+            self.line_end = self.buf_pos;
+        }
+    }
+
+    /// Get the next byte for a reader (`None`, if more input is needed).
+    pub fn read_byte(&mut self) -> Option<u8> {
+        if self.read_pos >= self.line_end {
+            return None;
+        }
+
+        let byte = self.input_buf[self.read_pos];
+        self.read_pos += 1;
+
+        if self.read_pos == self.line_end {
+            // All available input has been read -> Move unfinished input to the front
+            self.input_buf.copy_within(self.line_end..self.buf_pos, 0);
+            self.buf_pos -= self.line_end;
+            self.line_end = 0;
+            self.read_pos = 0;
+        }
+
+        return Some(byte);
+    }
+
+    /// Process a byte, received from the keyboard. Echoed characters are written to `echo`.
+    /// Returns true, if the interrupt character has been received with 'ISIG' set.
+    /// The caller is responsible for signalling the foreground process group in this case.
+    pub fn input(&mut self, byte: u8, echo: &dyn OutputStream) -> bool {
+        let flags = self.mode.lflag;
+
+        if flags & ISIG != 0 && byte == self.mode.cc[VINTR] {
+            if flags & ECHO != 0 {
+                echo.write_str("^C\n");
+            }
+
+            // Discard unfinished input and end the current line in canonical mode
+            self.buf_pos = self.line_end;
+            if flags & ICANON != 0 {
+                self.push(b'\n');
+                self.line_end = self.buf_pos;
+            }
+
+            return true;
+        }
+
+        if flags & ICANON == 0 {
+            if self.push(byte) {
+                self.line_end = self.buf_pos;
+                if flags & ECHO != 0 {
+                    echo.write_byte(byte);
+                }
+            }
+
+            return false;
+        }
+
+        if byte == self.mode.cc[VERASE] {
+            if self.buf_pos > self.line_end {
+                self.buf_pos -= 1;
+                if flags & (ECHO | ECHOE) == ECHO | ECHOE {
+                    echo.write_str("\x1b[1D \x1b[1D"); // Move cursor back and overwrite the erased character
+                }
+            }
+
+            return false;
+        }
+
+        // The last byte of the buffer is reserved for the line end
+        if (byte == b'\n' || self.buf_pos < INPUT_BUFFER_SIZE - 1) && self.push(byte) {
+            if flags & ECHO != 0 {
+                echo.write_byte(byte);
+            }
+            if byte == b'\n' {
+                self.line_end = self.buf_pos;
+            }
+        }
+
+        return false;
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.buf_pos >= INPUT_BUFFER_SIZE {
+            return false;
+        }
+
+        self.input_buf[self.buf_pos] = byte;
+        self.buf_pos += 1;
+        return true;
+    }
+}
@@ -1,9 +1,16 @@
 pub mod apic;
+pub mod block;
+pub mod hpet;
+pub mod keyboard_layout;
 pub mod pit;
 pub mod ps2;
 pub mod qemu_cfg;
+pub mod rtc;
 pub mod speaker;
+pub mod vbe;
 #[macro_use]
 pub mod terminal;
 pub mod lfb_terminal;
+pub mod line_discipline;
 pub mod serial;
+pub mod virtio_blk;
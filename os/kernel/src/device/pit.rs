@@ -1,19 +1,43 @@
 use crate::device::qemu_cfg;
 use crate::interrupt::interrupt_dispatcher::InterruptVector;
 use crate::interrupt::interrupt_handler::InterruptHandler;
+use crate::arch::tsc;
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::hint::spin_loop;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
 use spin::Mutex;
+use x86_64::instructions::interrupts;
 use x86_64::instructions::port::{Port, PortWriteOnly};
-use crate::{apic, interrupt_dispatcher, scheduler, timer};
+use crate::{apic, hpet, interrupt_dispatcher, percpu, scheduler, timer, vdso};
 
 pub const BASE_FREQUENCY: usize = 1193182;
 
+/// Duration, during which the local APIC timer is calibrated (must fit into the 16-bit PIT counter).
+const CALIBRATION_MS: u64 = 10;
+
+/// Ticks of the local APIC timer per millisecond (0, if it has not been calibrated, see 'calibrate_apic_timer()').
+pub static APIC_TICKS_PER_MS: AtomicU64 = AtomicU64::new(0);
+
 pub struct Timer {
     ctrl_port: Mutex<PortWriteOnly<u8>>,
     data_port: Mutex<Port<u8>>,
     interval_ns: usize,
     systime_ns: usize,
+    boot_time_ns: u64,
+    wakeup_source: Option<WakeupSource>,
+    /// Pending wakeups as (deadline, thread id), sorted by deadline (see 'precise_time_ns()')
+    wakeups: Mutex<Vec<(u64, usize)>>,
+}
+
+/// One-shot timer, that raises 'InterruptVector::ApicTimer' at a precise deadline.
+#[derive(Copy, Clone)]
+enum WakeupSource {
+    TscDeadline,
+    Hpet,
+    /// Calibrated local APIC timer, counting down the time until the deadline (the TSC is used as clock)
+    ApicOneShot,
 }
 
 struct TimerInterruptHandler {
@@ -22,7 +46,6 @@ struct TimerInterruptHandler {
 
 impl InterruptHandler for TimerInterruptHandler {
     fn trigger(&mut self) {
-        let mut systime = 1;
         self.pending_incs += 1;
 
         if let Some(mut timer) = timer().try_write() {
@@ -31,12 +54,10 @@ impl InterruptHandler for TimerInterruptHandler {
                 self.pending_incs -= 1;
             }
 
-            systime = timer.systime_ms();
+            vdso::update(timer.systime_ns as u64, timer.wall_time_ns());
         }
 
-        if systime % 10 == 0 {
-            scheduler().switch_thread();
-        }
+        scheduler().tick();
     }
 }
 
@@ -46,6 +67,26 @@ impl TimerInterruptHandler {
     }
 }
 
+/// Handles the local APIC timer, which raises the one-shot wakeup interrupt on the bootstrap processor
+/// and the periodic tick on application processors (see 'Timer::start_local_tick()').
+struct ApicTimerInterruptHandler;
+
+impl InterruptHandler for ApicTimerInterruptHandler {
+    fn trigger(&mut self) {
+        if percpu::current_cpu_id() != 0 {
+            scheduler().tick();
+            return;
+        }
+
+        // Wakeups, that are missed because the timer is locked, are still handled by the next tick (see 'Scheduler::sleep_ns()')
+        if let Some(timer) = timer().try_read() {
+            while let Some(thread_id) = timer.next_expired_wakeup() {
+                scheduler().unblock(thread_id);
+            }
+        }
+    }
+}
+
 impl Timer {
     pub const fn new() -> Self {
         Self {
@@ -53,6 +94,9 @@ impl Timer {
             data_port: Mutex::new(Port::new(0x40)),
             interval_ns: 0,
             systime_ns: 0,
+            boot_time_ns: 0,
+            wakeup_source: None,
+            wakeups: Mutex::new(Vec::new()),
         }
     }
 
@@ -79,15 +123,92 @@ impl Timer {
         }
     }
 
-    pub fn plugin(&self) {
+    /// Register the tick interrupt and choose a one-shot timer for precise wakeups
+    /// (the local APIC timer in TSC deadline mode, or the HPET, if the former is not supported).
+    /// The local APIC timer interrupt is always registered, since application processors use it for their ticks.
+    pub fn plugin(&mut self) {
         interrupt_dispatcher().assign(InterruptVector::Pit, Box::new(TimerInterruptHandler::new()));
         apic().allow(InterruptVector::Pit);
+
+        if tsc::deadline_available() {
+            apic().enable_tsc_deadline_timer();
+            self.wakeup_source = Some(WakeupSource::TscDeadline);
+        } else if hpet().is_some() {
+            self.wakeup_source = Some(WakeupSource::Hpet);
+        } else if tsc::frequency().is_some() && APIC_TICKS_PER_MS.load(Relaxed) != 0 {
+            self.wakeup_source = Some(WakeupSource::ApicOneShot);
+        }
+
+        interrupt_dispatcher().assign(InterruptVector::ApicTimer, Box::new(ApicTimerInterruptHandler));
+    }
+
+    /// Start the local APIC timer of the executing application processor in periodic mode with the tick interval of the PIT.
+    /// Returns false, if the local APIC timer has not been calibrated (see 'calibrate_apic_timer()').
+    pub fn start_local_tick(&self) -> bool {
+        if APIC_TICKS_PER_MS.load(Relaxed) == 0 {
+            return false;
+        }
+
+        apic().start_periodic_timer(apic_ticks(self.interval_ns as u64));
+        return true;
+    }
+
+    pub fn interval_ns(&self) -> usize {
+        return self.interval_ns;
     }
 
     pub fn systime_ms(&self) -> usize {
         return self.systime_ns / 1000000;
     }
 
+    /// Anchor the wall clock to `unix_time_ns` (nanoseconds since 1970-01-01 UTC) at the current system time.
+    /// Until this is called, the wall clock starts at the epoch on boot.
+    pub fn set_wall_clock(&mut self, unix_time_ns: u64) {
+        self.boot_time_ns = unix_time_ns - self.systime_ns as u64;
+    }
+
+    /// Current time in nanoseconds since 1970-01-01 UTC (with the resolution of a timer tick).
+    pub fn wall_time_ns(&self) -> u64 {
+        return self.boot_time_ns + self.systime_ns as u64;
+    }
+
+    /// Current time in nanoseconds of the clock used for wakeups (not related to the system time).
+    /// Returns `None`, if no one-shot timer is available, in which case 'schedule_wakeup()' always fails.
+    pub fn precise_time_ns(&self) -> Option<u64> {
+        return match self.wakeup_source? {
+            WakeupSource::TscDeadline | WakeupSource::ApicOneShot => tsc::time_ns(),
+            WakeupSource::Hpet => Some(hpet()?.time_ns())
+        };
+    }
+
+    /// Let the wakeup interrupt unblock the thread `thread_id` at `deadline` (see 'precise_time_ns()').
+    /// Returns false, if no one-shot timer is available or `deadline` has already passed.
+    pub fn schedule_wakeup(&self, deadline: u64, thread_id: usize) -> bool {
+        if self.wakeup_source.is_none() {
+            return false;
+        }
+
+        // The wakeup interrupt handler must not be able to interrupt us, while we hold the lock
+        return interrupts::without_interrupts(|| {
+            let mut wakeups = self.wakeups.lock();
+            let index = wakeups.partition_point(|wakeup| wakeup.0 <= deadline);
+            wakeups.insert(index, (deadline, thread_id));
+
+            if index == 0 && !self.arm_wakeup(deadline) {
+                wakeups.remove(0);
+                return false;
+            }
+
+            true
+        });
+    }
+
+    /// Remove all pending wakeups of the thread `thread_id` (e.g. after it has been woken up by other means).
+    /// The one-shot timer stays armed, but an interrupt without expired wakeups only reprograms it.
+    pub fn cancel_wakeup(&self, thread_id: usize) {
+        interrupts::without_interrupts(|| self.wakeups.lock().retain(|wakeup| wakeup.1 != thread_id));
+    }
+
     pub fn wait(ms: usize) {
         let end_time = timer().read().systime_ms() + ms;
         while timer().read().systime_ms() < end_time {
@@ -95,7 +216,120 @@ impl Timer {
         }
     }
 
+    /// Remove the first wakeup, if its deadline has passed, and return its thread id.
+    /// Otherwise, the one-shot timer is programmed for the next pending wakeup (or disarmed).
+    fn next_expired_wakeup(&self) -> Option<usize> {
+        let mut wakeups = self.wakeups.lock();
+
+        loop {
+            let now = self.precise_time_ns()?;
+            let deadline = match wakeups.first() {
+                Some(wakeup) => wakeup.0,
+                None => {
+                    self.arm_wakeup(0);
+                    return None;
+                }
+            };
+
+            if deadline <= now {
+                return Some(wakeups.remove(0).1);
+            }
+
+            if self.arm_wakeup(deadline) {
+                return None;
+            }
+        }
+    }
+
+    /// Program the one-shot timer (a deadline of 0 disarms it).
+    /// Returns false, if the deadline has passed without raising an interrupt.
+    /// The local APIC timer of an application processor runs its tick, so it is not armed there. The wakeup is then handled
+    /// by the next wakeup interrupt of the bootstrap processor or, at the latest, via the sleep list (see 'Scheduler::sleep_ns()').
+    fn arm_wakeup(&self, deadline: u64) -> bool {
+        if matches!(self.wakeup_source, Some(WakeupSource::TscDeadline | WakeupSource::ApicOneShot)) && percpu::current_cpu_id() != 0 {
+            return deadline == 0 || deadline > tsc::time_ns().expect("Timer: TSC frequency unknown!");
+        }
+
+        return match self.wakeup_source {
+            Some(WakeupSource::TscDeadline) => {
+                tsc::set_deadline_ns(deadline);
+                true
+            }
+            Some(WakeupSource::Hpet) => hpet().expect("Timer: HPET not available!").set_deadline_ns(deadline),
+            Some(WakeupSource::ApicOneShot) => {
+                if deadline == 0 {
+                    apic().start_oneshot_timer(0, false);
+                    return true;
+                }
+
+                let now = tsc::time_ns().expect("Timer: TSC frequency unknown!");
+                if deadline <= now {
+                    return false;
+                }
+
+                // Longer intervals are cut off, in which case the timer is armed again, when it fires before the deadline
+                apic().start_oneshot_timer(apic_ticks(deadline - now), false);
+                true
+            }
+            None => false
+        };
+    }
+
     fn inc_systime(&mut self) {
         self.systime_ns += self.interval_ns;
     }
 }
+
+/// Count the ticks of the local APIC timer during 'CALIBRATION_MS', measured with the HPET (if available) or with channel 2 of the PIT.
+/// Returns the ticks per millisecond, which are also stored in 'APIC_TICKS_PER_MS'.
+/// Must be called during boot with interrupts disabled, before the timer is plugged in.
+pub fn calibrate_apic_timer() -> u64 {
+    let elapsed_ticks = match hpet() {
+        Some(hpet) => {
+            apic().start_oneshot_timer(u32::MAX, true);
+            let start_ns = hpet.time_ns();
+            while hpet.time_ns() - start_ns < CALIBRATION_MS * 1000000 {
+                spin_loop();
+            }
+
+            u32::MAX - apic().timer_count()
+        }
+        None => {
+            let mut ctrl_port = PortWriteOnly::<u8>::new(0x43);
+            let mut data_port = Port::<u8>::new(0x42);
+            let mut ppi_port = Port::<u8>::new(0x61);
+            let count = BASE_FREQUENCY as u64 * CALIBRATION_MS / 1000;
+
+            unsafe {
+                // Channel 2 is gated by bit 0 of port 0x61 and its output can be read from bit 5 (speaker must stay off)
+                let ppi = ppi_port.read() & 0xfc;
+                ppi_port.write(ppi);
+
+                ctrl_port.write(0xb0); // Select channel 2, Use low-/high byte access mode, Set operating mode to interrupt on terminal count
+                data_port.write((count & 0xff) as u8); // Low byte
+                data_port.write(((count >> 8) & 0xff) as u8); // High byte
+
+                apic().start_oneshot_timer(u32::MAX, true);
+                ppi_port.write(ppi | 0x01); // Start counting
+                while ppi_port.read() & 0x20 == 0 {
+                    spin_loop();
+                }
+                ppi_port.write(ppi);
+            }
+
+            u32::MAX - apic().timer_count()
+        }
+    };
+
+    apic().start_oneshot_timer(0, true);
+
+    let ticks_per_ms = elapsed_ticks as u64 / CALIBRATION_MS;
+    APIC_TICKS_PER_MS.store(ticks_per_ms, Relaxed);
+    return ticks_per_ms;
+}
+
+/// Convert `ns` into a count for the local APIC timer (at least 1, so that the timer fires).
+fn apic_ticks(ns: u64) -> u32 {
+    let ticks = ns as u128 * APIC_TICKS_PER_MS.load(Relaxed) as u128 / 1000000;
+    return ticks.clamp(1, u32::MAX as u128) as u32;
+}
@@ -1,34 +1,64 @@
 use crate::interrupt::interrupt_dispatcher::InterruptVector;
 use crate::interrupt::interrupt_handler::InterruptHandler;
 use library_io::stream::InputStream;
+use library_syscall::MouseEvent;
 use alloc::boxed::Box;
-use log::info;
+use log::{info, warn};
 use nolock::queues::mpmc::bounded::scq::{Receiver, Sender};
 use nolock::queues::{mpmc, DequeueError};
-use ps2::error::{ControllerError, KeyboardError};
-use ps2::flags::{ControllerConfigFlags, KeyboardLedFlags};
+use ps2::error::{ControllerError, KeyboardError, MouseError};
+use ps2::flags::{ControllerConfigFlags, KeyboardLedFlags, MouseMovementFlags};
 use ps2::{Controller, KeyboardType};
 use spin::Mutex;
-use crate::{apic, interrupt_dispatcher, ps2_devices};
+use crate::{apic, entropy_pool, interrupt_dispatcher, ps2_devices};
+use crate::device::keyboard_layout::{KeyboardLayout, LayoutUS};
 
 const KEYBOARD_BUFFER_CAPACITY: usize = 128;
+const MOUSE_BUFFER_CAPACITY: usize = 128;
+
+/// Samples per second and resolution (3 = 8 counts per millimeter) of the mouse.
+const MOUSE_SAMPLE_RATE: u8 = 100;
+const MOUSE_RESOLUTION: u8 = 3;
+
+/// Sent by the mouse after its self test (followed by its device id), e.g. when it has been reset or plugged in again.
+const MOUSE_SELF_TEST_PASSED: u8 = 0xaa;
+const MOUSE_DEVICE_ID: u8 = 0x00;
+
+/// Bit 3 of the first byte of a mouse packet is always set, which is used to find the start of a packet.
+const MOUSE_PACKET_ALWAYS_ONE: u8 = 0x08;
 
 pub struct PS2 {
     controller: Mutex<Controller>,
     keyboard: Keyboard,
+    mouse: Mouse,
 }
 
 pub struct Keyboard {
     buffer: (Receiver<u8>, Sender<u8>),
+    layout: Mutex<Box<dyn KeyboardLayout>>,
+}
+
+/// Mouse on the second PS/2 port. Its packets are translated into 'MouseEvent's, which are buffered until they are read.
+pub struct Mouse {
+    buffer: (Receiver<MouseEvent>, Sender<MouseEvent>),
+    present: bool,
 }
 
 #[derive(Default)]
 struct KeyboardInterruptHandler;
 
+/// Collects the bytes of a mouse packet, since each interrupt only delivers a single byte.
+#[derive(Default)]
+struct MouseInterruptHandler {
+    packet: [u8; 3],
+    index: usize,
+}
+
 impl Keyboard {
     fn new(buffer_cap: usize) -> Self {
         Self {
             buffer: mpmc::bounded::scq::queue(buffer_cap),
+            layout: Mutex::new(Box::new(LayoutUS)),
         }
     }
 
@@ -36,6 +66,16 @@ impl Keyboard {
         interrupt_dispatcher().assign(InterruptVector::Keyboard, Box::new(KeyboardInterruptHandler::default()));
         apic().allow(InterruptVector::Keyboard);
     }
+
+    /// Replace the layout used to translate scan codes into characters (US by default).
+    pub fn set_layout(&self, layout: Box<dyn KeyboardLayout>) {
+        *self.layout.lock() = layout;
+    }
+
+    /// Translate the scan code of a character key with the current layout (see 'KeyboardLayout::translate()').
+    pub fn translate(&self, scancode: u8, shift: bool, altgr: bool) -> Option<char> {
+        return self.layout.lock().translate(scancode, shift, altgr);
+    }
 }
 
 impl InputStream for Keyboard {
@@ -50,10 +90,42 @@ impl InputStream for Keyboard {
     }
 }
 
+impl Mouse {
+    fn new(buffer_cap: usize) -> Self {
+        Self {
+            buffer: mpmc::bounded::scq::queue(buffer_cap),
+            present: false,
+        }
+    }
+
+    /// Register the interrupt handler, if a mouse has been initialized.
+    pub fn plugin(&self) {
+        if !self.present {
+            return;
+        }
+
+        interrupt_dispatcher().assign(InterruptVector::Mouse, Box::new(MouseInterruptHandler::default()));
+        apic().allow(InterruptVector::Mouse);
+    }
+
+    pub fn is_present(&self) -> bool {
+        return self.present;
+    }
+
+    /// Take the oldest buffered event (`None`, if the mouse has not been moved since the last call).
+    pub fn read_event(&self) -> Option<MouseEvent> {
+        return self.buffer.0.try_dequeue().ok();
+    }
+}
+
 impl InterruptHandler for KeyboardInterruptHandler {
     fn trigger(&mut self) {
         if let Some(mut controller) = ps2_devices().controller.try_lock() {
             if let Ok(data) = controller.read_data() {
+                if let Some(mut pool) = entropy_pool().try_lock() {
+                    pool.add_input_event(data);
+                }
+
                 let keyboard = ps2_devices().keyboard();
                 while keyboard.buffer.1.try_enqueue(data).is_err() {
                     if keyboard.buffer.0.try_dequeue().is_err() {
@@ -67,11 +139,73 @@ impl InterruptHandler for KeyboardInterruptHandler {
     }
 }
 
+impl InterruptHandler for MouseInterruptHandler {
+    fn trigger(&mut self) {
+        let mut controller = match ps2_devices().controller.try_lock() {
+            Some(controller) => controller,
+            None => panic!("Mouse: Controller is locked during interrupt!")
+        };
+
+        // Bytes, that have already been read while configuring the mouse, still raise an interrupt
+        let data = match controller.read_data() {
+            Ok(data) => data,
+            Err(_) => return
+        };
+
+        if let Some(mut pool) = entropy_pool().try_lock() {
+            pool.add_input_event(data);
+        }
+
+        // A mouse, that has been reset (e.g. after being plugged in again), has data reporting disabled and must be configured again
+        if self.index == 1 && self.packet[0] == MOUSE_SELF_TEST_PASSED && data == MOUSE_DEVICE_ID {
+            self.index = 0;
+            if let Err(err) = configure_mouse(&mut controller) {
+                warn!("Failed to configure mouse after reset (Error: {:?})", err);
+            }
+
+            return;
+        }
+
+        // Drop bytes until the start of the next packet, if the stream is out of sync
+        if self.index == 0 && data & MOUSE_PACKET_ALWAYS_ONE == 0 {
+            return;
+        }
+
+        self.packet[self.index] = data;
+        self.index += 1;
+        if self.index < self.packet.len() {
+            return;
+        }
+
+        self.index = 0;
+        let flags = MouseMovementFlags::from_bits_truncate(self.packet[0]);
+        if flags.intersects(MouseMovementFlags::X_OVERFLOW | MouseMovementFlags::Y_OVERFLOW) {
+            return;
+        }
+
+        // Movements are 9-bit two's complement values, with the sign bits in the first byte
+        let sign_extend = |value: u8, negative: bool| if negative { (value as u16 | 0xff00) as i16 } else { value as i16 };
+        let event = MouseEvent {
+            dx: sign_extend(self.packet[1], flags.contains(MouseMovementFlags::X_SIGN_BIT)),
+            dy: sign_extend(self.packet[2], flags.contains(MouseMovementFlags::Y_SIGN_BIT)),
+            buttons: (flags & (MouseMovementFlags::LEFT_BUTTON_PRESSED | MouseMovementFlags::RIGHT_BUTTON_PRESSED | MouseMovementFlags::MIDDLE_BUTTON_PRESSED)).bits()
+        };
+
+        let mouse = ps2_devices().mouse();
+        while mouse.buffer.1.try_enqueue(event).is_err() {
+            if mouse.buffer.0.try_dequeue().is_err() {
+                panic!("Mouse: Failed to store event in buffer!");
+            }
+        }
+    }
+}
+
 impl PS2 {
     pub fn new() -> Self {
         Self {
             controller: unsafe { Mutex::new(Controller::new()) },
             keyboard: Keyboard::new(KEYBOARD_BUFFER_CAPACITY),
+            mouse: Mouse::new(MOUSE_BUFFER_CAPACITY),
         }
     }
 
@@ -122,7 +256,7 @@ impl PS2 {
         if controller.test_mouse().is_ok() {
             // Enable mouse
             info!("Second port detected");
-            controller.enable_keyboard()?;
+            controller.enable_mouse()?;
             config.set(ControllerConfigFlags::DISABLE_MOUSE, false);
             config.set(ControllerConfigFlags::ENABLE_MOUSE_INTERRUPT, true);
             controller.write_config(config)?;
@@ -170,7 +304,36 @@ impl PS2 {
         return Ok(());
     }
 
+    pub fn init_mouse(&mut self) -> Result<(), MouseError> {
+        info!("Initializing mouse");
+        let mut controller = self.controller.lock();
+
+        // The self test is answered with 'MOUSE_SELF_TEST_PASSED' and the device id
+        controller.mouse().reset_and_self_test()?;
+        info!("Mouse has been reset and self test result is OK");
+
+        configure_mouse(&mut controller)?;
+        self.mouse.present = true;
+
+        return Ok(());
+    }
+
     pub fn keyboard(&self) -> &Keyboard {
         return &self.keyboard;
     }
+
+    pub fn mouse(&self) -> &Mouse {
+        return &self.mouse;
+    }
+}
+
+/// Set sample rate and resolution and enable the mouse in stream mode (sending a packet for every movement).
+fn configure_mouse(controller: &mut Controller) -> Result<(), MouseError> {
+    controller.mouse().set_defaults()?;
+    controller.mouse().set_sample_rate(MOUSE_SAMPLE_RATE)?;
+    controller.mouse().set_resolution(MOUSE_RESOLUTION)?;
+    controller.mouse().set_stream_mode()?;
+    controller.mouse().enable_data_reporting()?;
+
+    return Ok(());
 }
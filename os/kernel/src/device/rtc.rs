@@ -0,0 +1,77 @@
+use chrono::NaiveDate;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+const INDEX_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+
+/// Set in the index port, so that non-maskable interrupts are disabled while a CMOS register is selected.
+const NMI_DISABLE: u8 = 0x80;
+
+#[repr(u8)]
+#[derive(Copy, Clone)]
+enum Register {
+    Seconds = 0x00,
+    Minutes = 0x02,
+    Hours = 0x04,
+    Day = 0x07,
+    Month = 0x08,
+    Year = 0x09,
+    StatusA = 0x0a,
+    StatusB = 0x0b,
+}
+
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+const HOURS_24: u8 = 0x02;
+const BINARY_MODE: u8 = 0x04;
+const HOUR_PM: u8 = 0x80;
+
+/// Read the CMOS real time clock (expected to run in UTC) and return the seconds since the Unix epoch.
+/// The century register is not standardized, so the year is always interpreted as 20xx.
+pub fn read_unix_time() -> Option<i64> {
+    return interrupts::without_interrupts(|| {
+        // Registers are read until two consecutive reads match, so that an update of the clock in between is not missed
+        let mut time = read_time_registers();
+        loop {
+            let next_time = read_time_registers();
+            if next_time == time {
+                break;
+            }
+
+            time = next_time;
+        }
+
+        let [seconds, minutes, hours, day, month, year] = time;
+        let status = read_register(Register::StatusB);
+        let decode = |value: u8| if status & BINARY_MODE != 0 { value } else { (value >> 4) * 10 + (value & 0x0f) };
+
+        let pm = hours & HOUR_PM != 0;
+        let mut hours = decode(hours & !HOUR_PM);
+        if status & HOURS_24 == 0 {
+            hours = hours % 12 + if pm { 12 } else { 0 };
+        }
+
+        let date_time = NaiveDate::from_ymd_opt(2000 + decode(year) as i32, decode(month) as u32, decode(day) as u32)?
+            .and_hms_opt(hours as u32, decode(minutes) as u32, decode(seconds) as u32)?;
+
+        return Some(date_time.and_utc().timestamp());
+    });
+}
+
+fn read_time_registers() -> [u8; 6] {
+    while read_register(Register::StatusA) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+
+    return [Register::Seconds, Register::Minutes, Register::Hours, Register::Day, Register::Month, Register::Year].map(read_register);
+}
+
+fn read_register(register: Register) -> u8 {
+    let mut index_port = Port::<u8>::new(INDEX_PORT);
+    let mut data_port = Port::<u8>::new(DATA_PORT);
+
+    unsafe {
+        index_port.write(NMI_DISABLE | register as u8);
+        return data_port.read();
+    }
+}
@@ -9,6 +9,8 @@ use nolock::queues::mpmc::bounded::scq::{Receiver, Sender};
 use nolock::queues::{mpmc, DequeueError};
 use spin::Once;
 use x86_64::instructions::port::Port;
+use x86_64::instructions::interrupts;
+use crate::debug::gdb_stub;
 use crate::{apic, interrupt_dispatcher, serial_port};
 
 #[allow(dead_code)]
@@ -116,6 +118,104 @@ pub enum BaudRate {
     Baud2 = 57600,
 }
 
+impl BaudRate {
+    /// Get the baud rate for `bits_per_second` ('None', if the rate cannot be generated from the 115200 Hz base clock).
+    pub fn from_bits_per_second(bits_per_second: u32) -> Option<Self> {
+        return match bits_per_second {
+            115200 => Some(BaudRate::Baud115200),
+            57600 => Some(BaudRate::Baud57600),
+            38400 => Some(BaudRate::Baud38400),
+            28800 => Some(BaudRate::Baud28800),
+            23040 => Some(BaudRate::Baud23040),
+            19200 => Some(BaudRate::Baud19200),
+            14400 => Some(BaudRate::Baud14400),
+            12800 => Some(BaudRate::Baud12800),
+            11520 => Some(BaudRate::Baud11520),
+            9600 => Some(BaudRate::Baud9600),
+            7680 => Some(BaudRate::Baud7680),
+            7200 => Some(BaudRate::Baud7200),
+            6400 => Some(BaudRate::Baud6400),
+            5760 => Some(BaudRate::Baud5760),
+            4800 => Some(BaudRate::Baud4800),
+            4608 => Some(BaudRate::Baud4608),
+            3840 => Some(BaudRate::Baud3840),
+            3600 => Some(BaudRate::Baud3600),
+            3200 => Some(BaudRate::Baud3200),
+            2880 => Some(BaudRate::Baud2880),
+            2560 => Some(BaudRate::Baud2560),
+            2400 => Some(BaudRate::Baud2400),
+            2304 => Some(BaudRate::Baud2304),
+            1920 => Some(BaudRate::Baud1920),
+            1800 => Some(BaudRate::Baud1800),
+            1600 => Some(BaudRate::Baud1600),
+            1536 => Some(BaudRate::Baud1536),
+            1440 => Some(BaudRate::Baud1440),
+            1280 => Some(BaudRate::Baud1280),
+            1200 => Some(BaudRate::Baud1200),
+            1152 => Some(BaudRate::Baud1152),
+            960 => Some(BaudRate::Baud960),
+            900 => Some(BaudRate::Baud900),
+            800 => Some(BaudRate::Baud800),
+            768 => Some(BaudRate::Baud768),
+            720 => Some(BaudRate::Baud720),
+            640 => Some(BaudRate::Baud640),
+            600 => Some(BaudRate::Baud600),
+            576 => Some(BaudRate::Baud576),
+            512 => Some(BaudRate::Baud512),
+            480 => Some(BaudRate::Baud480),
+            450 => Some(BaudRate::Baud450),
+            400 => Some(BaudRate::Baud400),
+            384 => Some(BaudRate::Baud384),
+            360 => Some(BaudRate::Baud360),
+            320 => Some(BaudRate::Baud320),
+            300 => Some(BaudRate::Baud300),
+            288 => Some(BaudRate::Baud288),
+            256 => Some(BaudRate::Baud256),
+            240 => Some(BaudRate::Baud240),
+            225 => Some(BaudRate::Baud225),
+            200 => Some(BaudRate::Baud200),
+            192 => Some(BaudRate::Baud192),
+            180 => Some(BaudRate::Baud180),
+            160 => Some(BaudRate::Baud160),
+            150 => Some(BaudRate::Baud150),
+            144 => Some(BaudRate::Baud144),
+            128 => Some(BaudRate::Baud128),
+            120 => Some(BaudRate::Baud120),
+            100 => Some(BaudRate::Baud100),
+            96 => Some(BaudRate::Baud96),
+            90 => Some(BaudRate::Baud90),
+            80 => Some(BaudRate::Baud80),
+            75 => Some(BaudRate::Baud75),
+            72 => Some(BaudRate::Baud72),
+            64 => Some(BaudRate::Baud64),
+            60 => Some(BaudRate::Baud60),
+            50 => Some(BaudRate::Baud50),
+            48 => Some(BaudRate::Baud48),
+            45 => Some(BaudRate::Baud45),
+            40 => Some(BaudRate::Baud40),
+            36 => Some(BaudRate::Baud36),
+            32 => Some(BaudRate::Baud32),
+            30 => Some(BaudRate::Baud30),
+            25 => Some(BaudRate::Baud25),
+            24 => Some(BaudRate::Baud24),
+            20 => Some(BaudRate::Baud20),
+            18 => Some(BaudRate::Baud18),
+            16 => Some(BaudRate::Baud16),
+            15 => Some(BaudRate::Baud15),
+            12 => Some(BaudRate::Baud12),
+            10 => Some(BaudRate::Baud10),
+            9 => Some(BaudRate::Baud9),
+            8 => Some(BaudRate::Baud8),
+            6 => Some(BaudRate::Baud6),
+            5 => Some(BaudRate::Baud5),
+            4 => Some(BaudRate::Baud4),
+            3 => Some(BaudRate::Baud3),
+            2 => Some(BaudRate::Baud2),
+            _ => None
+        };
+    }
+}
+
 pub struct SerialPort {
     port: ComPort,
     buffer: Once<(Receiver<u8>, Sender<u8>)>,
@@ -201,6 +301,11 @@ impl InterruptHandler for SerialInterruptHandler {
 
                 while (line_status_reg.read() & 0x01) == 0x01 {
                     let byte = data_reg.read();
+                    if byte == gdb_stub::BREAK_BYTE && gdb_stub::is_active() {
+                        interrupts::int3();
+                        continue;
+                    }
+
                     match serial.buffer.get() {
                         Some(buffer) => {
                             while buffer.1.try_enqueue(byte).is_err() {
@@ -225,6 +330,10 @@ impl SerialPort {
         }
     }
 
+    pub fn port(&self) -> ComPort {
+        return self.port;
+    }
+
     pub fn init(&self, buffer_cap: usize, speed: BaudRate) {
         if !check_port(self.port) {
             panic!("Serial: Port [{:?}] not found!", self.port);
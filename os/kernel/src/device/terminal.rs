@@ -1,4 +1,5 @@
 use library_io::stream::{InputStream, OutputStream};
+use library_syscall::Termios;
 use core::fmt::Write;
 use core::ops::Deref;
 use core::{fmt, ptr};
@@ -6,6 +7,14 @@ use crate::terminal;
 
 pub trait Terminal: OutputStream + InputStream {
     fn clear(&self);
+
+    /// Process group, that receives a kill, when Ctrl+C is pressed (0 = none).
+    fn foreground_group(&self) -> usize;
+    fn set_foreground_group(&self, pgid: usize);
+
+    /// Settings of the line discipline, which processes input before it is returned by `read_byte()`.
+    fn termios(&self) -> Termios;
+    fn set_termios(&self, termios: Termios);
 }
 
 // Implementation of the 'core::fmt::Write' trait for our Terminal
@@ -0,0 +1,64 @@
+use log::{info, warn};
+use multiboot2::{BootInformation, FramebufferTag, VBEMemoryModel, VBEModeAttributes};
+
+/// Required bits per pixel (the terminal and the panic screen are fastest with 32 bpp).
+const REQUIRED_BPP: u8 = 32;
+
+/// Framebuffer parameters, as passed to 'init_terminal()' and 'panic_screen::init()'.
+#[derive(Clone, Copy, Debug)]
+pub struct FbInfo {
+    pub address: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8
+}
+
+impl FbInfo {
+    pub fn from_framebuffer_tag(tag: &FramebufferTag) -> Self {
+        return Self { address: tag.address(), pitch: tag.pitch(), width: tag.width(), height: tag.height(), bpp: tag.bpp() };
+    }
+
+    pub fn size(&self) -> u64 {
+        return self.height as u64 * self.pitch as u64;
+    }
+}
+
+/// Read the VBE mode information of the current video mode from the multiboot VBE information tag.
+/// This tag is only provided on BIOS systems (with EFI, the framebuffer is set up via GOP) and describes the mode the bootloader has set via VBE,
+/// which is more reliable than the framebuffer tag on some machines (e.g. a pitch derived from the width, instead of the one reported by VBE).
+/// Only supported 32 bpp graphics modes with a linear framebuffer are accepted, otherwise 'None' is returned and the framebuffer tag should be used.
+/// Other modes can neither be queried nor set here, since VBE functions need to be called via 'int 0x10' in real mode.
+/// Instead, the preferred resolution is requested from the bootloader by the framebuffer tag in the multiboot header (see 'boot.asm').
+pub fn vbe_probe(multiboot: &BootInformation) -> Option<FbInfo> {
+    let tag = multiboot.vbe_info_tag()?;
+    let control_info = tag.control_info;
+    let mode_info = tag.mode_info;
+
+    let signature = control_info.signature;
+    let version = control_info.version;
+    if &signature != b"VESA" {
+        warn!("VBE: Invalid controller information signature [{:?}]", signature);
+        return None;
+    }
+
+    let mode = tag.mode;
+    let attributes = mode_info.mode_attributes;
+    let memory_model = mode_info.memory_model;
+    let (width, height) = mode_info.resolution;
+    let bpp = mode_info.bpp;
+    info!("VBE [{}.{}]: Current mode is [0x{:x}] ([{}x{}], [{}] bpp)", version >> 8, version & 0xff, mode, width, height, bpp);
+
+    if !attributes.contains(VBEModeAttributes::SUPPORTED | VBEModeAttributes::GRAPHICS | VBEModeAttributes::LINEAR_FRAMEBUFFER) {
+        warn!("VBE: Current mode is not a graphics mode with linear framebuffer (Attributes: [{:?}])", attributes);
+        return None;
+    }
+
+    if memory_model != VBEMemoryModel::DirectColor || bpp != REQUIRED_BPP {
+        warn!("VBE: Current mode has unsupported pixel format ([{:?}], [{}] bpp)", memory_model, bpp);
+        return None;
+    }
+
+    // 'pitch' (BytesPerScanLine) is valid for all VBE versions (the separate linear framebuffer pitch of VBE 3.0 is not part of the tag)
+    return Some(FbInfo { address: mode_info.framebuffer_base_ptr as u64, pitch: mode_info.pitch as u32, width: width as u32, height: height as u32, bpp });
+}
@@ -0,0 +1,319 @@
+use alloc::boxed::Box;
+use core::hint::spin_loop;
+use core::ptr;
+use core::sync::atomic::{fence, Ordering};
+use log::info;
+use spin::Mutex;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::VirtAddr;
+use crate::device::block::{BlkError, BlockDevice, BLOCK_SIZE};
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::interrupt::interrupt_handler::InterruptHandler;
+use crate::memory::{physical, MemorySpace, PAGE_SIZE};
+use crate::memory::r#virtual::current_address_space;
+use crate::{apic, interrupt_dispatcher, scheduler, virtio_blk};
+
+/// QEMU's 'microvm' machine places its VirtIO MMIO transports at a fixed address (there is no device tree on x86).
+/// Each transport occupies 512 bytes and uses the legacy IRQ 5 + its slot number.
+const MMIO_BASE: u64 = 0xfeb00000;
+const MMIO_STRIDE: u64 = 0x200;
+const MMIO_SLOTS: u64 = 8;
+const IRQ_BASE: u8 = 5;
+
+// Register offsets (in bytes)
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG_CAPACITY: usize = 0x100;
+
+const MAGIC: u32 = 0x74726976; // "virt"
+const BLOCK_DEVICE_ID: u32 = 2;
+const LEGACY_VERSION: u32 = 1;
+
+// Device status bits
+const ACKNOWLEDGE: u32 = 1;
+const DRIVER: u32 = 2;
+const DRIVER_OK: u32 = 4;
+const FEATURES_OK: u32 = 8;
+
+/// Feature bit 32, which must be accepted by drivers for non-legacy devices.
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+// Descriptor flags
+const DESC_NEXT: u16 = 1;
+const DESC_WRITE: u16 = 2;
+
+// Request types and status values
+const REQUEST_IN: u32 = 0;
+const REQUEST_OUT: u32 = 1;
+const STATUS_OK: u8 = 0;
+
+/// The driver only has a single request in flight, which needs three descriptors (header, data and status).
+const QUEUE_SIZE: u16 = 4;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16
+}
+
+#[repr(C)]
+struct AvailableRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE as usize]
+}
+
+#[repr(C)]
+struct UsedElement {
+    id: u32,
+    len: u32
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElement; QUEUE_SIZE as usize]
+}
+
+/// Request header, data and status share one page, so that callers may pass buffers, that are not identity mapped.
+#[repr(C)]
+struct Request {
+    request_type: u32,
+    reserved: u32,
+    sector: u64,
+    data: [u8; BLOCK_SIZE],
+    status: u8
+}
+
+struct Queue {
+    descriptors: *mut Descriptor,
+    available: *mut AvailableRing,
+    used: *mut UsedRing,
+    request: *mut Request,
+    last_used_idx: u16
+}
+
+/// Block device using the VirtIO MMIO transport with a single split virtqueue.
+/// Requests are processed synchronously: The calling thread yields until the device has marked the request as used.
+/// The memory for the virtqueue (descriptors and available ring on the first page, used ring on the second page)
+/// and the request lies in identity mapped kernel memory, so that virtual addresses can be passed to the device.
+pub struct VirtioBlkDevice {
+    registers: *mut u32,
+    capacity: u64,
+    queue: Mutex<Queue>
+}
+
+unsafe impl Send for VirtioBlkDevice {}
+unsafe impl Sync for VirtioBlkDevice {}
+
+#[derive(Default)]
+struct VirtioBlkInterruptHandler {}
+
+impl VirtioBlkDevice {
+    /// Probe the MMIO transports for a block device and initialize its virtqueue.
+    pub fn new() -> Option<Self> {
+        let page = Page::containing_address(VirtAddr::new(MMIO_BASE));
+        let page_count = (MMIO_SLOTS * MMIO_STRIDE).div_ceil(PAGE_SIZE as u64);
+        current_address_space().write().map(PageRange { start: page, end: page + page_count }, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE);
+
+        let slot = (0..MMIO_SLOTS).find(|&slot| {
+            let registers = (MMIO_BASE + slot * MMIO_STRIDE) as *const u32;
+            unsafe { registers.byte_add(MAGIC_VALUE).read_volatile() == MAGIC && registers.byte_add(DEVICE_ID).read_volatile() == BLOCK_DEVICE_ID }
+        })?;
+
+        let mut device = Self { registers: (MMIO_BASE + slot * MMIO_STRIDE) as *mut u32, capacity: 0, queue: Mutex::new(Queue::new()) };
+        if !device.init() {
+            return None;
+        }
+
+        info!("VirtIO block device enabled (Slot: [{}], Capacity: [{} KiB])", slot, device.capacity * BLOCK_SIZE as u64 / 1024);
+        return Some(device);
+    }
+
+    /// Register the interrupt handler, which acknowledges completed requests.
+    /// Without a matching interrupt vector, the device works as well, since completion is detected by polling the used ring.
+    pub fn plugin(&self) {
+        let slot = (self.registers as u64 - MMIO_BASE) / MMIO_STRIDE;
+        if let Ok(vector) = InterruptVector::try_from(InterruptVector::Pit as u8 + IRQ_BASE + slot as u8) {
+            interrupt_dispatcher().assign(vector, Box::new(VirtioBlkInterruptHandler::default()));
+            apic().allow(vector);
+        }
+    }
+
+    fn init(&mut self) -> bool {
+        let legacy = self.read(VERSION) == LEGACY_VERSION;
+
+        // Reset device and negotiate features (no optional features are used)
+        self.write(STATUS, 0);
+        self.write(STATUS, ACKNOWLEDGE);
+        self.write(STATUS, ACKNOWLEDGE | DRIVER);
+
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, 0);
+        if !legacy {
+            self.write(DEVICE_FEATURES_SEL, 1);
+            self.write(DRIVER_FEATURES_SEL, 1);
+            self.write(DRIVER_FEATURES, self.read(DEVICE_FEATURES) & FEATURE_VERSION_1);
+
+            self.write(STATUS, ACKNOWLEDGE | DRIVER | FEATURES_OK);
+            if self.read(STATUS) & FEATURES_OK == 0 {
+                info!("VirtIO block device does not accept features -> VirtIO block device disabled");
+                return false;
+            }
+        }
+
+        self.write(QUEUE_SEL, 0);
+        if (self.read(QUEUE_NUM_MAX) as u16) < QUEUE_SIZE {
+            info!("VirtIO block device queue is too small -> VirtIO block device disabled");
+            return false;
+        }
+
+        let queue = self.queue.get_mut();
+        let (descriptors, available, used) = (queue.descriptors as u64, queue.available as u64, queue.used as u64);
+        self.write(QUEUE_NUM, QUEUE_SIZE as u32);
+        if legacy {
+            self.write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            self.write(QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(QUEUE_PFN, (descriptors / PAGE_SIZE as u64) as u32);
+        } else {
+            self.write(QUEUE_DESC_LOW, descriptors as u32);
+            self.write(QUEUE_DESC_HIGH, (descriptors >> 32) as u32);
+            self.write(QUEUE_DRIVER_LOW, available as u32);
+            self.write(QUEUE_DRIVER_HIGH, (available >> 32) as u32);
+            self.write(QUEUE_DEVICE_LOW, used as u32);
+            self.write(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            self.write(QUEUE_READY, 1);
+        }
+
+        let status = if legacy { ACKNOWLEDGE | DRIVER | DRIVER_OK } else { ACKNOWLEDGE | DRIVER | FEATURES_OK | DRIVER_OK };
+        self.write(STATUS, status);
+
+        self.capacity = unsafe { (self.registers.byte_add(CONFIG_CAPACITY) as *const u64).read_volatile() };
+        return true;
+    }
+
+    /// Pass the request in the request page to the device and wait for its completion.
+    fn submit(&self, queue: &mut Queue, request_type: u32, lba: u64) -> Result<(), BlkError> {
+        if lba >= self.capacity {
+            return Err(BlkError::OutOfRange);
+        }
+
+        unsafe {
+            let request = queue.request;
+            (*request).request_type = request_type;
+            (*request).reserved = 0;
+            (*request).sector = lba;
+            (*request).status = 0xff;
+
+            let data_flags = if request_type == REQUEST_IN { DESC_NEXT | DESC_WRITE } else { DESC_NEXT };
+            queue.descriptors.write_volatile(Descriptor { addr: request as u64, len: 16, flags: DESC_NEXT, next: 1 });
+            queue.descriptors.add(1).write_volatile(Descriptor { addr: ptr::addr_of!((*request).data) as u64, len: BLOCK_SIZE as u32, flags: data_flags, next: 2 });
+            queue.descriptors.add(2).write_volatile(Descriptor { addr: ptr::addr_of!((*request).status) as u64, len: 1, flags: DESC_WRITE, next: 0 });
+
+            let available = queue.available;
+            let idx = ptr::addr_of!((*available).idx).read_volatile();
+            ptr::addr_of_mut!((*available).ring[(idx % QUEUE_SIZE) as usize]).write_volatile(0);
+            fence(Ordering::SeqCst);
+            ptr::addr_of_mut!((*available).idx).write_volatile(idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+        }
+
+        self.write(QUEUE_NOTIFY, 0);
+
+        // During boot (before the scheduler is running), the calling thread cannot yield
+        while unsafe { ptr::addr_of!((*queue.used).idx).read_volatile() } == queue.last_used_idx {
+            match scheduler().try_current_thread() {
+                Some(_) => scheduler().yield_cpu(),
+                None => spin_loop()
+            }
+        }
+
+        fence(Ordering::SeqCst);
+        queue.last_used_idx = queue.last_used_idx.wrapping_add(1);
+
+        return match unsafe { ptr::addr_of!((*queue.request).status).read_volatile() } {
+            STATUS_OK => Ok(()),
+            _ => Err(BlkError::IoError)
+        };
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        return unsafe { self.registers.byte_add(offset).read_volatile() };
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { self.registers.byte_add(offset).write_volatile(value); }
+    }
+}
+
+impl BlockDevice for VirtioBlkDevice {
+    fn read_block(&self, lba: u64, buf: &mut [u8; BLOCK_SIZE]) -> Result<(), BlkError> {
+        let mut queue = self.queue.lock();
+        self.submit(&mut queue, REQUEST_IN, lba)?;
+
+        unsafe { buf.copy_from_slice(&(*queue.request).data); }
+        return Ok(());
+    }
+
+    fn write_block(&self, lba: u64, buf: &[u8; BLOCK_SIZE]) -> Result<(), BlkError> {
+        let mut queue = self.queue.lock();
+        unsafe { (*queue.request).data.copy_from_slice(buf); }
+
+        return self.submit(&mut queue, REQUEST_OUT, lba);
+    }
+
+    fn capacity(&self) -> u64 {
+        return self.capacity;
+    }
+}
+
+impl Queue {
+    fn new() -> Self {
+        let frames = physical::alloc(3, MemorySpace::Kernel);
+        let start = frames.start.start_address().as_u64() as *mut u8;
+        unsafe { start.write_bytes(0, 3 * PAGE_SIZE); }
+
+        // Layout of a legacy virtqueue: Descriptors, directly followed by the available ring and the used ring on the next page
+        let descriptors = start as *mut Descriptor;
+        let available = unsafe { descriptors.add(QUEUE_SIZE as usize) } as *mut AvailableRing;
+        let used = unsafe { start.add(PAGE_SIZE) } as *mut UsedRing;
+        let request = unsafe { start.add(2 * PAGE_SIZE) } as *mut Request;
+
+        return Self { descriptors, available, used, request, last_used_idx: 0 };
+    }
+}
+
+impl InterruptHandler for VirtioBlkInterruptHandler {
+    fn trigger(&mut self) {
+        if let Some(device) = virtio_blk() {
+            let status = device.read(INTERRUPT_STATUS);
+            device.write(INTERRUPT_ACK, status);
+        }
+    }
+}
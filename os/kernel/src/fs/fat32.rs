@@ -0,0 +1,447 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::char::{decode_utf16, REPLACEMENT_CHARACTER};
+use core::cmp::min;
+use spin::Mutex;
+use crate::device::block::{BlkError, BlockDevice, BLOCK_SIZE};
+use crate::fs::vfs::{FsError, Stat, VfsNode};
+
+// Offsets in the boot sector (BIOS parameter block)
+const BPB_BYTES_PER_SECTOR: usize = 11;
+const BPB_SECTORS_PER_CLUSTER: usize = 13;
+const BPB_RESERVED_SECTORS: usize = 14;
+const BPB_FAT_COUNT: usize = 16;
+const BPB_ROOT_ENTRY_COUNT: usize = 17;
+const BPB_TOTAL_SECTORS_16: usize = 19;
+const BPB_FAT_SIZE_16: usize = 22;
+const BPB_TOTAL_SECTORS_32: usize = 32;
+const BPB_FAT_SIZE_32: usize = 36;
+const BPB_ROOT_CLUSTER: usize = 44;
+const BOOT_SIGNATURE: usize = 510;
+
+// Offsets in a directory entry
+const DIR_ENTRY_SIZE: usize = 32;
+const DIR_ATTRIBUTES: usize = 11;
+const DIR_CLUSTER_HIGH: usize = 20;
+const DIR_CLUSTER_LOW: usize = 26;
+const DIR_FILE_SIZE: usize = 28;
+
+// Directory entry attributes
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+// Markers in the first byte of a directory entry
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xe5;
+const ENTRY_KANJI_E5: u8 = 0x05;
+
+// Long file name (VFAT) entries store 13 UCS-2 characters each, spread over three fields
+const LFN_LAST: u8 = 0x40;
+const LFN_ORDER_MASK: u8 = 0x1f;
+const LFN_CHECKSUM: usize = 13;
+const LFN_CHAR_OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const LFN_MAX_ENTRIES: usize = 20;
+
+const CLUSTER_MASK: u32 = 0x0fffffff;
+const CLUSTER_END: u32 = 0x0ffffff8;
+const FIRST_CLUSTER: u32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FatError {
+    /// The device does not contain a FAT32 volume with a sector size of 'BLOCK_SIZE' bytes
+    InvalidBootSector,
+    /// A path component does not exist
+    NotFound,
+    /// A path component (other than the last one) is not a directory
+    NotADirectory,
+    /// The path refers to a directory, which cannot be read as a file
+    IsADirectory,
+    /// A cluster chain is shorter than the file or contains a free or bad cluster
+    CorruptedChain,
+    /// The block device has failed to read a sector
+    Device(BlkError),
+}
+
+impl From<BlkError> for FatError {
+    fn from(err: BlkError) -> Self {
+        return FatError::Device(err);
+    }
+}
+
+/// Location of the FAT and the data area on the device (in sectors).
+#[derive(Copy, Clone)]
+struct Geometry {
+    sectors_per_cluster: u32,
+    fat_start: u64,
+    data_start: u64,
+    cluster_count: u32,
+    root_cluster: u32,
+}
+
+#[derive(Copy, Clone)]
+struct DirEntry {
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+}
+
+/// Read-only access to a FAT32 volume. Cloning is cheap, since only the device reference and geometry are copied.
+#[derive(Clone)]
+pub struct Fat32Volume {
+    device: Arc<dyn BlockDevice>,
+    geometry: Geometry,
+}
+
+/// File opened with `Fat32Volume::open()`, which is read sequentially (seeking backwards walks the cluster chain again).
+pub struct FatFile {
+    volume: Fat32Volume,
+    first_cluster: u32,
+    size: u32,
+    position: u32,
+    // Cluster containing `position` and its index in the cluster chain (so that the chain is only walked once)
+    cluster: u32,
+    cluster_index: u32,
+}
+
+impl Fat32Volume {
+    /// Parse the boot sector of `device` and check, that it describes a FAT32 volume.
+    pub fn mount(device: Arc<dyn BlockDevice>) -> Result<Self, FatError> {
+        let mut boot_sector = [0u8; BLOCK_SIZE];
+        device.read_block(0, &mut boot_sector)?;
+
+        let sectors_per_cluster = boot_sector[BPB_SECTORS_PER_CLUSTER] as u32;
+        let reserved_sectors = read_u16(&boot_sector, BPB_RESERVED_SECTORS) as u64;
+        let fat_count = boot_sector[BPB_FAT_COUNT] as u64;
+        let fat_size = read_u32(&boot_sector, BPB_FAT_SIZE_32) as u64;
+        let root_cluster = read_u32(&boot_sector, BPB_ROOT_CLUSTER);
+        let total_sectors = match read_u16(&boot_sector, BPB_TOTAL_SECTORS_16) {
+            0 => read_u32(&boot_sector, BPB_TOTAL_SECTORS_32) as u64,
+            sectors => sectors as u64
+        };
+
+        // FAT12/16 volumes have a fixed root directory and a 16-bit FAT size
+        if read_u16(&boot_sector, BOOT_SIGNATURE) != 0xaa55 || read_u16(&boot_sector, BPB_BYTES_PER_SECTOR) as usize != BLOCK_SIZE
+            || !sectors_per_cluster.is_power_of_two() || fat_count == 0 || fat_size == 0
+            || read_u16(&boot_sector, BPB_ROOT_ENTRY_COUNT) != 0 || read_u16(&boot_sector, BPB_FAT_SIZE_16) != 0 {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let data_start = reserved_sectors + fat_count * fat_size;
+        if total_sectors <= data_start || total_sectors > device.capacity() {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        let cluster_count = min((total_sectors - data_start) / sectors_per_cluster as u64, (CLUSTER_END - FIRST_CLUSTER) as u64) as u32;
+        let geometry = Geometry { sectors_per_cluster, fat_start: reserved_sectors, data_start, cluster_count, root_cluster };
+
+        let volume = Self { device, geometry };
+        if !volume.is_valid_cluster(root_cluster) {
+            return Err(FatError::InvalidBootSector);
+        }
+
+        return Ok(volume);
+    }
+
+    /// Open the file at `path` (components separated by '/', relative to the root directory).
+    /// Names are matched against long file names and short (8.3) names, ignoring the case of ASCII letters.
+    pub fn open(&self, path: &str) -> Result<FatFile, FatError> {
+        let mut entry = DirEntry { attributes: ATTR_DIRECTORY, first_cluster: self.geometry.root_cluster, size: 0 };
+
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if entry.attributes & ATTR_DIRECTORY == 0 {
+                return Err(FatError::NotADirectory);
+            }
+
+            entry = self.find_entry(entry.first_cluster, name)?;
+
+            // '..' in a subdirectory of the root directory refers to cluster 0
+            if entry.attributes & ATTR_DIRECTORY != 0 && entry.first_cluster == 0 {
+                entry.first_cluster = self.geometry.root_cluster;
+            }
+        }
+
+        if entry.attributes & ATTR_DIRECTORY != 0 {
+            return Err(FatError::IsADirectory);
+        }
+
+        return Ok(FatFile { volume: self.clone(), first_cluster: entry.first_cluster, size: entry.size, position: 0, cluster: entry.first_cluster, cluster_index: 0 });
+    }
+
+    fn find_entry(&self, dir_cluster: u32, name: &str) -> Result<DirEntry, FatError> {
+        let mut sector = [0u8; BLOCK_SIZE];
+        let mut long_name = [0u16; LFN_MAX_ENTRIES * LFN_CHAR_OFFSETS.len()];
+        let mut long_name_checksum: Option<u8> = None;
+        let mut cluster = Some(dir_cluster);
+        let mut visited_clusters = 0;
+
+        while let Some(current) = cluster {
+            // A cyclic cluster chain would otherwise never end
+            visited_clusters += 1;
+            if !self.is_valid_cluster(current) || visited_clusters > self.geometry.cluster_count {
+                return Err(FatError::CorruptedChain);
+            }
+
+            for index in 0..self.geometry.sectors_per_cluster as u64 {
+                self.device.read_block(self.cluster_sector(current) + index, &mut sector)?;
+
+                for raw_entry in sector.chunks_exact(DIR_ENTRY_SIZE) {
+                    match raw_entry[0] {
+                        ENTRY_END => return Err(FatError::NotFound),
+                        ENTRY_DELETED => {
+                            long_name_checksum = None;
+                            continue;
+                        }
+                        _ => {}
+                    }
+
+                    let attributes = raw_entry[DIR_ATTRIBUTES];
+                    if attributes & ATTR_LONG_NAME == ATTR_LONG_NAME {
+                        long_name_checksum = store_long_name_part(raw_entry, &mut long_name, long_name_checksum);
+                        continue;
+                    }
+
+                    let checksum = long_name_checksum.take();
+                    if attributes & ATTR_VOLUME_ID != 0 {
+                        continue;
+                    }
+
+                    // A long name belongs to the following short entry, if the checksum of its short name matches
+                    let matches_long_name = checksum == Some(short_name_checksum(&raw_entry[0..11]))
+                        && decode_long_name(&long_name).eq_ignore_ascii_case(name);
+
+                    if matches_long_name || decode_short_name(&raw_entry[0..11]).eq_ignore_ascii_case(name) {
+                        let first_cluster = (read_u16(raw_entry, DIR_CLUSTER_HIGH) as u32) << 16 | read_u16(raw_entry, DIR_CLUSTER_LOW) as u32;
+                        return Ok(DirEntry { attributes, first_cluster, size: read_u32(raw_entry, DIR_FILE_SIZE) });
+                    }
+                }
+            }
+
+            cluster = self.next_cluster(current)?;
+        }
+
+        return Err(FatError::NotFound);
+    }
+
+    /// Look up the successor of `cluster` in the FAT (`None`, if `cluster` is the last one of its chain).
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, FatError> {
+        let offset = cluster as u64 * 4;
+        let mut sector = [0u8; BLOCK_SIZE];
+        self.device.read_block(self.geometry.fat_start + offset / BLOCK_SIZE as u64, &mut sector)?;
+
+        let next = read_u32(&sector, (offset % BLOCK_SIZE as u64) as usize) & CLUSTER_MASK;
+        if next >= CLUSTER_END {
+            return Ok(None);
+        }
+
+        if !self.is_valid_cluster(next) {
+            return Err(FatError::CorruptedChain);
+        }
+
+        return Ok(Some(next));
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        return cluster >= FIRST_CLUSTER && cluster - FIRST_CLUSTER < self.geometry.cluster_count;
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u64 {
+        return self.geometry.data_start + (cluster - FIRST_CLUSTER) as u64 * self.geometry.sectors_per_cluster as u64;
+    }
+
+    fn cluster_size(&self) -> u32 {
+        return self.geometry.sectors_per_cluster * BLOCK_SIZE as u32;
+    }
+}
+
+impl FatFile {
+    pub fn size(&self) -> u32 {
+        return self.size;
+    }
+
+    /// Move the position to `position` (at most the end of the file).
+    pub fn seek(&mut self, position: u32) -> Result<(), FatError> {
+        let position = min(position, self.size);
+        if position == self.position {
+            return Ok(());
+        }
+
+        let cluster_index = position / self.volume.cluster_size();
+        if cluster_index < self.cluster_index {
+            self.cluster = self.first_cluster;
+            self.cluster_index = 0;
+        }
+
+        // At the end of the file, the cluster following the last one does not exist
+        if position < self.size {
+            while self.cluster_index < cluster_index {
+                self.cluster = self.volume.next_cluster(self.cluster)?.ok_or(FatError::CorruptedChain)?;
+                self.cluster_index += 1;
+            }
+        }
+
+        self.position = position;
+        return Ok(());
+    }
+
+    /// Read up to `buf.len()` bytes from the current position and advance it.
+    /// Returns the number of bytes read, which is 0 at the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, FatError> {
+        let cluster_size = self.volume.cluster_size();
+        let mut sector = [0u8; BLOCK_SIZE];
+        let mut read = 0;
+
+        while read < buf.len() && self.position < self.size {
+            // Since the file is read sequentially, the position is at most one cluster ahead
+            if self.position / cluster_size != self.cluster_index {
+                self.cluster = self.volume.next_cluster(self.cluster)?.ok_or(FatError::CorruptedChain)?;
+                self.cluster_index += 1;
+            }
+
+            if !self.volume.is_valid_cluster(self.cluster) {
+                return Err(FatError::CorruptedChain);
+            }
+
+            let cluster_offset = self.position % cluster_size;
+            let sector_offset = cluster_offset as usize % BLOCK_SIZE;
+            let length = min(min(BLOCK_SIZE - sector_offset, buf.len() - read), (self.size - self.position) as usize);
+
+            self.volume.device.read_block(self.volume.cluster_sector(self.cluster) + (cluster_offset as usize / BLOCK_SIZE) as u64, &mut sector)?;
+            buf[read..read + length].copy_from_slice(&sector[sector_offset..sector_offset + length]);
+
+            read += length;
+            self.position += length as u32;
+        }
+
+        return Ok(read);
+    }
+}
+
+/// Open file of a FAT32 volume, mounted in the VFS. Writing is not supported.
+struct FatNode {
+    file: Mutex<FatFile>
+}
+
+impl VfsNode for Fat32Volume {
+    fn open(&self, path: &str, _create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        return match Fat32Volume::open(self, path) {
+            Ok(file) => Ok(Arc::new(FatNode { file: Mutex::new(file) })),
+            Err(err) => Err(err.into())
+        };
+    }
+
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, FsError> {
+        return Err(FsError::IsADirectory);
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8]) -> Result<usize, FsError> {
+        return Err(FsError::IsADirectory);
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        return Ok(Stat { size: 0, directory: true });
+    }
+
+    fn unlink(&self, _path: &str) -> Result<(), FsError> {
+        return Err(FsError::ReadOnly);
+    }
+}
+
+impl VfsNode for FatNode {
+    fn open(&self, _path: &str, _create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        return Err(FsError::NotADirectory);
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let mut file = self.file.lock();
+        file.seek(min(offset, u32::MAX as u64) as u32)?;
+
+        return Ok(file.read(buf)?);
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8]) -> Result<usize, FsError> {
+        return Err(FsError::ReadOnly);
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        return Ok(Stat { size: self.file.lock().size() as u64, directory: false });
+    }
+
+    fn unlink(&self, _path: &str) -> Result<(), FsError> {
+        return Err(FsError::NotADirectory);
+    }
+}
+
+impl From<FatError> for FsError {
+    fn from(err: FatError) -> Self {
+        return match err {
+            FatError::NotFound => FsError::NotFound,
+            FatError::NotADirectory => FsError::NotADirectory,
+            FatError::IsADirectory => FsError::IsADirectory,
+            FatError::InvalidBootSector | FatError::CorruptedChain | FatError::Device(_) => FsError::IoError
+        };
+    }
+}
+
+/// Copy the characters of a long file name entry to their position in `long_name`.
+/// Returns the checksum, that the following short entry must match (`None`, if the long name is invalid).
+fn store_long_name_part(entry: &[u8], long_name: &mut [u16], checksum: Option<u8>) -> Option<u8> {
+    let order = (entry[0] & LFN_ORDER_MASK) as usize;
+    if order == 0 || order > LFN_MAX_ENTRIES {
+        return None;
+    }
+
+    // Long name entries are stored in reverse order, starting with the last part of the name
+    let checksum = if entry[0] & LFN_LAST != 0 {
+        long_name.fill(0);
+        entry[LFN_CHECKSUM]
+    } else if checksum == Some(entry[LFN_CHECKSUM]) {
+        entry[LFN_CHECKSUM]
+    } else {
+        return None;
+    };
+
+    for (index, &offset) in LFN_CHAR_OFFSETS.iter().enumerate() {
+        long_name[(order - 1) * LFN_CHAR_OFFSETS.len() + index] = read_u16(entry, offset);
+    }
+
+    return Some(checksum);
+}
+
+/// Long names are terminated by 0x0000 (and padded with 0xffff), unless they fill the last entry completely.
+fn decode_long_name(long_name: &[u16]) -> String {
+    let length = long_name.iter().position(|&char| char == 0x0000 || char == 0xffff).unwrap_or(long_name.len());
+    return decode_utf16(long_name[..length].iter().copied())
+        .map(|char| char.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect();
+}
+
+/// Convert an 8.3 name (padded with spaces) to 'NAME.EXT'.
+fn decode_short_name(short_name: &[u8]) -> String {
+    let mut name = String::new();
+    for (index, &byte) in short_name.iter().enumerate() {
+        if index == 8 && short_name[8] != b' ' {
+            name.push('.');
+        }
+
+        match byte {
+            b' ' => {}
+            ENTRY_KANJI_E5 if index == 0 => name.push(ENTRY_DELETED as char),
+            _ => name.push(byte as char)
+        }
+    }
+
+    return name;
+}
+
+fn short_name_checksum(short_name: &[u8]) -> u8 {
+    return short_name.iter().fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte));
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+}
@@ -0,0 +1,59 @@
+use alloc::sync::Arc;
+use core::array;
+use crate::fs::vfs::VfsNode;
+
+pub mod fat32;
+pub mod pipe;
+pub mod tmpfs;
+pub mod vfs;
+
+pub const MAX_OPEN_FILES: usize = 16;
+
+/// File opened by a process. The position is advanced by reading and writing.
+pub struct OpenFile {
+    pub node: Arc<dyn VfsNode>,
+    pub position: u64
+}
+
+/// Open files of a process, indexed by their file descriptor.
+pub struct FileTable {
+    files: [Option<OpenFile>; MAX_OPEN_FILES]
+}
+
+impl OpenFile {
+    pub fn new(node: Arc<dyn VfsNode>) -> Self {
+        Self { node, position: 0 }
+    }
+}
+
+impl FileTable {
+    pub fn new() -> Self {
+        Self { files: array::from_fn(|_| None) }
+    }
+
+    /// Store `file` in the lowest free slot and return its file descriptor (`None`, if the table is full).
+    pub fn insert(&mut self, file: OpenFile) -> Option<usize> {
+        let fd = self.files.iter().position(|slot| slot.is_none())?;
+        self.files[fd] = Some(file);
+
+        return Some(fd);
+    }
+
+    pub fn get_mut(&mut self, fd: usize) -> Option<&mut OpenFile> {
+        return self.files.get_mut(fd)?.as_mut();
+    }
+
+    /// Advance the position of `fd` by `count` bytes, if it still refers to `node`.
+    /// Used after reading or writing without holding the table's lock, during which another thread may have closed `fd`.
+    pub fn advance(&mut self, fd: usize, node: &Arc<dyn VfsNode>, count: u64) {
+        if let Some(file) = self.get_mut(fd) {
+            if Arc::ptr_eq(&file.node, node) {
+                file.position += count;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, fd: usize) -> Option<OpenFile> {
+        return self.files.get_mut(fd)?.take();
+    }
+}
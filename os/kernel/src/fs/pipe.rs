@@ -0,0 +1,169 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::min;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU32};
+use core::sync::atomic::Ordering::{Acquire, Release};
+use spin::Mutex;
+use crate::fs::vfs::{FsError, Stat, VfsNode};
+use crate::scheduler;
+
+/// Capacity of the ring buffer of a pipe (in bytes).
+pub const PIPE_CAPACITY: usize = 4096;
+
+/// Unidirectional byte stream between a 'PipeReader' and a 'PipeWriter'.
+/// Blocked readers and writers wait on the futex 'sequence', which is incremented on every change of the pipe.
+/// Kernel memory is identity mapped, so the address of 'sequence' is also its physical address (the key used by the scheduler).
+struct Pipe {
+    buffer: Mutex<VecDeque<u8>>,
+    sequence: AtomicU32,
+    reader_closed: AtomicBool,
+    writer_closed: AtomicBool
+}
+
+/// Read end of a pipe. Reading blocks, while the pipe is empty, and returns 0, after the write end has been closed.
+pub struct PipeReader {
+    pipe: Arc<Pipe>
+}
+
+/// Write end of a pipe. Writing blocks, while the pipe is full, and fails, after the read end has been closed.
+pub struct PipeWriter {
+    pipe: Arc<Pipe>
+}
+
+/// Create a pipe and return its read and write end.
+pub fn create() -> (Arc<PipeReader>, Arc<PipeWriter>) {
+    let pipe = Arc::new(Pipe {
+        buffer: Mutex::new(VecDeque::with_capacity(PIPE_CAPACITY)),
+        sequence: AtomicU32::new(0),
+        reader_closed: AtomicBool::new(false),
+        writer_closed: AtomicBool::new(false)
+    });
+
+    return (Arc::new(PipeReader { pipe: Arc::clone(&pipe) }), Arc::new(PipeWriter { pipe }));
+}
+
+impl Pipe {
+    fn futex_key(&self) -> u64 {
+        return ptr::from_ref(&self.sequence) as u64;
+    }
+
+    /// Block, until the pipe has changed since `sequence` has been read (must be read with the buffer locked).
+    fn wait(&self, sequence: u32) {
+        let _ = scheduler().futex_wait(self.futex_key(), None, || self.sequence.load(Acquire) == sequence);
+    }
+
+    /// Wake up all blocked readers and writers. Must be called after every change of the pipe.
+    fn notify(&self) {
+        self.sequence.fetch_add(1, Release);
+        scheduler().futex_wake(self.futex_key(), usize::MAX);
+    }
+}
+
+impl VfsNode for PipeReader {
+    fn open(&self, _path: &str, _create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        return Err(FsError::NotADirectory);
+    }
+
+    /// Read up to `buf.len()` bytes, blocking until at least one byte is available (`offset` is ignored).
+    fn read(&self, _offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let sequence = {
+                let mut buffer = self.pipe.buffer.lock();
+                if !buffer.is_empty() {
+                    let count = min(buf.len(), buffer.len());
+                    buffer.drain(..count).zip(buf.iter_mut()).for_each(|(byte, target)| *target = byte);
+                    drop(buffer);
+
+                    self.pipe.notify();
+                    return Ok(count);
+                }
+
+                if self.pipe.writer_closed.load(Acquire) {
+                    return Ok(0);
+                }
+
+                self.pipe.sequence.load(Acquire)
+            };
+
+            self.pipe.wait(sequence);
+        }
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8]) -> Result<usize, FsError> {
+        return Err(FsError::InvalidAccess);
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        return Ok(Stat { size: self.pipe.buffer.lock().len() as u64, directory: false });
+    }
+
+    fn unlink(&self, _path: &str) -> Result<(), FsError> {
+        return Err(FsError::NotADirectory);
+    }
+}
+
+impl VfsNode for PipeWriter {
+    fn open(&self, _path: &str, _create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        return Err(FsError::NotADirectory);
+    }
+
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, FsError> {
+        return Err(FsError::InvalidAccess);
+    }
+
+    /// Write as many bytes of `buf` as fit into the pipe, blocking until there is space for at least one byte (`offset` is ignored).
+    fn write(&self, _offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let sequence = {
+                let mut buffer = self.pipe.buffer.lock();
+                if self.pipe.reader_closed.load(Acquire) {
+                    return Err(FsError::BrokenPipe);
+                }
+
+                if buffer.len() < PIPE_CAPACITY {
+                    let count = min(buf.len(), PIPE_CAPACITY - buffer.len());
+                    buffer.extend(&buf[..count]);
+                    drop(buffer);
+
+                    self.pipe.notify();
+                    return Ok(count);
+                }
+
+                self.pipe.sequence.load(Acquire)
+            };
+
+            self.pipe.wait(sequence);
+        }
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        return Ok(Stat { size: self.pipe.buffer.lock().len() as u64, directory: false });
+    }
+
+    fn unlink(&self, _path: &str) -> Result<(), FsError> {
+        return Err(FsError::NotADirectory);
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.reader_closed.store(true, Release);
+        self.pipe.notify();
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.writer_closed.store(true, Release);
+        self.pipe.notify();
+    }
+}
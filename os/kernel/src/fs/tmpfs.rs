@@ -0,0 +1,122 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
+use spin::Mutex;
+use crate::fs::vfs::{FsError, Stat, VfsNode};
+
+/// Contents of all files, indexed by their path relative to the mount point (without leading '/').
+/// Since the contents are global, every mount of a 'RamFs' shows the same files.
+static FILES: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+/// Files are kept in kernel memory, so their size is limited to prevent a single write from exhausting the kernel heap.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Filesystem, that keeps its files in memory, so that threads can share data without persistent storage.
+/// It has no directories: A path (e.g. 'a/b') just names a file.
+pub struct RamFs {}
+
+/// Open file of a 'RamFs'. It refers to the file by path, so that it sees the changes of all other open files.
+struct RamFile {
+    path: String
+}
+
+impl RamFs {
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl VfsNode for RamFs {
+    fn open(&self, path: &str, create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        let path = normalize(path);
+        if path.is_empty() {
+            return Err(FsError::IsADirectory);
+        }
+
+        let mut files = FILES.lock();
+        if !files.contains_key(&path) {
+            if !create {
+                return Err(FsError::NotFound);
+            }
+
+            files.insert(path.clone(), Vec::new());
+        }
+
+        return Ok(Arc::new(RamFile { path }));
+    }
+
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, FsError> {
+        return Err(FsError::IsADirectory);
+    }
+
+    fn write(&self, _offset: u64, _buf: &[u8]) -> Result<usize, FsError> {
+        return Err(FsError::IsADirectory);
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        return Ok(Stat { size: 0, directory: true });
+    }
+
+    fn unlink(&self, path: &str) -> Result<(), FsError> {
+        return match FILES.lock().remove(&normalize(path)) {
+            Some(_) => Ok(()),
+            None => Err(FsError::NotFound)
+        };
+    }
+}
+
+impl VfsNode for RamFile {
+    fn open(&self, _path: &str, _create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        return Err(FsError::NotADirectory);
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let files = FILES.lock();
+        let data = files.get(&self.path).ok_or(FsError::NotFound)?;
+        if offset >= data.len() as u64 {
+            return Ok(0);
+        }
+
+        let start = offset as usize;
+        let length = min(buf.len(), data.len() - start);
+        buf[..length].copy_from_slice(&data[start..start + length]);
+
+        return Ok(length);
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        if offset.saturating_add(buf.len() as u64) > MAX_FILE_SIZE {
+            return Err(FsError::FileTooLarge);
+        }
+
+        let mut files = FILES.lock();
+        let data = files.get_mut(&self.path).ok_or(FsError::NotFound)?;
+
+        // Writing behind the end of the file fills the gap with zeros
+        let start = offset as usize;
+        if data.len() < start + buf.len() {
+            data.resize(start + buf.len(), 0);
+        }
+
+        data[start..start + buf.len()].copy_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        let files = FILES.lock();
+        let data = files.get(&self.path).ok_or(FsError::NotFound)?;
+
+        return Ok(Stat { size: data.len() as u64, directory: false });
+    }
+
+    fn unlink(&self, _path: &str) -> Result<(), FsError> {
+        return Err(FsError::NotADirectory);
+    }
+}
+
+/// Remove empty components, so that e.g. 'a//b/' and 'a/b' name the same file.
+fn normalize(path: &str) -> String {
+    return path.split('/').filter(|component| !component.is_empty()).collect::<Vec<&str>>().join("/");
+}
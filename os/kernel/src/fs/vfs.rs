@@ -0,0 +1,112 @@
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FsError {
+    /// The path does not exist
+    NotFound,
+    /// A file has been used like a directory
+    NotADirectory,
+    /// A directory has been used like a file
+    IsADirectory,
+    /// The filesystem cannot be modified
+    ReadOnly,
+    /// The file would exceed the maximum size of the filesystem
+    FileTooLarge,
+    /// The path is not absolute
+    InvalidPath,
+    /// The underlying device has failed or contains corrupted data
+    IoError,
+    /// The file does not support this kind of access (e.g. writing to the read end of a pipe)
+    InvalidAccess,
+    /// The read end of a pipe has been closed
+    BrokenPipe,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Stat {
+    pub size: u64,
+    pub directory: bool,
+}
+
+/// File or directory of a mounted filesystem. The root directory of a filesystem is mounted in the 'Vfs'.
+/// Directory nodes implement `open()` and `unlink()`, file nodes `read()` and `write()`.
+pub trait VfsNode: Send + Sync {
+    /// Look up `path` (relative to this directory), creating an empty file, if `create` is set and it does not exist.
+    fn open(&self, path: &str, create: bool) -> Result<Arc<dyn VfsNode>, FsError>;
+
+    /// Read up to `buf.len()` bytes starting at `offset` and return the number of bytes read (0 at the end of the file).
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError>;
+
+    /// Write `buf` at `offset` (extending the file, if necessary) and return the number of bytes written.
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<usize, FsError>;
+
+    fn stat(&self) -> Result<Stat, FsError>;
+
+    /// Remove the file at `path` (relative to this directory).
+    fn unlink(&self, path: &str) -> Result<(), FsError>;
+
+    /// Write cached data of the filesystem back to its device (called on the root directory of a mount).
+    /// Filesystems, that write through to their device, have nothing to do.
+    fn sync(&self) -> Result<(), FsError> {
+        return Ok(());
+    }
+}
+
+/// Filesystems mounted into a single directory tree. Paths are resolved by the mount with the longest matching prefix.
+pub struct Vfs {
+    mounts: Vec<(String, Arc<dyn VfsNode>)>
+}
+
+impl Vfs {
+    pub const fn new() -> Self {
+        Self { mounts: Vec::new() }
+    }
+
+    /// Mount the root directory `node` at the absolute path `mount_point` (replacing an existing mount at the same path).
+    pub fn mount(&mut self, mount_point: &str, node: Arc<dyn VfsNode>) {
+        let mount_point = mount_point.trim_end_matches('/').to_string();
+        self.mounts.retain(|(path, _)| *path != mount_point);
+        self.mounts.push((mount_point, node));
+    }
+
+    pub fn open(&self, path: &str, create: bool) -> Result<Arc<dyn VfsNode>, FsError> {
+        let (node, relative_path) = self.resolve(path)?;
+        return node.open(relative_path, create);
+    }
+
+    pub fn unlink(&self, path: &str) -> Result<(), FsError> {
+        let (node, relative_path) = self.resolve(path)?;
+        return node.unlink(relative_path);
+    }
+
+    /// Synchronize all mounted filesystems (e.g. before the system is powered off).
+    pub fn sync(&self) -> Result<(), FsError> {
+        for (_, node) in self.mounts.iter() {
+            node.sync()?;
+        }
+
+        return Ok(());
+    }
+
+    /// Find the mount, that `path` belongs to, and return its root directory and the path relative to it.
+    fn resolve<'a>(&self, path: &'a str) -> Result<(&Arc<dyn VfsNode>, &'a str), FsError> {
+        if !path.starts_with('/') {
+            return Err(FsError::InvalidPath);
+        }
+
+        return self.mounts.iter()
+            .filter_map(|(mount_point, node)| {
+                let relative_path = path.strip_prefix(mount_point.as_str())?;
+                if relative_path.is_empty() || relative_path.starts_with('/') {
+                    Some((mount_point.len(), node, relative_path.trim_start_matches('/')))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(length, _, _)| *length)
+            .map(|(_, node, relative_path)| (node, relative_path))
+            .ok_or(FsError::NotFound);
+    }
+}
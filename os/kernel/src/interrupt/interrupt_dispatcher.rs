@@ -4,10 +4,10 @@ use alloc::vec::Vec;
 use core::ops::Deref;
 use core::ptr;
 use spin::Mutex;
-use x86_64::registers::control::Cr2;
 use x86_64::set_general_handler;
+use x86_64::instructions::segmentation::GS;
 use x86_64::structures::idt::InterruptStackFrame;
-use crate::{apic, idt, interrupt_dispatcher};
+use crate::{apic, entropy_pool, idt, interrupt_dispatcher, percpu, scheduler};
 
 #[repr(u8)]
 #[derive(PartialEq, PartialOrd, Copy, Clone, Debug)]
@@ -55,6 +55,9 @@ pub enum InterruptVector {
     SecondaryAta = 0x2f,
     // Possibly some other interrupts supported by IO APICs
 
+    // Inter-processor interrupts
+    TlbShootdown = 0xf0,
+
     // Local APIC interrupts (247 - 254)
     Cmci = 0xf8,
     ApicTimer = 0xf9,
@@ -152,6 +155,8 @@ impl TryFrom<u8> for InterruptVector {
                 Ok(InterruptVector::SecondaryAta)
             }
 
+            value if value == InterruptVector::TlbShootdown as u8 => Ok(InterruptVector::TlbShootdown),
+
             value if value == InterruptVector::Cmci as u8 => Ok(InterruptVector::Cmci),
             value if value == InterruptVector::ApicTimer as u8 => Ok(InterruptVector::ApicTimer),
             value if value == InterruptVector::Thermal as u8 => Ok(InterruptVector::Thermal),
@@ -181,7 +186,15 @@ pub fn setup_idt() {
 
     set_general_handler!(&mut idt, handle_exception, 0..31);
     set_general_handler!(&mut idt, handle_interrupt, 32..255);
-    set_general_handler!(&mut idt, handle_page_fault, 14);
+    crate::arch::exception::install(&mut idt);
+
+    #[cfg(feature = "fpu_emulate")]
+    crate::arch::fpu::install(&mut idt);
+}
+
+/// Load the IDT, set up by 'setup_idt()', on the executing CPU.
+pub fn load_idt() {
+    let idt = idt().lock();
 
     unsafe {
         // We need to obtain a static reference to the IDT for the following operation.
@@ -193,21 +206,49 @@ pub fn setup_idt() {
 }
 
 fn handle_exception(frame: InterruptStackFrame, index: u8, error: Option<u64>) {
+    if (frame.code_segment & 0x3) == 3 {
+        // Switch to the kernel's GS base (see 'percpu'), since the panic does not return to user mode
+        unsafe { GS::swap(); }
+    }
+
     panic!("CPU Exception: [{} - {:?}]\nError code: [{:?}]\n{:?}", index, InterruptVector::try_from(index).unwrap(), error, frame);
 }
 
-fn handle_page_fault(frame: InterruptStackFrame, _index: u8, error: Option<u64>) {
-    panic!("Page Fault!\nError code: [{:?}]\nAddress: [{:0>16x}]\n{:?}", error, Cr2::read(), frame);
-}
+fn handle_interrupt(frame: InterruptStackFrame, index: u8, _error: Option<u64>) {
+    // Interrupts from user mode switch to the kernel's GS base (see 'percpu') and back before returning
+    let user_mode = (frame.code_segment & 0x3) == 3;
+    if user_mode {
+        unsafe { GS::swap(); }
+    }
+
+    // Application processors get their ticks from their local APIC timer instead of the PIT (see 'Timer::start_local_tick()')
+    let tick = index == InterruptVector::Pit as u8 || (index == InterruptVector::ApicTimer as u8 && percpu::current_cpu_id() != 0);
+    if tick {
+        // Charge the tick to the interrupted thread (user time, if it has been interrupted in ring 3)
+        scheduler().account_tick(user_mode);
+    }
+
+    // Interrupt timings are a source of entropy (skipped, if the pool is currently in use by the interrupted thread)
+    if let Some(mut pool) = entropy_pool().try_lock() {
+        pool.add_interrupt_timing(index);
+    }
 
-fn handle_interrupt(_frame: InterruptStackFrame, index: u8, _error: Option<u64>) {
     interrupt_dispatcher().dispatch(index);
+
+    // Killed threads are terminated here, since they cannot hold any kernel locks while running in user mode
+    if tick && user_mode {
+        scheduler().exit_if_killed();
+    }
+
+    if user_mode {
+        unsafe { GS::swap(); }
+    }
 }
 
 impl InterruptDispatcher {
     pub fn new() -> Self {
         let mut int_vectors = Vec::<Mutex<Vec<Box<dyn InterruptHandler>>>>::new();
-        for _ in 32..MAX_VECTORS {
+        for _ in 0..MAX_VECTORS {
             int_vectors.push(Mutex::new(Vec::new()));
         }
 
@@ -215,6 +256,7 @@ impl InterruptDispatcher {
     }
 
     pub fn assign(&self, vector: InterruptVector, handler: Box<dyn InterruptHandler>) {
+        trace!(TRACE_INTERRUPT, "Assigning handler to vector [{:?}]", vector);
         match self.int_vectors.get(vector as usize) {
             Some(vec) => vec.lock().push(handler),
             None => panic!("Assigning interrupt handler to illegal vector number {}!", vector as u8)
@@ -0,0 +1,59 @@
+use alloc::vec::Vec;
+use log::info;
+use x86_64::PhysAddr;
+use crate::acpi::dmar::{Dmar, PciEndpoint, RemappingUnit};
+use crate::iommu::vtd::VtdUnit;
+use crate::{acpi_tables, iommu};
+
+pub mod vtd;
+
+/// All DMA remapping units of the system, together with the devices they are responsible for.
+pub struct Iommu {
+    units: Vec<(RemappingUnit, VtdUnit)>
+}
+
+impl Iommu {
+    /// Parse the DMAR table and enable all remapping units on PCI segment 0.
+    /// Returns `None`, if the system has no IOMMU.
+    pub fn new() -> Option<Self> {
+        let dmar = match acpi_tables()?.lock().find_table::<Dmar>() {
+            Ok(dmar) => dmar,
+            Err(_) => return None
+        };
+
+        info!("DMAR table found (Host address width: [{}] bits)", dmar.host_address_width());
+
+        let units: Vec<(RemappingUnit, VtdUnit)> = dmar.remapping_units().into_iter()
+            .filter(|unit| unit.segment == 0)
+            .map(|unit| {
+                let vtd = VtdUnit::new(unit.register_base);
+                (unit, vtd)
+            })
+            .collect();
+
+        if units.is_empty() {
+            return None;
+        }
+
+        return Some(Self { units });
+    }
+
+    fn unit_for(&self, bus: u8, dev: u8, func: u8) -> Option<&VtdUnit> {
+        let endpoint = PciEndpoint { bus, device: dev, function: func };
+
+        // Units listing the device explicitly take precedence over a unit covering all remaining devices
+        return self.units.iter()
+            .find(|(unit, _)| unit.endpoints.contains(&endpoint))
+            .or_else(|| self.units.iter().find(|(unit, _)| unit.include_pci_all))
+            .map(|(_, vtd)| vtd);
+    }
+}
+
+/// Allow the PCI device (`bus`, `dev`, `func`) to access `size` bytes of physical memory starting at `paddr` via DMA,
+/// using the I/O virtual address `iova`. Drivers must register all DMA buffers this way before handing them to a device.
+/// If the device is not behind any remapping unit, it can access all of physical memory and nothing needs to be done.
+pub fn iommu_map(bus: u8, dev: u8, func: u8, iova: u64, paddr: PhysAddr, size: usize) {
+    if let Some(unit) = iommu().and_then(|iommu| iommu.unit_for(bus, dev, func)) {
+        unit.map(bus, dev, func, iova, paddr, size);
+    }
+}
@@ -0,0 +1,271 @@
+use core::arch::asm;
+use core::ptr;
+use log::info;
+use smallmap::Map;
+use spin::Mutex;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTable, PageTableFlags};
+use x86_64::{PhysAddr, VirtAddr};
+use crate::memory::{physical, MemorySpace, PAGE_SIZE};
+use crate::memory::r#virtual::current_address_space;
+
+// Register offsets (Intel VT-d specification, chapter 11)
+const CAPABILITY_REGISTER: u64 = 0x08;
+const EXTENDED_CAPABILITY_REGISTER: u64 = 0x10;
+const GLOBAL_COMMAND_REGISTER: u64 = 0x18;
+const GLOBAL_STATUS_REGISTER: u64 = 0x1c;
+const ROOT_TABLE_ADDRESS_REGISTER: u64 = 0x20;
+const CONTEXT_COMMAND_REGISTER: u64 = 0x28;
+
+const GLOBAL_TRANSLATION_ENABLE: u32 = 1 << 31;
+const GLOBAL_SET_ROOT_TABLE_POINTER: u32 = 1 << 30;
+const GLOBAL_WRITE_BUFFER_FLUSH: u32 = 1 << 27;
+// One-shot bits, that must not be written back when preserving the current status
+const GLOBAL_ONE_SHOT_MASK: u32 = 0x96ffffff;
+
+const CONTEXT_INVALIDATE: u64 = 1 << 63;
+const CONTEXT_GLOBAL_INVALIDATION: u64 = 1 << 61;
+const IOTLB_INVALIDATE: u64 = 1 << 63;
+const IOTLB_GLOBAL_INVALIDATION: u64 = 1 << 60;
+const IOTLB_DRAIN_READS: u64 = 1 << 49;
+const IOTLB_DRAIN_WRITES: u64 = 1 << 48;
+
+const ENTRY_PRESENT: u64 = 1 << 0;
+const ENTRY_ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+// Second level page table entries use the same bit positions for read and write access as regular page tables
+const SECOND_LEVEL_FLAGS: PageTableFlags = PageTableFlags::from_bits_truncate(PageTableFlags::PRESENT.bits() | PageTableFlags::WRITABLE.bits());
+
+/// Root and context tables consist of 256 entries with 128 bits each.
+type TranslationTable = [[u64; 2]; 256];
+
+struct Tables {
+    root_table: *mut TranslationTable,
+    // Second level page table for each device, identified by its source id (bus, device, function)
+    domains: Map<u16, *mut PageTable>,
+    next_domain_id: u16
+}
+
+/// A single DMA Remapping Hardware Unit. After initialization, translation is enabled with an empty root table,
+/// blocking all DMA requests. Devices only get access to memory regions, that have explicitly been mapped via `map()`.
+/// Only created, if enabled on the kernel command line (see 'CmdlineArgs::iommu()').
+pub struct VtdUnit {
+    registers: u64,
+    iotlb_register: u64,
+    levels: usize,
+    address_width: u64,
+    max_domains: usize,
+    coherent: bool,
+    caching_mode: bool,
+    write_buffer_flush: bool,
+    tables: Mutex<Tables>
+}
+
+unsafe impl Send for VtdUnit {}
+unsafe impl Sync for VtdUnit {}
+
+impl VtdUnit {
+    pub fn new(base: PhysAddr) -> Self {
+        // Map the register page into the kernel address space
+        let register_page = Page::from_start_address(VirtAddr::new(base.as_u64())).expect("VT-d: Register base address is not page aligned!");
+        VtdUnit::map_registers(PageRange { start: register_page, end: register_page + 1 });
+
+        let capabilities = unsafe { ptr::read_volatile((base.as_u64() + CAPABILITY_REGISTER) as *const u64) };
+        let extended_capabilities = unsafe { ptr::read_volatile((base.as_u64() + EXTENDED_CAPABILITY_REGISTER) as *const u64) };
+
+        // The IOTLB registers may be located on a subsequent page
+        let iotlb_register = base.as_u64() + ((extended_capabilities >> 8) & 0x3ff) * 16 + 0x08;
+        let register_end_page = Page::containing_address(VirtAddr::new(iotlb_register + 8)) + 1;
+        if register_end_page > register_page + 1 {
+            VtdUnit::map_registers(PageRange { start: register_page + 1, end: register_end_page });
+        }
+
+        // Choose the largest supported page table depth (4-level or 3-level)
+        let supported_widths = (capabilities >> 8) & 0x1f;
+        let (levels, address_width) = if supported_widths & 0x04 != 0 {
+            (4, 2)
+        } else if supported_widths & 0x02 != 0 {
+            (3, 1)
+        } else {
+            panic!("VT-d: Neither 3-level nor 4-level page tables are supported!");
+        };
+
+        let root_table = physical::alloc(1, MemorySpace::Kernel).start.start_address().as_u64() as *mut TranslationTable;
+        unsafe { root_table.write_bytes(0, 1); }
+
+        let unit = Self {
+            registers: base.as_u64(),
+            iotlb_register,
+            levels,
+            address_width,
+            max_domains: 1 << (4 + 2 * (capabilities & 0x07)),
+            coherent: extended_capabilities & 0x01 != 0,
+            caching_mode: capabilities & (1 << 7) != 0,
+            write_buffer_flush: capabilities & (1 << 4) != 0,
+            tables: Mutex::new(Tables { root_table, domains: Map::new(), next_domain_id: 1 })
+        };
+
+        // Program root table pointer
+        unit.flush_cache(root_table as u64, PAGE_SIZE);
+        unit.write_register(ROOT_TABLE_ADDRESS_REGISTER, root_table as u64);
+        unit.global_command(GLOBAL_SET_ROOT_TABLE_POINTER);
+        unit.invalidate_context_cache();
+        unit.invalidate_iotlb();
+
+        // Enable DMA remapping -> From now on, all DMA requests from devices without a mapping are blocked
+        unit.global_command(GLOBAL_TRANSLATION_ENABLE);
+        info!("VT-d remapping unit at [0x{:x}] enabled ({}-level page tables, {} domains)", base.as_u64(), levels, unit.max_domains);
+
+        return unit;
+    }
+
+    /// Allow the device (`bus`, `dev`, `func`) to access `size` bytes starting at `paddr`, using the I/O virtual address `iova`.
+    /// Each device gets its own domain, so that devices cannot access each other's buffers.
+    pub fn map(&self, bus: u8, dev: u8, func: u8, iova: u64, paddr: PhysAddr, size: usize) {
+        if iova % PAGE_SIZE as u64 != 0 || !paddr.is_aligned(PAGE_SIZE as u64) {
+            panic!("VT-d: Addresses must be page aligned!");
+        }
+        if iova + size as u64 > 1 << (12 + 9 * self.levels) {
+            panic!("VT-d: I/O virtual address [0x{:x}] exceeds supported address width!", iova);
+        }
+
+        let mut tables = self.tables.lock();
+        let source_id = (bus as u16) << 8 | (dev as u16 & 0x1f) << 3 | (func as u16 & 0x07);
+        let page_table = match tables.domains.get(&source_id) {
+            Some(page_table) => *page_table,
+            None => self.create_domain(&mut tables, bus, (dev & 0x1f) << 3 | (func & 0x07))
+        };
+
+        let page_count = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        for i in 0..page_count {
+            let offset = (i * PAGE_SIZE) as u64;
+            self.map_page(unsafe { page_table.as_mut().unwrap() }, iova + offset, paddr + offset, self.levels);
+        }
+
+        // Hardware implementations with caching mode may cache not-present entries
+        if self.caching_mode {
+            self.invalidate_iotlb();
+        } else {
+            self.flush_write_buffer();
+        }
+    }
+
+    fn create_domain(&self, tables: &mut Tables, bus: u8, devfn: u8) -> *mut PageTable {
+        if tables.next_domain_id as usize >= self.max_domains {
+            panic!("VT-d: Out of domain identifiers!");
+        }
+
+        let root_table = unsafe { tables.root_table.as_mut().unwrap() };
+        let root_entry = &mut root_table[bus as usize];
+        if root_entry[0] & ENTRY_PRESENT == 0 {
+            let context_table = physical::alloc(1, MemorySpace::Kernel).start.start_address().as_u64();
+            unsafe { (context_table as *mut TranslationTable).write_bytes(0, 1); }
+            self.flush_cache(context_table, PAGE_SIZE);
+
+            root_entry[0] = context_table | ENTRY_PRESENT;
+            self.flush_cache(ptr::from_ref(root_entry) as u64, 16);
+        }
+
+        let page_table = physical::alloc(1, MemorySpace::Kernel).start.start_address().as_u64() as *mut PageTable;
+        unsafe { page_table.as_mut().unwrap().zero(); }
+        self.flush_cache(page_table as u64, PAGE_SIZE);
+
+        // Translation type 0 -> Untranslated requests are translated via the second level page table
+        let domain_id = tables.next_domain_id;
+        let context_table = unsafe { ((root_entry[0] & ENTRY_ADDRESS_MASK) as *mut TranslationTable).as_mut().unwrap() };
+        let context_entry = &mut context_table[devfn as usize];
+        context_entry[1] = self.address_width | (domain_id as u64) << 8;
+        context_entry[0] = page_table as u64 | ENTRY_PRESENT;
+        self.flush_cache(ptr::from_ref(context_entry) as u64, 16);
+
+        tables.next_domain_id += 1;
+        tables.domains.insert((bus as u16) << 8 | devfn as u16, page_table);
+
+        self.invalidate_context_cache();
+        self.invalidate_iotlb();
+
+        return page_table;
+    }
+
+    fn map_page(&self, table: &mut PageTable, iova: u64, paddr: PhysAddr, level: usize) {
+        let index = ((iova >> 12 >> ((level - 1) * 9)) & 0x1ff) as usize;
+        let entry = &mut table[index];
+
+        if level > 1 {
+            if entry.is_unused() { // Entry is empty -> Allocate new page frame
+                let phys_frame = physical::alloc(1, MemorySpace::Kernel).start;
+                unsafe { (phys_frame.start_address().as_u64() as *mut PageTable).as_mut().unwrap().zero(); }
+                self.flush_cache(phys_frame.start_address().as_u64(), PAGE_SIZE);
+
+                entry.set_frame(phys_frame, SECOND_LEVEL_FLAGS);
+                self.flush_cache(ptr::from_ref(entry) as u64, 8);
+            }
+
+            let next_level_table = unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
+            self.map_page(next_level_table, iova, paddr, level - 1);
+        } else {
+            entry.set_addr(paddr, SECOND_LEVEL_FLAGS);
+            self.flush_cache(ptr::from_ref(entry) as u64, 8);
+        }
+    }
+
+    fn map_registers(pages: PageRange) {
+        current_address_space().write().map(pages, MemorySpace::Kernel, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE);
+    }
+
+    /// Remapping hardware without coherent page walks reads tables directly from memory,
+    /// so modified entries need to be written back from the CPU caches.
+    fn flush_cache(&self, addr: u64, size: usize) {
+        if self.coherent {
+            return;
+        }
+
+        for line in (addr & !0x3f..addr + size as u64).step_by(64) {
+            unsafe { asm!("clflush [{}]", in(reg) line, options(nostack, preserves_flags)); }
+        }
+    }
+
+    fn flush_write_buffer(&self) {
+        if self.write_buffer_flush {
+            self.global_command(GLOBAL_WRITE_BUFFER_FLUSH);
+        }
+    }
+
+    fn invalidate_context_cache(&self) {
+        self.flush_write_buffer();
+        self.write_register(CONTEXT_COMMAND_REGISTER, CONTEXT_INVALIDATE | CONTEXT_GLOBAL_INVALIDATION);
+        while self.read_register(CONTEXT_COMMAND_REGISTER) & CONTEXT_INVALIDATE != 0 {}
+    }
+
+    fn invalidate_iotlb(&self) {
+        self.flush_write_buffer();
+        let iotlb = self.iotlb_register as *mut u64;
+        unsafe {
+            ptr::write_volatile(iotlb, IOTLB_INVALIDATE | IOTLB_GLOBAL_INVALIDATION | IOTLB_DRAIN_READS | IOTLB_DRAIN_WRITES);
+            while ptr::read_volatile(iotlb) & IOTLB_INVALIDATE != 0 {}
+        }
+    }
+
+    /// Issue a command via the global command register and wait for the hardware to complete it.
+    fn global_command(&self, command: u32) {
+        let status = unsafe { ptr::read_volatile((self.registers + GLOBAL_STATUS_REGISTER) as *const u32) } & GLOBAL_ONE_SHOT_MASK;
+        unsafe { ptr::write_volatile((self.registers + GLOBAL_COMMAND_REGISTER) as *mut u32, status | command); }
+
+        loop {
+            let status = unsafe { ptr::read_volatile((self.registers + GLOBAL_STATUS_REGISTER) as *const u32) };
+            // Write buffer flush status is cleared on completion, all other status bits are set
+            let done = if command == GLOBAL_WRITE_BUFFER_FLUSH { status & command == 0 } else { status & command != 0 };
+            if done {
+                break;
+            }
+        }
+    }
+
+    fn read_register(&self, offset: u64) -> u64 {
+        unsafe { ptr::read_volatile((self.registers + offset) as *const u64) }
+    }
+
+    fn write_register(&self, offset: u64, value: u64) {
+        unsafe { ptr::write_volatile((self.registers + offset) as *mut u64, value); }
+    }
+}
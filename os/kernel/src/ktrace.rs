@@ -0,0 +1,34 @@
+use core::sync::atomic::AtomicBool;
+use library_syscall::{KTRACE_INTERRUPT, KTRACE_MEMORY, KTRACE_SCHEDULER, KTRACE_SYSCALL};
+
+// Trace flags of all subsystems (disabled by default).
+// They are not mangled, so that they can also be toggled by a debugger (e.g. 'set var TRACE_SCHEDULER = 1' in GDB).
+#[no_mangle]
+pub static TRACE_SCHEDULER: AtomicBool = AtomicBool::new(false);
+#[no_mangle]
+pub static TRACE_MEMORY: AtomicBool = AtomicBool::new(false);
+#[no_mangle]
+pub static TRACE_SYSCALL: AtomicBool = AtomicBool::new(false);
+#[no_mangle]
+pub static TRACE_INTERRUPT: AtomicBool = AtomicBool::new(false);
+
+/// Log a message, if tracing is enabled for the given subsystem flag (e.g. `trace!(TRACE_SCHEDULER, "...")`).
+/// Traces are logged as info messages, so that they are not filtered out in release builds.
+macro_rules! trace {
+    ($flag:ident, $($arg:tt)*) => ({
+        if $crate::ktrace::$flag.load(core::sync::atomic::Ordering::Relaxed) {
+            log::info!($($arg)*);
+        }
+    });
+}
+
+/// Trace flag of a subsystem, identified by one of the 'KTRACE_*' constants.
+pub fn flag(subsystem: u32) -> Option<&'static AtomicBool> {
+    return match subsystem {
+        KTRACE_SCHEDULER => Some(&TRACE_SCHEDULER),
+        KTRACE_MEMORY => Some(&TRACE_MEMORY),
+        KTRACE_SYSCALL => Some(&TRACE_SYSCALL),
+        KTRACE_INTERRUPT => Some(&TRACE_INTERRUPT),
+        _ => None
+    };
+}
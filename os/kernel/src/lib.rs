@@ -7,10 +7,15 @@
 #![feature(panic_info_message)]
 #![feature(fmt_internals)]
 #![feature(abi_x86_interrupt)]
+#![feature(inline_const)]
 #![allow(internal_features)]
 #![no_std]
 
+use crate::arch::iopb::TssWithIopb;
+use crate::cmdline::CmdlineArgs;
+use crate::crypto::entropy::EntropyPool;
 use crate::device::apic::Apic;
+use crate::device::hpet::Hpet;
 use crate::device::lfb_terminal::{CursorThread, LFBTerminal};
 use crate::device::pit::Timer;
 use crate::device::ps2::PS2;
@@ -18,30 +23,50 @@ use crate::device::serial;
 use crate::device::serial::{BaudRate, ComPort, SerialPort};
 use crate::device::speaker::Speaker;
 use crate::device::terminal::Terminal;
+use crate::device::virtio_blk::VirtioBlkDevice;
+use crate::fs::vfs::Vfs;
+use crate::iommu::Iommu;
 use crate::memory::alloc::{AcpiHandler, KernelAllocator};
 use crate::interrupt::interrupt_dispatcher::InterruptDispatcher;
 use crate::log::Logger;
+use crate::module::KernelModule;
+use crate::thread::process::ProcessTable;
 use crate::thread::scheduler::Scheduler;
 use crate::thread::thread::Thread;
 use alloc::boxed::Box;
-use acpi::AcpiTables;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use ::acpi::AcpiTables;
 use spin::{Mutex, Once, RwLock};
 use uefi::table::{Runtime, SystemTable};
 use x86_64::structures::gdt::GlobalDescriptorTable;
 use x86_64::structures::idt::InterruptDescriptorTable;
-use x86_64::structures::tss::TaskStateSegment;
-use x86_64::VirtAddr;
 
 extern crate alloc;
 
+#[macro_use]
+pub mod ktrace;
 #[macro_use]
 pub mod device;
+pub mod acpi;
+pub mod arch;
+pub mod bench;
 pub mod boot;
+pub mod cmdline;
+pub mod crypto;
+pub mod debug;
+pub mod fs;
 pub mod interrupt;
+pub mod iommu;
 pub mod memory;
 pub mod log;
+pub mod module;
+pub mod percpu;
+pub mod smp;
+pub mod sync;
 pub mod syscall;
 pub mod thread;
+pub mod vdso;
 
 struct EfiSystemTable {
     table: SystemTable<Runtime>,
@@ -57,26 +82,38 @@ impl EfiSystemTable {
 }
 
 static GDT: Mutex<GlobalDescriptorTable> = Mutex::new(GlobalDescriptorTable::new());
-static TSS: Mutex<TaskStateSegment> = Mutex::new(TaskStateSegment::new());
+static TSS: Mutex<TssWithIopb> = Mutex::new(TssWithIopb::new());
 static IDT: Mutex<InterruptDescriptorTable> = Mutex::new(InterruptDescriptorTable::new());
+static CMDLINE: Once<CmdlineArgs> = Once::new();
 static EFI_SYSTEM_TABLE: Once<EfiSystemTable> = Once::new();
 static ACPI_TABLES: Once<Mutex<AcpiTables<AcpiHandler>>> = Once::new();
+static IOMMU: Once<Iommu> = Once::new();
+static MODULES: Once<Vec<KernelModule>> = Once::new();
 
 #[global_allocator]
 static ALLOCATOR: KernelAllocator = KernelAllocator::new();
 static LOGGER: Mutex<Logger> = Mutex::new(Logger::new());
 static SCHEDULER: Once<Scheduler> = Once::new();
+static ENTROPY_POOL: Mutex<EntropyPool> = Mutex::new(EntropyPool::new());
 static INTERRUPT_DISPATCHER: Once<InterruptDispatcher> = Once::new();
 
 static APIC: Once<Apic> = Once::new();
+static HPET: Once<Hpet> = Once::new();
 static TIMER: RwLock<Timer> = RwLock::new(Timer::new());
 static SPEAKER: Mutex<Speaker> = Mutex::new(Speaker::new());
 static SERIAL_PORT: Once<SerialPort> = Once::new();
 static TERMINAL: Once<LFBTerminal> = Once::new();
 static PS2: Once<PS2> = Once::new();
+static VIRTIO_BLK: Once<Arc<VirtioBlkDevice>> = Once::new();
+static VFS: RwLock<Vfs> = RwLock::new(Vfs::new());
+static PROCESS_TABLE: RwLock<ProcessTable> = RwLock::new(ProcessTable::new());
 
 pub trait Service {}
 
+pub fn init_cmdline(args: CmdlineArgs) {
+    CMDLINE.call_once(|| args);
+}
+
 pub fn init_efi_system_table(table: SystemTable<Runtime>) {
     EFI_SYSTEM_TABLE.call_once(|| EfiSystemTable::new(table));
 }
@@ -95,10 +132,26 @@ pub fn init_acpi_tables(rsdp_addr: usize) {
     });
 }
 
+pub fn init_iommu() {
+    if let Some(iommu) = Iommu::new() {
+        IOMMU.call_once(|| iommu);
+    }
+}
+
+pub fn init_modules(modules: Vec<KernelModule>) {
+    MODULES.call_once(|| modules);
+}
+
 pub fn init_apic() {
     APIC.call_once(|| Apic::new());
 }
 
+pub fn init_hpet() {
+    if let Some(hpet) = Hpet::new() {
+        HPET.call_once(|| hpet);
+    }
+}
+
 pub fn init_serial_port() {
     let mut serial: Option<SerialPort> = None;
     if serial::check_port(ComPort::Com1) {
@@ -123,7 +176,7 @@ pub fn init_terminal(buffer: *mut u8, pitch: u32, width: u32, height: u32, bpp:
     scheduler().ready(Thread::new_kernel_thread(Box::new(|| {
         let mut cursor_thread = CursorThread::new(&TERMINAL.get().unwrap());
         cursor_thread.run();
-    })))
+    }), None))
 }
 
 pub fn init_keyboard() {
@@ -131,11 +184,20 @@ pub fn init_keyboard() {
         let mut ps2 = PS2::new();
         ps2.init_controller().unwrap_or_else(|err| panic!("Failed to initialize PS2 controller (Error: {:?})", err));
         ps2.init_keyboard().unwrap_or_else(|err| panic!("Failed to initialize PS2 keyboard (Error: {:?})", err));
+        if let Err(err) = ps2.init_mouse() {
+            ::log::warn!("Failed to initialize PS2 mouse (Error: {:?})", err);
+        }
 
         return ps2;
     });
 }
 
+pub fn init_virtio_blk() {
+    if let Some(device) = VirtioBlkDevice::new() {
+        VIRTIO_BLK.call_once(|| Arc::new(device));
+    }
+}
+
 pub fn terminal_initialized() -> bool {
     return TERMINAL.get().is_some();
 }
@@ -144,7 +206,7 @@ pub fn gdt() -> &'static Mutex<GlobalDescriptorTable> {
     return &GDT;
 }
 
-pub fn tss() -> &'static Mutex<TaskStateSegment> {
+pub fn tss() -> &'static Mutex<TssWithIopb> {
     return &TSS;
 }
 
@@ -152,8 +214,26 @@ pub fn idt() -> &'static Mutex<InterruptDescriptorTable> {
     return &IDT;
 }
 
-pub fn acpi_tables() -> &'static Mutex<AcpiTables<AcpiHandler>> {
-    return ACPI_TABLES.get().expect("Trying to access ACPI tables before initialization!");
+/// Kernel command line arguments (panics, if called before they have been parsed during boot).
+pub fn cmdline() -> &'static CmdlineArgs {
+    return CMDLINE.get().expect("Trying to access kernel command line before initialization!");
+}
+
+/// ACPI tables ('None' before initialization or if ACPI has been disabled on the kernel command line).
+pub fn acpi_tables() -> Option<&'static Mutex<AcpiTables<AcpiHandler>>> {
+    return ACPI_TABLES.get();
+}
+
+pub fn iommu() -> Option<&'static Iommu> {
+    return IOMMU.get();
+}
+
+/// Modules loaded by the bootloader (empty before initialization).
+pub fn modules() -> &'static [KernelModule] {
+    return match MODULES.get() {
+        Some(modules) => modules.as_slice(),
+        None => &[],
+    };
 }
 
 pub fn efi_system_table() -> Option<&'static SystemTable<Runtime>> {
@@ -181,10 +261,31 @@ pub fn scheduler() -> &'static Scheduler {
     return &SCHEDULER.get().unwrap();
 }
 
+pub fn entropy_pool() -> &'static Mutex<EntropyPool> {
+    return &ENTROPY_POOL;
+}
+
 pub fn apic() -> &'static Apic {
     return APIC.get().expect("Trying to access APIC before initialization!");
 }
 
+pub fn hpet() -> Option<&'static Hpet> {
+    return HPET.get();
+}
+
+pub fn virtio_blk() -> Option<&'static Arc<VirtioBlkDevice>> {
+    return VIRTIO_BLK.get();
+}
+
+/// Directory tree with all mounted filesystems, which is accessed by the 'Open' system call.
+pub fn vfs() -> &'static RwLock<Vfs> {
+    return &VFS;
+}
+
+pub fn process_table() -> &'static RwLock<ProcessTable> {
+    return &PROCESS_TABLE;
+}
+
 pub fn timer() -> &'static RwLock<Timer> {
     return &TIMER;
 }
@@ -204,13 +305,3 @@ pub fn terminal() -> &'static dyn Terminal {
 pub fn ps2_devices() -> &'static PS2 {
     return PS2.get().expect("Trying to access keyboard before initialization!");
 }
-
-#[no_mangle]
-pub extern "C" fn tss_set_rsp0(rsp0: u64) {
-    tss().lock().privilege_stack_table[0] = VirtAddr::new(rsp0);
-}
-
-#[no_mangle]
-pub extern "C" fn tss_get_rsp0() -> u64 {
-    return tss().lock().privilege_stack_table[0].as_u64();
-}
@@ -8,13 +8,29 @@ use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::fmt::Write;
 use core::ops::Deref;
-use core::ptr;
+use core::{fmt, ptr, str};
 use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+use spin::Mutex;
 use crate::boot::built_info;
 
+const EARLY_LOG_SIZE: usize = 4096;
+
+/// Messages logged before the first stream has been registered and without a serial port to echo them.
+/// Does not allocate, so it can be written to before heap initialization and from the panic handler.
+static EARLY_LOG: Mutex<EarlyLog> = Mutex::new(EarlyLog::new());
+
+/// Ring buffer of raw bytes, overwriting the oldest bytes when full.
+struct EarlyLog {
+    buffer: [u8; EARLY_LOG_SIZE],
+    start: usize,
+    len: usize,
+    overwritten: bool,
+}
+
 pub struct Logger {
-    level: Level,
+    level: LevelFilter,
     streams: Vec<Box<&'static dyn OutputStream>>,
     serial: Option<SerialPort>,
 }
@@ -35,6 +51,11 @@ impl log::Log for Logger {
 
         let mut logger = logger().lock();
         if logger.streams.is_empty() {
+            if logger.serial.is_none() {
+                let mut early_log = EARLY_LOG.lock();
+                let _ = write!(early_log, "{}[0.000]{}[{}]{}[{}@{:0>3}] {}\n", ansi::FOREGROUND_CYAN, ansi_color(level), level_token(level), ansi::FOREGROUND_DEFAULT, file, line, record.args());
+            }
+
             if let Some(serial) = logger.serial.as_mut() {
                 serial.write_str(ansi::FOREGROUND_CYAN);
                 serial.write_str("[0.000]");
@@ -75,7 +96,7 @@ impl log::Log for Logger {
 impl Logger {
     pub const fn new() -> Self {
         Self {
-            level: Level::Info,
+            level: LevelFilter::Info,
             streams: Vec::new(),
             serial: None,
         }
@@ -102,7 +123,7 @@ impl Logger {
         }
 
         if built_info::PROFILE == "debug" {
-            logger.level = Level::Debug;
+            logger.level = LevelFilter::Debug;
         }
 
         unsafe {
@@ -111,7 +132,18 @@ impl Logger {
         }
     }
 
+    /// Only log messages up to `level` (e.g. given on the kernel command line).
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+        log::set_max_level(level);
+    }
+
+    /// The first registered stream receives all messages, that have been buffered in the early log.
     pub fn register(&mut self, stream: &'static dyn OutputStream) {
+        if self.streams.is_empty() {
+            drain_early_log(stream);
+        }
+
         self.streams.push(Box::new(stream));
     }
 
@@ -120,6 +152,67 @@ impl Logger {
             !ptr::addr_eq(ptr::from_ref(*element.as_ref()), ptr::from_ref(stream))
         });
     }
+
+    /// Stop writing to the serial port (as registered stream and as early output), so that it can be used exclusively (e.g. by the GDB stub).
+    /// Messages logged without any other stream are kept in the early log.
+    pub fn detach_serial(&mut self, serial: &dyn OutputStream) {
+        self.remove(serial);
+        self.serial = None;
+    }
+}
+
+/// Write all buffered early log messages to `stream` and empty the early log.
+pub fn drain_early_log(stream: &dyn OutputStream) {
+    let mut early_log = EARLY_LOG.lock();
+    let EarlyLog { buffer, start, len, overwritten } = &mut *early_log;
+
+    // Make the buffered bytes contiguous (oldest first)
+    buffer.rotate_left(*start);
+    let mut bytes = &buffer[..*len];
+
+    // The oldest message has been cut off by newer ones, so skip to the next complete one
+    if *overwritten {
+        bytes = match bytes.iter().position(|&byte| byte == b'\n') {
+            Some(index) => &bytes[index + 1..],
+            None => &[]
+        };
+    }
+
+    let text = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => unsafe { str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) }
+    };
+
+    stream.write_str(text);
+    (*start, *len, *overwritten) = (0, 0, false);
+}
+
+/// Release the early log's lock, in case the panicking thread has been interrupted while holding it.
+pub unsafe fn force_unlock_early_log() {
+    EARLY_LOG.force_unlock();
+}
+
+impl EarlyLog {
+    const fn new() -> Self {
+        Self { buffer: [0; EARLY_LOG_SIZE], start: 0, len: 0, overwritten: false }
+    }
+}
+
+impl fmt::Write for EarlyLog {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for &byte in string.as_bytes() {
+            self.buffer[(self.start + self.len) % EARLY_LOG_SIZE] = byte;
+
+            if self.len < EARLY_LOG_SIZE {
+                self.len += 1;
+            } else {
+                self.start = (self.start + 1) % EARLY_LOG_SIZE;
+                self.overwritten = true;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 fn ansi_color(level: Level) -> &'static str {
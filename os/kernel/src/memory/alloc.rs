@@ -2,12 +2,13 @@ use crate::Service;
 use acpi::PhysicalMapping;
 use core::alloc::{AllocError, Allocator, GlobalAlloc, Layout};
 use core::ptr::NonNull;
-use linked_list_allocator::LockedHeap;
+use linked_list_allocator::Heap;
 use x86_64::structures::paging::frame::PhysFrameRange;
 use crate::memory::PAGE_SIZE;
+use crate::sync::SpinlockBackoff;
 
 pub struct KernelAllocator {
-    heap: LockedHeap,
+    heap: SpinlockBackoff<Heap>,
 }
 
 #[derive(Default, Clone)]
@@ -91,7 +92,7 @@ impl<'a> AcpiAllocator<'a> {
 
 impl KernelAllocator {
     pub const fn new() -> Self {
-        Self { heap: LockedHeap::empty() }
+        Self { heap: SpinlockBackoff::new(Heap::empty()) }
     }
 
     pub unsafe fn init(&self, frames: &PhysFrameRange) {
@@ -101,4 +102,9 @@ impl KernelAllocator {
     pub fn is_initialized(&self) -> bool {
         return self.heap.lock().size() > 0;
     }
+
+    /// Contention counter of the heap lock (see 'SpinlockBackoff::contentions()').
+    pub fn lock_contentions(&self) -> u64 {
+        return self.heap.contentions();
+    }
 }
@@ -3,7 +3,9 @@ use x86_64::structures::paging::PhysFrame;
 
 pub mod alloc;
 pub mod physical;
+pub mod shm;
 pub mod r#virtual;
+pub mod slab;
 
 #[derive(Clone, Copy)]
 pub enum MemorySpace {
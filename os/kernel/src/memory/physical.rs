@@ -1,6 +1,9 @@
 use alloc::vec::Vec;
+use core::cmp::{max, min};
 use core::fmt::{Debug, Formatter};
 use core::ptr;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
 use log::{debug, info};
 use spin::{Mutex, Once};
 use x86_64::PhysAddr;
@@ -8,10 +11,58 @@ use x86_64::structures::paging::frame::PhysFrameRange;
 use x86_64::structures::paging::PhysFrame;
 use crate::memory::{KERNEL_PHYS_LIMIT, MemorySpace, PAGE_SIZE};
 
-static KERNEL_PAGE_FRAME_ALLOCATOR: Mutex<PageFrameListAllocator> = Mutex::new(PageFrameListAllocator::new());
-static USER_PAGE_FRAME_ALLOCATOR: Mutex<PageFrameListAllocator> = Mutex::new(PageFrameListAllocator::new());
+// One allocator per zone (indexed by 'Zone as usize') for both kernel and user memory
+static KERNEL_PAGE_FRAME_ALLOCATORS: [Mutex<PageFrameListAllocator>; ZONE_COUNT] = [Mutex::new(PageFrameListAllocator::new()), Mutex::new(PageFrameListAllocator::new()), Mutex::new(PageFrameListAllocator::new())];
+static USER_PAGE_FRAME_ALLOCATORS: [Mutex<PageFrameListAllocator>; ZONE_COUNT] = [Mutex::new(PageFrameListAllocator::new()), Mutex::new(PageFrameListAllocator::new()), Mutex::new(PageFrameListAllocator::new())];
 static PHYS_LIMIT: Once<PhysFrame> = Once::new();
 
+// Frame counts for both allocators together
+static TOTAL_FRAMES: AtomicU64 = AtomicU64::new(0);
+static FREE_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+/// The page frame allocators do not distinguish NUMA nodes, so all memory belongs to node 0.
+/// As long as this is the only node, every memory policy results in the same allocations.
+pub const ONLINE_NODE_MASK: u64 = 0x01;
+
+const ZONE_COUNT: usize = 3;
+const ZONE_DMA_END: u64 = 16 * 1024 * 1024;
+const ZONE_DMA32_END: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Physical memory is split into zones, since some devices can only address a part of it.
+/// Each zone is managed by separate allocators, so that allocations without restrictions do not use up the lower zones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    /// 'ZONE_DMA': Frames below 16 MiB, usable by legacy ISA DMA.
+    Dma = 0,
+    /// 'ZONE_DMA32': Frames between 16 MiB and 4 GiB, usable by devices with 32-bit DMA addresses (e.g. most PCI devices).
+    Dma32 = 1,
+    /// 'ZONE_NORMAL': Frames above 4 GiB.
+    Normal = 2
+}
+
+impl Zone {
+    /// Order, in which zones are tried by 'alloc()' (the scarce low memory is used last).
+    const FALLBACK_ORDER: [Zone; ZONE_COUNT] = [Zone::Normal, Zone::Dma32, Zone::Dma];
+
+    /// Start and end address of this zone (the end is exclusive).
+    fn bounds(&self) -> (u64, u64) {
+        return match self {
+            Zone::Dma => (0, ZONE_DMA_END),
+            Zone::Dma32 => (ZONE_DMA_END, ZONE_DMA32_END),
+            Zone::Normal => (ZONE_DMA32_END, u64::MAX)
+        };
+    }
+
+    /// Zone, that contains `frame`.
+    pub fn of(frame: PhysFrame) -> Zone {
+        return match frame.start_address().as_u64() {
+            address if address < ZONE_DMA_END => Zone::Dma,
+            address if address < ZONE_DMA32_END => Zone::Dma32,
+            _ => Zone::Normal
+        };
+    }
+}
+
 /// Initialize page frame allocation with available memory regions, obtained during the boot process.
 pub unsafe fn init(mut regions: Vec<PhysFrameRange>, kernel_heap_end: PhysFrame) {
     regions.sort_by(|range1, range2| range1.start.cmp(&range2.start));
@@ -53,6 +104,8 @@ pub unsafe fn init(mut regions: Vec<PhysFrameRange>, kernel_heap_end: PhysFrame)
     info!("Physical kernel memory: [{} MiB]", kernel_phys_limit.start_address().as_u64() / 1024 / 1024);
     KERNEL_PHYS_LIMIT.call_once(|| kernel_phys_limit);
 
+    TOTAL_FRAMES.store(regions.iter().map(|region| region.count() as u64).sum(), Relaxed);
+
     for mut region in regions {
         // Check if the given region transcends over the physical kernel limit
         if region.start < kernel_phys_limit && region.end >= kernel_phys_limit {
@@ -68,28 +121,73 @@ pub unsafe fn init(mut regions: Vec<PhysFrameRange>, kernel_heap_end: PhysFrame)
     }
 
 
-    debug!("Kernel page frame allocator:\n{:?}", KERNEL_PAGE_FRAME_ALLOCATOR.lock());
-    debug!("User page frame allocator:\n{:?}", USER_PAGE_FRAME_ALLOCATOR.lock());
+    for zone in Zone::FALLBACK_ORDER.iter().rev() {
+        debug!("Kernel page frame allocator ({:?}):\n{:?}", zone, allocator(MemorySpace::Kernel, *zone).lock());
+        debug!("User page frame allocator ({:?}):\n{:?}", zone, allocator(MemorySpace::User, *zone).lock());
+    }
 }
 
 /// Allocate `frame_count` contiguous page frames in either kernel or user space, depending on `space`.
+/// Frames are taken from 'Zone::Normal' if possible and only from the lower zones, if it is exhausted.
 pub fn alloc(frame_count: usize, space: MemorySpace) -> PhysFrameRange {
-    unsafe {
-        return match space {
-            MemorySpace::Kernel => KERNEL_PAGE_FRAME_ALLOCATOR.lock().alloc_block(frame_count),
-            MemorySpace::User => USER_PAGE_FRAME_ALLOCATOR.lock().alloc_block(frame_count)
-        }
-    }
+    let frames = Zone::FALLBACK_ORDER.iter()
+        .find_map(|zone| unsafe { allocator(space, *zone).lock().try_alloc_block(frame_count) })
+        .expect("PageFrameAllocator: Out of memory!");
+
+    FREE_FRAMES.fetch_sub(frames.count() as u64, Relaxed);
+    return frames;
+}
+
+/// Allocate a single page frame inside `zone` (e.g. for a DMA buffer of a device, that can only address the lower zones).
+/// Kernel memory is preferred, but user memory is used as well, since the kernel address space maps all physical memory.
+pub fn alloc_frame_in_zone(zone: Zone) -> Option<PhysFrame> {
+    let frames = unsafe { allocator(MemorySpace::Kernel, zone).lock().try_alloc_block(1) }
+        .or_else(|| unsafe { allocator(MemorySpace::User, zone).lock().try_alloc_block(1) })?;
+
+    FREE_FRAMES.fetch_sub(1, Relaxed);
+    return Some(frames.start);
 }
 
 /// Free `frame_count` contiguous page frames starting at `addr`.
 /// Unsafe because invalid parameters may break the list allocator.
 pub unsafe fn free(frames: PhysFrameRange) {
-    if frames.start < kernel_phys_limit() {
-        KERNEL_PAGE_FRAME_ALLOCATOR.lock().free_block(frames);
-    } else {
-        USER_PAGE_FRAME_ALLOCATOR.lock().free_block(frames);
+    let space = if frames.start < kernel_phys_limit() { MemorySpace::Kernel } else { MemorySpace::User };
+    for part in split_by_zone(frames) {
+        allocator(space, Zone::of(part.start)).lock().free_block(part);
     }
+
+    FREE_FRAMES.fetch_add(frames.count() as u64, Relaxed);
+}
+
+/// Split `frames` at the zone boundaries, so that each part lies inside a single zone (used when registering memory regions during boot).
+pub fn split_by_zone(frames: PhysFrameRange) -> impl Iterator<Item = PhysFrameRange> {
+    let frames_start = frames.start.start_address().as_u64();
+    let frames_end = frames.end.start_address().as_u64();
+
+    return Zone::FALLBACK_ORDER.into_iter().rev().filter_map(move |zone| {
+        let (zone_start, zone_end) = zone.bounds();
+        let start = max(frames_start, zone_start);
+        let end = min(frames_end, zone_end);
+
+        if start >= end {
+            return None;
+        }
+
+        return Some(PhysFrameRange { start: PhysFrame::containing_address(PhysAddr::new(start)), end: PhysFrame::containing_address(PhysAddr::new(end)) });
+    });
+}
+
+/// Number of page frames managed by the kernel and user allocators (available memory regions after boot).
+pub fn total_frames() -> u64 {
+    return TOTAL_FRAMES.load(Relaxed);
+}
+
+pub fn free_frames() -> u64 {
+    return FREE_FRAMES.load(Relaxed);
+}
+
+pub fn allocated_frames() -> u64 {
+    return total_frames().saturating_sub(free_frames());
 }
 
 pub fn phys_limit() -> PhysFrame {
@@ -100,6 +198,13 @@ pub fn kernel_phys_limit() -> PhysFrame {
     return *KERNEL_PHYS_LIMIT.get().expect("PageFrameAllocator: 'KERNEL_PHYS_LIMIT' accessed before initialization!");
 }
 
+fn allocator(space: MemorySpace, zone: Zone) -> &'static Mutex<PageFrameListAllocator> {
+    return match space {
+        MemorySpace::Kernel => &KERNEL_PAGE_FRAME_ALLOCATORS[zone as usize],
+        MemorySpace::User => &USER_PAGE_FRAME_ALLOCATORS[zone as usize]
+    };
+}
+
 fn calc_page_table_memory(levels: usize) -> usize {
     let available_memory: usize = phys_limit().start_address().align_up(0x200000u64).as_u64() as usize;
 
@@ -231,8 +336,8 @@ impl PageFrameListAllocator {
         return None;
     }
 
-    /// Allocate `frame_count` page frames.
-    unsafe fn alloc_block(&mut self, frame_count: usize) -> PhysFrameRange {
+    /// Allocate `frame_count` page frames ('None', if there is no large enough block).
+    unsafe fn try_alloc_block(&mut self, frame_count: usize) -> Option<PhysFrameRange> {
         match self.find_free_block(frame_count) {
             Some(block) => {
                 let remaining = PhysFrameRange { start: block.start() + frame_count as u64, end: block.end() };
@@ -240,9 +345,9 @@ impl PageFrameListAllocator {
                     self.insert(remaining);
                 }
                 
-                return PhysFrameRange { start: block.start(), end: remaining.start };
+                return Some(PhysFrameRange { start: block.start(), end: remaining.start });
             },
-            None => panic!("PageFrameAllocator: Out of memory!")
+            None => return None
         }
     }
 
@@ -0,0 +1,88 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+use library_syscall::{Errno, SHM_PRIVATE};
+use spin::Mutex;
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::structures::paging::frame::PhysFrameRange;
+use x86_64::structures::paging::page::PageRange;
+use crate::memory::{MemorySpace, PAGE_SIZE, physical};
+use crate::memory::r#virtual::AddressSpace;
+
+// Shared memory regions, which can be attached to multiple address spaces at the same time.
+// The page frames are taken from the kernel allocator, so 'AddressSpace::unmap()' does not free them (like frames mapped with 'map_physical()').
+// Instead, a region is freed, when its last attachment is detached.
+
+static SHM_REGIONS: Mutex<BTreeMap<u64, ShmRegion>> = Mutex::new(BTreeMap::new());
+static HANDLE_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+struct ShmRegion {
+    key: u64,
+    frames: PhysFrameRange,
+    /// Address space id and pages of each attachment (the region's reference count is the number of attachments)
+    attachments: Vec<(usize, PageRange)>
+}
+
+/// Create a zeroed region of `page_count` pages and return its handle.
+/// If a region with the same `key` (other than 'SHM_PRIVATE') exists, its handle is returned instead (it must have at least `page_count` pages).
+pub fn create(page_count: usize, key: u64) -> Result<u64, Errno> {
+    let mut regions = SHM_REGIONS.lock();
+    if key != SHM_PRIVATE {
+        if let Some((handle, region)) = regions.iter().find(|(_, region)| region.key == key) {
+            return if region.frames.count() >= page_count { Ok(*handle) } else { Err(Errno::InvalidArgument) };
+        }
+    }
+
+    let frames = physical::alloc(page_count, MemorySpace::Kernel);
+    unsafe { (frames.start.start_address().as_u64() as *mut u8).write_bytes(0, page_count * PAGE_SIZE); }
+
+    let handle = HANDLE_COUNTER.fetch_add(1, Relaxed);
+    regions.insert(handle, ShmRegion { key, frames, attachments: Vec::new() });
+    return Ok(handle);
+}
+
+/// Map the region `handle` into `address_space` at the pages returned by `choose_pages` (called with the address space and the region's page count).
+pub fn attach(handle: u64, address_space: &mut AddressSpace, choose_pages: impl FnOnce(&AddressSpace, usize) -> Option<PageRange>) -> Result<PageRange, Errno> {
+    let mut regions = SHM_REGIONS.lock();
+    let region = regions.get_mut(&handle).ok_or(Errno::InvalidArgument)?;
+    let pages = choose_pages(address_space, region.frames.count()).ok_or(Errno::InvalidArgument)?;
+
+    address_space.map_physical(pages, region.frames.start, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+    region.attachments.push((address_space.id(), pages));
+    return Ok(pages);
+}
+
+/// Unmap the attachment starting at `start` from `address_space` and free its region, if this has been the last attachment.
+pub fn detach(address_space: &mut AddressSpace, start: Page) -> Result<(), Errno> {
+    let mut regions = SHM_REGIONS.lock();
+    let (handle, index) = regions.iter()
+        .find_map(|(handle, region)| region.attachments.iter()
+            .position(|attachment| attachment.0 == address_space.id() && attachment.1.start == start)
+            .map(|index| (*handle, index)))
+        .ok_or(Errno::InvalidArgument)?;
+
+    let region = regions.get_mut(&handle).unwrap();
+    let (_, pages) = region.attachments.remove(index);
+    address_space.unmap(pages);
+
+    if region.attachments.is_empty() {
+        let region = regions.remove(&handle).unwrap();
+        unsafe { physical::free(region.frames); }
+    }
+
+    return Ok(());
+}
+
+/// Detach all regions from `address_space` (e.g. before it is dropped), freeing regions without other attachments.
+pub fn detach_all(address_space: &mut AddressSpace) {
+    let starts = SHM_REGIONS.lock().values()
+        .flat_map(|region| region.attachments.iter())
+        .filter(|attachment| attachment.0 == address_space.id())
+        .map(|attachment| attachment.1.start)
+        .collect::<Vec<Page>>();
+
+    for start in starts {
+        let _ = detach(address_space, start);
+    }
+}
@@ -0,0 +1,103 @@
+use core::alloc::{AllocError, Allocator, Layout};
+use core::mem::{align_of, size_of};
+use core::ptr::NonNull;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use crate::allocator;
+use crate::memory::{physical, MemorySpace, PAGE_SIZE};
+
+/// Number of page frames, that are allocated at once, when a slab allocator runs out of free slots.
+const SLAB_PAGES: usize = 4;
+
+/// Allocates objects of one fixed size from slabs of contiguous page frames (identity mapped kernel memory).
+/// Free slots are kept in an intrusive free list, so allocating and freeing are O(1) and do not fragment the kernel heap.
+/// Slabs are never returned to the page frame allocator.
+/// Allocations, that do not fit into a slot, are passed on to the kernel heap (deallocations are sorted by their layout).
+pub struct SlabAllocator {
+    slot_size: usize,
+    slot_align: usize,
+    free_list: Mutex<Option<NonNull<FreeSlot>>>
+}
+
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>
+}
+
+unsafe impl Send for SlabAllocator {}
+unsafe impl Sync for SlabAllocator {}
+
+impl SlabAllocator {
+    /// Create an allocator for objects of up to `size` bytes with an alignment of up to `align` bytes (a power of two up to 'PAGE_SIZE').
+    pub const fn new(size: usize, align: usize) -> Self {
+        // Each slot must be able to hold a free list entry
+        let slot_align = if align > align_of::<FreeSlot>() { align } else { align_of::<FreeSlot>() };
+        let slot_size = if size > size_of::<FreeSlot>() { size } else { size_of::<FreeSlot>() };
+
+        Self { slot_size: slot_size.next_multiple_of(slot_align), slot_align, free_list: Mutex::new(None) }
+    }
+
+    fn fits(&self, layout: Layout) -> bool {
+        return layout.size() <= self.slot_size && layout.align() <= self.slot_align;
+    }
+
+    /// Interrupts are disabled while the free list is locked, so that the allocator can be used from interrupt handlers
+    /// without deadlocking on a lock held by the interrupted thread.
+    fn alloc_slot(&self) -> NonNull<u8> {
+        return interrupts::without_interrupts(|| {
+            let mut free_list = self.free_list.lock();
+            if free_list.is_none() {
+                *free_list = self.grow();
+            }
+
+            let slot = free_list.expect("SlabAllocator: Failed to allocate slab!");
+            *free_list = unsafe { slot.as_ref().next };
+
+            slot.cast()
+        });
+    }
+
+    fn free_slot(&self, ptr: NonNull<u8>) {
+        interrupts::without_interrupts(|| {
+            let mut free_list = self.free_list.lock();
+            let mut slot = ptr.cast::<FreeSlot>();
+
+            unsafe { slot.as_mut().next = *free_list; }
+            *free_list = Some(slot);
+        });
+    }
+
+    /// Allocate a new slab and link all of its slots (lowest address first).
+    fn grow(&self) -> Option<NonNull<FreeSlot>> {
+        let frames = physical::alloc(SLAB_PAGES, MemorySpace::Kernel);
+        let start = frames.start.start_address().as_u64() as usize;
+        let slot_count = (SLAB_PAGES * PAGE_SIZE) / self.slot_size;
+
+        let mut next = None;
+        for index in (0..slot_count).rev() {
+            let slot = (start + index * self.slot_size) as *mut FreeSlot;
+            unsafe { slot.write(FreeSlot { next }); }
+            next = NonNull::new(slot);
+        }
+
+        return next;
+    }
+}
+
+unsafe impl Allocator for SlabAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.fits(layout) {
+            return allocator().allocate(layout);
+        }
+
+        return Ok(NonNull::slice_from_raw_parts(self.alloc_slot(), self.slot_size));
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if !self.fits(layout) {
+            allocator().deallocate(ptr, layout);
+            return;
+        }
+
+        self.free_slot(ptr);
+    }
+}
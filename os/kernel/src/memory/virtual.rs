@@ -2,19 +2,65 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cmp::min;
 use core::ops::Deref;
-use spin::RwLock;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicU64, AtomicUsize};
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release};
+use spin::{Mutex, RwLock};
+use x86_64::instructions::interrupts;
 use x86_64::structures::paging::{Page, PageTable, PageTableFlags, PageTableIndex, PhysFrame};
+use x86_64::structures::paging::page_table::PageTableEntry;
 use x86_64::{PhysAddr, VirtAddr};
 use x86_64::registers::control::Cr3;
+use x86_64::instructions::tlb;
 use x86_64::structures::paging::page::PageRange;
-use crate::memory::{MemorySpace, PAGE_SIZE, physical};
-use crate::memory::physical::phys_limit;
+use x86_64::structures::paging::frame::PhysFrameRange;
+use crate::memory::{MemorySpace, PAGE_SIZE, physical, shm};
+use crate::memory::physical::{kernel_phys_limit, phys_limit};
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::interrupt::interrupt_handler::InterruptHandler;
+use crate::smp::MAX_CPUS;
+use crate::{apic, percpu, smp};
 
 static ADDRESS_SPACES: RwLock<Vec<Arc<RwLock<AddressSpace>>>> = RwLock::new(Vec::new());
+static ADDRESS_SPACE_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+/// The kernel address space is created first. Its mappings are shared with all other address spaces.
+const KERNEL_ADDRESS_SPACE_ID: u64 = 1;
+
+/// Values of the TLB shootdown slots, which are no page addresses (see 'tlb_shootdown()').
+const NO_SHOOTDOWN: u64 = 1;
+const FLUSH_ALL: u64 = 2;
+
+/// Ranges with more pages are flushed completely instead of page by page.
+const FLUSH_ALL_THRESHOLD: usize = 32;
+
+/// Address to be flushed by each CPU (indexed by CPU id), set before the shootdown IPI is sent.
+static SHOOTDOWN_SLOTS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(NO_SHOOTDOWN) }; MAX_CPUS];
+static SHOOTDOWN_ASID: AtomicU64 = AtomicU64::new(0);
+static SHOOTDOWN_PENDING: AtomicUsize = AtomicUsize::new(0);
+static SHOOTDOWN_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set (in an otherwise unused bit) in entries of user address spaces, that reference a page table of the kernel address space.
+/// Such tables are shared instead of copied and only copied, when they need to be modified (see `next_level_table_mut()`).
+const SHARED_TABLE: PageTableFlags = PageTableFlags::BIT_9;
+
+/// Set in level 1 entries, whose page frame has been allocated for this address space (and is freed, when it is dropped).
+const OWNED_FRAME: PageTableFlags = PageTableFlags::BIT_10;
 
 pub struct AddressSpace {
+    id: usize,
     root_table: *mut PageTable,
-    depth: usize
+    depth: usize,
+    user_frames: usize,
+    lazy_ranges: Vec<LazyRange>,
+    lazy_frames: usize
+}
+
+/// Pages registered with `map_lazy()`, which get a page frame on their first access.
+#[derive(Copy, Clone)]
+struct LazyRange {
+    pages: PageRange,
+    flags: PageTableFlags
 }
 
 unsafe impl Send for AddressSpace {}
@@ -55,13 +101,197 @@ pub fn kernel_address_space() -> Arc<RwLock<AddressSpace>> {
     ADDRESS_SPACES.read().get(0).expect("Trying to access kernel address space before initialization!").clone()
 }
 
+/// Allocate `page_count` pages for a kernel stack with a guard page directly below it.
+/// The guard page is marked as not present in the kernel address space (and thus in all address spaces created afterwards),
+/// so that a stack overflow causes a page fault instead of silently overwriting other memory.
+pub fn alloc_kernel_stack(page_count: usize) -> PageRange {
+    let frames = physical::alloc(page_count + 1, MemorySpace::Kernel);
+    let guard_page = Page::containing_address(VirtAddr::new(frames.start.start_address().as_u64()));
+    kernel_address_space().write().set_guard_page(guard_page);
+
+    return PageRange { start: guard_page + 1, end: guard_page + 1 + page_count as u64 };
+}
+
+/// Flush the page containing `addr` from the TLB of the executing CPU and of all other online CPUs, that may have cached it.
+/// `asid` is the id of the modified address space (other CPUs skip the flush, if they use another one, unless it is the kernel address space).
+/// The other CPUs flush the page in their handler for 'InterruptVector::TlbShootdown' and the call returns after all of them have done so.
+pub fn tlb_shootdown(addr: VirtAddr, asid: u64) {
+    shootdown(addr.align_down(PAGE_SIZE as u64).as_u64(), asid);
+}
+
+/// Like 'tlb_shootdown()' for all pages in `pages` (flushing the whole TLB for large ranges).
+fn tlb_shootdown_range(pages: PageRange, asid: u64) {
+    if pages.count() > FLUSH_ALL_THRESHOLD {
+        shootdown(FLUSH_ALL, asid);
+    } else {
+        pages.for_each(|page| shootdown(page.start_address().as_u64(), asid));
+    }
+}
+
+fn shootdown(addr: u64, asid: u64) {
+    flush(addr);
+
+    let cpus = smp::online_cpus();
+    if cpus <= 1 {
+        return;
+    }
+
+    // Interrupts are disabled while holding the lock, so that the holder cannot be preempted by another thread waiting for it
+    interrupts::without_interrupts(|| {
+        let current_cpu = percpu::current_cpu_id() as usize;
+
+        // While another CPU performs a shootdown, requests to this CPU are handled here, since the IPI cannot be received
+        let _lock = loop {
+            if let Some(lock) = SHOOTDOWN_LOCK.try_lock() {
+                break lock;
+            }
+
+            handle_shootdown();
+            spin_loop();
+        };
+
+        SHOOTDOWN_ASID.store(asid, Relaxed);
+        SHOOTDOWN_PENDING.store(cpus - 1, Release);
+        for (cpu, slot) in SHOOTDOWN_SLOTS.iter().enumerate().take(cpus) {
+            if cpu != current_cpu {
+                slot.store(addr, Release);
+            }
+        }
+
+        apic().send_ipi_to_others(InterruptVector::TlbShootdown);
+        while SHOOTDOWN_PENDING.load(Acquire) > 0 {
+            spin_loop();
+        }
+    });
+}
+
+/// Flush the address in the executing CPU's shootdown slot (if any) and acknowledge it.
+fn handle_shootdown() {
+    let slot = &SHOOTDOWN_SLOTS[percpu::current_cpu_id() as usize];
+    let addr = slot.swap(NO_SHOOTDOWN, AcqRel);
+    if addr == NO_SHOOTDOWN {
+        return;
+    }
+
+    let asid = SHOOTDOWN_ASID.load(Relaxed);
+    if asid == KERNEL_ADDRESS_SPACE_ID || asid == percpu::address_space_id() {
+        flush(addr);
+    }
+
+    SHOOTDOWN_PENDING.fetch_sub(1, AcqRel);
+}
+
+fn flush(addr: u64) {
+    if addr == FLUSH_ALL {
+        tlb::flush_all();
+    } else {
+        tlb::flush(VirtAddr::new(addr));
+    }
+}
+
+/// Handler for 'InterruptVector::TlbShootdown', sent by 'tlb_shootdown()' (registered by 'smp::init()').
+pub struct TlbShootdownHandler;
+
+impl InterruptHandler for TlbShootdownHandler {
+    fn trigger(&mut self) {
+        handle_shootdown();
+    }
+}
+
+/// Flags of the page containing `addr` in the active address space (`None`, if it is not mapped).
+/// 'USER_ACCESSIBLE' and 'WRITABLE' are only set, if they are set on all page table levels.
+pub fn active_page_flags(addr: VirtAddr) -> Option<PageTableFlags> {
+    let root_table = unsafe { (Cr3::read().0.start_address().as_u64() as *const PageTable).as_ref().unwrap() };
+    return page_flags_in_table(root_table, addr, 4);
+}
+
+fn page_flags_in_table(table: &PageTable, addr: VirtAddr, level: usize) -> Option<PageTableFlags> {
+    let entry = &table[page_table_index(addr, level)];
+    let flags = entry.flags();
+    if !flags.contains(PageTableFlags::PRESENT) {
+        return None;
+    }
+    if level == 1 || flags.contains(PageTableFlags::HUGE_PAGE) {
+        return Some(flags);
+    }
+
+    let next_level_table = unsafe { (entry.addr().as_u64() as *const PageTable).as_ref().unwrap() };
+    let next_level_flags = page_flags_in_table(next_level_table, addr, level - 1)?;
+    let inherited_flags = PageTableFlags::USER_ACCESSIBLE | PageTableFlags::WRITABLE;
+
+    return Some(next_level_flags & (flags | !inherited_flags));
+}
+
+/// Physical address, `addr` is mapped to in the active address space (`None`, if it is not mapped).
+pub fn active_phys_addr(addr: VirtAddr) -> Option<PhysAddr> {
+    let root_table = unsafe { (Cr3::read().0.start_address().as_u64() as *const PageTable).as_ref().unwrap() };
+    return phys_addr_in_table(root_table, addr, 4);
+}
+
+fn phys_addr_in_table(table: &PageTable, addr: VirtAddr, level: usize) -> Option<PhysAddr> {
+    let entry = &table[page_table_index(addr, level)];
+    if !entry.flags().contains(PageTableFlags::PRESENT) {
+        return None;
+    }
+    if level == 1 || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+        let page_offset = addr.as_u64() & ((1u64 << (12 + 9 * (level - 1))) - 1);
+        return Some(entry.addr() + page_offset);
+    }
+
+    let next_level_table = unsafe { (entry.addr().as_u64() as *const PageTable).as_ref().unwrap() };
+    return phys_addr_in_table(next_level_table, addr, level - 1);
+}
+
+/// Set the protection key (page table entry bits 59 to 62) and the 'WRITABLE' flag of the 4 KiB page containing `addr` in the active address space.
+/// Returns `false`, if the page is not mapped or part of a huge page.
+pub fn protect_active_page(addr: VirtAddr, writable: bool, pkey: u8) -> bool {
+    let root_table = unsafe { (Cr3::read().0.start_address().as_u64() as *mut PageTable).as_mut().unwrap() };
+    if !protect_page_in_table(root_table, addr, writable, pkey, 4) {
+        return false;
+    }
+
+    tlb_shootdown(addr, percpu::address_space_id());
+    return true;
+}
+
+fn protect_page_in_table(table: &mut PageTable, addr: VirtAddr, writable: bool, pkey: u8, level: usize) -> bool {
+    let entry = &mut table[page_table_index(addr, level)];
+    let flags = entry.flags();
+    // Tables shared with the kernel address space must not be modified (see 'AddressSpace::from_other()')
+    if !flags.contains(PageTableFlags::PRESENT) || (level > 1 && flags.intersects(PageTableFlags::HUGE_PAGE | SHARED_TABLE)) {
+        return false;
+    }
+
+    if level == 1 {
+        let pkey_flags = PageTableFlags::BIT_59 | PageTableFlags::BIT_60 | PageTableFlags::BIT_61 | PageTableFlags::BIT_62;
+        let mut new_flags = (flags - pkey_flags) | PageTableFlags::from_bits_truncate(((pkey & 0x0f) as u64) << 59);
+        new_flags.set(PageTableFlags::WRITABLE, writable);
+        entry.set_flags(new_flags);
+
+        return true;
+    }
+
+    let next_level_table = unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
+    return protect_page_in_table(next_level_table, addr, writable, pkey, level - 1);
+}
+
 fn page_table_index(virt_addr: VirtAddr, level: usize) -> PageTableIndex {
     return PageTableIndex::new_truncate((virt_addr.as_u64() >> 12 >> ((level as u8 - 1) * 9)) as u16);
 }
 
 impl Drop for AddressSpace {
+    /// Free all page frames owned by this address space, its shared memory attachments and its private page tables.
+    /// Tables shared with the kernel address space are left untouched. The address space must not be active on any CPU.
     fn drop(&mut self) {
-        todo!()
+        if self.id as u64 == KERNEL_ADDRESS_SPACE_ID {
+            panic!("AddressSpace: Trying to drop the kernel address space!");
+        }
+
+        shm::detach_all(self);
+
+        let depth = self.depth;
+        AddressSpace::free_table(self.root_table_mut(), depth);
+        unsafe { physical::free(PhysFrameRange { start: self.page_table_address(), end: self.page_table_address() + 1 }); }
     }
 }
 
@@ -71,16 +301,23 @@ impl AddressSpace {
         let root_table = table_addr.start_address().as_u64() as *mut PageTable;
         unsafe { root_table.as_mut().unwrap().zero(); }
 
-        Self { root_table, depth }
+        Self { id: ADDRESS_SPACE_ID_COUNTER.fetch_add(1, Relaxed), root_table, depth, user_frames: 0, lazy_ranges: Vec::new(), lazy_frames: 0 }
     }
 
+    /// Create an address space with the same mappings as `other`, sharing all page tables below the root table (copy-on-write).
+    /// Since the kernel address space is never dropped, the shared tables need no reference counting.
     pub fn from_other(other: &AddressSpace) -> Self {
         let mut address_space = AddressSpace::new(other.depth);
-        AddressSpace::copy_table(other.root_table(), address_space.root_table_mut(), other.depth);
+        AddressSpace::share_table(other.root_table(), address_space.root_table_mut());
 
         return address_space;
     }
 
+    /// Unique id, used as process id for all threads in this address space.
+    pub fn id(&self) -> usize {
+        return self.id;
+    }
+
     pub fn page_table_address(&self) -> PhysFrame {
         PhysFrame::from_start_address(PhysAddr::new(self.root_table.cast_const() as u64)).unwrap()
     }
@@ -89,7 +326,143 @@ impl AddressSpace {
         let depth = self.depth;
         let root_table = self.root_table_mut();
 
-        AddressSpace::map_in_table(root_table, pages, space, flags, depth)
+        let mapped_pages = AddressSpace::map_in_table(root_table, pages, space, flags, depth);
+        tlb_shootdown_range(pages, self.id as u64);
+        if let MemorySpace::User = space {
+            self.user_frames += mapped_pages;
+        }
+
+        return mapped_pages;
+    }
+
+    /// Register `pages` for demand paging, without allocating page frames. Their page table entries stay not present,
+    /// until the first access causes a page fault, which is resolved by `handle_lazy_fault()`.
+    pub fn map_lazy(&mut self, pages: PageRange, flags: PageTableFlags) {
+        self.lazy_ranges.push(LazyRange { pages, flags });
+    }
+
+    /// Back the page containing `addr` with a zeroed user frame, if it has been registered with `map_lazy()` and is not present yet.
+    /// Returns `false` for all other addresses, which are genuine invalid accesses.
+    pub fn handle_lazy_fault(&mut self, addr: VirtAddr) -> bool {
+        let page = Page::containing_address(addr);
+        let flags = match self.lazy_ranges.iter().find(|range| range.pages.start <= page && page < range.pages.end) {
+            Some(range) => range.flags,
+            None => return false
+        };
+
+        // A fault on a present page is a protection violation
+        if page_flags_in_table(self.root_table(), page.start_address(), self.depth).is_some() {
+            return false;
+        }
+
+        // Page frames are not cleared by the allocator and may contain data of other threads (user frames are identity mapped)
+        let frame = physical::alloc(1, MemorySpace::User).start;
+        unsafe { (frame.start_address().as_u64() as *mut u8).write_bytes(0, PAGE_SIZE); }
+
+        let depth = self.depth;
+        AddressSpace::level_1_entry(self.root_table_mut(), page, depth).set_frame(frame, flags | OWNED_FRAME);
+        tlb_shootdown(page.start_address(), self.id as u64);
+
+        self.user_frames += 1;
+        self.lazy_frames += 1;
+        trace!(TRACE_MEMORY, "Mapped lazy page [{:?}] to frame [{:?}]", page, frame);
+        return true;
+    }
+
+    /// Map `pages` to the contiguous page frames starting at `frame` (e.g. to share kernel memory with user space).
+    /// The frames are not counted as user frames, since they are not owned by this address space.
+    pub fn map_physical(&mut self, pages: PageRange, frame: PhysFrame, flags: PageTableFlags) {
+        let depth = self.depth;
+        for (index, page) in pages.enumerate() {
+            let entry = AddressSpace::level_1_entry(self.root_table_mut(), page, depth);
+            entry.set_frame(frame + index as u64, flags);
+        }
+
+        tlb_shootdown_range(pages, self.id as u64);
+    }
+
+    /// Like `map_physical()`, but the frames have been allocated for user space and are owned by this address space
+    /// (they are counted as user frames and freed by `unmap()`).
+    pub fn map_user_frames(&mut self, pages: PageRange, frame: PhysFrame, flags: PageTableFlags) {
+        self.map_physical(pages, frame, flags | OWNED_FRAME);
+        self.user_frames += pages.count();
+    }
+
+    /// Keep the mapping of `page`, but clear 'PRESENT' and 'WRITABLE', so that every access causes a page fault.
+    pub fn set_guard_page(&mut self, page: Page) {
+        let depth = self.depth;
+        let entry = AddressSpace::level_1_entry(self.root_table_mut(), page, depth);
+        entry.set_flags(entry.flags() - (PageTableFlags::PRESENT | PageTableFlags::WRITABLE));
+        tlb_shootdown(page.start_address(), self.id as u64);
+    }
+
+    /// Remove the mappings of `pages` and free the page frames, that have been allocated for user space mappings.
+    /// Frames below the kernel's physical limit (e.g. mapped with `map_physical()`) are not owned by this address space and stay allocated.
+    /// Returns the number of freed page frames.
+    pub fn unmap(&mut self, pages: PageRange) -> usize {
+        let depth = self.depth;
+        let mut freed_frames = 0;
+
+        for page in pages {
+            let entry = match AddressSpace::mapped_level_1_entry(self.root_table_mut(), page, depth) {
+                Some(entry) => entry,
+                None => continue
+            };
+
+            let frame = PhysFrame::containing_address(entry.addr());
+            entry.set_unused();
+            tlb_shootdown(page.start_address(), self.id as u64);
+
+            if frame >= kernel_phys_limit() {
+                unsafe { physical::free(PhysFrameRange { start: frame, end: frame + 1 }); }
+                freed_frames += 1;
+
+                if self.is_lazy(page) {
+                    self.lazy_frames -= min(1, self.lazy_frames);
+                }
+            }
+        }
+
+        // Remove the unmapped pages from the lazy ranges (splitting ranges, that are only partially unmapped)
+        let mut lazy_ranges = Vec::with_capacity(self.lazy_ranges.len());
+        for range in self.lazy_ranges.drain(..) {
+            if range.pages.end <= pages.start || range.pages.start >= pages.end {
+                lazy_ranges.push(range);
+                continue;
+            }
+
+            if range.pages.start < pages.start {
+                lazy_ranges.push(LazyRange { pages: PageRange { start: range.pages.start, end: pages.start }, flags: range.flags });
+            }
+            if range.pages.end > pages.end {
+                lazy_ranges.push(LazyRange { pages: PageRange { start: pages.end, end: range.pages.end }, flags: range.flags });
+            }
+        }
+        self.lazy_ranges = lazy_ranges;
+
+        self.user_frames -= min(freed_frames, self.user_frames);
+        return freed_frames;
+    }
+
+    /// Check if `page` is mapped, including pages registered with `map_lazy()`, that have not been accessed yet.
+    pub fn is_mapped(&self, page: Page) -> bool {
+        return self.is_lazy(page) || page_flags_in_table(self.root_table(), page.start_address(), self.depth).is_some();
+    }
+
+    fn is_lazy(&self, page: Page) -> bool {
+        return self.lazy_ranges.iter().any(|range| range.pages.start <= page && page < range.pages.end);
+    }
+
+    /// Number of page frames, that have been allocated for user space mappings in this address space.
+    pub fn user_frame_count(&self) -> usize {
+        return self.user_frames;
+    }
+
+    /// Number of user pages, that count against the address space limit:
+    /// Eagerly mapped user frames and all pages registered with `map_lazy()`, whether they have been accessed or not.
+    pub fn reserved_page_count(&self) -> usize {
+        let lazy_pages = self.lazy_ranges.iter().map(|range| range.pages.count()).sum::<usize>();
+        return self.user_frames - min(self.lazy_frames, self.user_frames) + lazy_pages;
     }
 
     fn root_table(&self) -> &PageTable {
@@ -100,28 +473,94 @@ impl AddressSpace {
         unsafe { self.root_table.as_mut().unwrap() }
     }
 
-    fn copy_table(source: &PageTable, target: &mut PageTable, level: usize) {
-        if level > 1 { // On all levels larger than 1, we allocate new page frames
-            for (index, target_entry) in target.iter_mut().enumerate() {
-                let source_entry = &source[index];
-                if source_entry.is_unused() { // Skip empty entries
-                    continue;
+    /// Free the frames owned by `table` (of the given `level`) and all private tables below it.
+    fn free_table(table: &mut PageTable, level: usize) {
+        for entry in table.iter_mut() {
+            if entry.is_unused() {
+                continue;
+            }
+
+            let flags = entry.flags();
+            let frame = PhysFrame::containing_address(entry.addr());
+            if level == 1 {
+                if flags.contains(OWNED_FRAME) {
+                    unsafe { physical::free(PhysFrameRange { start: frame, end: frame + 1 }); }
                 }
+            } else if !flags.intersects(PageTableFlags::HUGE_PAGE | SHARED_TABLE) {
+                let next_level_table = unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
+                AddressSpace::free_table(next_level_table, level - 1);
+                unsafe { physical::free(PhysFrameRange { start: frame, end: frame + 1 }); }
+            }
 
-                let phys_frame = physical::alloc(1, MemorySpace::Kernel).start;
-                let flags = source[index].flags();
-                target_entry.set_frame(phys_frame, flags);
+            entry.set_unused();
+        }
+    }
 
-                let next_level_source = unsafe { (source_entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
-                let next_level_target = unsafe { (target_entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
-                AddressSpace::copy_table(next_level_source, next_level_target, level - 1);
-            }
-        } else { // Only on the last level, we create a 1:1 copy of the page table
-            for (index, target_entry) in target.iter_mut().enumerate() {
-                let source_entry = &source[index];
+    /// Copy all entries of `source` (a table above level 1) into `target` and mark the referenced tables as shared.
+    fn share_table(source: &PageTable, target: &mut PageTable) {
+        for (index, target_entry) in target.iter_mut().enumerate() {
+            let source_entry = &source[index];
+            if source_entry.is_unused() || source_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
                 target_entry.set_addr(source_entry.addr(), source_entry.flags());
+            } else {
+                target_entry.set_addr(source_entry.addr(), source_entry.flags() | SHARED_TABLE);
+            }
+        }
+    }
+
+    /// Get the table referenced by `entry` (of a table above level 1) for modification.
+    /// A shared table is replaced by a private copy first, which shares its own next level tables (if `next_level` > 1).
+    /// The copy contains the same translations, so no TLB entries become stale.
+    fn next_level_table_mut(entry: &mut PageTableEntry, next_level: usize) -> &mut PageTable {
+        if entry.flags().contains(SHARED_TABLE) {
+            let source = unsafe { (entry.addr().as_u64() as *const PageTable).as_ref().unwrap() };
+            let phys_frame = physical::alloc(1, MemorySpace::Kernel).start;
+            let target = unsafe { (phys_frame.start_address().as_u64() as *mut PageTable).as_mut().unwrap() };
+
+            if next_level > 1 {
+                AddressSpace::share_table(source, target);
+            } else {
+                target.clone_from(source);
             }
+
+            entry.set_frame(phys_frame, entry.flags() - SHARED_TABLE);
+        }
+
+        return unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
+    }
+
+    /// Like `level_1_entry()`, but without allocating missing page tables.
+    /// Returns `None`, if the page is not mapped or part of a huge page.
+    fn mapped_level_1_entry(table: &mut PageTable, page: Page, level: usize) -> Option<&mut PageTableEntry> {
+        let entry = &mut table[page_table_index(page.start_address(), level)];
+        if !entry.flags().contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        if level == 1 {
+            return Some(entry);
         }
+        if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            return None;
+        }
+
+        let next_level_table = AddressSpace::next_level_table_mut(entry, level - 1);
+        return AddressSpace::mapped_level_1_entry(next_level_table, page, level - 1);
+    }
+
+    fn level_1_entry(table: &mut PageTable, page: Page, level: usize) -> &mut PageTableEntry {
+        let entry = &mut table[page_table_index(page.start_address(), level)];
+        if level == 1 {
+            return entry;
+        }
+
+        if entry.addr().is_null() { // Entry is empty -> Allocate new page frame
+            let phys_frame = physical::alloc(1, MemorySpace::Kernel).start;
+            entry.set_frame(phys_frame, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+            unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap().zero(); }
+        }
+
+        let next_level_table = AddressSpace::next_level_table_mut(entry, level - 1);
+        return AddressSpace::level_1_entry(next_level_table, page, level - 1);
     }
 
     fn map_in_table(table: &mut PageTable, mut pages: PageRange, space: MemorySpace, flags: PageTableFlags, level: usize) -> usize {
@@ -138,7 +577,7 @@ impl AddressSpace {
                     next_level_table = unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
                     next_level_table.zero();
                 } else {
-                    next_level_table = unsafe { (entry.addr().as_u64() as *mut PageTable).as_mut().unwrap() };
+                    next_level_table = AddressSpace::next_level_table_mut(entry, level - 1);
                 }
 
                 let allocated_pages = AddressSpace::map_in_table(next_level_table, pages, space, flags, level - 1);
@@ -172,7 +611,7 @@ impl AddressSpace {
                         }
 
                         let phys_frame = physical::alloc(1, MemorySpace::User).start;
-                        entry.set_frame(phys_frame, flags);
+                        entry.set_frame(phys_frame, flags | OWNED_FRAME);
                     }
                 }
             }
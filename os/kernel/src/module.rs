@@ -0,0 +1,51 @@
+use alloc::string::{String, ToString};
+use core::slice;
+use multiboot2::ModuleTag;
+use x86_64::instructions::tlb;
+use x86_64::structures::paging::frame::PhysFrameRange;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTableFlags, PhysFrame};
+use x86_64::{PhysAddr, VirtAddr};
+use crate::memory::PAGE_SIZE;
+use crate::memory::r#virtual::kernel_address_space;
+
+/// A file, that has been loaded into memory by the bootloader alongside the kernel (e.g. the initial user program).
+pub struct KernelModule {
+    name: String,
+    data: &'static [u8]
+}
+
+impl KernelModule {
+    /// Map the module's memory read-only into the kernel address space (identity mapped).
+    /// Must be called after paging has been initialized and before any user address space is created.
+    pub fn map(tag: &ModuleTag) -> Self {
+        let frames = frame_range(tag);
+        let pages = PageRange {
+            start: Page::containing_address(VirtAddr::new(frames.start.start_address().as_u64())),
+            end: Page::containing_address(VirtAddr::new(frames.end.start_address().as_u64()))
+        };
+
+        kernel_address_space().write().map_physical(pages, frames.start, PageTableFlags::PRESENT);
+        tlb::flush_all();
+
+        let data = unsafe { slice::from_raw_parts(tag.start_address() as usize as *const u8, tag.module_size() as usize) };
+        return Self { name: tag.cmdline().unwrap_or("").to_string(), data };
+    }
+
+    /// The command line, that the bootloader has been configured with for this module (usually its path).
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        return self.data;
+    }
+}
+
+/// Page frames occupied by the module (modules are page aligned, as requested in the Multiboot2 header).
+pub fn frame_range(tag: &ModuleTag) -> PhysFrameRange {
+    let start = PhysFrame::containing_address(PhysAddr::new(tag.start_address() as u64));
+    let end = PhysFrame::containing_address(PhysAddr::new(tag.end_address() as u64).align_up(PAGE_SIZE as u64));
+
+    return PhysFrameRange { start, end };
+}
@@ -0,0 +1,105 @@
+use alloc::boxed::Box;
+use core::arch::asm;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use x86_64::registers::model_specific::GsBase;
+use x86_64::VirtAddr;
+use crate::arch::iopb::{IoPorts, TssWithIopb};
+use crate::thread::scheduler::Scheduler;
+use crate::thread::thread::Thread;
+
+/// Data belonging to a single CPU, found via the GS base of the executing CPU.
+/// While running in user mode, the GS base belongs to user space. Every entry into the kernel from user mode
+/// (system calls, interrupts and exceptions) executes 'SWAPGS', as does every return to user mode.
+#[repr(C)]
+pub struct PercpuData {
+    /// Address of this struct (must be the first field, so that it can be read with 'mov reg, gs:[0]')
+    this: *const PercpuData,
+    cpu_id: u32,
+    tss: *mut TssWithIopb,
+    scheduler: &'static Scheduler,
+    /// Thread running on this CPU (updated by 'Thread::switch()'), readable without locking the scheduler
+    current_thread: AtomicPtr<Thread>,
+    /// Thread, that this CPU has switched away from, until the switch is complete (see 'Thread::finish_switch()')
+    previous_thread: AtomicPtr<Thread>,
+    /// Id of the current thread's address space, readable without locking it (e.g. during a TLB shootdown)
+    address_space_id: AtomicU64
+}
+
+/// Set by 'init()' on the bootstrap processor. Before, only the bootstrap processor is running (see 'is_initialized()').
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Create the data of the executing CPU and store its address in the GS base.
+/// Called once per CPU by 'boot::init_cpu()' (the bootstrap processor has id 0, application processors are numbered in startup order).
+pub fn init(cpu_id: u32, tss: *mut TssWithIopb) {
+    let data = Box::leak(Box::new(PercpuData { this: ptr::null(), cpu_id, tss, scheduler: crate::scheduler(), current_thread: AtomicPtr::new(ptr::null_mut()), previous_thread: AtomicPtr::new(ptr::null_mut()), address_space_id: AtomicU64::new(0) }));
+    data.this = ptr::from_ref(data);
+
+    GsBase::write(VirtAddr::new(data.this as u64));
+    INITIALIZED.store(true, Ordering::Release);
+}
+
+/// Check if the per-CPU data of the bootstrap processor has been set up. Until then, 'current_cpu_id()' must not be called.
+pub fn is_initialized() -> bool {
+    return INITIALIZED.load(Ordering::Acquire);
+}
+
+fn percpu() -> &'static PercpuData {
+    let data: *const PercpuData;
+    unsafe {
+        asm!("mov {}, gs:[0]", out(reg) data, options(nostack, readonly, preserves_flags));
+        return data.as_ref().expect("Per-CPU data accessed before initialization!");
+    }
+}
+
+pub fn current_cpu_id() -> u32 {
+    return percpu().cpu_id;
+}
+
+/// Scheduler responsible for the executing CPU.
+pub fn scheduler() -> &'static Scheduler {
+    return percpu().scheduler;
+}
+
+/// Thread running on the executing CPU (null before the scheduler has been started).
+/// The pointer is only valid while the thread is running, so it must not be kept across thread switches.
+pub fn current_thread() -> *const Thread {
+    return percpu().current_thread.load(Ordering::Relaxed);
+}
+
+pub fn set_current_thread(thread: &Thread, address_space_id: usize) {
+    let data = percpu();
+    data.current_thread.store(ptr::from_ref(thread).cast_mut(), Ordering::Relaxed);
+    data.address_space_id.store(address_space_id as u64, Ordering::Relaxed);
+}
+
+/// Remember `thread` as the thread, that the executing CPU is switching away from.
+pub fn set_previous_thread(thread: &Thread) {
+    percpu().previous_thread.store(ptr::from_ref(thread).cast_mut(), Ordering::Relaxed);
+}
+
+/// Take the thread, that the executing CPU has switched away from (null, if there is none).
+pub fn take_previous_thread() -> *const Thread {
+    return percpu().previous_thread.swap(ptr::null_mut(), Ordering::Relaxed);
+}
+
+/// Id of the address space, the executing CPU is using (0 before the scheduler has been started).
+pub fn address_space_id() -> u64 {
+    return percpu().address_space_id.load(Ordering::Relaxed);
+}
+
+/// Set the kernel stack, used when the executing CPU enters the kernel from user mode (rsp0 in its TSS).
+#[no_mangle]
+pub extern "C" fn tss_set_rsp0(rsp0: u64) {
+    unsafe { (*percpu().tss).privilege_stack_table[0] = VirtAddr::new(rsp0); }
+}
+
+#[no_mangle]
+pub extern "C" fn tss_get_rsp0() -> u64 {
+    return unsafe { (*percpu().tss).privilege_stack_table[0].as_u64() };
+}
+
+/// Allow exactly `ports` in the I/O permission bitmap of the executing CPU's TSS (see 'TssWithIopb::load_ports()').
+pub fn tss_load_io_ports(ports: &IoPorts) {
+    unsafe { (*percpu().tss).load_ports(ports); }
+}
@@ -0,0 +1,196 @@
+use alloc::boxed::Box;
+use core::hint::spin_loop;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use acpi::madt::Madt;
+use acpi::platform::ProcessorState;
+use log::{info, warn};
+use x86_64::registers::control::Cr3;
+use x86_64::registers::model_specific::{Efer, EferFlags};
+use x86_64::structures::gdt::GlobalDescriptorTable;
+use x86_64::structures::paging::frame::PhysFrameRange;
+use x86_64::structures::paging::PhysFrame;
+use x86_64::{PhysAddr, VirtAddr};
+use crate::arch::exception::{DOUBLE_FAULT_IST_INDEX, DOUBLE_FAULT_STACK_SIZE};
+use crate::arch::iopb::TssWithIopb;
+use crate::boot;
+use crate::device::pit::Timer;
+use crate::memory::alloc::AcpiAllocator;
+use crate::memory::PAGE_SIZE;
+use crate::memory::r#virtual::alloc_kernel_stack;
+use crate::interrupt::interrupt_dispatcher::InterruptVector;
+use crate::memory::r#virtual::TlbShootdownHandler;
+use crate::{acpi_tables, allocator, apic, interrupt_dispatcher, scheduler, timer};
+
+/// Physical address, the trampoline for application processors (see 'boot.asm') is copied to.
+/// It must be page aligned and below 1 MiB, since the startup IPI only carries its page number.
+const TRAMPOLINE_ADDRESS: u64 = 0x8000;
+
+/// Limited by the size of thread affinity masks.
+pub const MAX_CPUS: usize = 64;
+
+const AP_STACK_PAGES: usize = 16;
+const STARTUP_TIMEOUT_MS: usize = 100;
+
+/// Number of running CPUs (including the bootstrap processor).
+static ONLINE_CPUS: AtomicUsize = AtomicUsize::new(1);
+
+/// Set by an application processor in 'ap_main()', after it has read its parameters from the trampoline.
+static AP_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Parameters of the trampoline, written before each startup IPI (layout must match 'ap_trampoline_params' in 'boot.asm').
+#[repr(C)]
+struct TrampolineParams {
+    cr3: u64,
+    efer: u64,
+    stack: u64,
+    entry: u64,
+    cpu: u64
+}
+
+/// Id and descriptor tables of an application processor, passed to 'ap_main()' by the trampoline.
+struct ApInfo {
+    id: u32,
+    gdt: &'static GlobalDescriptorTable,
+    tss: *mut TssWithIopb
+}
+
+extern "C" {
+    static ap_trampoline: u8;
+    static ap_trampoline_params: u8;
+    static ap_trampoline_end: u8;
+}
+
+/// The page, the trampoline is copied to. It must be excluded from the available memory during boot.
+pub fn trampoline_frames() -> PhysFrameRange {
+    let start = PhysFrame::from_start_address(PhysAddr::new(TRAMPOLINE_ADDRESS)).unwrap();
+    return PhysFrameRange { start, end: start + 1 };
+}
+
+pub fn online_cpus() -> usize {
+    return ONLINE_CPUS.load(Ordering::Acquire);
+}
+
+/// Start all application processors described by the MADT, one after another (INIT-SIPI-SIPI sequence).
+/// Each processor gets its own stack, GDT and TSS and continues in 'ap_main()'.
+/// Must be called by the bootstrap processor with interrupts enabled, since waiting relies on the timer.
+pub fn init() {
+    let madt = match acpi_tables() {
+        Some(tables) => tables.lock().find_table::<Madt>().expect("MADT not available!"),
+        None => return
+    };
+    let int_model = madt.parse_interrupt_model_in(AcpiAllocator::new(allocator())).expect("Interrupt model not found in MADT!");
+    let processors = match int_model.1 {
+        Some(cpu_info) => cpu_info.application_processors,
+        None => return
+    };
+
+    if processors.is_empty() {
+        return;
+    }
+
+    // The trampoline loads CR3 in 32-bit mode, so the kernel page tables must be located below 4 GiB
+    let cr3 = Cr3::read().0.start_address().as_u64();
+    if cr3 > u32::MAX as u64 {
+        warn!("Kernel page tables are located above 4 GiB -> Application processors are not started");
+        return;
+    }
+
+    let params = unsafe {
+        let start = ptr::addr_of!(ap_trampoline);
+        let size = ptr::addr_of!(ap_trampoline_end) as usize - start as usize;
+        if size > PAGE_SIZE {
+            panic!("SMP: Trampoline does not fit into a single page!");
+        }
+
+        ptr::copy_nonoverlapping(start, TRAMPOLINE_ADDRESS as *mut u8, size);
+        (TRAMPOLINE_ADDRESS as usize + (ptr::addr_of!(ap_trampoline_params) as usize - start as usize)) as *mut TrampolineParams
+    };
+
+    interrupt_dispatcher().assign(InterruptVector::TlbShootdown, Box::new(TlbShootdownHandler));
+
+    let efer = EferFlags::LONG_MODE_ENABLE | (Efer::read() & EferFlags::NO_EXECUTE_ENABLE);
+    let vector = (TRAMPOLINE_ADDRESS / PAGE_SIZE as u64) as u8;
+
+    for processor in processors.iter().filter(|processor| processor.state != ProcessorState::Disabled) {
+        if online_cpus() >= MAX_CPUS {
+            warn!("Only [{}] CPUs are supported -> Remaining application processors are not started", MAX_CPUS);
+            break;
+        }
+
+        let cpu = alloc_ap_info(online_cpus() as u32);
+        let stack = alloc_kernel_stack(AP_STACK_PAGES);
+        unsafe {
+            params.write_volatile(TrampolineParams {
+                cr3,
+                efer: efer.bits(),
+                stack: stack.end.start_address().as_u64(),
+                entry: ap_main as u64,
+                cpu: ptr::from_ref(cpu) as u64
+            });
+        }
+
+        AP_STARTED.store(false, Ordering::Release);
+        apic().send_init_ipi(processor.local_apic_id);
+        Timer::wait(10);
+
+        // The second startup IPI is only needed, if the processor missed the first one
+        for _ in 0..2 {
+            apic().send_startup_ipi(processor.local_apic_id, vector);
+            if wait_for_startup() {
+                break;
+            }
+        }
+
+        if AP_STARTED.load(Ordering::Acquire) {
+            info!("Started application processor [{}] (Local APIC: [{}])", processor.processor_uid, processor.local_apic_id);
+        } else {
+            warn!("Application processor [{}] (Local APIC: [{}]) did not respond", processor.processor_uid, processor.local_apic_id);
+        }
+    }
+
+    info!("[{}] CPUs online", online_cpus());
+}
+
+/// Create the GDT and TSS of the application processor with `id` (they must live as long as the processor runs).
+fn alloc_ap_info(id: u32) -> &'static ApInfo {
+    let tss = Box::leak(Box::new(TssWithIopb::new()));
+    let double_fault_stack = alloc_kernel_stack(DOUBLE_FAULT_STACK_SIZE / PAGE_SIZE);
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = VirtAddr::new(double_fault_stack.end.start_address().as_u64());
+
+    let tss = ptr::from_mut(tss);
+    let mut gdt = Box::new(GlobalDescriptorTable::new());
+    boot::build_gdt(&mut gdt, unsafe { tss.as_ref().unwrap() });
+
+    return Box::leak(Box::new(ApInfo { id, gdt: Box::leak(gdt), tss }));
+}
+
+fn wait_for_startup() -> bool {
+    let end_time = timer().read().systime_ms() + STARTUP_TIMEOUT_MS;
+    while timer().read().systime_ms() < end_time {
+        if AP_STARTED.load(Ordering::Acquire) {
+            return true;
+        }
+
+        spin_loop();
+    }
+
+    return false;
+}
+
+/// Entry point of application processors, called by the trampoline in long mode with the kernel page tables and a fresh stack.
+/// Each application processor gets its ticks from its local APIC timer and schedules the threads, that are assigned to it
+/// (threads created on it and threads moved to it with 'sched_setaffinity', see 'Scheduler::ready()' and 'Scheduler::set_affinity()').
+extern "C" fn ap_main(cpu: &'static ApInfo) -> ! {
+    boot::init_cpu(cpu.id, cpu.gdt, cpu.tss);
+    apic().enable_local_apic();
+    if !timer().read().start_local_tick() {
+        warn!("Local APIC timer of CPU [{}] is not calibrated -> Threads on this CPU are not preempted", cpu.id);
+    }
+
+    ONLINE_CPUS.fetch_add(1, Ordering::AcqRel);
+    AP_STARTED.store(true, Ordering::Release);
+
+    scheduler().start();
+    panic!("SMP: Scheduler of CPU [{}] has returned!", cpu.id);
+}
@@ -0,0 +1,121 @@
+use core::hint::spin_loop;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+use spin::{Mutex, MutexGuard};
+use crate::scheduler;
+
+/// Maximum number of 'pause' instructions executed between two lock attempts.
+const MAX_PAUSES: u32 = 256;
+
+/// Spinlock, that waits with an exponentially growing number of 'pause' instructions after each failed attempt,
+/// instead of retrying immediately like 'spin::Mutex'. This reduces the traffic on the cache line of the lock, while other CPUs are waiting for it.
+pub struct SpinlockBackoff<T> {
+    inner: Mutex<T>,
+    contentions: AtomicU64
+}
+
+/// Same as 'SpinlockBackoff', but gives up the CPU (see 'Scheduler::yield_current()') after each attempt, once the maximum backoff has been reached.
+/// This allows other threads on the same CPU to run (possibly the one holding the lock).
+pub struct SpinlockYield<T> {
+    inner: Mutex<T>,
+    contentions: AtomicU64
+}
+
+/// Number of 'pause' instructions to execute before the next lock attempt.
+struct Backoff {
+    pauses: u32
+}
+
+impl Backoff {
+    const fn new() -> Self {
+        Self { pauses: 1 }
+    }
+
+    /// Execute the current number of 'pause' instructions and double it for the next call.
+    /// Returns `true`, if the maximum has already been reached.
+    fn pause(&mut self) -> bool {
+        for _ in 0..self.pauses {
+            spin_loop();
+        }
+
+        if self.pauses >= MAX_PAUSES {
+            return true;
+        }
+
+        self.pauses *= 2;
+        return false;
+    }
+}
+
+impl<T> SpinlockBackoff<T> {
+    pub const fn new(value: T) -> Self {
+        Self { inner: Mutex::new(value), contentions: AtomicU64::new(0) }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        return lock_contended(&self.inner, &self.contentions, || {});
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        return self.inner.try_lock();
+    }
+
+    /// Release the lock without a guard (e.g. when the owner cannot continue after a panic).
+    ///
+    /// # Safety
+    /// The lock must not be used by its current owner afterwards, since the protected data may be accessed by others from then on.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock();
+    }
+
+    /// Number of 'lock()' calls, that found the lock already taken (printed by 'bench::run_benchmarks()').
+    pub fn contentions(&self) -> u64 {
+        return self.contentions.load(Relaxed);
+    }
+}
+
+impl<T> SpinlockYield<T> {
+    pub const fn new(value: T) -> Self {
+        Self { inner: Mutex::new(value), contentions: AtomicU64::new(0) }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        return lock_contended(&self.inner, &self.contentions, || scheduler().yield_current());
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        return self.inner.try_lock();
+    }
+
+    /// Release the lock without a guard (e.g. when the owner cannot continue after a panic).
+    ///
+    /// # Safety
+    /// The lock must not be used by its current owner afterwards, since the protected data may be accessed by others from then on.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock();
+    }
+
+    /// Number of 'lock()' calls, that found the lock already taken (printed by 'bench::run_benchmarks()').
+    pub fn contentions(&self) -> u64 {
+        return self.contentions.load(Relaxed);
+    }
+}
+
+/// Try to take `lock` with exponential backoff between the attempts, calling `at_limit` after each attempt once the backoff has reached 'MAX_PAUSES'.
+fn lock_contended<'a, T>(lock: &'a Mutex<T>, contentions: &AtomicU64, at_limit: impl Fn()) -> MutexGuard<'a, T> {
+    if let Some(guard) = lock.try_lock() {
+        return guard;
+    }
+
+    contentions.fetch_add(1, Relaxed);
+    let mut backoff = Backoff::new();
+    loop {
+        if backoff.pause() {
+            at_limit();
+        }
+
+        if let Some(guard) = lock.try_lock() {
+            return guard;
+        }
+    }
+}
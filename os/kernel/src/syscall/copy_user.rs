@@ -0,0 +1,174 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::arch::global_asm;
+use core::mem::{size_of, MaybeUninit};
+use core::{ptr, slice};
+use x86_64::structures::paging::PageTableFlags;
+use x86_64::VirtAddr;
+use crate::memory::PAGE_SIZE;
+use crate::memory::r#virtual::active_page_flags;
+use crate::arch::exception::handle_lazy_fault;
+
+// User space is the lower half of the canonical address space
+pub const USER_SPACE_END: u64 = 0x0000800000000000;
+
+/// Error, returned if a user pointer is invalid, or a fault occurs while accessing user memory.
+#[derive(Copy, Clone, Debug)]
+pub struct Fault;
+
+extern "C" {
+    fn __copy_user_bytes(dst: *mut u8, src: *const u8, length: usize) -> usize;
+    static __copy_user_start: u8;
+    static __copy_user_end: u8;
+    static __copy_user_fixup: u8;
+}
+
+// Copy 'length' bytes from 'src' to 'dst' and return the number of bytes, that have not been copied.
+// A page fault (or general protection fault) between '__copy_user_start' and '__copy_user_end' continues at '__copy_user_fixup',
+// where rcx still contains the remaining byte count.
+global_asm!(
+    ".global __copy_user_bytes",
+    ".global __copy_user_start",
+    ".global __copy_user_end",
+    ".global __copy_user_fixup",
+    "__copy_user_bytes:",
+    "mov rcx, rdx",
+    "__copy_user_start:",
+    "rep movsb",
+    "__copy_user_end:",
+    "xor eax, eax",
+    "ret",
+    "__copy_user_fixup:",
+    "mov rax, rcx",
+    "ret"
+);
+
+/// Check if a fault at `rip` happened while copying from/to user space.
+/// Returns the address, at which execution must continue in this case (called by the exception handler).
+pub fn fixup(rip: u64) -> Option<u64> {
+    let (start, end, fixup) = unsafe { (ptr::addr_of!(__copy_user_start) as u64, ptr::addr_of!(__copy_user_end) as u64, ptr::addr_of!(__copy_user_fixup) as u64) };
+
+    return if (start..end).contains(&rip) { Some(fixup) } else { None };
+}
+
+/// Check, that `length` bytes at `addr` are in user space and mapped readable for user mode.
+pub fn validate_user_read(addr: *const u8, length: usize) -> Result<(), Fault> {
+    return validate(addr as u64, length, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE);
+}
+
+/// Check, that `length` bytes at `addr` are in user space and mapped writable for user mode.
+pub fn validate_user_write(addr: *mut u8, length: usize) -> Result<(), Fault> {
+    return validate(addr as u64, length, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::WRITABLE);
+}
+
+/// Flags of the page containing `addr` in the active address space, like 'active_page_flags()'.
+/// A page registered for demand paging (see 'AddressSpace::map_lazy()'), that has not been accessed yet, is faulted in first,
+/// so that pages of a fresh 'mmap()' are accepted as system call buffers.
+pub fn user_page_flags(addr: VirtAddr) -> Option<PageTableFlags> {
+    return match active_page_flags(addr) {
+        Some(flags) => Some(flags),
+        None if handle_lazy_fault(addr) => active_page_flags(addr),
+        None => None
+    };
+}
+
+/// # Safety
+/// `dst` must be valid for writing `length` bytes of kernel memory. The user address `src` is validated here.
+unsafe fn copy_from_user_raw(dst: *mut u8, src: u64, length: usize) -> Result<(), Fault> {
+    validate(src, length, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE)?;
+    if __copy_user_bytes(dst, src as *const u8, length) != 0 {
+        return copy_failed();
+    }
+
+    return Ok(());
+}
+
+/// # Safety
+/// `src` must be valid for reading `length` bytes of kernel memory. The user address `dst` is validated here.
+unsafe fn copy_to_user_raw(dst: u64, src: *const u8, length: usize) -> Result<(), Fault> {
+    validate(dst, length, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::WRITABLE)?;
+    if __copy_user_bytes(dst as *mut u8, src, length) != 0 {
+        return copy_failed();
+    }
+
+    return Ok(());
+}
+
+#[cold]
+fn copy_failed() -> Result<(), Fault> {
+    return Err(Fault);
+}
+
+/// Copy `dst.len()` bytes from the user address `src` into `dst`.
+pub fn copy_from_user(dst: &mut [u8], src: *const u8) -> Result<(), Fault> {
+    // User pointers are treated as plain addresses, since they are validated before each access
+    let src = src as u64;
+    return unsafe { copy_from_user_raw(dst.as_mut_ptr(), src, dst.len()) };
+}
+
+/// Copy `src` to the user address `dst`.
+pub fn copy_to_user(dst: *mut u8, src: &[u8]) -> Result<(), Fault> {
+    let dst = dst as u64;
+    return unsafe { copy_to_user_raw(dst, src.as_ptr(), src.len()) };
+}
+
+pub fn read_user<T: Copy>(src: *const T) -> Result<T, Fault> {
+    // Zeroed, so that the bytes may be accessed as a slice before they are copied
+    let mut value = MaybeUninit::<T>::zeroed();
+    let bytes = unsafe { slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_from_user(bytes, src as *const u8)?;
+
+    return Ok(unsafe { value.assume_init() });
+}
+
+pub fn write_user<T: Copy>(dst: *mut T, value: &T) -> Result<(), Fault> {
+    let bytes = unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    return copy_to_user(dst as *mut u8, bytes);
+}
+
+/// Copy a null terminated string of at most `max` bytes (without terminator). Fails, if the string is not valid UTF-8.
+pub fn copy_string_from_user(src: *const u8, max: usize) -> Result<String, Fault> {
+    let mut bytes = Vec::new();
+    let mut addr = src;
+
+    // Copy page by page, since the string may end right before an unmapped page
+    while bytes.len() < max {
+        let page_remaining = PAGE_SIZE - (addr as usize % PAGE_SIZE);
+        let chunk_size = page_remaining.min(max - bytes.len());
+        let mut chunk = [0u8; PAGE_SIZE];
+        copy_from_user(&mut chunk[..chunk_size], addr)?;
+
+        match chunk[..chunk_size].iter().position(|byte| *byte == 0) {
+            Some(end) => {
+                bytes.extend_from_slice(&chunk[..end]);
+                return String::from_utf8(bytes).map_err(|_| Fault);
+            }
+            None => bytes.extend_from_slice(&chunk[..chunk_size])
+        }
+
+        addr = addr.wrapping_add(chunk_size);
+    }
+
+    return String::from_utf8(bytes).map_err(|_| Fault);
+}
+
+fn validate(addr: u64, length: usize, required_flags: PageTableFlags) -> Result<(), Fault> {
+    if length == 0 {
+        return Ok(());
+    }
+
+    let end = addr.checked_add(length as u64).ok_or(Fault)?;
+    if addr == 0 || end > USER_SPACE_END {
+        return Err(Fault);
+    }
+
+    let mut page = addr & !(PAGE_SIZE as u64 - 1);
+    while page < end {
+        match user_page_flags(VirtAddr::new(page)) {
+            Some(flags) if flags.contains(required_flags) => page += PAGE_SIZE as u64,
+            _ => return Err(Fault)
+        }
+    }
+
+    return Ok(());
+}
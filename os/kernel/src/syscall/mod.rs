@@ -1,18 +1,1334 @@
-use crate::scheduler;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::mem::size_of;
+use core::sync::atomic::Ordering;
+use library_syscall::{Errno, KernelTime, MouseEvent, UEFI_VARIABLE_MAX_SIZE, UEFI_VARIABLE_NAME_MAX, FUTEX_WAIT, FUTEX_WAIT_FOREVER, FUTEX_WAKE, MemInfo, RLimit, Rusage, SchedParam, SigAction, Termios, Timeval, Timezone, Tms, CLK_TCK, TCGETS, TCSETS, GRND_NONBLOCK, GRND_RANDOM, MPOL_BIND, MPOL_DEFAULT, MPOL_F_ADDR, MPOL_F_MEMS_ALLOWED, MPOL_F_NODE, MPOL_INTERLEAVE, MAP_ANONYMOUS, O_CREAT, PATH_MAX, MAP_FIXED, MAP_PRIVATE, NSIG, PER_QUERY, PRIORITY_LEVELS, PKEY_DISABLE_ACCESS, PKEY_DISABLE_WRITE, PROT_EXEC, PROT_READ, PROT_WRITE, RLIMIT_AS, RLIM_INFINITY, RLIM_NLIMITS, SA_NODEFER, SA_RESETHAND, RUSAGE_CHILDREN, RUSAGE_SELF, SCHED_FIFO, SCHED_OTHER, SCHED_PRIORITY_MAX, SCHED_PRIORITY_MIN, SCHED_RR, WATCHPOINT_COUNT, WATCH_EXECUTE, WATCH_READ_WRITE, WATCH_WRITE};
+use crate::{efi_system_table, entropy_pool, ktrace, modules, percpu, ps2_devices, scheduler, terminal, timer, vfs};
+use crate::fs::{pipe, OpenFile};
+use crate::fs::vfs::FsError;
+use crate::thread::scheduler::online_cpu_mask;
+use crate::thread::signal;
+use crate::debug::dcookie;
+use crate::syscall::copy_user::{copy_from_user, copy_to_user, read_user, user_page_flags, validate_user_read, validate_user_write, write_user, USER_SPACE_END};
+use crate::memory::{physical, shm};
+use crate::memory::physical::{phys_limit, ONLINE_NODE_MASK};
+use crate::memory::PAGE_SIZE;
+use crate::memory::r#virtual::{active_phys_addr, protect_active_page, AddressSpace};
+use crate::arch::{iopb, pkey};
+use crate::boot::efi_time_to_unix_ns;
+use crate::acpi::power;
+use log::{info, warn};
+use crate::device::{keyboard_layout, rtc};
+use x86_64::instructions::interrupts;
+use uefi::{CStr16, Guid, Status};
+use uefi::table::runtime::{VariableAttributes, VariableVendor};
+use crate::arch::debug_registers;
+use crate::arch::debug_registers::{WatchCondition, WatchSize};
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::structures::paging::page::PageRange;
+use x86_64::VirtAddr;
+use crate::thread::process::KERNEL_PID;
+use crate::thread::thread::{MemPolicy, Thread, ThreadRef};
 
+pub mod copy_user;
 pub mod syscall_dispatcher;
 
+/// Set the last error of the calling thread (returned by 'sys_get_errno').
+pub fn sys_set_errno(code: i32) {
+    scheduler().current_thread().set_last_errno(code);
+}
+
+/// Record `errno` as the last error of the calling thread and return it as negative value (like all failing system calls).
+fn error(errno: Errno) -> i64 {
+    sys_set_errno(errno as i32);
+    return -(errno as i64);
+}
+
+/// Return the error of the last failed system call of the calling thread (0, if no system call has failed yet).
+/// Successful system calls do not reset the value. Errors of the vDSO functions are not recorded.
+#[no_mangle]
+pub extern "C" fn sys_get_errno() -> i32 {
+    return scheduler().current_thread().last_errno();
+}
+
 #[no_mangle]
 pub extern "C" fn sys_thread_switch() {
     scheduler().switch_thread();
 }
 
+#[no_mangle]
+pub extern "C" fn sys_thread_yield() -> isize {
+    Thread::yield_now();
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_get_tid() -> usize {
+    return scheduler().current_thread().id();
+}
+
+/// Threads sharing an address space form a process (see 'Process').
+#[no_mangle]
+pub extern "C" fn sys_get_pid() -> usize {
+    return scheduler().current_thread().process().pid();
+}
+
+/// The FS base is saved and restored on thread switches, so it can be used as thread-local storage pointer.
+#[no_mangle]
+pub extern "C" fn sys_set_fs_base(addr: usize) -> isize {
+    if addr as u64 >= USER_SPACE_END {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    scheduler().current_thread().set_fs_base(addr as u64);
+    return 0;
+}
+
 #[no_mangle]
 pub extern "C" fn sys_thread_sleep(ms: usize) {
     scheduler().sleep(ms);
 }
 
+/// Unlike 'sys_thread_sleep()', the delay is not rounded up to whole timer ticks (if a TSC deadline timer or HPET is available).
+#[no_mangle]
+pub extern "C" fn sys_sleep_ns(ns: u64) -> isize {
+    scheduler().sleep_ns(ns);
+    return 0;
+}
+
+/// Enable (`enable` != 0) or disable tracing of a kernel subsystem ('KTRACE_*').
+#[no_mangle]
+pub extern "C" fn sys_ktrace_enable(subsystem: u32, enable: usize) -> isize {
+    return match ktrace::flag(subsystem) {
+        Some(flag) => {
+            flag.store(enable != 0, Ordering::Relaxed);
+            0
+        }
+        None => error(Errno::InvalidArgument) as isize
+    };
+}
+
+/// Return the number of modules, that have been loaded by the bootloader.
+#[no_mangle]
+pub extern "C" fn sys_list_modules() -> isize {
+    return modules().len() as isize;
+}
+
+/// Copy up to `len` bytes of the module with the given index to `buf` and return the module's full size,
+/// so that the caller can detect a too small buffer.
+#[no_mangle]
+pub extern "C" fn sys_get_module(index: usize, buf: *mut u8, len: usize) -> isize {
+    let module = match modules().get(index) {
+        Some(module) => module,
+        None => return error(Errno::InvalidArgument) as isize
+    };
+
+    let data = module.data();
+    if copy_to_user(buf, &data[..min(len, data.len())]).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return data.len() as isize;
+}
+
+/// Replace the image of the calling process with the module `module_index` (a statically linked ELF64 executable, see 'Thread::exec()').
+/// Does not return on success. Only single threaded processes may call this, since other threads would keep running without their image.
+#[no_mangle]
+pub extern "C" fn sys_exec(module_index: usize) -> isize {
+    let module = match modules().get(module_index) {
+        Some(module) => module,
+        None => return error(Errno::InvalidArgument) as isize
+    };
+
+    if scheduler().current_thread().process().threads().len() > 1 {
+        return error(Errno::OperationNotPermitted) as isize;
+    }
+
+    let err = Thread::exec(module.data());
+    warn!("Failed to execute module [{}] (Error: {:?})", module.name(), err);
+    return error(Errno::InvalidArgument) as isize;
+}
+
+/// Open the file at the absolute `path` (`len` bytes, not null terminated) and return its file descriptor.
+/// With 'O_CREAT' in `flags`, a missing file is created (if the filesystem supports it).
+#[no_mangle]
+pub extern "C" fn sys_open(path: *const u8, len: usize, flags: u32) -> isize {
+    if len > PATH_MAX {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    let mut bytes = vec![0u8; len];
+    if copy_from_user(&mut bytes, path).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    let path = match core::str::from_utf8(&bytes) {
+        Ok(path) => path,
+        Err(_) => return error(Errno::InvalidArgument) as isize
+    };
+
+    let node = match vfs().read().open(path, flags & O_CREAT != 0) {
+        Ok(node) => node,
+        Err(err) => return error(fs_errno(err)) as isize
+    };
+
+    return match scheduler().current_thread().files().lock().insert(OpenFile::new(node)) {
+        Some(fd) => fd as isize,
+        None => error(Errno::TooManyOpenFiles) as isize
+    };
+}
+
+/// Read up to `len` bytes from the file `fd` into `buf` and return the number of bytes read (0 at the end of the file).
+#[no_mangle]
+pub extern "C" fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let thread = scheduler().current_thread();
+
+    // The file table is shared by all threads of the process, so it must not stay locked while reading blocks (e.g. on an empty pipe)
+    let (node, position) = match thread.files().lock().get_mut(fd) {
+        Some(file) => (Arc::clone(&file.node), file.position),
+        None => return error(Errno::BadFileDescriptor) as isize
+    };
+
+    // The file is read into a kernel buffer first, since the user buffer may be unmapped
+    let mut buffer = vec![0u8; min(len, PAGE_SIZE)];
+    let mut total = 0;
+    let mut failure = None;
+    while total < len {
+        let requested = min(len - total, PAGE_SIZE);
+        let count = match node.read(position + total as u64, &mut buffer[..requested]) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(err) => {
+                failure = Some(fs_errno(err));
+                break;
+            }
+        };
+
+        if copy_to_user(buf.wrapping_add(total), &buffer[..count]).is_err() {
+            failure = Some(Errno::BadAddress);
+            break;
+        }
+
+        total += count;
+
+        // A short read means, that no more data is available right now (e.g. at the end of a file or in an empty pipe)
+        if count < requested {
+            break;
+        }
+    }
+
+    thread.files().lock().advance(fd, &node, total as u64);
+    return match failure {
+        Some(errno) => error(errno) as isize,
+        None => total as isize
+    };
+}
+
+/// Write `len` bytes from `buf` to the file `fd` at its position and return the number of bytes written.
+#[no_mangle]
+pub extern "C" fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    let thread = scheduler().current_thread();
+
+    // Like in 'sys_read()', the file table is not locked while writing blocks (e.g. on a full pipe)
+    let (node, position) = match thread.files().lock().get_mut(fd) {
+        Some(file) => (Arc::clone(&file.node), file.position),
+        None => return error(Errno::BadFileDescriptor) as isize
+    };
+
+    let mut buffer = vec![0u8; min(len, PAGE_SIZE)];
+    let mut total = 0;
+    let mut failure = None;
+    while total < len {
+        let count = min(len - total, PAGE_SIZE);
+        if copy_from_user(&mut buffer[..count], buf.wrapping_add(total)).is_err() {
+            failure = Some(Errno::BadAddress);
+            break;
+        }
+
+        match node.write(position + total as u64, &buffer[..count]) {
+            Ok(written) => total += written,
+            Err(err) => {
+                failure = Some(fs_errno(err));
+                break;
+            }
+        }
+    }
+
+    thread.files().lock().advance(fd, &node, total as u64);
+    return match failure {
+        Some(errno) => error(errno) as isize,
+        None => total as isize
+    };
+}
+
+/// Create a pipe and write the file descriptors of its read end and its write end to `fds[0]` and `fds[1]`.
+/// Reading blocks, while the pipe is empty, and returns 0, after the write end has been closed.
+/// Writing blocks, while the pipe is full ('PIPE_CAPACITY' bytes), and fails with 'BrokenPipe', after the read end has been closed.
+#[no_mangle]
+pub extern "C" fn sys_pipe(fds: *mut [i32; 2]) -> isize {
+    let (reader, writer) = pipe::create();
+    let thread = scheduler().current_thread();
+    let mut files = thread.files().lock();
+
+    let read_fd = match files.insert(OpenFile::new(reader)) {
+        Some(fd) => fd,
+        None => return error(Errno::TooManyOpenFiles) as isize
+    };
+    let write_fd = match files.insert(OpenFile::new(writer)) {
+        Some(fd) => fd,
+        None => {
+            files.remove(read_fd);
+            return error(Errno::TooManyOpenFiles) as isize;
+        }
+    };
+
+    if write_user(fds, &[read_fd as i32, write_fd as i32]).is_err() {
+        files.remove(read_fd);
+        files.remove(write_fd);
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_close(fd: usize) -> isize {
+    return match scheduler().current_thread().files().lock().remove(fd) {
+        Some(_) => 0,
+        None => error(Errno::BadFileDescriptor) as isize
+    };
+}
+
+/// Watch `size` bytes at the user space address `addr` with the debug register `index` (see 'debug_registers::set_watchpoint()').
+/// When the watchpoint is hit, the calling thread receives 'SIGTRAP' with `addr` as fault address.
+#[no_mangle]
+pub extern "C" fn sys_set_watchpoint(index: usize, addr: usize, condition: u32, size: usize) -> isize {
+    let condition = match condition {
+        WATCH_EXECUTE => WatchCondition::Execute,
+        WATCH_WRITE => WatchCondition::Write,
+        WATCH_READ_WRITE => WatchCondition::ReadWrite,
+        _ => return error(Errno::InvalidArgument) as isize
+    };
+    let size = match size {
+        1 => WatchSize::Byte,
+        2 => WatchSize::Word,
+        4 => WatchSize::DoubleWord,
+        8 => WatchSize::QuadWord,
+        _ => return error(Errno::InvalidArgument) as isize
+    };
+
+    // Kernel addresses must not be watched, since the exceptions would be raised in kernel mode
+    if addr.checked_add(size as usize).map_or(true, |end| end as u64 > USER_SPACE_END) {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    return match debug_registers::set_watchpoint(index, VirtAddr::new(addr as u64), condition, size) {
+        Ok(()) => 0,
+        Err(_) => error(Errno::InvalidArgument) as isize
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn sys_clear_watchpoint(index: usize) -> isize {
+    if index >= WATCHPOINT_COUNT {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    debug_registers::clear_watchpoint(index);
+    return 0;
+}
+
+fn fs_errno(err: FsError) -> Errno {
+    return match err {
+        FsError::NotFound | FsError::InvalidPath => Errno::NoSuchFile,
+        FsError::NotADirectory => Errno::NotADirectory,
+        FsError::IsADirectory => Errno::IsADirectory,
+        FsError::ReadOnly => Errno::ReadOnlyFilesystem,
+        FsError::FileTooLarge => Errno::FileTooLarge,
+        FsError::IoError => Errno::IoError,
+        FsError::InvalidAccess => Errno::BadFileDescriptor,
+        FsError::BrokenPipe => Errno::BrokenPipe
+    };
+}
+
 #[no_mangle]
 pub extern "C" fn sys_thread_exit() {
     scheduler().exit();
-}
\ No newline at end of file
+}
+
+/// Only threads in the same process can be joined. Threads, that have already exited, are reported as missing.
+#[no_mangle]
+pub extern "C" fn sys_thread_join(tid: usize) -> isize {
+    let current = scheduler().current_thread();
+    if tid == current.id() {
+        return error(Errno::Deadlock) as isize;
+    }
+
+    match scheduler().find_thread(tid) {
+        Some(thread) if thread.process().pid() == current.process().pid() => {},
+        _ => return error(Errno::NoSuchProcess) as isize
+    }
+
+    // The thread may exit between the check above and joining it
+    if !scheduler().try_join(tid) {
+        return error(Errno::NoSuchProcess) as isize;
+    }
+
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sched_yield() -> i32 {
+    scheduler().yield_cpu();
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_getrusage(who: i32, usage: *mut Rusage) -> i32 {
+    let thread = scheduler().current_thread();
+    let tick_ns = timer().read().interval_ns();
+    let rusage = match who {
+        RUSAGE_SELF => {
+            thread.resource_usage().update_max_rss(thread.resident_set_kib());
+            thread.resource_usage().as_rusage(tick_ns)
+        }
+        RUSAGE_CHILDREN => thread.children_resource_usage().as_rusage(tick_ns),
+        _ => return error(Errno::InvalidArgument) as i32,
+    };
+
+    if write_user(usage, &rusage).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    return 0;
+}
+
+/// Write the frame counts of the physical memory allocator (see 'physical::total_frames()') to `info`.
+#[no_mangle]
+pub extern "C" fn sys_mem_info(info: *mut MemInfo) -> i32 {
+    let mem_info = MemInfo {
+        total_frames: physical::total_frames(),
+        free_frames: physical::free_frames(),
+        allocated_frames: physical::allocated_frames(),
+        frame_size: PAGE_SIZE as u64,
+    };
+
+    if write_user(info, &mem_info).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    return 0;
+}
+
+/// Returns the elapsed time since boot in clock ticks ('CLK_TCK' per second).
+#[no_mangle]
+pub extern "C" fn sys_times(buffer: *mut Tms) -> i64 {
+    let tick_ns = timer().read().interval_ns();
+    let to_clock_ticks = |ns: u64| (ns / (1000000000 / CLK_TCK)) as i64;
+
+    if !buffer.is_null() {
+        let thread = scheduler().current_thread();
+        let times = Tms {
+            utime: to_clock_ticks(thread.resource_usage().user_time_ns(tick_ns)),
+            stime: to_clock_ticks(thread.resource_usage().kernel_time_ns(tick_ns)),
+            cutime: to_clock_ticks(thread.children_resource_usage().user_time_ns(tick_ns)),
+            cstime: to_clock_ticks(thread.children_resource_usage().kernel_time_ns(tick_ns)),
+        };
+
+        if write_user(buffer, &times).is_err() {
+            return error(Errno::BadAddress) as i64;
+        }
+    }
+
+    return (timer().read().systime_ms() as u64 * CLK_TCK / 1000) as i64;
+}
+
+/// Wall clock time, based on the EFI time read during boot and the system timer.
+/// `timezone` is always UTC. Both pointers may be null.
+#[no_mangle]
+pub extern "C" fn sys_gettimeofday(time: *mut Timeval, timezone: *mut Timezone) -> i32 {
+    if !time.is_null() && write_user(time, &Timeval::from_ns(timer().read().wall_time_ns())).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+    if !timezone.is_null() && write_user(timezone, &Timezone::default()).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    return 0;
+}
+
+/// Wall clock time, read from the EFI runtime services (or from the CMOS real time clock, if EFI is not available).
+/// Unlike 'sys_gettimeofday', this does not depend on the system timer since boot.
+#[no_mangle]
+pub extern "C" fn sys_get_time(time: *mut KernelTime) -> isize {
+    // EFI runtime services are not reentrant, so the call must not be interrupted by another thread
+    let efi_time = efi_system_table()
+        .and_then(|system_table| interrupts::without_interrupts(|| unsafe { system_table.runtime_services() }.get_time()).ok())
+        .and_then(|time| efi_time_to_unix_ns(&time))
+        .map(|unix_time_ns| KernelTime { seconds_since_epoch: (unix_time_ns / 1000000000) as i64, nanoseconds: (unix_time_ns % 1000000000) as u32 });
+
+    let kernel_time = match efi_time.or_else(|| rtc::read_unix_time().map(|seconds| KernelTime { seconds_since_epoch: seconds, nanoseconds: 0 })) {
+        Some(kernel_time) => kernel_time,
+        None => return error(Errno::IoError) as isize
+    };
+
+    return match write_user(time, &kernel_time) {
+        Ok(()) => 0,
+        Err(_) => error(Errno::BadAddress) as isize
+    };
+}
+
+/// Wait on ('FUTEX_WAIT') or wake threads waiting on ('FUTEX_WAKE') the 32-bit futex word at `addr`.
+/// 'FUTEX_WAIT' blocks, if the word equals `val`, until the futex is woken up or `timeout_ns` nanoseconds have passed ('FUTEX_WAIT_FOREVER' = no timeout).
+/// 'FUTEX_WAKE' wakes up to `val` waiting threads and returns their number. Futexes are identified by their physical address,
+/// so threads of different address spaces can share a futex in shared memory.
+#[no_mangle]
+pub extern "C" fn sys_futex(addr: *const u32, op: u32, val: u32, timeout_ns: u64) -> isize {
+    if addr as usize % size_of::<u32>() != 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    // Reading the word faults its page in (see 'copy_user::user_page_flags()'), so that it is mapped, when it is read again with the scheduler locked
+    if read_user(addr).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+    let key = match active_phys_addr(VirtAddr::new(addr as u64)) {
+        Some(phys_addr) => phys_addr.as_u64(),
+        None => return error(Errno::BadAddress) as isize
+    };
+
+    return match op {
+        FUTEX_WAIT => {
+            let timeout_ns = if timeout_ns == FUTEX_WAIT_FOREVER { None } else { Some(timeout_ns) };
+            match scheduler().futex_wait(key, timeout_ns, || read_user(addr).is_ok_and(|value| value == val)) {
+                Ok(()) => 0,
+                Err(errno) => error(errno) as isize
+            }
+        }
+        FUTEX_WAKE => scheduler().futex_wake(key, val as usize) as isize,
+        _ => error(Errno::InvalidArgument) as isize
+    };
+}
+
+/// Switch the layout of the PS/2 keyboard to `id` (one of the 'KEYBOARD_LAYOUT_*' constants).
+/// Fails with 'InvalidArgument', if `id` does not name a known layout.
+#[no_mangle]
+pub extern "C" fn sys_set_keyboard_layout(id: u32) -> isize {
+    return match keyboard_layout::from_id(id) {
+        Some(layout) => {
+            ps2_devices().keyboard().set_layout(layout);
+            0
+        }
+        None => error(Errno::InvalidArgument) as isize
+    };
+}
+
+/// Take the oldest buffered event of the PS/2 mouse and write it to `event`.
+/// Returns 1, if an event has been written, and 0 without blocking, if the mouse has not been moved since the last call.
+#[no_mangle]
+pub extern "C" fn sys_read_mouse_event(event: *mut MouseEvent) -> isize {
+    let mouse = ps2_devices().mouse();
+    if !mouse.is_present() {
+        return error(Errno::NoSuchDevice) as isize;
+    }
+    if validate_user_write(event as *mut u8, size_of::<MouseEvent>()).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return match mouse.read_event() {
+        Some(mouse_event) => match write_user(event, &mouse_event) {
+            Ok(()) => 1,
+            Err(_) => error(Errno::BadAddress) as isize
+        },
+        None => 0
+    };
+}
+
+/// Power off the system via ACPI (see 'power::shutdown()'). `code` is only logged, since there is nobody to receive it.
+#[no_mangle]
+pub extern "C" fn sys_shutdown(code: i32) -> ! {
+    info!("Thread [{}] requested shutdown (Exit code: [{}])", scheduler().current_thread().id(), code);
+    power::shutdown();
+}
+
+/// Restart the system via the ACPI reset register (see 'power::reboot()').
+#[no_mangle]
+pub extern "C" fn sys_reboot() -> ! {
+    info!("Thread [{}] requested reboot", scheduler().current_thread().id());
+    power::reboot();
+}
+
+/// Read the UEFI variable `name` (`name_len` UCS-2 characters without terminator) of the vendor `vendor_guid` into `buf`.
+/// `buf_len` contains the size of `buf` and receives the size of the variable (also, if `buf` is too small, in which case 'ResultOutOfRange' is returned).
+/// Returns the number of bytes read. Variables larger than 'UEFI_VARIABLE_MAX_SIZE' cannot be read.
+#[no_mangle]
+pub extern "C" fn sys_uefi_get_variable(name: *const u16, name_len: usize, vendor_guid: *const [u8; 16], buf: *mut u8, buf_len: *mut usize) -> isize {
+    let system_table = match efi_system_table() {
+        Some(system_table) => system_table,
+        None => return error(Errno::NoSuchDevice) as isize
+    };
+    let name = match copy_uefi_variable_name(name, name_len) {
+        Ok(name) => name,
+        Err(errno) => return error(errno) as isize
+    };
+    let vendor = match read_user(vendor_guid) {
+        Ok(guid) => VariableVendor(Guid::from_bytes(guid)),
+        Err(_) => return error(Errno::BadAddress) as isize
+    };
+    let capacity = match read_user(buf_len) {
+        Ok(capacity) => min(capacity, UEFI_VARIABLE_MAX_SIZE),
+        Err(_) => return error(Errno::BadAddress) as isize
+    };
+    if validate_user_write(buf, capacity).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    // EFI runtime services are not reentrant, so the calls must not be interrupted by another thread
+    let runtime_services = unsafe { system_table.runtime_services() };
+    let name = CStr16::from_u16_with_nul(&name).unwrap();
+    let size = match interrupts::without_interrupts(|| runtime_services.get_variable_size(name, &vendor)) {
+        Ok(size) => size,
+        Err(err) => return error(efi_status_to_errno(err.status())) as isize
+    };
+
+    if write_user(buf_len, &size).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+    if size > UEFI_VARIABLE_MAX_SIZE {
+        return error(Errno::FileTooLarge) as isize;
+    }
+    if size > capacity {
+        return error(Errno::ResultOutOfRange) as isize;
+    }
+
+    // The variable is read into a kernel buffer, since the firmware must not fault on user memory
+    let mut data = vec![0u8; size];
+    let length = match interrupts::without_interrupts(|| runtime_services.get_variable(name, &vendor, &mut data).map(|(value, _)| value.len())) {
+        Ok(length) => length,
+        Err(err) => return error(efi_status_to_errno(err.status())) as isize
+    };
+
+    if copy_to_user(buf, &data[..length]).is_err() || write_user(buf_len, &length).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return length as isize;
+}
+
+/// Write `data_len` bytes from `data` to the UEFI variable `name` (`name_len` UCS-2 characters without terminator) of the vendor `vendor_guid`.
+/// `attributes` is a combination of the 'UEFI_VARIABLE_*' flags. Writing 0 bytes (without 'UEFI_VARIABLE_APPEND_WRITE') deletes the variable.
+#[no_mangle]
+pub extern "C" fn sys_uefi_set_variable(name: *const u16, name_len: usize, vendor_guid: *const [u8; 16], attributes: u32, data: *const u8, data_len: usize) -> isize {
+    let system_table = match efi_system_table() {
+        Some(system_table) => system_table,
+        None => return error(Errno::NoSuchDevice) as isize
+    };
+    let attributes = match VariableAttributes::from_bits(attributes) {
+        Some(attributes) if (attributes - UEFI_VARIABLE_ATTRIBUTES).is_empty() => attributes,
+        _ => return error(Errno::InvalidArgument) as isize
+    };
+    if data_len > UEFI_VARIABLE_MAX_SIZE {
+        return error(Errno::FileTooLarge) as isize;
+    }
+
+    let name = match copy_uefi_variable_name(name, name_len) {
+        Ok(name) => name,
+        Err(errno) => return error(errno) as isize
+    };
+    let vendor = match read_user(vendor_guid) {
+        Ok(guid) => VariableVendor(Guid::from_bytes(guid)),
+        Err(_) => return error(Errno::BadAddress) as isize
+    };
+    let mut value = vec![0u8; data_len];
+    if copy_from_user(&mut value, data).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    // EFI runtime services are not reentrant, so the call must not be interrupted by another thread
+    let runtime_services = unsafe { system_table.runtime_services() };
+    let name = CStr16::from_u16_with_nul(&name).unwrap();
+    return match interrupts::without_interrupts(|| runtime_services.set_variable(name, &vendor, attributes, &value)) {
+        Ok(()) => 0,
+        Err(err) => error(efi_status_to_errno(err.status())) as isize
+    };
+}
+
+/// Attributes, that user programs may pass to 'sys_uefi_set_variable()' (authenticated variables are not supported).
+const UEFI_VARIABLE_ATTRIBUTES: VariableAttributes = VariableAttributes::NON_VOLATILE
+    .union(VariableAttributes::BOOTSERVICE_ACCESS)
+    .union(VariableAttributes::RUNTIME_ACCESS)
+    .union(VariableAttributes::APPEND_WRITE);
+
+/// Copy a UEFI variable name of `name_len` characters from user space and append the terminator.
+/// Names containing a null character are rejected, since the firmware would silently cut them off.
+fn copy_uefi_variable_name(name: *const u16, name_len: usize) -> Result<Vec<u16>, Errno> {
+    if name_len == 0 || name_len > UEFI_VARIABLE_NAME_MAX {
+        return Err(Errno::InvalidArgument);
+    }
+
+    let mut bytes = vec![0u8; name_len * size_of::<u16>()];
+    copy_from_user(&mut bytes, name as *const u8).map_err(|_| Errno::BadAddress)?;
+
+    let mut buffer: Vec<u16> = bytes.chunks_exact(size_of::<u16>()).map(|pair| u16::from_ne_bytes([pair[0], pair[1]])).collect();
+    buffer.push(0);
+    if buffer[..name_len].contains(&0) {
+        return Err(Errno::InvalidArgument);
+    }
+
+    return Ok(buffer);
+}
+
+/// Map the status of a failed EFI runtime service call to an error number.
+fn efi_status_to_errno(status: Status) -> Errno {
+    return match status {
+        Status::NOT_FOUND => Errno::NoSuchFile,
+        Status::INVALID_PARAMETER => Errno::InvalidArgument,
+        Status::BUFFER_TOO_SMALL => Errno::ResultOutOfRange,
+        Status::WRITE_PROTECTED | Status::SECURITY_VIOLATION => Errno::OperationNotPermitted,
+        Status::OUT_OF_RESOURCES => Errno::NoSpace,
+        Status::UNSUPPORTED => Errno::NoSuchDevice,
+        _ => Errno::IoError
+    };
+}
+
+/// Allow the calling process direct access to `port` with 'IN'/'OUT' instructions (used by user mode drivers).
+/// Only processes started by the kernel (e.g. the initial program) may request ports and ports used by kernel drivers are never granted.
+/// The permission is stored per process (at most 'MAX_PROCESS_PORTS' ports) and loaded into the TSS, whenever one of its threads is switched to.
+/// Threads of the process, that are currently running on other CPUs, get access after their next thread switch.
+#[no_mangle]
+pub extern "C" fn sys_request_ioport(port: u16) -> isize {
+    let thread = scheduler().current_thread();
+    let process = thread.process();
+
+    // There is no capability model ('CAP_SYS_RAWIO'), so only direct children of the kernel process are privileged
+    if process.parent_pid().map_or(true, |pid| pid != KERNEL_PID) {
+        return error(Errno::OperationNotPermitted) as isize;
+    }
+    if iopb::is_reserved(port) {
+        return error(Errno::OperationNotPermitted) as isize;
+    }
+
+    if !process.io_ports().contains(port) {
+        if !process.allow_io_port(port) {
+            return error(Errno::NoSpace) as isize;
+        }
+
+        info!("Granting process [{}] user mode access to I/O port [0x{:x}]", process.pid(), port);
+    }
+
+    // Load the new permission on this CPU right away (a thread switch in between loads it as well)
+    interrupts::without_interrupts(|| percpu::tss_load_io_ports(&process.io_ports()));
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_getrandom(buffer: *mut u8, length: usize, flags: u32) -> isize {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM) != 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+    if validate_user_write(buffer, length).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    // Random bytes are generated into a kernel buffer and copied chunk by chunk
+    let mut chunk = [0u8; 256];
+    let mut written = 0;
+    while written < length {
+        let chunk_size = min(chunk.len(), length - written);
+        if !entropy_pool().lock().fill(&mut chunk[..chunk_size]) {
+            if flags & GRND_NONBLOCK != 0 {
+                return error(Errno::TryAgain) as isize;
+            }
+
+            // Wait for interrupts to provide more entropy
+            scheduler().sleep(10);
+            continue;
+        }
+
+        if copy_to_user(buffer.wrapping_add(written), &chunk[..chunk_size]).is_err() {
+            return error(Errno::BadAddress) as isize;
+        }
+
+        written += chunk_size;
+    }
+
+    chunk.fill(0);
+    return length as isize;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sched_getaffinity(tid: usize, cpu_set_size: usize, mask: *mut u8) -> isize {
+    let thread = match thread_or_current(tid) {
+        Some(thread) => thread,
+        None => return error(Errno::NoSuchProcess) as isize,
+    };
+
+    // The kernel's CPU mask is 8 bytes long; smaller buffers cannot hold it
+    let mask_size = size_of::<u64>();
+    if cpu_set_size < mask_size {
+        return error(Errno::InvalidArgument) as isize;
+    }
+    let bytes = thread.affinity_mask().to_le_bytes();
+    if copy_to_user(mask, &bytes[..mask_size]).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return mask_size as isize;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sched_setaffinity(tid: usize, cpu_set_size: usize, mask: *const u8) -> i32 {
+    let thread = match modifiable_thread(tid) {
+        Ok(thread) => thread,
+        Err(errno) => return error(errno) as i32,
+    };
+
+    // Bits for CPUs beyond the kernel's mask size are ignored
+    let mut bytes = [0u8; size_of::<u64>()];
+    if copy_from_user(&mut bytes[..min(cpu_set_size, size_of::<u64>())], mask).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    let new_mask = u64::from_le_bytes(bytes) & online_cpu_mask();
+    if new_mask == 0 {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    scheduler().set_affinity(&thread, new_mask);
+    return 0;
+}
+
+/// Change the priority level of the calling thread. Raising it is allowed as well, since aging keeps other threads from starving.
+#[no_mangle]
+pub extern "C" fn sys_set_priority(priority: u32) -> i32 {
+    if priority as usize >= PRIORITY_LEVELS {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    scheduler().set_priority(&scheduler().current_thread(), priority as u8);
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sched_setscheduler(tid: usize, policy: i32, param: *const SchedParam) -> i32 {
+    let thread = match modifiable_thread(tid) {
+        Ok(thread) => thread,
+        Err(errno) => return error(errno) as i32,
+    };
+    let param = match read_user(param) {
+        Ok(param) => param,
+        Err(_) => return error(Errno::BadAddress) as i32
+    };
+
+    let valid = match policy {
+        SCHED_OTHER => param.sched_priority == 0,
+        SCHED_FIFO | SCHED_RR => param.sched_priority >= SCHED_PRIORITY_MIN && param.sched_priority <= SCHED_PRIORITY_MAX,
+        _ => false
+    };
+    if !valid {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    // There is no capability model ('CAP_SYS_ADMIN'), so only kernel threads are privileged to use real-time policies
+    if policy != SCHED_OTHER && !scheduler().current_thread().is_kernel_thread() {
+        return error(Errno::OperationNotPermitted) as i32;
+    }
+
+    scheduler().set_sched_policy(&thread, policy, param.sched_priority);
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sched_getscheduler(tid: usize) -> i32 {
+    return match thread_or_current(tid) {
+        Some(thread) => thread.sched_policy(),
+        None => error(Errno::NoSuchProcess) as i32,
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn sys_setpgid(pid: usize, pgid: usize) -> i32 {
+    let thread = match modifiable_thread(pid) {
+        Ok(thread) => thread,
+        Err(errno) => return error(errno) as i32,
+    };
+
+    // A group id of 0 creates a new group, named after the thread
+    let pgid = if pgid == 0 { thread.id() } else { pgid };
+    if pgid != thread.id() && !scheduler().threads().iter().any(|other| other.process_group() == pgid) {
+        return error(Errno::OperationNotPermitted) as i32;
+    }
+
+    thread.set_process_group(pgid);
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_getpgid(pid: usize) -> i64 {
+    return match thread_or_current(pid) {
+        Some(thread) => thread.process_group() as i64,
+        None => error(Errno::NoSuchProcess) as i64,
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn sys_killpg(pgid: usize) -> i32 {
+    let current = scheduler().current_thread();
+    let pgid = if pgid == 0 { current.process_group() } else { pgid };
+
+    // Like 'modifiable_thread()', user threads may only kill threads of their own process
+    let foreign = |thread: &ThreadRef| !thread.is_kernel_thread() && thread.process().pid() != current.process().pid();
+    if !current.is_kernel_thread() && scheduler().threads().iter().any(|thread| thread.process_group() == pgid && foreign(thread)) {
+        return error(Errno::OperationNotPermitted) as i32;
+    }
+
+    drop(current);
+    if scheduler().kill_group(pgid) == 0 {
+        return error(Errno::NoSuchProcess) as i32;
+    }
+
+    return 0;
+}
+
+/// There are no file descriptors yet, so the standard streams (0-2) refer to the kernel terminal.
+#[no_mangle]
+pub extern "C" fn sys_tcsetpgrp(fd: i32, pgid: usize) -> i32 {
+    if !(0..=2).contains(&fd) {
+        return error(Errno::BadFileDescriptor) as i32;
+    }
+    // User threads may only select a group, that contains a thread of their own process
+    let current = scheduler().current_thread();
+    let member = |thread: &ThreadRef| current.is_kernel_thread() || thread.process().pid() == current.process().pid();
+    if !scheduler().threads().iter().any(|thread| thread.process_group() == pgid && member(thread)) {
+        return error(Errno::OperationNotPermitted) as i32;
+    }
+
+    terminal().set_foreground_group(pgid);
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_setrlimit(resource: u32, limit: *const RLimit) -> i32 {
+    if resource as usize >= RLIM_NLIMITS {
+        return error(Errno::InvalidArgument) as i32;
+    }
+    let new_limit = match read_user(limit) {
+        Ok(limit) => limit,
+        Err(_) => return error(Errno::BadAddress) as i32
+    };
+
+    let thread = scheduler().current_thread();
+    if new_limit.cur > new_limit.max {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    // There are no privileged threads, so hard limits can only be lowered
+    if new_limit.max > thread.resource_limit(resource).max {
+        return error(Errno::OperationNotPermitted) as i32;
+    }
+
+    thread.set_resource_limit(resource, new_limit);
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_getrlimit(resource: u32, limit: *mut RLimit) -> i32 {
+    if resource as usize >= RLIM_NLIMITS {
+        return error(Errno::InvalidArgument) as i32;
+    }
+    if write_user(limit, &scheduler().current_thread().resource_limit(resource)).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    return 0;
+}
+
+/// `maxnode` is the number of bits in `nodemask`. Only node 0 exists, so all other bits must be cleared.
+#[no_mangle]
+pub extern "C" fn sys_set_mempolicy(mode: u32, nodemask: *const u64, maxnode: u64) -> i32 {
+    let nodemask = match mode {
+        MPOL_DEFAULT => 0,
+        MPOL_BIND | MPOL_INTERLEAVE => {
+            let word_count = maxnode.div_ceil(64) as usize;
+            if validate_user_read(nodemask as *const u8, word_count * size_of::<u64>()).is_err() {
+                return error(Errno::BadAddress) as i32;
+            }
+
+            let mut words = (0..word_count).map(|index| read_user(nodemask.wrapping_add(index)));
+            match words.next() {
+                Some(Ok(first)) if first & !ONLINE_NODE_MASK == 0 && first != 0 => {
+                    if !words.all(|word| matches!(word, Ok(0))) {
+                        return error(Errno::InvalidArgument) as i32;
+                    }
+
+                    first
+                },
+                Some(Err(_)) => return error(Errno::BadAddress) as i32,
+                _ => return error(Errno::InvalidArgument) as i32
+            }
+        },
+        _ => return error(Errno::InvalidArgument) as i32
+    };
+
+    scheduler().current_thread().set_mem_policy(MemPolicy { mode, nodemask });
+    return 0;
+}
+
+/// Without flags, the calling thread's policy is returned. There are no per-mapping policies,
+/// so with 'MPOL_F_ADDR' the thread's policy is returned as well ('addr' is not checked).
+/// 'MPOL_F_NODE' returns a node id in `mode`, which is always 0.
+#[no_mangle]
+pub extern "C" fn sys_get_mempolicy(mode: *mut u32, nodemask: *mut u64, maxnode: u64, _addr: *const u8, flags: u32) -> i32 {
+    if flags & !(MPOL_F_NODE | MPOL_F_ADDR | MPOL_F_MEMS_ALLOWED) != 0 || (flags & MPOL_F_MEMS_ALLOWED != 0 && flags != MPOL_F_MEMS_ALLOWED) {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    let policy = match flags {
+        MPOL_F_MEMS_ALLOWED => MemPolicy { mode: MPOL_DEFAULT, nodemask: ONLINE_NODE_MASK },
+        _ => scheduler().current_thread().mem_policy()
+    };
+
+    if !mode.is_null() {
+        let value = if flags & MPOL_F_NODE != 0 { 0 } else { policy.mode };
+        if write_user(mode, &value).is_err() {
+            return error(Errno::BadAddress) as i32;
+        }
+    }
+
+    if !nodemask.is_null() {
+        if maxnode == 0 {
+            return error(Errno::InvalidArgument) as i32;
+        }
+
+        for index in 0..maxnode.div_ceil(64) as usize {
+            let word = if index == 0 { policy.nodemask } else { 0 };
+            if write_user(nodemask.wrapping_add(index), &word).is_err() {
+                return error(Errno::BadAddress) as i32;
+            }
+        }
+    }
+
+    return 0;
+}
+
+/// Mappings without 'MAP_FIXED' are placed between these addresses (below the user stack).
+const MMAP_START: u64 = 0x100000000000;
+const MMAP_END: u64 = 0x400000000000;
+
+/// Map zeroed memory into the calling thread's address space and return its address.
+/// Without 'MAP_FIXED', `addr` is used if the range is free and otherwise the first free range in the mmap area.
+/// With 'MAP_FIXED', the range at `addr` must be free, since replacing existing mappings is not supported.
+#[no_mangle]
+pub extern "C" fn sys_mmap(addr: usize, length: usize, flags: u32) -> isize {
+    if flags & !(MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) != 0 || length == 0 || addr % PAGE_SIZE != 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+    let page_count = match length.checked_next_multiple_of(PAGE_SIZE) {
+        Some(length) => length / PAGE_SIZE,
+        None => return error(Errno::OutOfMemory) as isize
+    };
+
+    let thread = scheduler().current_thread();
+    let address_limit = thread.resource_limit(RLIMIT_AS).cur;
+    let mut address_space = thread.address_space().write();
+    if address_limit != RLIM_INFINITY && ((address_space.reserved_page_count() + page_count) * PAGE_SIZE) as u64 > address_limit {
+        return error(Errno::OutOfMemory) as isize;
+    }
+
+    let hint = user_page_range(addr, page_count * PAGE_SIZE).filter(|pages| pages.clone().all(|page| !address_space.is_mapped(page)));
+    let pages = match hint {
+        Some(pages) => pages,
+        None if flags & MAP_FIXED != 0 => return error(Errno::InvalidArgument) as isize,
+        None => match find_free_pages(&address_space, page_count) {
+            Some(pages) => pages,
+            None => return error(Errno::OutOfMemory) as isize
+        }
+    };
+
+    // Page frames are allocated and zeroed on the first access to each page (see 'AddressSpace::handle_lazy_fault()')
+    address_space.map_lazy(pages, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+    trace!(TRACE_SYSCALL, "Thread [{}]: mmap [{:?}]", thread.id(), pages);
+
+    return pages.start.start_address().as_u64() as isize;
+}
+
+/// Unmapping pages, that are not mapped, is not an error.
+#[no_mangle]
+pub extern "C" fn sys_munmap(addr: usize, length: usize) -> isize {
+    if addr % PAGE_SIZE != 0 || length == 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+    let pages = match length.checked_next_multiple_of(PAGE_SIZE).and_then(|length| user_page_range(addr, length)) {
+        Some(pages) => pages,
+        None => return error(Errno::InvalidArgument) as isize
+    };
+
+    let thread = scheduler().current_thread();
+    trace!(TRACE_SYSCALL, "Thread [{}]: munmap [{:?}]", thread.id(), pages);
+    thread.address_space().write().unmap(pages);
+    return 0;
+}
+
+/// Create a shared memory region of at least `size` bytes and return its handle (see 'shm::create()').
+/// Regions are zeroed and exist, until their last attachment is detached.
+#[no_mangle]
+pub extern "C" fn sys_shm_create(size: usize, key: u64) -> isize {
+    if size == 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+    let page_count = match size.checked_next_multiple_of(PAGE_SIZE) {
+        Some(size) => size / PAGE_SIZE,
+        None => return error(Errno::OutOfMemory) as isize
+    };
+
+    return match shm::create(page_count, key) {
+        Ok(handle) => handle as isize,
+        Err(errno) => error(errno) as isize
+    };
+}
+
+/// Map the shared memory region `handle` into the calling thread's address space and return its address.
+/// The region is mapped at `addr`, which must be page aligned and free, or, if `addr` is 0, at the first free range in the mmap area.
+#[no_mangle]
+pub extern "C" fn sys_shm_attach(handle: isize, addr: usize) -> isize {
+    if handle <= 0 || addr % PAGE_SIZE != 0 {
+        return error(Errno::InvalidArgument) as isize;
+    }
+
+    let thread = scheduler().current_thread();
+    let mut address_space = thread.address_space().write();
+    let result = shm::attach(handle as u64, &mut address_space, |address_space, page_count| {
+        if addr == 0 {
+            return find_free_pages(address_space, page_count);
+        }
+
+        return user_page_range(addr, page_count * PAGE_SIZE).filter(|pages| pages.clone().all(|page| !address_space.is_mapped(page)));
+    });
+
+    return match result {
+        Ok(pages) => {
+            trace!(TRACE_SYSCALL, "Thread [{}]: shm_attach [{}] at [{:?}]", thread.id(), handle, pages);
+            pages.start.start_address().as_u64() as isize
+        }
+        Err(errno) => error(errno) as isize
+    };
+}
+
+/// Unmap the shared memory region attached at `addr` (the address returned by 'sys_shm_attach').
+#[no_mangle]
+pub extern "C" fn sys_shm_detach(addr: usize) -> isize {
+    let start = match user_page_range(addr, PAGE_SIZE) {
+        Some(pages) => pages.start,
+        None => return error(Errno::InvalidArgument) as isize
+    };
+
+    let thread = scheduler().current_thread();
+    trace!(TRACE_SYSCALL, "Thread [{}]: shm_detach [{:?}]", thread.id(), start);
+    return match shm::detach(&mut thread.address_space().write(), start) {
+        Ok(()) => 0,
+        Err(errno) => error(errno) as isize
+    };
+}
+
+/// Pages from `addr` to `addr + length` (both page aligned), if they are in user space and above the kernel's identity mapping.
+fn user_page_range(addr: usize, length: usize) -> Option<PageRange> {
+    let end = addr.checked_add(length)? as u64;
+    if (addr as u64) < phys_limit().start_address().as_u64() || end > USER_SPACE_END {
+        return None;
+    }
+
+    let start = Page::from_start_address(VirtAddr::new(addr as u64)).ok()?;
+    return Some(PageRange { start, end: start + (length / PAGE_SIZE) as u64 });
+}
+
+fn find_free_pages(address_space: &AddressSpace, page_count: usize) -> Option<PageRange> {
+    let mut start = Page::containing_address(VirtAddr::new(MMAP_START));
+    let end = Page::containing_address(VirtAddr::new(MMAP_END));
+
+    while start + page_count as u64 <= end {
+        let pages = PageRange { start, end: start + page_count as u64 };
+        match pages.clone().filter(|page| address_space.is_mapped(*page)).last() {
+            Some(mapped_page) => start = mapped_page + 1,
+            None => return Some(pages)
+        }
+    }
+
+    return None;
+}
+
+/// Allocate a protection key for the calling thread and set its initial access rights in 'PKRU'.
+#[no_mangle]
+pub extern "C" fn sys_pkey_alloc(flags: u32, access_rights: u32) -> i32 {
+    if flags != 0 || access_rights & !(PKEY_DISABLE_ACCESS | PKEY_DISABLE_WRITE) != 0 {
+        return error(Errno::InvalidArgument) as i32;
+    }
+    if !pkey::pkeys_available() {
+        return error(Errno::NoSpace) as i32;
+    }
+
+    let pkey = match scheduler().current_thread().alloc_pkey() {
+        Some(pkey) => pkey,
+        None => return error(Errno::NoSpace) as i32
+    };
+
+    // 'PKRU' holds two bits (access disable, write disable) per key
+    let pkru = pkey::read_pkru() & !(0b11 << (pkey * 2));
+    pkey::write_pkru(pkru | (access_rights << (pkey * 2)));
+
+    return pkey as i32;
+}
+
+/// All pages in the range must be mapped. Pages of 'mmap()', that have not been accessed yet, are faulted in, so that the key is set in their page table entries.
+/// Pages without read access are not supported, and 'PROT_EXEC' is accepted but has no effect, since the kernel does not use the no-execute bit.
+#[no_mangle]
+pub extern "C" fn sys_pkey_mprotect(addr: usize, len: usize, prot: u32, pkey: i32) -> i32 {
+    if pkey < 0 || !scheduler().current_thread().is_pkey_allocated(pkey as usize) {
+        return error(Errno::InvalidArgument) as i32;
+    }
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 || prot & PROT_READ == 0 || addr % PAGE_SIZE != 0 {
+        return error(Errno::InvalidArgument) as i32;
+    }
+    let end = match addr.checked_add(len) {
+        Some(end) => end.next_multiple_of(PAGE_SIZE),
+        None => return error(Errno::InvalidArgument) as i32
+    };
+
+    // The kernel's identity mapping must keep key 0, since the kernel accesses it on behalf of all threads
+    if (addr as u64) < phys_limit().start_address().as_u64() || end as u64 > USER_SPACE_END {
+        return error(Errno::OutOfMemory) as i32;
+    }
+
+    // Check all pages first, so that the range is either changed completely or not at all
+    for page in (addr..end).step_by(PAGE_SIZE) {
+        match user_page_flags(VirtAddr::new(page as u64)) {
+            Some(flags) if flags.contains(PageTableFlags::USER_ACCESSIBLE) && !flags.contains(PageTableFlags::HUGE_PAGE) => {},
+            _ => return error(Errno::OutOfMemory) as i32
+        }
+    }
+
+    for page in (addr..end).step_by(PAGE_SIZE) {
+        protect_active_page(VirtAddr::new(page as u64), prot & PROT_WRITE != 0, pkey as u8);
+    }
+
+    return 0;
+}
+
+/// Key 0 is the default key of all pages and cannot be freed. Pages still tagged with a freed key keep it.
+#[no_mangle]
+pub extern "C" fn sys_pkey_free(pkey: i32) -> i32 {
+    let thread = scheduler().current_thread();
+    if pkey <= 0 || !thread.is_pkey_allocated(pkey as usize) {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    thread.free_pkey(pkey as usize);
+    return 0;
+}
+
+/// Copy the path registered under `cookie` into `buffer` (without null terminator) and return its length.
+#[no_mangle]
+pub extern "C" fn sys_lookup_dcookie(cookie: u64, buffer: *mut u8, length: usize) -> isize {
+    let entry = match dcookie::lookup(cookie) {
+        Some(entry) => entry,
+        None => return error(Errno::InvalidArgument) as isize
+    };
+
+    if entry.path.len() > length {
+        return error(Errno::ResultOutOfRange) as isize;
+    }
+    if copy_to_user(buffer, entry.path.as_bytes()).is_err() {
+        return error(Errno::BadAddress) as isize;
+    }
+
+    return entry.path.len() as isize;
+}
+
+#[no_mangle]
+pub extern "C" fn sys_sigaction(signum: u32, action: *const SigAction, old_action: *mut SigAction) -> i32 {
+    if signum == 0 || signum as usize >= NSIG {
+        return error(Errno::InvalidArgument) as i32;
+    }
+
+    let thread = scheduler().current_thread();
+    let mut signals = thread.signals().lock();
+    if !old_action.is_null() && write_user(old_action, &signals.action(signum)).is_err() {
+        return error(Errno::BadAddress) as i32;
+    }
+
+    if !action.is_null() {
+        let action = match read_user(action) {
+            Ok(action) => action,
+            Err(_) => return error(Errno::BadAddress) as i32
+        };
+        if action.flags & !(SA_NODEFER | SA_RESETHAND) != 0 || !signal::is_user_address(action.handler as u64) {
+            return error(Errno::InvalidArgument) as i32;
+        }
+
+        signals.set_action(signum, action);
+    }
+
+    return 0;
+}
+
+/// Return from a signal handler. Only returns (with an error), if no signal handler is running.
+#[no_mangle]
+pub extern "C" fn sys_sigreturn() -> i32 {
+    signal::sigreturn();
+    return error(Errno::InvalidArgument) as i32;
+}
+
+/// Only terminal requests are supported, with file descriptors 0-2 referring to the terminal.
+#[no_mangle]
+pub extern "C" fn sys_ioctl(fd: i32, request: u64, arg: usize) -> i32 {
+    if !(0..=2).contains(&fd) {
+        return error(Errno::BadFileDescriptor) as i32;
+    }
+    let result = match request {
+        TCGETS => write_user(arg as *mut Termios, &terminal().termios()),
+        TCSETS => read_user(arg as *const Termios).map(|termios| terminal().set_termios(termios)),
+        _ => return error(Errno::InappropriateIoctl) as i32
+    };
+
+    return match result {
+        Ok(()) => 0,
+        Err(_) => error(Errno::BadAddress) as i32
+    };
+}
+
+/// Returns the previous personality of the calling thread (unchanged, if `persona` is 'PER_QUERY').
+#[no_mangle]
+pub extern "C" fn sys_personality(persona: u32) -> i64 {
+    let thread = scheduler().current_thread();
+    if persona == PER_QUERY {
+        return thread.personality() as i64;
+    }
+
+    return thread.set_personality(persona) as i64;
+}
+
+/// Returns the previous file creation mask. Only the permission bits (0o777) of `mask` are used.
+#[no_mangle]
+pub extern "C" fn sys_umask(mask: u32) -> u32 {
+    return scheduler().current_thread().set_umask(mask as u16) as u32;
+}
+
+fn thread_or_current(tid: usize) -> Option<ThreadRef> {
+    return match tid {
+        0 => Some(scheduler().current_thread()),
+        _ => scheduler().find_thread(tid),
+    };
+}
+
+/// Like 'thread_or_current()', but fails with 'OperationNotPermitted', if the thread belongs to another process than the calling thread.
+/// Kernel threads may modify any thread.
+fn modifiable_thread(tid: usize) -> Result<ThreadRef, Errno> {
+    let thread = thread_or_current(tid).ok_or(Errno::NoSuchProcess)?;
+    let current = scheduler().current_thread();
+    if !current.is_kernel_thread() && thread.process().pid() != current.process().pid() {
+        return Err(Errno::OperationNotPermitted);
+    }
+
+    return Ok(thread);
+}
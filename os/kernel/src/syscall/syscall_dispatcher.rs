@@ -4,7 +4,7 @@ use x86_64::registers::model_specific::{LStar, Star};
 use x86_64::structures::gdt::SegmentSelector;
 use x86_64::{PrivilegeLevel, VirtAddr};
 use library_syscall::NUM_SYSCALLS;
-use crate::syscall::{sys_thread_exit, sys_thread_sleep, sys_thread_switch};
+use crate::syscall::{sys_getrandom, sys_getrusage, sys_sched_getaffinity, sys_sched_setaffinity, sys_sched_yield, sys_setpgid, sys_getpgid, sys_killpg, sys_tcsetpgrp, sys_setrlimit, sys_getrlimit, sys_set_mempolicy, sys_get_mempolicy, sys_lookup_dcookie, sys_sigaction, sys_sigreturn, sys_ioctl, sys_personality, sys_umask, sys_times, sys_gettimeofday, sys_sched_setscheduler, sys_sched_getscheduler, sys_pkey_alloc, sys_pkey_mprotect, sys_pkey_free, sys_set_priority, sys_mmap, sys_munmap, sys_thread_join, sys_get_errno, sys_thread_yield, sys_get_tid, sys_get_pid, sys_set_fs_base, sys_mem_info, sys_sleep_ns, sys_ktrace_enable, sys_list_modules, sys_get_module, sys_open, sys_read, sys_close, sys_write, sys_set_watchpoint, sys_clear_watchpoint, sys_get_time, sys_futex, sys_shm_create, sys_shm_attach, sys_shm_detach, sys_pipe, sys_read_mouse_event, sys_shutdown, sys_reboot, sys_uefi_get_variable, sys_uefi_set_variable, sys_request_ioport, sys_exec, sys_set_keyboard_layout, sys_thread_exit, sys_thread_sleep, sys_thread_switch};
 
 
 pub fn init() {
@@ -44,6 +44,66 @@ impl SyscallTable {
                 sys_thread_switch as *const _,
                 sys_thread_sleep as *const _,
                 sys_thread_exit as *const _,
+                sys_getrusage as *const _,
+                sys_getrandom as *const _,
+                sys_sched_getaffinity as *const _,
+                sys_sched_setaffinity as *const _,
+                sys_sched_yield as *const _,
+                sys_setpgid as *const _,
+                sys_getpgid as *const _,
+                sys_killpg as *const _,
+                sys_tcsetpgrp as *const _,
+                sys_setrlimit as *const _,
+                sys_getrlimit as *const _,
+                sys_set_mempolicy as *const _,
+                sys_get_mempolicy as *const _,
+                sys_lookup_dcookie as *const _,
+                sys_sigaction as *const _,
+                sys_sigreturn as *const _,
+                sys_ioctl as *const _,
+                sys_personality as *const _,
+                sys_umask as *const _,
+                sys_times as *const _,
+                sys_gettimeofday as *const _,
+                sys_sched_setscheduler as *const _,
+                sys_sched_getscheduler as *const _,
+                sys_pkey_alloc as *const _,
+                sys_pkey_mprotect as *const _,
+                sys_pkey_free as *const _,
+                sys_set_priority as *const _,
+                sys_mmap as *const _,
+                sys_munmap as *const _,
+                sys_thread_join as *const _,
+                sys_get_errno as *const _,
+                sys_thread_yield as *const _,
+                sys_get_tid as *const _,
+                sys_get_pid as *const _,
+                sys_set_fs_base as *const _,
+                sys_mem_info as *const _,
+                sys_sleep_ns as *const _,
+                sys_ktrace_enable as *const _,
+                sys_list_modules as *const _,
+                sys_get_module as *const _,
+                sys_open as *const _,
+                sys_read as *const _,
+                sys_close as *const _,
+                sys_write as *const _,
+                sys_set_watchpoint as *const _,
+                sys_clear_watchpoint as *const _,
+                sys_get_time as *const _,
+                sys_futex as *const _,
+                sys_shm_create as *const _,
+                sys_shm_attach as *const _,
+                sys_shm_detach as *const _,
+                sys_pipe as *const _,
+                sys_read_mouse_event as *const _,
+                sys_shutdown as *const _,
+                sys_reboot as *const _,
+                sys_uefi_get_variable as *const _,
+                sys_uefi_set_variable as *const _,
+                sys_request_ioport as *const _,
+                sys_exec as *const _,
+                sys_set_keyboard_layout as *const _,
             ],
         }
     }
@@ -56,13 +116,16 @@ unsafe impl Sync for SyscallTable {}
 #[no_mangle]
 // This functions does not take any parameters per its declaration,
 // but in reality, it takes at least the system call ID in rax
-// and may take additional parameters for the system call in rdi, rsi and rdx.
+// and may take additional parameters for the system call in rdi, rsi, rdx, r10, r8 and r9.
 unsafe extern "C" fn syscall_handler() {
     asm!(
     // We are now in ring 0, but still on the user stack
     // Disable interrupts until we have switched to kernel stack
     "cli",
 
+    // Switch to the kernel's GS base (per-CPU data, needed by 'tss_get_rsp0')
+    "swapgs",
+
     // Save registers (except rax, which is used for system call ID and return value)
     "push rbx",
     "push rcx", // Contains rip for returning to ring 3
@@ -92,6 +155,9 @@ unsafe extern "C" fn syscall_handler() {
     "mov rax, r15", // Restore system call ID
     "mov rsp, rbx", // Switch to kernel stack
     "push rcx", // Save user rsp on stack
+    "mov r8, [rcx + 56]", // Restore fifth parameter (r8 has been saved on the user stack)
+    "mov r9, [rcx + 48]", // Restore sixth parameter (r9 has been saved on the user stack)
+    "mov rcx, [rcx + 40]", // Fourth parameter is passed in r10, since rcx is overwritten by 'syscall'
     "sti",
 
     // Check if system call ID is in bounds
@@ -121,8 +187,9 @@ unsafe extern "C" fn syscall_handler() {
     "pop rcx", // Contains rip for returning to ring 3
     "pop rbx",
 
-    // Return to Ring 3
+    // Return to Ring 3 with the user's GS base
     // Interrupts will be enabled automatically, because eflags gets restored from r11
+    "swapgs",
     "sysretq",
     const NUM_SYSCALLS,
     options(noreturn)
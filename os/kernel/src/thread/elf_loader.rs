@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTableFlags};
+use x86_64::VirtAddr;
+use crate::memory::physical::phys_limit;
+use crate::memory::r#virtual::AddressSpace;
+use crate::memory::{physical, MemorySpace, PAGE_SIZE};
+use crate::syscall::copy_user::USER_SPACE_END;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+const ELF_TYPE_EXECUTABLE: u16 = 2;
+const ELF_MACHINE_X86_64: u16 = 0x3e;
+
+const HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const PT_LOAD: u32 = 1;
+
+// Segment flags ('PF_X' is ignored, since the kernel does not use the no-execute bit)
+#[allow(dead_code)]
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+#[allow(dead_code)]
+const PF_R: u32 = 0x4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ElfError {
+    /// The file is truncated, not a 64-bit little endian executable or contains invalid program headers
+    MalformedHeader,
+    /// The file has not been built for x86_64
+    UnsupportedArchitecture,
+    /// A segment lies in the identity mapped kernel space or beyond the end of user space
+    KernelSpaceOverlap,
+    /// Two segments share a page, or a segment overlaps an existing mapping (e.g. the user stack)
+    SegmentOverlap,
+}
+
+/// Loadable segments and entry point of a validated ELF file.
+pub struct ElfImage<'a> {
+    entry: VirtAddr,
+    segments: Vec<Segment<'a>>,
+}
+
+struct Segment<'a> {
+    pages: PageRange,
+    address: VirtAddr,
+    data: &'a [u8],
+    flags: PageTableFlags,
+}
+
+/// Parse and validate a statically linked ELF64 executable, without loading it.
+pub fn parse(bytes: &[u8]) -> Result<ElfImage, ElfError> {
+    if bytes.len() < HEADER_SIZE || bytes[0..4] != ELF_MAGIC || bytes[4] != ELF_CLASS_64 || bytes[5] != ELF_DATA_LITTLE_ENDIAN
+        || read_u16(bytes, 0x10) != ELF_TYPE_EXECUTABLE {
+        return Err(ElfError::MalformedHeader);
+    }
+    if read_u16(bytes, 0x12) != ELF_MACHINE_X86_64 {
+        return Err(ElfError::UnsupportedArchitecture);
+    }
+
+    let entry = VirtAddr::try_new(read_u64(bytes, 0x18)).map_err(|_| ElfError::MalformedHeader)?;
+    let header_offset = read_u64(bytes, 0x20) as usize;
+    let header_size = read_u16(bytes, 0x36) as usize;
+    let header_count = read_u16(bytes, 0x38) as usize;
+    if header_size < PROGRAM_HEADER_SIZE || header_offset.checked_add(header_size * header_count).map_or(true, |end| end > bytes.len()) {
+        return Err(ElfError::MalformedHeader);
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for index in 0..header_count {
+        let header = &bytes[header_offset + index * header_size..header_offset + (index + 1) * header_size];
+        if read_u32(header, 0x00) != PT_LOAD || read_u64(header, 0x28) == 0 {
+            continue;
+        }
+
+        let segment = parse_segment(bytes, header)?;
+        if segments.iter().any(|other| ranges_overlap(&segment.pages, &other.pages)) {
+            return Err(ElfError::SegmentOverlap);
+        }
+
+        segments.push(segment);
+    }
+
+    // The entry point must lie inside one of the loaded segments
+    if !segments.iter().any(|segment| segment.address <= entry && entry < segment.pages.end.start_address()) {
+        return Err(ElfError::MalformedHeader);
+    }
+
+    return Ok(ElfImage { entry, segments });
+}
+
+/// Load all 'PT_LOAD' segments of an ELF64 executable into `address_space` and return its entry point.
+/// Each segment gets its own zeroed page frames, which are owned by the address space. Nothing is mapped, if an error is returned.
+pub fn load_elf(bytes: &[u8], address_space: &mut AddressSpace) -> Result<VirtAddr, ElfError> {
+    let image = parse(bytes)?;
+    if image.segments.iter().any(|segment| segment.pages.into_iter().any(|page| address_space.is_mapped(page))) {
+        return Err(ElfError::SegmentOverlap);
+    }
+
+    for segment in image.segments.iter() {
+        // User frames are identity mapped in the kernel address space, so they can be filled before mapping them
+        let frames = physical::alloc(segment.pages.count(), MemorySpace::User);
+        let frame_start = frames.start.start_address().as_u64() as *mut u8;
+        let page_offset = (segment.address - segment.pages.start.start_address()) as usize;
+
+        unsafe {
+            frame_start.write_bytes(0, segment.pages.count() * PAGE_SIZE);
+            frame_start.add(page_offset).copy_from_nonoverlapping(segment.data.as_ptr(), segment.data.len());
+        }
+
+        address_space.map_user_frames(segment.pages, frames.start, segment.flags);
+    }
+
+    return Ok(image.entry);
+}
+
+impl ElfImage<'_> {
+    pub fn entry(&self) -> VirtAddr {
+        return self.entry;
+    }
+
+    /// Check if any segment shares a page with `pages`.
+    pub fn overlaps(&self, pages: PageRange) -> bool {
+        return self.segments.iter().any(|segment| ranges_overlap(&segment.pages, &pages));
+    }
+}
+
+fn parse_segment<'a>(bytes: &'a [u8], header: &[u8]) -> Result<Segment<'a>, ElfError> {
+    let flags = read_u32(header, 0x04);
+    let offset = read_u64(header, 0x08) as usize;
+    let address = read_u64(header, 0x10);
+    let file_size = read_u64(header, 0x20) as usize;
+    let memory_size = read_u64(header, 0x28);
+
+    if file_size as u64 > memory_size || offset.checked_add(file_size).map_or(true, |end| end > bytes.len()) {
+        return Err(ElfError::MalformedHeader);
+    }
+
+    // Everything below the physical memory limit is identity mapped for the kernel (see 'create_address_space()')
+    let end = address.checked_add(memory_size).ok_or(ElfError::KernelSpaceOverlap)?;
+    if address < phys_limit().start_address().as_u64() || end > USER_SPACE_END {
+        return Err(ElfError::KernelSpaceOverlap);
+    }
+
+    let start_page = Page::containing_address(VirtAddr::new(address));
+    let end_page = Page::containing_address(VirtAddr::new(end - 1)) + 1;
+
+    let mut page_flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+    if flags & PF_W != 0 {
+        page_flags |= PageTableFlags::WRITABLE;
+    }
+
+    return Ok(Segment { pages: PageRange { start: start_page, end: end_page }, address: VirtAddr::new(address), data: &bytes[offset..offset + file_size], flags: page_flags });
+}
+
+fn ranges_overlap(first: &PageRange, second: &PageRange) -> bool {
+    return first.start < second.end && second.start < first.end;
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    return u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+}
@@ -1,2 +1,5 @@
+pub mod elf_loader;
+pub mod process;
 pub mod scheduler;
+pub mod signal;
 pub mod thread;
@@ -0,0 +1,129 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+use spin::{Mutex, RwLock};
+use x86_64::instructions::interrupts;
+use crate::arch::iopb::IoPorts;
+use crate::sync::SpinlockYield;
+use crate::fs::FileTable;
+use crate::memory::r#virtual::{kernel_address_space, AddressSpace};
+
+/// The kernel process (containing all kernel threads) always has this id. User processes are numbered from 1.
+pub const KERNEL_PID: usize = 0;
+
+static PID_COUNTER: AtomicUsize = AtomicUsize::new(KERNEL_PID + 1);
+
+/// An address space together with the threads running in it and the resources they share (e.g. open files).
+/// Each thread keeps its own stacks and register state.
+pub struct Process {
+    pid: usize,
+    parent_pid: Option<usize>,
+    address_space: Arc<RwLock<AddressSpace>>,
+    threads: Mutex<Vec<usize>>,
+    open_files: SpinlockYield<FileTable>,
+    io_ports: Mutex<IoPorts>
+}
+
+/// All processes with at least one thread, indexed by their process id.
+pub struct ProcessTable {
+    processes: BTreeMap<usize, Arc<Process>>
+}
+
+impl Process {
+    fn new(pid: usize, parent_pid: Option<usize>, address_space: Arc<RwLock<AddressSpace>>) -> Self {
+        Self { pid, parent_pid, address_space, threads: Mutex::new(Vec::new()), open_files: SpinlockYield::new(FileTable::new()), io_ports: Mutex::new(IoPorts::new()) }
+    }
+
+    pub fn pid(&self) -> usize {
+        return self.pid;
+    }
+
+    /// Id of the process, whose thread has created this process ('None' for the kernel process).
+    pub fn parent_pid(&self) -> Option<usize> {
+        return self.parent_pid;
+    }
+
+    pub fn address_space(&self) -> &Arc<RwLock<AddressSpace>> {
+        return &self.address_space;
+    }
+
+    /// Ids of all threads of this process, that have not exited yet.
+    pub fn threads(&self) -> Vec<usize> {
+        return self.threads.lock().clone();
+    }
+
+    /// Open files, shared by all threads of this process.
+    /// The lock is only taken by system calls, so a thread waiting for it may give up the CPU to the thread holding it (see 'SpinlockYield').
+    pub fn files(&self) -> &SpinlockYield<FileTable> {
+        return &self.open_files;
+    }
+
+    /// I/O ports, that user mode threads of this process may access (loaded into the TSS on thread switches).
+    pub fn io_ports(&self) -> IoPorts {
+        return *self.io_ports.lock();
+    }
+
+    /// Grant access to `port` and return `false`, if the process already has 'MAX_PROCESS_PORTS' ports.
+    /// Interrupts are disabled, since 'Thread::switch()' reads the ports of the next thread's process.
+    pub fn allow_io_port(&self, port: u16) -> bool {
+        return interrupts::without_interrupts(|| self.io_ports.lock().insert(port));
+    }
+
+    pub fn add_thread(&self, thread_id: usize) {
+        self.threads.lock().push(thread_id);
+    }
+
+    /// Close all open files (e.g. to signal the end of a pipe, after the last thread has exited).
+    pub fn close_files(&self) {
+        let files = mem::replace(&mut *self.open_files.lock(), FileTable::new());
+        drop(files);
+    }
+
+    /// Remove an exited thread and return `true`, if it has been the last one.
+    pub fn remove_thread(&self, thread_id: usize) -> bool {
+        let mut threads = self.threads.lock();
+        threads.retain(|id| *id != thread_id);
+
+        return threads.is_empty();
+    }
+}
+
+impl ProcessTable {
+    pub const fn new() -> Self {
+        Self { processes: BTreeMap::new() }
+    }
+
+    /// Create and register a new process, running in `address_space`.
+    pub fn create(&mut self, parent_pid: Option<usize>, address_space: Arc<RwLock<AddressSpace>>) -> Arc<Process> {
+        let pid = PID_COUNTER.fetch_add(1, Relaxed);
+        let process = Arc::new(Process::new(pid, parent_pid, address_space));
+        self.processes.insert(pid, Arc::clone(&process));
+
+        return process;
+    }
+
+    /// Get the kernel process, which runs in the kernel address space. It is created on first use and never removed.
+    pub fn kernel_process(&mut self) -> Arc<Process> {
+        let process = self.processes.entry(KERNEL_PID)
+            .or_insert_with(|| Arc::new(Process::new(KERNEL_PID, None, kernel_address_space())));
+
+        return Arc::clone(process);
+    }
+
+    pub fn get(&self, pid: usize) -> Option<Arc<Process>> {
+        return self.processes.get(&pid).cloned();
+    }
+
+    /// Unregister the process `pid` (after its last thread has exited). The kernel process is never removed.
+    /// The process itself is freed, when the last reference to it is dropped.
+    pub fn remove(&mut self, pid: usize) -> Option<Arc<Process>> {
+        if pid == KERNEL_PID {
+            return None;
+        }
+
+        return self.processes.remove(&pid);
+    }
+}
@@ -1,24 +1,74 @@
-use crate::thread::thread::Thread;
-use alloc::collections::VecDeque;
+use crate::thread::thread::{Thread, ThreadRef};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::format;
-use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::{array, mem, ptr};
+use core::cmp::min;
+use core::ops::DerefMut;
 use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering::Relaxed;
 use smallmap::Map;
-use spin::Mutex;
-use crate::{apic, timer};
+use spin::{Mutex, MutexGuard};
+use x86_64::instructions::interrupts;
+use library_syscall::{Errno, PRIORITY_LEVELS, RLIMIT_CPU, RLIM_INFINITY, SCHED_RR};
+use crate::{apic, percpu, process_table, timer};
+use crate::smp::{online_cpus, MAX_CPUS};
+use crate::sync::SpinlockBackoff;
+
+/// Normal threads, that have been ready for more timer ticks, are picked before threads of higher priority (aging).
+const STARVATION_TICKS: usize = 100;
+
+/// Within each priority level, normal threads are scheduled with a multi-level feedback queue:
+/// Threads start on the high feedback level and drop to the next lower level each time they use up their quantum.
+pub const FEEDBACK_LEVELS: usize = 3;
+pub const FEEDBACK_HIGH: u8 = 0;
+pub const FEEDBACK_LOW: u8 = FEEDBACK_LEVELS as u8 - 1;
+
+/// Quantum (in timer ticks) per feedback level. Real-time threads ('SCHED_RR') always use the quantum of the high level.
+const QUANTUM_TICKS: [usize; FEEDBACK_LEVELS] = [10, 20, 40];
+
+/// All normal threads are moved back to the high feedback level in this interval (in timer ticks).
+const BOOST_TICKS: usize = 1000;
+
+/// Number of ready queues for normal threads (one per priority and feedback level).
+const QUEUE_COUNT: usize = PRIORITY_LEVELS * FEEDBACK_LEVELS;
 
 static THREAD_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+/// Timer ticks since boot (only counted by the bootstrap processor, so that all CPUs age their threads at the same rate).
+static TICKS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn next_thread_id() -> usize {
     THREAD_ID_COUNTER.fetch_add(1, Relaxed)
 }
 
+/// CPUs, that threads may be scheduled on (bit n represents CPU n, see 'smp::online_cpus()').
+pub fn online_cpu_mask() -> u64 {
+    return match online_cpus() {
+        MAX_CPUS => u64::MAX,
+        count => (1 << count) - 1
+    };
+}
+
+/// Id of the executing CPU (0, until the per-CPU data of the bootstrap processor has been set up during boot).
+fn current_cpu() -> usize {
+    return if percpu::is_initialized() { percpu::current_cpu_id() as usize } else { 0 };
+}
+
+/// Normal threads are kept in one queue per priority and feedback level, together with the tick at which they have been enqueued.
+/// The priority level takes precedence, so the feedback level only orders threads of the same priority (see `queue_index()`).
+/// Real-time threads ('SCHED_FIFO', 'SCHED_RR') are kept in their own queue, sorted by priority (highest priority at the back),
+/// and are always dequeued before normal threads. Each CPU has its own ready state, which is only dequeued by that CPU.
+/// The idle thread of the CPU is not queued and only runs, if no other thread is ready.
 struct ReadyState {
     initialized: bool,
-    current_thread: Option<Rc<Thread>>,
-    ready_queue: VecDeque<Rc<Thread>>,
+    current_thread: Option<ThreadRef>,
+    idle_thread: Option<ThreadRef>,
+    ready_queues: [VecDeque<(ThreadRef, usize)>; QUEUE_COUNT],
+    realtime_queue: VecDeque<ThreadRef>,
+    last_boost: usize,
 }
 
 impl ReadyState {
@@ -26,15 +76,221 @@ impl ReadyState {
         Self {
             initialized: false,
             current_thread: None,
-            ready_queue: VecDeque::new(),
+            idle_thread: None,
+            ready_queues: array::from_fn(|_| VecDeque::new()),
+            realtime_queue: VecDeque::new(),
+            last_boost: 0,
+        }
+    }
+
+    /// Enqueue a thread behind all ready threads of the same priority.
+    fn enqueue(&mut self, thread: ThreadRef) {
+        if self.is_idle(thread.as_ref()) {
+            return;
+        }
+
+        if thread.is_realtime() {
+            let priority = thread.sched_priority();
+            let index = self.realtime_queue.iter()
+                .position(|other| other.sched_priority() >= priority)
+                .unwrap_or(self.realtime_queue.len());
+            self.realtime_queue.insert(index, thread);
+        } else {
+            let index = queue_index(thread.as_ref());
+            self.ready_queues[index].push_front((thread, TICKS.load(Relaxed)));
+        }
+    }
+
+    fn dequeue(&mut self) -> Option<ThreadRef> {
+        if let Some(thread) = self.realtime_queue.pop_back() {
+            return Some(thread);
+        }
+
+        return match self.next_level() {
+            Some(level) => self.ready_queues[level].pop_back().map(|entry| entry.0),
+            None => self.idle_thread.clone()
+        };
+    }
+
+    fn is_idle(&self, thread: &Thread) -> bool {
+        return self.idle_thread.as_ref().is_some_and(|idle_thread| ptr::eq(idle_thread.as_ref(), thread));
+    }
+
+    /// Ready queue, from which the next normal thread is dequeued.
+    /// This is the highest non-empty queue, unless a lower queue has a starving thread (the longest waiting one is served first).
+    fn next_level(&self) -> Option<usize> {
+        let starving_level = self.ready_queues.iter().enumerate()
+            .filter_map(|(level, queue)| queue.back().map(|entry| (level, entry.1)))
+            .filter(|(_, enqueue_tick)| ReadyState::is_starving(*enqueue_tick))
+            .min_by_key(|(_, enqueue_tick)| *enqueue_tick)
+            .map(|(level, _)| level);
+
+        return starving_level.or_else(|| (0..QUEUE_COUNT).rev().find(|level| !self.ready_queues[*level].is_empty()));
+    }
+
+    fn is_starving(enqueue_tick: usize) -> bool {
+        return TICKS.load(Relaxed).wrapping_sub(enqueue_tick) > STARVATION_TICKS;
+    }
+
+    /// Remove a thread from its ready queue and return whether it has been queued.
+    fn remove(&mut self, thread_id: usize) -> bool {
+        let count = self.ready_count();
+        self.realtime_queue.retain(|thread| thread.id() != thread_id);
+        for queue in self.ready_queues.iter_mut() {
+            queue.retain(|entry| entry.0.id() != thread_id);
+        }
+
+        return self.ready_count() != count;
+    }
+
+    /// Move all ready normal threads back to the high feedback level of their priority (keeping their enqueue ticks for aging).
+    fn boost(&mut self) {
+        for index in 0..QUEUE_COUNT {
+            if index % FEEDBACK_LEVELS == FEEDBACK_LEVELS - 1 {
+                continue;
+            }
+
+            while let Some(entry) = self.ready_queues[index].pop_back() {
+                entry.0.set_feedback_level(FEEDBACK_HIGH);
+                let high_index = queue_index(entry.0.as_ref());
+                self.ready_queues[high_index].push_front(entry);
+            }
+        }
+    }
+
+    fn ready_count(&self) -> usize {
+        return self.realtime_queue.len() + self.ready_queues.iter().map(|queue| queue.len()).sum::<usize>();
+    }
+
+    fn ready_threads(&self) -> impl Iterator<Item = &ThreadRef> {
+        return self.realtime_queue.iter().chain(self.ready_queues.iter().flatten().map(|entry| &entry.0));
+    }
+
+    /// Check if the next ready thread may replace `current`.
+    /// A normal thread is replaced by a thread in a higher ready queue or by a starving thread.
+    /// A thread in the same queue only replaces it, if it gives up the CPU or its quantum has expired.
+    /// A real-time thread is only replaced by a thread of higher real-time priority (normal threads have real-time priority 0)
+    /// or of equal priority, if it gives up the CPU or uses 'SCHED_RR' and its quantum has expired.
+    fn next_runs_before(&self, current: &Thread, reason: SwitchReason) -> bool {
+        if self.is_idle(current) {
+            return self.ready_count() > 0;
         }
+
+        if !current.is_realtime() && self.realtime_queue.is_empty() {
+            return match self.next_level().and_then(|level| self.ready_queues[level].back()) {
+                Some((next, enqueue_tick)) => {
+                    let (next_index, current_index) = (queue_index(next.as_ref()), queue_index(current));
+                    next_index > current_index || (next_index == current_index && reason != SwitchReason::Tick) || ReadyState::is_starving(*enqueue_tick)
+                }
+                None => false
+            };
+        }
+
+        // Normal threads never replace a real-time thread
+        let next = match self.realtime_queue.back() {
+            Some(thread) => thread,
+            None => return false
+        };
+
+        let same_priority_runs = match reason {
+            SwitchReason::Yield => true,
+            SwitchReason::QuantumExpired => current.sched_policy() == SCHED_RR,
+            SwitchReason::Tick => false
+        };
+
+        return next.sched_priority() > current.sched_priority() || (next.sched_priority() == current.sched_priority() && same_priority_runs);
     }
 }
 
+/// Index of the ready queue for a normal thread (higher index = dequeued first).
+fn queue_index(thread: &Thread) -> usize {
+    return thread.priority() as usize * FEEDBACK_LEVELS + (FEEDBACK_LOW - thread.feedback_level()) as usize;
+}
+
+/// Sleeping threads, sorted by their wakeup time (system time in milliseconds, i.e. timer ticks).
+/// Inserting a thread takes O(log n) and waking up k threads takes O(k + log n), since only expired entries are visited.
+struct SleepQueue {
+    queue: BTreeMap<usize, Vec<ThreadRef>>
+}
+
+impl SleepQueue {
+    const fn new() -> Self {
+        Self { queue: BTreeMap::new() }
+    }
+
+    fn insert(&mut self, thread: ThreadRef, wakeup_time: usize) {
+        self.queue.entry(wakeup_time).or_insert_with(Vec::new).push(thread);
+    }
+
+    /// Remove the thread `thread_id` before its wakeup time and return it (`None`, if it is not sleeping).
+    fn remove(&mut self, thread_id: usize) -> Option<ThreadRef> {
+        let (wakeup_time, index) = self.queue.iter()
+            .find_map(|(wakeup_time, threads)| threads.iter().position(|thread| thread.id() == thread_id).map(|index| (*wakeup_time, index)))?;
+
+        let threads = self.queue.get_mut(&wakeup_time).unwrap();
+        let thread = threads.remove(index);
+        if threads.is_empty() {
+            self.queue.remove(&wakeup_time);
+        }
+
+        return Some(thread);
+    }
+
+    /// Remove all threads, whose wakeup time is not after `time`, and pass them to `wake`.
+    fn wake_expired(&mut self, time: usize, mut wake: impl FnMut(ThreadRef)) {
+        while let Some(entry) = self.queue.first_entry() {
+            if *entry.key() > time {
+                break;
+            }
+
+            entry.remove().into_iter().for_each(|thread| wake(thread));
+        }
+    }
+
+    /// Remove all threads, for which `predicate` returns true, and pass them to `wake`.
+    fn wake_matching(&mut self, predicate: impl Fn(&Thread) -> bool, mut wake: impl FnMut(ThreadRef)) {
+        self.queue.retain(|_, threads| {
+            threads.retain(|thread| {
+                if predicate(thread) {
+                    wake(Arc::clone(thread));
+                    return false;
+                }
+
+                return true;
+            });
+
+            return !threads.is_empty();
+        });
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &ThreadRef> {
+        return self.queue.values().flatten();
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum SwitchReason {
+    /// The current thread gives up the CPU voluntarily
+    Yield,
+    /// Timer tick, before the current thread's quantum has expired
+    Tick,
+    /// Timer tick, after which the current thread's quantum has expired
+    QuantumExpired
+}
+
+/// The ready queues are kept per CPU (see 'ReadyState'), while threads waiting for an event are kept in lists shared by all CPUs.
+/// Each thread is only enqueued on its own CPU (see 'Thread::cpu()'), which only changes, when its affinity mask no longer allows it (see 'set_affinity()').
 pub struct Scheduler {
-    state: Mutex<ReadyState>,
-    sleep_list: Mutex<Vec<(Rc<Thread>, usize)>>,
-    join_map: Mutex<Map<usize, Vec<Rc<Thread>>>>,
+    /// Ready state of each CPU (indexed by CPU id). Only 'threads()' holds more than one of them at a time.
+    states: Vec<SpinlockBackoff<ReadyState>>,
+    /// Threads, that have been woken up by other CPUs (indexed by CPU id), until their CPU moves them into its ready queues (see 'wake()').
+    /// No other lock is taken while holding one of these, so they may be taken while holding any other scheduler lock.
+    incoming: Vec<Mutex<Vec<ThreadRef>>>,
+    sleep_list: Mutex<SleepQueue>,
+    join_map: Mutex<Map<usize, Vec<ThreadRef>>>,
+    /// Threads waiting in 'futex_wait()', keyed by the physical address of the futex word.
+    /// Waiters with a timeout (second tuple element) are also in the sleep list, until they are woken up or time out.
+    futex_queues: Mutex<Map<u64, Vec<(ThreadRef, bool)>>>,
 }
 
 unsafe impl Send for Scheduler {}
@@ -43,105 +299,525 @@ unsafe impl Sync for Scheduler {}
 impl Scheduler {
     pub fn new() -> Self {
         Self {
-            state: Mutex::new(ReadyState::new()),
-            sleep_list: Mutex::new(Vec::new()),
+            states: (0..MAX_CPUS).map(|_| SpinlockBackoff::new(ReadyState::new())).collect(),
+            incoming: (0..MAX_CPUS).map(|_| Mutex::new(Vec::new())).collect(),
+            sleep_list: Mutex::new(SleepQueue::new()),
             join_map: Mutex::new(Map::new()),
+            futex_queues: Mutex::new(Map::new()),
         }
     }
 
+    /// Mark the scheduler of the executing CPU as started (called by the first thread on each CPU).
     pub fn set_init(&self) {
-        self.state.lock().initialized = true;
+        self.lock_state().initialized = true;
     }
 
-    pub fn current_thread(&self) -> Rc<Thread> {
-        let state = self.state.lock();
+    pub fn current_thread(&self) -> ThreadRef {
+        let state = self.lock_state();
         return Scheduler::current(&state);
     }
 
+    /// Like `current_thread()`, but returns `None` instead of waiting, if the scheduler state is locked (e.g. in an exception handler).
+    pub fn try_current_thread(&self) -> Option<ThreadRef> {
+        return self.try_lock_state()?.current_thread.as_ref().map(|thread| Arc::clone(thread));
+    }
+
+    /// Collect all threads known to the scheduler (running, ready, sleeping, waiting for a join or on a futex) on all CPUs.
+    /// Futex waiters with a timeout are also in the sleep list, so they are only collected once.
+    /// The idle threads of the CPUs are not included.
+    pub fn threads(&self) -> Vec<ThreadRef> {
+        // The ready states are locked in the order of the CPU ids
+        return interrupts::without_interrupts(|| {
+            let states: Vec<MutexGuard<ReadyState>> = self.states[..online_cpus()].iter().map(|state| state.lock()).collect();
+            let sleep_list = self.sleep_list.lock();
+            let futex_queues = self.futex_queues.lock();
+            let join_map = self.join_map.lock();
+            let incoming: Vec<MutexGuard<Vec<ThreadRef>>> = self.incoming[..online_cpus()].iter().map(|incoming| incoming.lock()).collect();
+
+            return states.iter().flat_map(|state| state.current_thread.iter().filter(|thread| !state.is_idle(thread)).chain(state.ready_threads()))
+                .chain(incoming.iter().flat_map(|incoming| incoming.iter()))
+                .chain(sleep_list.iter())
+                .chain(join_map.values().flatten())
+                .chain(futex_queues.values().flatten().filter(|entry| !entry.1).map(|entry| &entry.0))
+                .map(|thread| Arc::clone(thread))
+                .collect();
+        });
+    }
+
+    /// Call `f` for all threads known to the scheduler, like `threads()`, but without allocating memory and without waiting for locks
+    /// (e.g. while the system is halted by the GDB stub). Threads in lists, that are currently locked, are skipped.
+    pub fn for_each_thread(&self, mut f: impl FnMut(&Thread)) {
+        for state in self.states[..online_cpus()].iter() {
+            if let Some(state) = state.try_lock() {
+                state.current_thread.iter().filter(|thread| !state.is_idle(thread)).chain(state.ready_threads()).for_each(|thread| f(thread));
+            }
+        }
+        for incoming in self.incoming[..online_cpus()].iter() {
+            if let Some(incoming) = incoming.try_lock() {
+                incoming.iter().for_each(|thread| f(thread));
+            }
+        }
+        if let Some(sleep_list) = self.sleep_list.try_lock() {
+            sleep_list.iter().for_each(|thread| f(thread));
+        }
+        if let Some(join_map) = self.join_map.try_lock() {
+            join_map.values().flatten().for_each(|thread| f(thread));
+        }
+        if let Some(futex_queues) = self.futex_queues.try_lock() {
+            futex_queues.values().flatten().filter(|entry| !entry.1).for_each(|entry| f(&entry.0));
+        }
+    }
+
+    pub fn find_thread(&self, thread_id: usize) -> Option<ThreadRef> {
+        return self.threads().into_iter().find(|thread| thread.id() == thread_id);
+    }
+
+    /// Kill all user threads in the process group `pgid` and return how many threads have been killed.
+    /// Kernel threads are never killed, since they may hold kernel locks at any time.
+    /// Sleeping threads and threads waiting on a futex are woken up, so that they can terminate without waiting for their timeout.
+    pub fn kill_group(&self, pgid: usize) -> usize {
+        let threads: Vec<ThreadRef> = self.threads().into_iter()
+            .filter(|thread| thread.process_group() == pgid && !thread.is_kernel_thread())
+            .collect();
+
+        let mut state = self.lock_state();
+        let mut sleep_list = self.sleep_list.lock();
+        let mut futex_queues = self.futex_queues.lock();
+        for thread in threads.iter() {
+            thread.kill();
+        }
+
+        sleep_list.wake_matching(|thread| thread.is_killed(), |thread| self.wake(&mut state, thread));
+
+        // Waiters with a timeout have already been woken up via the sleep list and remove themselves from their futex queue
+        for waiters in futex_queues.values_mut() {
+            waiters.retain(|entry| {
+                if entry.0.is_killed() && !entry.1 {
+                    self.wake(&mut state, Arc::clone(&entry.0));
+                    return false;
+                }
+
+                return true;
+            });
+        }
+
+        return threads.len();
+    }
+
+    /// Terminate the current thread, if it has been killed.
+    /// Must only be called from an interrupt handler (after EOI), when a thread has been interrupted in user mode.
+    pub fn exit_if_killed(&self) {
+        if self.current_thread().is_killed() {
+            self.exit();
+        }
+    }
+
+    /// Start scheduling on the executing CPU (called once by each CPU, after its local APIC has been enabled).
+    /// The CPU gets its own idle thread, which only runs, if no other thread is ready (see 'ReadyState').
     pub fn start(&self) {
+        let cpu = current_cpu();
+        let idle_thread = Thread::new_kernel_thread(Box::new(|| {
+            loop {
+                interrupts::enable_and_hlt();
+            }
+        }), None);
+        idle_thread.set_affinity_mask(1 << cpu);
+        idle_thread.set_cpu(cpu as u32);
+
         let thread;
 
         {
-            let mut state = self.state.lock();
+            let mut state = self.lock_state();
+            state.idle_thread = Some(idle_thread);
+            self.take_incoming(&mut state);
+
             thread = state
-                .ready_queue
-                .pop_back()
+                .dequeue()
                 .expect("Scheduler: Failed to dequeue first thread!");
-            state.current_thread = Some(Rc::clone(&thread));
+            state.current_thread = Some(Arc::clone(&thread));
         }
 
         Thread::start_first(thread.as_ref());
     }
 
-    pub fn ready(&self, thread: Rc<Thread>) {
+    /// Make a new thread ready. It is scheduled on the executing CPU, if its affinity mask allows it, or on the first allowed CPU otherwise.
+    pub fn ready(&self, thread: ThreadRef) {
         let id = thread.id();
-        let mut state = self.state.lock();
+        trace!(TRACE_SCHEDULER, "Thread [{}] ready", id);
+        let mut state = self.lock_state();
         let mut join_map = self.join_map.lock();
 
-        state.ready_queue.push_front(thread);
+        let cpu = current_cpu();
+        let mask = thread.affinity_mask();
+        thread.set_cpu(if mask & (1 << cpu) != 0 { cpu as u32 } else { mask.trailing_zeros() });
+
+        self.wake(&mut state, thread);
         join_map.insert(id, Vec::new());
     }
 
+    /// Enqueue a ready thread (that is not queued yet) on the given feedback level with a new quantum.
+    pub fn enqueue(&self, thread: ThreadRef, level: u8) {
+        thread.set_feedback_level(min(level, FEEDBACK_LOW));
+        let mut state = self.lock_state();
+        self.wake(&mut state, thread);
+    }
+
+    /// Change the scheduling policy and priority of a thread and move it into the matching ready queue.
+    /// Permission checks are left to the caller.
+    pub fn set_sched_policy(&self, thread: &ThreadRef, policy: i32, priority: i32) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.lock_home_state(thread);
+            let queued = state.remove(thread.id());
+
+            thread.set_sched_policy(policy, priority);
+            if queued {
+                state.enqueue(Arc::clone(thread));
+            }
+        });
+    }
+
+    /// Change the priority level of a normal thread and move it into the matching ready queue.
+    pub fn set_priority(&self, thread: &ThreadRef, priority: u8) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.lock_home_state(thread);
+            let queued = state.remove(thread.id());
+
+            thread.set_priority(priority);
+            if queued {
+                state.enqueue(Arc::clone(thread));
+            }
+        });
+    }
+
+    /// Change the set of CPUs, that a thread may run on (bit n represents CPU n). `mask` must contain at least one online CPU.
+    /// If the thread's CPU is no longer allowed, it is moved to the first allowed CPU. A running thread moves,
+    /// when it is preempted or blocks (see 'wake()'). Permission checks are left to the caller.
+    pub fn set_affinity(&self, thread: &ThreadRef, mask: u64) {
+        interrupts::without_interrupts(|| {
+            let mut state = self.lock_home_state(thread);
+            thread.set_affinity_mask(mask);
+            if mask & (1 << thread.cpu()) != 0 {
+                return;
+            }
+
+            let target = mask.trailing_zeros();
+            thread.set_cpu(target);
+            if state.remove(thread.id()) {
+                self.incoming[target as usize].lock().push(Arc::clone(thread));
+            }
+        });
+    }
+
     pub fn sleep(&self, ms: usize) {
+        self.sleep_until(timer().read().systime_ms() + ms);
+    }
+
+    /// Block the current thread until the system time (in milliseconds, i.e. timer ticks) reaches `wakeup_time`.
+    /// The thread is woken up by the timer interrupt (see 'check_sleep_list()'), without busy waiting.
+    pub fn sleep_until(&self, wakeup_time: usize) {
         {
-            let wakeup_time = timer().read().systime_ms() + ms;
-            let state = self.state.lock();
+            let state = self.lock_state();
             let mut sleep_list = self.sleep_list.lock();
 
             let thread = Scheduler::current(&state);
-            sleep_list.push((thread, wakeup_time));
+            sleep_list.insert(thread, wakeup_time);
         }
 
         self.block();
     }
 
+    /// Sleep for `ns` nanoseconds, woken up by the timer's one-shot wakeup interrupt.
+    /// The thread is also put into the sleep list with a deadline one tick later, so that it still wakes up,
+    /// if the wakeup interrupt could not unblock it. Without a one-shot timer, this falls back to `sleep()`.
+    pub fn sleep_ns(&self, ns: u64) {
+        let ms = ns.div_ceil(1000000) as usize;
+        let now = match timer().read().precise_time_ns() {
+            Some(now) => now,
+            None => {
+                self.sleep(ms);
+                return;
+            }
+        };
+
+        let thread_id;
+        {
+            let wakeup_time = timer().read().systime_ms() + ms + 1;
+            let state = self.lock_state();
+            let mut sleep_list = self.sleep_list.lock();
+
+            let thread = Scheduler::current(&state);
+            thread_id = thread.id();
+            sleep_list.insert(thread, wakeup_time);
+        }
+
+        if !timer().read().schedule_wakeup(now.saturating_add(ns), thread_id) {
+            // Deadline has already passed
+            self.sleep_list.lock().remove(thread_id);
+            return;
+        }
+
+        self.block();
+    }
+
+    /// Wake up a thread from the sleep list before its wakeup time.
+    /// Called from interrupt context, so nothing happens if the scheduler is locked (the thread then wakes up at its wakeup time).
+    pub fn unblock(&self, thread_id: usize) {
+        if let Some(mut state) = self.try_lock_state() {
+            if let Some(mut sleep_list) = self.sleep_list.try_lock() {
+                if let Some(thread) = sleep_list.remove(thread_id) {
+                    self.wake(&mut state, thread);
+                }
+            }
+        }
+    }
+
+    /// Block the current thread on the futex with the physical address `key`, if `value_matches()` returns true.
+    /// The value is checked while the scheduler is locked, so that a concurrent 'futex_wake()' cannot be missed.
+    /// With a timeout (in nanoseconds), the thread is woken up like in 'sleep_ns()', if no other thread wakes it up before.
+    pub fn futex_wait(&self, key: u64, timeout_ns: Option<u64>, value_matches: impl FnOnce() -> bool) -> Result<(), Errno> {
+        let (now, wakeup_time) = match timeout_ns {
+            Some(ns) => {
+                let timer = timer().read();
+                let now = timer.precise_time_ns();
+                // Without a one-shot timer, the sleep list is the only wakeup source, so no extra tick is needed
+                (now, timer.systime_ms() + ns.div_ceil(1000000) as usize + now.map_or(0, |_| 1))
+            }
+            None => (None, 0)
+        };
+
+        let thread_id;
+        {
+            let state = self.lock_state();
+            let mut sleep_list = self.sleep_list.lock();
+            let mut futex_queues = self.futex_queues.lock();
+
+            if !value_matches() {
+                return Err(Errno::TryAgain);
+            }
+            if timeout_ns == Some(0) {
+                return Err(Errno::TimedOut);
+            }
+
+            let thread = Scheduler::current(&state);
+            thread_id = thread.id();
+            if timeout_ns.is_some() {
+                sleep_list.insert(Arc::clone(&thread), wakeup_time);
+            }
+
+            match futex_queues.get_mut(&key) {
+                Some(waiters) => waiters.push((thread, timeout_ns.is_some())),
+                None => {
+                    futex_queues.insert(key, vec![(thread, timeout_ns.is_some())]);
+                }
+            }
+        }
+
+        if let (Some(ns), Some(now)) = (timeout_ns, now) {
+            // If the deadline has already passed, the thread wakes up via the sleep list
+            timer().read().schedule_wakeup(now.saturating_add(ns), thread_id);
+        }
+
+        self.block();
+
+        // A thread, that has been woken up by 'futex_wake()', has been removed from the queue
+        let timed_out = {
+            let mut futex_queues = self.futex_queues.lock();
+            let waiters = futex_queues.get_mut(&key);
+            let index = waiters.as_ref().and_then(|waiters| waiters.iter().position(|entry| entry.0.id() == thread_id));
+
+            match (waiters, index) {
+                (Some(waiters), Some(index)) => {
+                    waiters.remove(index);
+                    if waiters.is_empty() {
+                        futex_queues.remove(&key);
+                    }
+
+                    true
+                }
+                _ => false
+            }
+        };
+
+        if timeout_ns.is_some() {
+            timer().read().cancel_wakeup(thread_id);
+        }
+
+        return if timed_out { Err(Errno::TimedOut) } else { Ok(()) };
+    }
+
+    /// Wake up to `count` threads waiting on the futex with the physical address `key` (in the order they started waiting)
+    /// and return how many threads have been woken up. Waiters, that have already timed out, are skipped.
+    pub fn futex_wake(&self, key: u64, count: usize) -> usize {
+        let mut state = self.lock_state();
+        let mut sleep_list = self.sleep_list.lock();
+        let mut futex_queues = self.futex_queues.lock();
+
+        let waiters = match futex_queues.get_mut(&key) {
+            Some(waiters) => waiters,
+            None => return 0
+        };
+
+        let mut woken = 0;
+        waiters.retain(|entry| {
+            if woken >= count {
+                return true;
+            }
+
+            if entry.1 {
+                // Waiters with a timeout, that are no longer in the sleep list, are already running again
+                if sleep_list.remove(entry.0.id()).is_none() {
+                    return true;
+                }
+            }
+
+            self.wake(&mut state, Arc::clone(&entry.0));
+            woken += 1;
+            return false;
+        });
+
+        if waiters.is_empty() {
+            futex_queues.remove(&key);
+        }
+
+        return woken;
+    }
+
+    /// Called by the timer interrupt on every tick.
+    /// Charges the tick to the current thread's quantum (demoting a normal thread to the next lower feedback level, if it has expired),
+    /// boosts all normal threads periodically and switches to the next thread, if it may replace the current one.
+    pub fn tick(&self) {
+        let reason;
+
+        {
+            let mut state = match self.try_lock_state() {
+                Some(state) if state.initialized => state,
+                _ => return
+            };
+
+            let ticks = TICKS.load(Relaxed);
+            if ticks.wrapping_sub(state.last_boost) >= BOOST_TICKS {
+                state.last_boost = ticks;
+                self.boost(&mut state);
+            }
+
+            let current = Scheduler::current(&state);
+            let level = if current.is_realtime() { FEEDBACK_HIGH } else { current.feedback_level() };
+            if current.use_quantum_tick() >= QUANTUM_TICKS[level as usize] {
+                current.set_feedback_level(if current.is_realtime() { level } else { min(level + 1, FEEDBACK_LOW) });
+                reason = SwitchReason::QuantumExpired;
+            } else {
+                reason = SwitchReason::Tick;
+            }
+        }
+
+        self.preempt(reason);
+    }
+
+    /// Switch to the next thread like after an expired quantum.
     pub fn switch_thread(&self) {
+        self.preempt(SwitchReason::QuantumExpired);
+    }
+
+    fn preempt(&self, reason: SwitchReason) {
         let current;
         let next;
 
-        if let Some(mut state) = self.state.try_lock() {
+        if let Some(mut state) = self.try_lock_state() {
             if !state.initialized {
                 return;
             }
 
+            self.take_incoming(&mut state);
             if let Some(mut sleep_list) = self.sleep_list.try_lock() {
-                Scheduler::check_sleep_list(&mut state, &mut sleep_list);
+                self.check_sleep_list(&mut state, &mut sleep_list);
             }
 
-            next = match state.ready_queue.pop_back() {
-                Some(thread) => thread,
-                None => return,
-            };
-
             current = Scheduler::current(&state);
-            state.current_thread = Some(Rc::clone(&next));
+            if !state.next_runs_before(current.as_ref(), reason) {
+                return;
+            }
 
-            state.ready_queue.push_front(Rc::clone(&current));
+            next = state.dequeue().unwrap();
+            state.current_thread = Some(Arc::clone(&next));
+
+            // The current thread may have been moved to another CPU while running (see 'set_affinity()')
+            self.wake(&mut state, Arc::clone(&current));
         } else {
             return;
         }
 
+        current.resource_usage().involuntary_switch();
+
         apic().end_of_interrupt();
         Thread::switch(current.as_ref(), next.as_ref());
     }
 
+    /// Give up the CPU, but only if another thread with at least the same priority is ready to run.
+    /// Otherwise the calling thread just continues.
+    pub fn yield_cpu(&self) {
+        self.yield_locked(self.lock_state());
+    }
+
+    /// Like 'yield_cpu()', but returns immediately instead of waiting, if the scheduler state is locked or interrupts are disabled.
+    /// Called by 'SpinlockYield::lock()' while waiting for a lock, which may be held by a thread on the same CPU.
+    pub fn yield_current(&self) {
+        if !interrupts::are_enabled() {
+            return;
+        }
+
+        if let Some(state) = self.try_lock_state() {
+            if state.initialized {
+                self.yield_locked(state);
+            }
+        }
+    }
+
+    /// Contention counter of the ready state locks of all CPUs (see 'SpinlockBackoff::contentions()').
+    pub fn state_contentions(&self) -> u64 {
+        return self.states.iter().map(|state| state.contentions()).sum();
+    }
+
+    fn yield_locked(&self, mut state: MutexGuard<ReadyState>) {
+        self.take_incoming(&mut state);
+        if let Some(mut sleep_list) = self.sleep_list.try_lock() {
+            self.check_sleep_list(&mut state, &mut sleep_list);
+        }
+
+        let current = Scheduler::current(&state);
+        if !state.next_runs_before(current.as_ref(), SwitchReason::Yield) {
+            return;
+        }
+
+        let next = state.dequeue().unwrap();
+        state.current_thread = Some(Arc::clone(&next));
+        self.wake(&mut state, Arc::clone(&current));
+        drop(state);
+
+        current.resource_usage().voluntary_switch();
+
+        Thread::switch(current.as_ref(), next.as_ref());
+    }
+
     pub fn block(&self) {
         let current;
         let next;
 
         {
-            let mut state = self.state.lock();
+            let mut state = self.lock_state();
             let mut sleep_list = self.sleep_list.lock();
-            let mut next_thread = state.ready_queue.pop_back();
+            self.take_incoming(&mut state);
+            let mut next_thread = state.dequeue();
 
+            // Only possible before the idle thread of this CPU has been created (see 'start()')
             while next_thread.is_none() {
-                Scheduler::check_sleep_list(&mut state, &mut sleep_list);
-                next_thread = state.ready_queue.pop_back();
+                self.check_sleep_list(&mut state, &mut sleep_list);
+                self.take_incoming(&mut state);
+                next_thread = state.dequeue();
             }
 
             current = Scheduler::current(&state);
             next = next_thread.unwrap();
-            state.current_thread = Some(Rc::clone(&next));
+            state.current_thread = Some(Arc::clone(&next));
 
             // Thread has enqueued itself into sleep list and waited so long, that it dequeued itself in the meantime
             if current.id() == next.id() {
@@ -149,39 +825,61 @@ impl Scheduler {
             }
         }
 
+        current.resource_usage().voluntary_switch();
+
         Thread::switch(current.as_ref(), next.as_ref());
     }
 
     pub fn join(&self, thread_id: usize) {
+        if !self.try_join(thread_id) {
+            panic!("Scheduler: Missing join_map entry for thread id {}!", thread_id);
+        }
+    }
+
+    /// Block the current thread until thread `thread_id` has exited.
+    /// Returns `false` without blocking, if there is no such thread (e.g. because it has already exited).
+    pub fn try_join(&self, thread_id: usize) -> bool {
         {
-            let state = self.state.lock();
+            let state = self.lock_state();
             let mut join_map = self.join_map.lock();
 
             let thread = Scheduler::current(&state);
-            let join_list = join_map.get_mut(&thread_id).expect(
-                format!(
-                    "Scheduler: Missing join_map entry for thread id {}!",
-                    thread.id()
-                )
-                .as_str(),
-            );
-
-            join_list.push(thread);
+            match join_map.get_mut(&thread_id) {
+                Some(join_list) => join_list.push(thread),
+                None => return false
+            }
         }
 
         self.block();
+        return true;
     }
 
     pub fn exit(&self) {
+        let thread = self.current_thread();
+        trace!(TRACE_SCHEDULER, "Thread [{}] exiting", thread.id());
+
+        // The process ends with its last thread (this is done before locking the scheduler, since closing files may wake up other threads)
+        if thread.process().remove_thread(thread.id()) {
+            let process = process_table().write().remove(thread.process().pid());
+            if let Some(process) = process {
+                process.close_files();
+            }
+        }
+        drop(thread);
+
         {
-            let mut state = self.state.lock();
+            let mut state = self.lock_state();
             let mut join_map = self.join_map.lock();
 
             let thread = Scheduler::current(&state);
             let join_list = join_map.get_mut(&thread.id()).expect(format!("Scheduler: Missing join_map entry for thread id {}!", thread.id()).as_str());
 
-            for thread in join_list {
-                state.ready_queue.push_front(Rc::clone(thread));
+            // Joining threads are charged with the resources used by the exiting thread (like waiting for a child process)
+            thread.resource_usage().update_max_rss(thread.resident_set_kib());
+            for joining_thread in join_list {
+                joining_thread.children_resource_usage().add(thread.resource_usage());
+                joining_thread.children_resource_usage().add(thread.children_resource_usage());
+                self.wake(&mut state, Arc::clone(joining_thread));
             }
 
             join_map.remove(&thread.id());
@@ -190,22 +888,108 @@ impl Scheduler {
         self.block();
     }
 
-    fn current(state: &ReadyState) -> Rc<Thread> {
-        return Rc::clone(state.current_thread.as_ref().expect("Scheduler: Trying to access current thread before initialization!"));
+    /// Charge a timer tick to the currently running thread and kill it, if it has exceeded its CPU time limit.
+    /// Called from interrupt context, so the tick is dropped if the scheduler state is locked.
+    pub fn account_tick(&self, user_mode: bool) {
+        if current_cpu() == 0 {
+            TICKS.fetch_add(1, Relaxed);
+        }
+
+        if let Some(state) = self.try_lock_state() {
+            if let Some(thread) = state.current_thread.as_ref() {
+                thread.resource_usage().tick(user_mode);
+
+                let cpu_limit = thread.try_resource_limit(RLIMIT_CPU).map_or(RLIM_INFINITY, |limit| limit.cur);
+                if cpu_limit != RLIM_INFINITY {
+                    if let Some(timer) = timer().try_read() {
+                        if thread.resource_usage().cpu_time_ns(timer.interval_ns()) >= cpu_limit.saturating_mul(1000000000) {
+                            thread.kill();
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    fn check_sleep_list(state: &mut ReadyState, sleep_list: &mut Vec<(Rc<Thread>, usize)>) {
+    /// Move all normal threads back to the high feedback level (ready, running and, if their lists are not locked, blocked threads).
+    fn boost(&self, state: &mut ReadyState) {
+        state.boost();
+        if let Some(thread) = state.current_thread.as_ref() {
+            if !thread.is_realtime() {
+                thread.set_feedback_level(FEEDBACK_HIGH);
+            }
+        }
+
+        if let Some(sleep_list) = self.sleep_list.try_lock() {
+            sleep_list.iter().for_each(|thread| thread.set_feedback_level(FEEDBACK_HIGH));
+        }
+        if let Some(join_map) = self.join_map.try_lock() {
+            join_map.values().flatten().for_each(|thread| thread.set_feedback_level(FEEDBACK_HIGH));
+        }
+        if let Some(futex_queues) = self.futex_queues.try_lock() {
+            futex_queues.values().flatten().for_each(|entry| entry.0.set_feedback_level(FEEDBACK_HIGH));
+        }
+    }
+
+    fn current(state: &ReadyState) -> ThreadRef {
+        return Arc::clone(state.current_thread.as_ref().expect("Scheduler: Trying to access current thread before initialization!"));
+    }
+
+    /// Wake up all threads, whose wakeup time has been reached (called on every timer tick via 'preempt()').
+    fn check_sleep_list(&self, state: &mut ReadyState, sleep_list: &mut SleepQueue) {
         if let Some(timer) = timer().try_read() {
-            let time = timer.systime_ms();
+            sleep_list.wake_expired(timer.systime_ms(), |thread| self.wake(state, thread));
+        }
+    }
 
-            sleep_list.retain(|entry| {
-                if time >= entry.1 {
-                    state.ready_queue.push_front(Rc::clone(&entry.0));
-                    return false;
-                }
+    /// Lock the ready state of the executing CPU. The calling thread cannot be moved to another CPU, while it holds the lock,
+    /// since the timer interrupt does not preempt it then (see 'preempt()'). It may only have been moved before, so the CPU is checked again.
+    fn lock_state(&self) -> MutexGuard<ReadyState> {
+        loop {
+            let cpu = current_cpu();
+            let state = self.states[cpu].lock();
+            if current_cpu() == cpu {
+                return state;
+            }
+        }
+    }
 
-                return true;
-            });
+    /// Like 'lock_state()', but returns `None` instead of waiting, if the ready state of the executing CPU is locked.
+    fn try_lock_state(&self) -> Option<MutexGuard<ReadyState>> {
+        let cpu = current_cpu();
+        let state = self.states[cpu].try_lock()?;
+        return if current_cpu() == cpu { Some(state) } else { None };
+    }
+
+    /// Lock the ready state of the CPU, that `thread` is scheduled on (possibly another CPU).
+    /// Must be called with interrupts disabled, so that the executing CPU does not switch threads, while holding the state of another CPU.
+    fn lock_home_state(&self, thread: &Thread) -> MutexGuard<ReadyState> {
+        loop {
+            let cpu = thread.cpu() as usize;
+            let state = self.states[cpu].lock();
+            if thread.cpu() as usize == cpu {
+                return state;
+            }
+        }
+    }
+
+    /// Make a woken up thread ready on its CPU (see 'Thread::cpu()'). Threads of other CPUs are handed over via their incoming list,
+    /// since a CPU only locks its own ready state (see 'take_incoming()'). `state` must be the ready state of the executing CPU.
+    fn wake(&self, state: &mut ReadyState, thread: ThreadRef) {
+        let cpu = thread.cpu() as usize;
+        if cpu == current_cpu() {
+            state.enqueue(thread);
+        } else {
+            self.incoming[cpu].lock().push(thread);
+        }
+    }
+
+    /// Move the threads, that other CPUs have woken up for the executing CPU, into its ready queues.
+    /// Threads, that have been moved to another CPU in the meantime (see 'set_affinity()'), are passed on.
+    fn take_incoming(&self, state: &mut ReadyState) {
+        let threads = mem::take(self.incoming[current_cpu()].lock().deref_mut());
+        for thread in threads {
+            self.wake(state, thread);
         }
     }
 }
@@ -0,0 +1,123 @@
+use alloc::vec::Vec;
+use core::mem::size_of;
+use x86_64::structures::gdt::SegmentSelector;
+use x86_64::PrivilegeLevel::Ring3;
+use log::warn;
+use library_syscall::{SigAction, SignalFrame, NSIG, SA_NODEFER, SA_RESETHAND, SIG_DFL};
+use crate::arch::exception::{restore_state, ExceptionState};
+use crate::scheduler;
+use crate::syscall::copy_user::{read_user, write_user, USER_SPACE_END};
+
+// Area below the user stack pointer, which may be used by leaf functions and must not be overwritten (System V ABI)
+const RED_ZONE_SIZE: u64 = 128;
+
+// Flags, that may be restored from a signal frame (CF, PF, AF, ZF, SF, TF, DF, OF and AC)
+const USER_RFLAGS: u64 = 0x40dd5;
+
+/// Signal actions of a thread, together with the currently blocked signals and the addresses of active signal frames.
+pub struct SignalState {
+    actions: [SigAction; NSIG],
+    mask: u64,
+    frames: Vec<u64>
+}
+
+impl SignalState {
+    pub const fn new() -> Self {
+        Self { actions: [SigAction { handler: SIG_DFL, mask: 0, flags: 0 }; NSIG], mask: 0, frames: Vec::new() }
+    }
+
+    pub fn action(&self, signum: u32) -> SigAction {
+        return self.actions[signum as usize];
+    }
+
+    pub fn set_action(&mut self, signum: u32, action: SigAction) {
+        self.actions[signum as usize] = action;
+    }
+}
+
+/// Push a signal frame on the user stack and redirect the current thread to its handler for `signum`.
+/// Returns false, if there is no handler, or the signal is blocked (in which case the exception is fatal).
+pub fn deliver(state: &mut ExceptionState, signum: u32, fault_address: u64) -> bool {
+    let thread = scheduler().current_thread();
+    let mut signals = thread.signals().lock();
+    let action = signals.action(signum);
+    if action.handler == SIG_DFL || signals.mask & (1 << signum) != 0 {
+        return false;
+    }
+
+    // Checked by 'sys_sigaction()' already, but a kernel address would make 'iretq' fault after switching to the user's GS base
+    if !is_user_address(action.handler as u64) {
+        return false;
+    }
+
+    let frame = SignalFrame {
+        signum: signum as u64,
+        error_code: state.error_code,
+        fault_address,
+        registers: state.registers,
+        rip: state.rip,
+        rflags: state.rflags,
+        rsp: state.rsp,
+        mask: signals.mask
+    };
+
+    // If the user stack is not writable, the signal cannot be delivered
+    let frame_addr = state.rsp.wrapping_sub(RED_ZONE_SIZE + size_of::<SignalFrame>() as u64) & !0x0f;
+    let return_addr = frame_addr.wrapping_sub(8);
+    if write_user(frame_addr as *mut SignalFrame, &frame).is_err() || write_user(return_addr as *mut u64, &0).is_err() { // Handlers must not return, but use 'SigReturn'
+        return false;
+    }
+
+    signals.frames.push(frame_addr);
+    signals.mask |= action.mask;
+    if action.flags & SA_NODEFER == 0 {
+        signals.mask |= 1 << signum;
+    }
+    if action.flags & SA_RESETHAND != 0 {
+        signals.set_action(signum, SigAction::default());
+    }
+
+    state.registers[7] = signum as u64; // rdi
+    state.registers[6] = frame_addr; // rsi
+    state.rip = action.handler as u64;
+    state.rsp = return_addr;
+
+    return true;
+}
+
+/// Restore the user state from the most recent signal frame of the current thread and return to user mode.
+/// Only returns, if there is no active signal frame, or it cannot be read.
+/// A frame with `rip` or `rsp` outside of user space terminates the thread, since 'iretq' would fault in kernel mode with the user's GS base loaded.
+pub fn sigreturn() {
+    let state = {
+        let thread = scheduler().current_thread();
+        let mut signals = thread.signals().lock();
+        let frame = match signals.frames.pop().map(|frame_addr| read_user(frame_addr as *const SignalFrame)) {
+            Some(Ok(frame)) => frame,
+            _ => return
+        };
+
+        signals.mask = frame.mask;
+        ExceptionState {
+            registers: frame.registers,
+            error_code: 0,
+            rip: frame.rip,
+            cs: SegmentSelector::new(4, Ring3).0 as u64,
+            rflags: (frame.rflags & USER_RFLAGS) | 0x202, // Interrupts enabled
+            rsp: frame.rsp,
+            ss: SegmentSelector::new(3, Ring3).0 as u64
+        }
+    };
+
+    if !is_user_address(state.rip) || !is_user_address(state.rsp) {
+        warn!("Thread [{}] terminated by invalid signal frame (rip: [{:0>16x}], rsp: [{:0>16x}])", scheduler().current_thread().id(), state.rip, state.rsp);
+        scheduler().exit();
+    }
+
+    unsafe { restore_state(&state); }
+}
+
+/// Check if `addr` may be loaded into `rip` or `rsp` when returning to user mode.
+pub fn is_user_address(addr: u64) -> bool {
+    return addr < USER_SPACE_END;
+}
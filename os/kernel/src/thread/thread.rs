@@ -1,77 +1,243 @@
 use crate::thread::scheduler;
 use alloc::boxed::Box;
-use alloc::rc::Rc;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::asm;
-use core::ptr;
-use spin::RwLock;
+use core::hint::spin_loop;
+use core::cmp::min;
+use core::mem::{align_of, size_of};
+use core::ops::Range;
+use core::{mem, ptr};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize};
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use spin::{Mutex, RwLock};
+use x86_64::instructions::interrupts;
+use x86_64::registers::control::Cr3;
 use x86_64::structures::gdt::SegmentSelector;
 use x86_64::PrivilegeLevel::Ring3;
 use x86_64::structures::paging::{Page, PageTableFlags};
 use x86_64::structures::paging::page::PageRange;
 use x86_64::VirtAddr;
+use library_syscall::{RLimit, Rusage, Timeval, DEFAULT_PRIORITY, PER_LINUX, PRIORITY_LEVELS, RLIM_NLIMITS, SCHED_FIFO, SCHED_OTHER, SCHED_RR};
 use library_thread::usr_thread_exit;
 use crate::memory::{MemorySpace, PAGE_SIZE};
-use crate::memory::r#virtual::{AddressSpace, create_address_space, kernel_address_space};
-use crate::{scheduler, tss};
+use crate::memory::slab::SlabAllocator;
+use crate::memory::r#virtual::{AddressSpace, alloc_kernel_stack, create_address_space};
+use crate::{percpu, process_table, scheduler, vdso};
+use crate::thread::elf_loader;
+use crate::thread::elf_loader::ElfError;
+use crate::thread::process::Process;
+use crate::thread::signal::SignalState;
+use crate::fs::FileTable;
+use crate::sync::SpinlockYield;
+use crate::arch::{fsbase, pkey};
+use crate::arch::xsave::FpuArea;
+use crate::arch::debug_registers::Watchpoints;
+#[cfg(feature = "fpu_emulate")]
+use crate::arch::fpu::FpuState;
 
 const STACK_SIZE_PAGES: usize = 16;
 const USER_STACK_ADDRESS: usize = 0x400000000000;
+const DEFAULT_UMASK: u16 = 0o022;
+// Protection key 0 is used for all pages without an explicit key, so it is always allocated
+const DEFAULT_PKEY_MASK: u16 = 0x0001;
+
+/// Threads are allocated from their own slab. Besides the thread, each slot holds the two reference counts of 'Arc'.
+static THREAD_SLAB: SlabAllocator = SlabAllocator::new(size_of::<Thread>() + 2 * size_of::<usize>(), align_of::<Thread>());
+
+/// Reference counted pointer to a thread, allocated from the thread slab.
+/// The reference counts are atomic, since threads are referenced by the scheduler lists of all CPUs.
+pub type ThreadRef = Arc<Thread, &'static SlabAllocator>;
 
 pub struct Thread {
     id: usize,
     kernel_stack: Vec<u64>,
     user_stack: Vec<u64>,
-    address_space: Arc<RwLock<AddressSpace>>,
+    process: Arc<Process>,
     old_rsp0: VirtAddr,
     entry: Box<dyn FnMut()>,
+    /// Entry point of a user thread loaded from an ELF file (instead of `entry`)
+    user_entry: Option<VirtAddr>,
+    priority: AtomicU8,
+    feedback_level: AtomicU8,
+    quantum_ticks: AtomicUsize,
+    usage: ResourceUsage,
+    children_usage: ResourceUsage,
+    affinity_mask: AtomicU64,
+    /// CPU, whose ready queues the thread is enqueued in (see 'Scheduler::ready()' and 'Scheduler::set_affinity()')
+    cpu: AtomicU32,
+    /// Set while the thread is running on a CPU, until its registers have been saved after switching away from it (see 'finish_switch()')
+    on_cpu: AtomicBool,
+    process_group: AtomicUsize,
+    killed: AtomicBool,
+    resource_limits: Mutex<[RLimit; RLIM_NLIMITS]>,
+    mem_policy: Mutex<MemPolicy>,
+    signals: Mutex<SignalState>,
+    personality: AtomicU32,
+    umask: AtomicU16,
+    sched_policy: AtomicI32,
+    sched_priority: AtomicI32,
+    pkey_alloc_mask: AtomicU16,
+    pkru: AtomicU32,
+    fs_base: AtomicU64,
+    last_errno: AtomicI32,
+    fpu_area: Mutex<FpuArea>,
+    watchpoints: Mutex<Watchpoints>,
+    #[cfg(feature = "fpu_emulate")]
+    fpu_state: Mutex<FpuState>,
+}
+
+/// NUMA memory policy of a thread. `mode` is one of the 'MPOL_*' constants and `nodemask` a bitmap of node ids.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemPolicy {
+    pub mode: u32,
+    pub nodemask: u64
+}
+
+/// Resource accounting for a single thread.
+/// The counters are updated by the scheduler and from interrupt context, so they are kept in atomics.
+#[derive(Default)]
+pub struct ResourceUsage {
+    user_ticks: AtomicU64,
+    kernel_ticks: AtomicU64,
+    max_rss_kib: AtomicU64,
+    minor_faults: AtomicU64,
+    voluntary_switches: AtomicU64,
+    involuntary_switches: AtomicU64,
 }
 
 impl Thread {
-    pub fn new_kernel_thread(entry: Box<dyn FnMut()>) -> Rc<Thread> {
+    /// Threads without a `priority` get 'DEFAULT_PRIORITY'. Priorities above the highest level are clamped.
+    pub fn new_kernel_thread(entry: Box<dyn FnMut()>, priority: Option<u8>) -> ThreadRef {
+        let kernel_stack = Thread::alloc_kernel_stack();
+
+        let id = scheduler::next_thread_id();
+        let process = process_table().write().kernel_process();
+        process.add_thread(id);
+
         let mut thread = Thread {
-            id: scheduler::next_thread_id(),
-            kernel_stack: Vec::with_capacity((STACK_SIZE_PAGES * PAGE_SIZE) / 8),
+            id,
+            kernel_stack,
             user_stack: Vec::with_capacity(0),
-            address_space: kernel_address_space(),
+            process,
             old_rsp0: VirtAddr::zero(),
             entry,
+            user_entry: None,
+            priority: AtomicU8::new(min(priority.unwrap_or(DEFAULT_PRIORITY), PRIORITY_LEVELS as u8 - 1)),
+            feedback_level: AtomicU8::new(scheduler::FEEDBACK_HIGH),
+            quantum_ticks: AtomicUsize::new(0),
+            usage: ResourceUsage::default(),
+            children_usage: ResourceUsage::default(),
+            affinity_mask: AtomicU64::new(scheduler::online_cpu_mask()),
+            cpu: AtomicU32::new(0),
+            on_cpu: AtomicBool::new(false),
+            process_group: AtomicUsize::new(id),
+            killed: AtomicBool::new(false),
+            resource_limits: Mutex::new([RLimit::INFINITY; RLIM_NLIMITS]),
+            mem_policy: Mutex::new(MemPolicy::default()),
+            signals: Mutex::new(SignalState::new()),
+            personality: AtomicU32::new(PER_LINUX),
+            umask: AtomicU16::new(DEFAULT_UMASK),
+            sched_policy: AtomicI32::new(SCHED_OTHER),
+            sched_priority: AtomicI32::new(0),
+            pkey_alloc_mask: AtomicU16::new(DEFAULT_PKEY_MASK),
+            pkru: AtomicU32::new(0),
+            fs_base: AtomicU64::new(0),
+            last_errno: AtomicI32::new(0),
+            fpu_area: Mutex::new(FpuArea::new()),
+            watchpoints: Mutex::new(Watchpoints::new()),
+            #[cfg(feature = "fpu_emulate")]
+            fpu_state: Mutex::new(FpuState::new()),
         };
 
         thread.prepare_kernel_stack();
-        return Rc::new(thread);
+        return Arc::new_in(thread, &THREAD_SLAB);
     }
 
     #[allow(dead_code)]
-    pub fn new_user_thread(entry: Box<dyn FnMut()>) -> Rc<Thread> {
-        let address_space = create_address_space();
-        let user_stack_start = Page::from_start_address(VirtAddr::new(USER_STACK_ADDRESS as u64)).unwrap();
+    pub fn new_user_thread(entry: Box<dyn FnMut()>, priority: Option<u8>) -> ThreadRef {
+        // The kernel stack must be allocated first, so that its guard page is also missing in the new address space
+        let kernel_stack = Thread::alloc_kernel_stack();
+        let address_space = Thread::create_user_address_space();
+
+        return Thread::new_user_thread_in(kernel_stack, address_space, entry, None, priority);
+    }
+
+    /// Create a user thread, running the statically linked ELF64 executable `elf` (e.g. a Multiboot2 module) in a new address space.
+    pub fn new_user_thread_from_elf(elf: &[u8], priority: Option<u8>) -> Result<ThreadRef, ElfError> {
+        // The file is validated before allocating anything, so that nothing needs to be freed on errors
+        let image = elf_loader::parse(elf)?;
+        if image.overlaps(Thread::user_stack_pages()) || image.overlaps(vdso::page_range()) {
+            return Err(ElfError::SegmentOverlap);
+        }
+
+        let kernel_stack = Thread::alloc_kernel_stack();
+        let address_space = Thread::create_user_address_space();
+        let entry = elf_loader::load_elf(elf, &mut address_space.write()).expect("Thread: Failed to load validated ELF file!");
+
+        return Ok(Thread::new_user_thread_in(kernel_stack, address_space, Box::new(|| {}), Some(entry), priority));
+    }
+
+    /// Without `user_entry`, the thread runs `entry` in user mode (kernel code is user accessible).
+    fn new_user_thread_in(kernel_stack: Vec<u64>, address_space: Arc<RwLock<AddressSpace>>, entry: Box<dyn FnMut()>, user_entry: Option<VirtAddr>, priority: Option<u8>) -> ThreadRef {
         let user_stack = unsafe { Vec::from_raw_parts(USER_STACK_ADDRESS as *mut u64, 0, (STACK_SIZE_PAGES * PAGE_SIZE) / 8) };
 
-        address_space.write().map(PageRange { start: user_stack_start, end: user_stack_start + STACK_SIZE_PAGES as u64 }, MemorySpace::User, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+        // Each user address space is a new process, created by the process of the current thread
+        let id = scheduler::next_thread_id();
+        let parent_pid = scheduler().try_current_thread().map(|thread| thread.process().pid());
+        let process = process_table().write().create(parent_pid, address_space);
+        process.add_thread(id);
 
         let mut thread = Thread {
-            id: scheduler::next_thread_id(),
-            kernel_stack: Vec::with_capacity((STACK_SIZE_PAGES * PAGE_SIZE) / 8),
+            id,
+            kernel_stack,
             user_stack,
-            address_space,
+            process,
             old_rsp0: VirtAddr::zero(),
             entry,
+            user_entry,
+            priority: AtomicU8::new(min(priority.unwrap_or(DEFAULT_PRIORITY), PRIORITY_LEVELS as u8 - 1)),
+            feedback_level: AtomicU8::new(scheduler::FEEDBACK_HIGH),
+            quantum_ticks: AtomicUsize::new(0),
+            usage: ResourceUsage::default(),
+            children_usage: ResourceUsage::default(),
+            affinity_mask: AtomicU64::new(scheduler::online_cpu_mask()),
+            cpu: AtomicU32::new(0),
+            on_cpu: AtomicBool::new(false),
+            process_group: AtomicUsize::new(id),
+            killed: AtomicBool::new(false),
+            resource_limits: Mutex::new([RLimit::INFINITY; RLIM_NLIMITS]),
+            mem_policy: Mutex::new(MemPolicy::default()),
+            signals: Mutex::new(SignalState::new()),
+            personality: AtomicU32::new(PER_LINUX),
+            umask: AtomicU16::new(DEFAULT_UMASK),
+            sched_policy: AtomicI32::new(SCHED_OTHER),
+            sched_priority: AtomicI32::new(0),
+            pkey_alloc_mask: AtomicU16::new(DEFAULT_PKEY_MASK),
+            pkru: AtomicU32::new(0),
+            fs_base: AtomicU64::new(0),
+            last_errno: AtomicI32::new(0),
+            fpu_area: Mutex::new(FpuArea::new()),
+            watchpoints: Mutex::new(Watchpoints::new()),
+            #[cfg(feature = "fpu_emulate")]
+            fpu_state: Mutex::new(FpuState::new()),
         };
 
         thread.prepare_kernel_stack();
-        return Rc::new(thread);
+        return Arc::new_in(thread, &THREAD_SLAB);
     }
 
     pub fn kickoff_kernel_thread() {
+        // New threads start here instead of returning from 'switch()'
+        Thread::finish_switch();
+
         let scheduler = scheduler();
         let thread = scheduler.current_thread();
         scheduler.set_init();
 
         unsafe {
             let thread_ptr = ptr::from_ref(thread.as_ref()) as *mut Thread;
-            tss().lock().privilege_stack_table[0] = VirtAddr::new(thread.kernel_stack_addr() as u64);
+            percpu::tss_set_rsp0(thread.kernel_stack_addr() as u64);
 
             if thread.is_kernel_thread() {
                 ((*thread_ptr).entry)();
@@ -83,6 +249,50 @@ impl Thread {
         scheduler.exit();
     }
 
+    /// Replace the image of the current thread's process with the statically linked ELF64 executable `elf` (e.g. a Multiboot2 module).
+    /// The new image runs in a fresh address space, starting with an empty user stack, while the kernel stack is preserved.
+    /// The old address space (and all frames owned by it) is freed. Signal handlers, watchpoints, protection keys and the FPU state are reset.
+    /// Only returns, if `elf` is invalid (in which case the old image keeps running).
+    pub fn exec(elf: &[u8]) -> ElfError {
+        // The file is validated before the old address space is touched, so that the caller can continue on errors
+        let image = match elf_loader::parse(elf) {
+            Ok(image) => image,
+            Err(err) => return err
+        };
+        if image.overlaps(Thread::user_stack_pages()) || image.overlaps(vdso::page_range()) {
+            return ElfError::SegmentOverlap;
+        }
+        drop(image);
+
+        // Nothing on this stack is dropped after jumping into the new image, so all references must be released before
+        let entry = {
+            let thread = scheduler().current_thread();
+            let address_space = Thread::create_user_address_space();
+            let entry = elf_loader::load_elf(elf, &mut address_space.write()).expect("Thread: Failed to load validated ELF file!");
+
+            // The contents are swapped, so that all references to the process' address space see the new one
+            interrupts::without_interrupts(|| {
+                let mut current_space = thread.address_space().write();
+                mem::swap(&mut *current_space, &mut *address_space.write());
+
+                percpu::set_current_thread(&thread, current_space.id());
+                unsafe { Cr3::write(current_space.page_table_address(), Cr3::read().1); }
+            });
+
+            // The old address space is no longer active, so its frames can be freed
+            drop(address_space);
+            thread.reset_user_state();
+
+            entry
+        };
+
+        // Page frames are not cleared by the allocator and may contain data of other threads
+        unsafe { ptr::write_bytes(USER_STACK_ADDRESS as *mut u8, 0, STACK_SIZE_PAGES * PAGE_SIZE); }
+
+        let user_rsp = (USER_STACK_ADDRESS + STACK_SIZE_PAGES * PAGE_SIZE - 8) as u64;
+        unsafe { thread_user_exec(entry.as_u64(), user_rsp, SegmentSelector::new(4, Ring3).0 as u64, SegmentSelector::new(3, Ring3).0 as u64); }
+    }
+
     pub fn kickoff_user_thread() {
         let thread = scheduler().current_thread();
 
@@ -94,12 +304,90 @@ impl Thread {
         usr_thread_exit();
     }
 
+    /// Give up the CPU voluntarily. The current thread is enqueued behind all ready threads of its priority and keeps
+    /// the rest of its quantum, but continues immediately, if no other thread of at least the same priority is ready.
+    pub fn yield_now() {
+        scheduler().yield_cpu();
+    }
+
+    /// Block the current thread until the system time reaches `wakeup_time` (in milliseconds, see 'Scheduler::sleep_until()').
+    pub fn sleep_until(wakeup_time: usize) {
+        scheduler().sleep_until(wakeup_time);
+    }
+
     pub fn start_first(thread: &Thread) {
+        thread.on_cpu.store(true, Relaxed);
+        percpu::set_current_thread(thread, thread.address_space().read().id());
+        percpu::tss_load_io_ports(&thread.process().io_ports());
+        thread.fpu_area.lock().restore();
+        fsbase::write_fs_base(thread.fs_base.load(Relaxed));
         unsafe { thread_kernel_start(thread.old_rsp0.as_u64()) }
     }
 
     pub fn switch(current: &Thread, next: &Thread) {
-        unsafe { thread_switch(ptr::from_ref(&current.old_rsp0) as *mut u64, next.old_rsp0.as_u64(), next.kernel_stack_addr() as u64, next.address_space.read().page_table_address().start_address().as_u64()); }
+        // A thread, that has been woken up while still running, may dequeue itself (switching would wait for itself below)
+        if ptr::eq(current, next) {
+            return;
+        }
+
+        // The last switch of this CPU is complete, even if the current thread has not reached 'finish_switch()' yet (e.g. when interrupted in 'kickoff_kernel_thread()')
+        Thread::finish_switch();
+
+        // A thread may be enqueued by another CPU, while that CPU is still switching away from it, so its registers must be saved first
+        while next.on_cpu.load(Acquire) {
+            spin_loop();
+        }
+        next.on_cpu.store(true, Relaxed);
+
+        // The kernel does not use the FPU itself (except for AES-NI), so the state can be switched before the registers
+        current.fpu_area.lock().save();
+        next.fpu_area.lock().restore();
+
+        if pkey::pkeys_available() {
+            current.pkru.store(pkey::read_pkru(), Relaxed);
+            pkey::write_pkru(next.pkru.load(Relaxed));
+        }
+
+        // The debug registers are only written, if one of the threads uses watchpoints
+        {
+            let next_watchpoints = next.watchpoints.lock();
+            if next_watchpoints.is_active() || current.watchpoints.lock().is_active() {
+                next_watchpoints.load();
+            }
+        }
+
+        // User space may have changed the FS base with 'WRFSBASE', so it is read back instead of relying on `set_fs_base()`
+        current.fs_base.store(fsbase::read_fs_base(), Relaxed);
+        fsbase::write_fs_base(next.fs_base.load(Relaxed));
+
+        let (next_cr3, next_address_space_id) = {
+            let address_space = next.address_space().read();
+            (address_space.page_table_address().start_address().as_u64(), address_space.id())
+        };
+
+        // The I/O permission bitmap is part of the per-CPU TSS, so it is switched with the process
+        percpu::tss_load_io_ports(&next.process().io_ports());
+
+        percpu::set_current_thread(next, next_address_space_id);
+        percpu::set_previous_thread(current);
+        unsafe { thread_switch(ptr::from_ref(&current.old_rsp0) as *mut u64, next.old_rsp0.as_u64(), next.kernel_stack_addr() as u64, next_cr3); }
+
+        Thread::finish_switch();
+    }
+
+    /// Allow other CPUs to switch to the thread, that the executing CPU has switched away from (see 'switch()').
+    /// Called by the next thread after the switch, when the previous thread's kernel stack is no longer in use.
+    fn finish_switch() {
+        let previous = percpu::take_previous_thread();
+        if let Some(previous) = unsafe { previous.as_ref() } {
+            previous.on_cpu.store(false, Release);
+        }
+    }
+
+    /// Set the FS base (thread-local storage pointer) of the current thread. `base` must be a canonical address.
+    pub fn set_fs_base(&self, base: u64) {
+        self.fs_base.store(base, Relaxed);
+        fsbase::write_fs_base(base);
     }
 
     pub fn is_kernel_thread(&self) -> bool {
@@ -115,10 +403,244 @@ impl Thread {
         return self.id;
     }
 
+    pub fn resource_usage(&self) -> &ResourceUsage {
+        return &self.usage;
+    }
+
+    /// Accumulated resource usage of all threads, that have been joined by this thread.
+    pub fn children_resource_usage(&self) -> &ResourceUsage {
+        return &self.children_usage;
+    }
+
+    pub fn address_space(&self) -> &Arc<RwLock<AddressSpace>> {
+        return self.process.address_space();
+    }
+
+    pub fn process(&self) -> &Arc<Process> {
+        return &self.process;
+    }
+
+    /// Size of the memory backing this thread's user space mappings in KiB.
+    pub fn resident_set_kib(&self) -> u64 {
+        return (self.address_space().read().user_frame_count() * PAGE_SIZE / 1024) as u64;
+    }
+
+    /// Set of CPUs, this thread is allowed to run on (bit n represents CPU n).
+    pub fn affinity_mask(&self) -> u64 {
+        return self.affinity_mask.load(Relaxed);
+    }
+
+    pub fn set_affinity_mask(&self, mask: u64) {
+        self.affinity_mask.store(mask, Relaxed);
+    }
+
+    /// CPU, that the thread is scheduled on (see 'Scheduler::ready()').
+    pub fn cpu(&self) -> u32 {
+        return self.cpu.load(Relaxed);
+    }
+
+    pub fn set_cpu(&self, cpu: u32) {
+        self.cpu.store(cpu, Relaxed);
+    }
+
+    /// Id of the process group (job), this thread belongs to. Initially, each thread forms its own group.
+    pub fn process_group(&self) -> usize {
+        return self.process_group.load(Relaxed);
+    }
+
+    pub fn set_process_group(&self, pgid: usize) {
+        self.process_group.store(pgid, Relaxed);
+    }
+
+    pub fn is_killed(&self) -> bool {
+        return self.killed.load(Relaxed);
+    }
+
+    /// Mark this thread for termination. It exits the next time it is interrupted in user mode.
+    pub fn kill(&self) {
+        self.killed.store(true, Relaxed);
+    }
+
+    /// Limit for `resource` (one of the 'RLIMIT_*' constants, which must be smaller than 'RLIM_NLIMITS').
+    pub fn resource_limit(&self, resource: u32) -> RLimit {
+        return self.resource_limits.lock()[resource as usize];
+    }
+
+    /// Like `resource_limit()`, but usable in interrupt context (returns `None`, if the limits are currently locked).
+    pub fn try_resource_limit(&self, resource: u32) -> Option<RLimit> {
+        return self.resource_limits.try_lock().map(|limits| limits[resource as usize]);
+    }
+
+    pub fn set_resource_limit(&self, resource: u32, limit: RLimit) {
+        self.resource_limits.lock()[resource as usize] = limit;
+    }
+
+    pub fn mem_policy(&self) -> MemPolicy {
+        return *self.mem_policy.lock();
+    }
+
+    pub fn set_mem_policy(&self, policy: MemPolicy) {
+        *self.mem_policy.lock() = policy;
+    }
+
+    /// Execution domain flags ('PER_*', 'ADDR_NO_RANDOMIZE', ...).
+    /// There is no ASLR yet, so the flags are stored but have no effect.
+    pub fn personality(&self) -> u32 {
+        return self.personality.load(Relaxed);
+    }
+
+    /// Set a new personality and return the previous one.
+    pub fn set_personality(&self, personality: u32) -> u32 {
+        return self.personality.swap(personality, Relaxed);
+    }
+
+    /// Permission bits, that are cleared when creating files (once there is a file system).
+    pub fn umask(&self) -> u16 {
+        return self.umask.load(Relaxed);
+    }
+
+    /// Set a new file creation mask and return the previous one.
+    pub fn set_umask(&self, umask: u16) -> u16 {
+        return self.umask.swap(umask & 0o777, Relaxed);
+    }
+
+    /// Priority level of a normal thread (0 to 'PRIORITY_LEVELS' - 1). Ignored for real-time threads.
+    pub fn priority(&self) -> u8 {
+        return self.priority.load(Relaxed);
+    }
+
+    /// Must only be called by the scheduler, which moves the thread into the matching ready queue.
+    pub fn set_priority(&self, priority: u8) {
+        self.priority.store(priority, Relaxed);
+    }
+
+    /// Feedback level of a normal thread ('FEEDBACK_HIGH' to 'FEEDBACK_LOW'), lowered each time the thread uses up its quantum.
+    pub fn feedback_level(&self) -> u8 {
+        return self.feedback_level.load(Relaxed);
+    }
+
+    /// Must only be called by the scheduler, which moves the thread into the matching ready queue.
+    /// Starts a new quantum on the given level.
+    pub fn set_feedback_level(&self, level: u8) {
+        self.feedback_level.store(level, Relaxed);
+        self.quantum_ticks.store(0, Relaxed);
+    }
+
+    /// Charge a timer tick to the current quantum and return the number of ticks used in it.
+    pub fn use_quantum_tick(&self) -> usize {
+        return self.quantum_ticks.fetch_add(1, Relaxed) + 1;
+    }
+
+    /// Scheduling policy ('SCHED_*'), used by the scheduler to pick the ready queue.
+    pub fn sched_policy(&self) -> i32 {
+        return self.sched_policy.load(Relaxed);
+    }
+
+    /// Real-time priority (1 to 99). Normal threads always have priority 0.
+    pub fn sched_priority(&self) -> i32 {
+        return self.sched_priority.load(Relaxed);
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        let policy = self.sched_policy();
+        return policy == SCHED_FIFO || policy == SCHED_RR;
+    }
+
+    /// Must only be called by the scheduler, which moves the thread into the matching ready queue.
+    pub fn set_sched_policy(&self, policy: i32, priority: i32) {
+        self.sched_policy.store(policy, Relaxed);
+        self.sched_priority.store(priority, Relaxed);
+    }
+
+    /// Allocate a free protection key and return it (`None`, if all keys are in use).
+    pub fn alloc_pkey(&self) -> Option<usize> {
+        let mask = self.pkey_alloc_mask.load(Relaxed);
+        let pkey = (!mask).trailing_zeros() as usize;
+        if pkey >= pkey::PKEY_COUNT {
+            return None;
+        }
+
+        self.pkey_alloc_mask.fetch_or(1 << pkey, Relaxed);
+        return Some(pkey);
+    }
+
+    pub fn free_pkey(&self, pkey: usize) {
+        self.pkey_alloc_mask.fetch_and(!(1 << pkey), Relaxed);
+    }
+
+    pub fn is_pkey_allocated(&self, pkey: usize) -> bool {
+        return pkey < pkey::PKEY_COUNT && self.pkey_alloc_mask.load(Relaxed) & (1 << pkey) != 0;
+    }
+
+    pub fn last_errno(&self) -> i32 {
+        return self.last_errno.load(Relaxed);
+    }
+
+    pub fn set_last_errno(&self, errno: i32) {
+        self.last_errno.store(errno, Relaxed);
+    }
+
+    pub fn signals(&self) -> &Mutex<SignalState> {
+        return &self.signals;
+    }
+
+    /// Open files of this thread's process (shared with all other threads of the process).
+    pub fn files(&self) -> &SpinlockYield<FileTable> {
+        return self.process.files();
+    }
+
+    /// Hardware breakpoints of this thread (see 'debug_registers').
+    pub fn watchpoints(&self) -> &Mutex<Watchpoints> {
+        return &self.watchpoints;
+    }
+
+    /// Software FPU state, used by the FPU emulator instead of the hardware registers.
+    #[cfg(feature = "fpu_emulate")]
+    pub fn fpu_state(&self) -> &Mutex<FpuState> {
+        return &self.fpu_state;
+    }
+
+    /// Check if `addr` is part of the guard page below the kernel stack (accessed on a kernel stack overflow).
+    pub fn is_kernel_stack_guard(&self, addr: u64) -> bool {
+        let stack_start = self.kernel_stack.as_ptr() as u64;
+        return (stack_start - PAGE_SIZE as u64..stack_start).contains(&addr);
+    }
+
+    /// Addresses covered by the kernel stack (excluding its guard page).
+    pub fn kernel_stack_range(&self) -> Range<u64> {
+        let stack_start = self.kernel_stack.as_ptr() as u64;
+        return stack_start..stack_start + (self.kernel_stack.capacity() * 8) as u64;
+    }
+
+    /// Stack pointer, at which the registers have been saved, when this thread has been switched away from (see 'thread_switch').
+    /// Only valid, while the thread is not running.
+    pub fn saved_stack_pointer(&self) -> u64 {
+        return self.old_rsp0.as_u64();
+    }
+
     pub fn kernel_stack_addr(&self) -> *const u64 {
         unsafe { return self.kernel_stack.as_ptr().offset(((self.kernel_stack.capacity() - 1) * 8) as isize); }
     }
 
+    /// Create a new address space with a user stack and the vDSO.
+    fn create_user_address_space() -> Arc<RwLock<AddressSpace>> {
+        let address_space = create_address_space();
+        address_space.write().map(Thread::user_stack_pages(), MemorySpace::User, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE);
+        vdso::map(&mut address_space.write());
+
+        return address_space;
+    }
+
+    fn user_stack_pages() -> PageRange {
+        let user_stack_start = Page::from_start_address(VirtAddr::new(USER_STACK_ADDRESS as u64)).unwrap();
+        return PageRange { start: user_stack_start, end: user_stack_start + STACK_SIZE_PAGES as u64 };
+    }
+
+    fn alloc_kernel_stack() -> Vec<u64> {
+        let pages = alloc_kernel_stack(STACK_SIZE_PAGES);
+        return unsafe { Vec::from_raw_parts(pages.start.start_address().as_mut_ptr(), 0, (STACK_SIZE_PAGES * PAGE_SIZE) / 8) };
+    }
+
     fn prepare_kernel_stack(&mut self) {
         let stack_addr = self.kernel_stack.as_ptr() as u64;
         let capacity = self.kernel_stack.capacity();
@@ -152,6 +674,32 @@ impl Thread {
         self.old_rsp0 = VirtAddr::new(stack_addr + ((capacity - 18) * 8) as u64);
     }
 
+    /// Reset the state, that refers to the user image of the current thread (see 'exec()').
+    fn reset_user_state(&self) {
+        self.set_fs_base(0);
+        *self.signals.lock() = SignalState::new();
+
+        {
+            let mut watchpoints = self.watchpoints.lock();
+            if watchpoints.is_active() {
+                *watchpoints = Watchpoints::new();
+                watchpoints.load();
+            }
+        }
+
+        self.pkey_alloc_mask.store(DEFAULT_PKEY_MASK, Relaxed);
+        self.pkru.store(0, Relaxed);
+        if pkey::pkeys_available() {
+            pkey::write_pkru(0);
+        }
+
+        let mut fpu_area = self.fpu_area.lock();
+        *fpu_area = FpuArea::new();
+        fpu_area.restore();
+        #[cfg(feature = "fpu_emulate")]
+        { *self.fpu_state.lock() = FpuState::new(); }
+    }
+
     fn switch_to_user_mode(&mut self) {
         let kernel_stack_addr = self.kernel_stack.as_ptr() as u64;
         let user_stack_addr = self.user_stack.as_ptr() as u64;
@@ -162,7 +710,7 @@ impl Thread {
         }
 
         self.kernel_stack[capacity - 7] = 0; // rdi
-        self.kernel_stack[capacity - 6] = Thread::kickoff_user_thread as u64; // Address of 'kickoff_user_thread()'
+        self.kernel_stack[capacity - 6] = self.user_entry.map_or(Thread::kickoff_user_thread as u64, |entry| entry.as_u64()); // Address of 'kickoff_user_thread()' or ELF entry point
 
         self.kernel_stack[capacity - 5] = SegmentSelector::new(4, Ring3).0 as u64; // cs = user code segment
         self.kernel_stack[capacity - 4] = 0x202; // rflags (Interrupts enabled)
@@ -178,6 +726,71 @@ impl Thread {
     }
 }
 
+impl ResourceUsage {
+    pub fn tick(&self, user_mode: bool) {
+        if user_mode {
+            self.user_ticks.fetch_add(1, Relaxed);
+        } else {
+            self.kernel_ticks.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub fn voluntary_switch(&self) {
+        self.voluntary_switches.fetch_add(1, Relaxed);
+    }
+
+    pub fn involuntary_switch(&self) {
+        self.involuntary_switches.fetch_add(1, Relaxed);
+    }
+
+    pub fn minor_fault(&self) {
+        self.minor_faults.fetch_add(1, Relaxed);
+    }
+
+    pub fn update_max_rss(&self, rss_kib: u64) {
+        self.max_rss_kib.fetch_max(rss_kib, Relaxed);
+    }
+
+    /// Add the counters of `other` to this accounting record (the maximum resident set size is merged instead).
+    pub fn add(&self, other: &ResourceUsage) {
+        self.user_ticks.fetch_add(other.user_ticks.load(Relaxed), Relaxed);
+        self.kernel_ticks.fetch_add(other.kernel_ticks.load(Relaxed), Relaxed);
+        self.max_rss_kib.fetch_max(other.max_rss_kib.load(Relaxed), Relaxed);
+        self.minor_faults.fetch_add(other.minor_faults.load(Relaxed), Relaxed);
+        self.voluntary_switches.fetch_add(other.voluntary_switches.load(Relaxed), Relaxed);
+        self.involuntary_switches.fetch_add(other.involuntary_switches.load(Relaxed), Relaxed);
+    }
+
+    /// Consumed CPU time in user mode, using `tick_ns` as length of a timer tick.
+    pub fn user_time_ns(&self, tick_ns: usize) -> u64 {
+        return self.user_ticks.load(Relaxed) * tick_ns as u64;
+    }
+
+    /// Consumed CPU time in kernel mode, using `tick_ns` as length of a timer tick.
+    pub fn kernel_time_ns(&self, tick_ns: usize) -> u64 {
+        return self.kernel_ticks.load(Relaxed) * tick_ns as u64;
+    }
+
+    /// Consumed CPU time (user and kernel), using `tick_ns` as length of a timer tick.
+    pub fn cpu_time_ns(&self, tick_ns: usize) -> u64 {
+        return (self.user_ticks.load(Relaxed) + self.kernel_ticks.load(Relaxed)) * tick_ns as u64;
+    }
+
+    /// Convert the counters into the system call representation, using `tick_ns` as length of a timer tick.
+    pub fn as_rusage(&self, tick_ns: usize) -> Rusage {
+        return Rusage {
+            utime: Timeval::from_ns(self.user_ticks.load(Relaxed) * tick_ns as u64),
+            stime: Timeval::from_ns(self.kernel_ticks.load(Relaxed) * tick_ns as u64),
+            maxrss: self.max_rss_kib.load(Relaxed),
+            // Lazy pages are always zero-filled, so no page fault needs to read backing data (there is no swap or file mapping)
+            majflt: 0,
+            minflt: self.minor_faults.load(Relaxed),
+            nvcsw: self.voluntary_switches.load(Relaxed),
+            nivcsw: self.involuntary_switches.load(Relaxed),
+        };
+    }
+}
+
 #[naked]
 unsafe extern "C" fn thread_kernel_start(old_rsp0: u64) {
     asm!(
@@ -208,6 +821,39 @@ unsafe extern "C" fn thread_user_start(old_rsp0: u64) {
     asm!(
     "mov rsp, rdi", // Load 'old_rsp' (first parameter)
     "pop rdi",
+    "swapgs", // Switch to the user's GS base
+    "iretq", // Switch to user-mode
+    options(noreturn)
+    )
+}
+
+/// Enter user mode at `entry` with the stack pointer `user_rsp` (see 'Thread::exec()').
+/// The general purpose registers are cleared, so that no kernel values are visible to the new image.
+#[naked]
+unsafe extern "C" fn thread_user_exec(entry: u64, user_rsp: u64, user_cs: u64, user_ss: u64) -> ! {
+    asm!(
+    "cli", // No interrupts between 'swapgs' and 'iretq'
+    "push rcx", // ss = user data segment (fourth parameter)
+    "push rsi", // rsp for user stack (second parameter)
+    "push 0x202", // rflags (Interrupts enabled)
+    "push rdx", // cs = user code segment (third parameter)
+    "push rdi", // Entry point (first parameter)
+    "xor rax, rax",
+    "xor rbx, rbx",
+    "xor rcx, rcx",
+    "xor rdx, rdx",
+    "xor rsi, rsi",
+    "xor rdi, rdi",
+    "xor rbp, rbp",
+    "xor r8, r8",
+    "xor r9, r9",
+    "xor r10, r10",
+    "xor r11, r11",
+    "xor r12, r12",
+    "xor r13, r13",
+    "xor r14, r14",
+    "xor r15, r15",
+    "swapgs", // Switch to the user's GS base
     "iretq", // Switch to user-mode
     options(noreturn)
     )
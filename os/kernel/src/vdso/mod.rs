@@ -0,0 +1,154 @@
+use core::arch::global_asm;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, AtomicU64, fence};
+use core::sync::atomic::Ordering::{Relaxed, Release};
+use spin::Once;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::{Page, PageTableFlags, PhysFrame};
+use x86_64::{PhysAddr, VirtAddr};
+use library_syscall::{Errno, CLOCK_MONOTONIC, CLOCK_REALTIME, VDSO_ADDRESS};
+use crate::memory::{MemorySpace, PAGE_SIZE};
+use crate::memory::physical;
+use crate::memory::r#virtual::AddressSpace;
+
+// The vDSO consists of a code page and a data page, mapped at 'VDSO_ADDRESS' into every user address space.
+// The code page contains functions, that user threads can call to read the time without entering the kernel.
+// They read the data page, which is updated by the timer interrupt and protected by a sequence lock
+// (the sequence number is odd while an update is in progress).
+
+const VDSO_DATA_ADDRESS: u64 = VDSO_ADDRESS + PAGE_SIZE as u64;
+
+/// Data shared with user space. The field offsets are hardcoded in the vDSO code below.
+#[repr(C, align(4096))]
+struct VdsoData {
+    seq: AtomicU32, // Offset 0
+    monotonic_time: AtomicU64, // Offset 8
+    wall_time: AtomicU64 // Offset 16
+}
+
+static VDSO_DATA: VdsoData = VdsoData { seq: AtomicU32::new(0), monotonic_time: AtomicU64::new(0), wall_time: AtomicU64::new(0) };
+static VDSO_CODE: Once<PhysFrame> = Once::new();
+
+extern "C" {
+    static __vdso_start: u8;
+    static __vdso_end: u8;
+}
+
+/// Publish new time values to user space (must only be called by the timer interrupt handler).
+pub fn update(monotonic_time_ns: u64, wall_time_ns: u64) {
+    let seq = VDSO_DATA.seq.load(Relaxed);
+    VDSO_DATA.seq.store(seq.wrapping_add(1), Relaxed);
+    fence(Release);
+
+    VDSO_DATA.monotonic_time.store(monotonic_time_ns, Relaxed);
+    VDSO_DATA.wall_time.store(wall_time_ns, Relaxed);
+    VDSO_DATA.seq.store(seq.wrapping_add(2), Release);
+}
+
+/// Pages occupied by the vDSO in every user address space.
+pub fn page_range() -> PageRange {
+    let code_page = Page::from_start_address(VirtAddr::new(VDSO_ADDRESS)).unwrap();
+    return PageRange { start: code_page, end: code_page + 2 };
+}
+
+/// Map the vDSO code (read-only, executable) and data (read-only) pages into `address_space`.
+pub fn map(address_space: &mut AddressSpace) {
+    let code_frame = *VDSO_CODE.call_once(|| {
+        let frame = physical::alloc(1, MemorySpace::Kernel).start;
+        unsafe {
+            let start = ptr::addr_of!(__vdso_start);
+            let size = ptr::addr_of!(__vdso_end) as usize - start as usize;
+            if size > PAGE_SIZE {
+                panic!("vDSO: Code does not fit into a single page!");
+            }
+
+            ptr::copy_nonoverlapping(start, frame.start_address().as_u64() as *mut u8, size);
+        }
+
+        return frame;
+    });
+    let data_frame = PhysFrame::containing_address(PhysAddr::new(ptr::addr_of!(VDSO_DATA) as u64));
+
+    let code_page = Page::from_start_address(VirtAddr::new(VDSO_ADDRESS)).unwrap();
+    let data_page = Page::from_start_address(VirtAddr::new(VDSO_DATA_ADDRESS)).unwrap();
+    address_space.map_physical(PageRange { start: code_page, end: code_page + 1 }, code_frame, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE);
+    address_space.map_physical(PageRange { start: data_page, end: data_page + 1 }, data_frame, PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE);
+}
+
+// vDSO code, which is copied into its own page. It must be position independent and only access the data page.
+// Entry points are placed at fixed offsets ('VDSO_CLOCK_GETTIME' and 'VDSO_GETTIMEOFDAY' in 'library_syscall').
+global_asm!(
+    ".pushsection .rodata.vdso, \"a\"",
+    ".global __vdso_start",
+    ".global __vdso_end",
+    "__vdso_start:",
+
+    // extern "C" fn __vdso_clock_gettime(clock: u32, time: *mut Timespec) -> i32 (offset 0x00)
+    "__vdso_clock_gettime:",
+    "mov r8, {data}",
+    "lea r9, [r8 + 16]", // Wall time
+    "cmp edi, {realtime}",
+    "je 2f",
+    "lea r9, [r8 + 8]", // Monotonic time
+    "cmp edi, {monotonic}",
+    "jne 4f",
+    "2:",
+    "mov ecx, dword ptr [r8]", // Read sequence number and retry, if an update is in progress or has happened meanwhile
+    "test ecx, 1",
+    "jnz 3f",
+    "mov rax, qword ptr [r9]",
+    "cmp ecx, dword ptr [r8]",
+    "jne 2b",
+    "xor edx, edx",
+    "mov rcx, 1000000000",
+    "div rcx",
+    "mov qword ptr [rsi], rax", // tv_sec
+    "mov qword ptr [rsi + 8], rdx", // tv_nsec
+    "xor eax, eax",
+    "ret",
+    "3:",
+    "pause",
+    "jmp 2b",
+    "4:",
+    "mov eax, {invalid}",
+    "ret",
+
+    // extern "C" fn __vdso_gettimeofday(time: *mut Timeval, timezone: *mut Timezone) -> i32 (offset 0x80)
+    ".org 0x80",
+    "__vdso_gettimeofday:",
+    "mov r8, {data}",
+    "2:",
+    "mov ecx, dword ptr [r8]",
+    "test ecx, 1",
+    "jnz 3f",
+    "mov rax, qword ptr [r8 + 16]",
+    "cmp ecx, dword ptr [r8]",
+    "jne 2b",
+    "test rdi, rdi",
+    "jz 4f",
+    "xor edx, edx",
+    "mov rcx, 1000",
+    "div rcx",
+    "xor edx, edx",
+    "mov rcx, 1000000",
+    "div rcx",
+    "mov qword ptr [rdi], rax", // tv_sec
+    "mov qword ptr [rdi + 8], rdx", // tv_usec
+    "4:",
+    "test rsi, rsi",
+    "jz 5f",
+    "mov qword ptr [rsi], 0", // Always UTC
+    "5:",
+    "xor eax, eax",
+    "ret",
+    "3:",
+    "pause",
+    "jmp 2b",
+
+    "__vdso_end:",
+    ".popsection",
+    data = const VDSO_DATA_ADDRESS,
+    realtime = const CLOCK_REALTIME,
+    monotonic = const CLOCK_MONOTONIC,
+    invalid = const -(Errno::InvalidArgument as i32),
+);
@@ -1,10 +1,23 @@
 use crate::lfb::LFB;
 use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::slice;
+
+/// Width of the words compared by 'diff_flush()' (in pixels).
+const DIFF_WORD_PIXELS: usize = 32;
 
 pub struct BufferedLFB {
     buffer: Vec<u8>,
     lfb: LFB,
     target_lfb: LFB,
+    // Copy of the framebuffer's content, used by 'diff_flush()' (empty, until 'diff_flush()' is called for the first time)
+    shadow: Vec<u8>,
+
+    // Area of the back buffer, that has been drawn to since the last flush (empty, if 'dirty_x1' <= 'dirty_x0')
+    dirty_x0: u32,
+    dirty_y0: u32,
+    dirty_x1: u32,
+    dirty_y1: u32,
 }
 
 impl BufferedLFB {
@@ -12,18 +25,121 @@ impl BufferedLFB {
         let buffer = Vec::with_capacity((lfb.height() * lfb.pitch()) as usize);
         let raw_buffer = buffer.as_ptr() as *mut u8;
 
-        Self { buffer, lfb: LFB::new(raw_buffer, lfb.pitch(), lfb.width(), lfb.height(), lfb.bpp()), target_lfb: lfb }
+        Self { buffer, lfb: LFB::new(raw_buffer, lfb.pitch(), lfb.width(), lfb.height(), lfb.bpp()), target_lfb: lfb, shadow: Vec::new(), dirty_x0: 0, dirty_y0: 0, dirty_x1: 0, dirty_y1: 0 }
     }
 
+    /// Access the back buffer. Since any pixel may be drawn, the whole screen is marked as dirty.
     pub fn lfb(&mut self) -> &mut LFB {
+        self.mark_dirty(0, 0, self.lfb.width(), self.lfb.height());
+        &mut self.lfb
+    }
+
+    /// Access the back buffer for drawing inside the given rectangle only, which is marked as dirty.
+    pub fn region_lfb(&mut self, x: u32, y: u32, width: u32, height: u32) -> &mut LFB {
+        self.mark_dirty(x, y, width, height);
         &mut self.lfb
     }
 
+    /// Access the framebuffer directly. Since its content is unknown afterwards, the next 'diff_flush()' copies the whole back buffer.
     pub fn direct_lfb(&mut self) -> &mut LFB {
+        self.shadow = Vec::new();
         &mut self.target_lfb
     }
 
     pub fn flush(&mut self) {
-        unsafe { self.target_lfb.buffer().copy_from(self.buffer.as_ptr(), (self.lfb.height() * self.lfb.pitch()) as usize); }
+        let size = (self.lfb.height() * self.lfb.pitch()) as usize;
+        unsafe { self.target_lfb.buffer().copy_from(self.buffer.as_ptr(), size); }
+        if !self.shadow.is_empty() {
+            unsafe { self.shadow.as_mut_ptr().copy_from(self.buffer.as_ptr(), size); }
+        }
+
+        self.reset_dirty();
+    }
+
+    /// Copy only the words of 'DIFF_WORD_PIXELS' pixels, that differ from what has last been written to the framebuffer, and reset the dirty area.
+    /// Reading and comparing the back buffer is much cheaper than writing to the framebuffer, if only small parts of the screen change (e.g. a blinking cursor).
+    /// The first call copies the whole back buffer and allocates a shadow copy of the framebuffer (same size as the back buffer).
+    /// Returns the number of bytes written to the framebuffer.
+    pub fn diff_flush(&mut self) -> usize {
+        let size = (self.lfb.height() * self.lfb.pitch()) as usize;
+        let back = unsafe { slice::from_raw_parts(self.buffer.as_ptr(), size) };
+        if self.shadow.is_empty() {
+            self.flush();
+            self.shadow = back.to_vec();
+            return size;
+        }
+
+        let bytes_per_pixel = ((self.lfb.bpp() + 7) / 8) as usize;
+        let pitch = self.lfb.pitch() as usize;
+        let row_length = self.lfb.width() as usize * bytes_per_pixel;
+        let word_length = DIFF_WORD_PIXELS * bytes_per_pixel;
+        let mut written = 0;
+
+        for row in 0..self.lfb.height() as usize {
+            for word in (0..row_length).step_by(word_length) {
+                let start = row * pitch + word;
+                let end = start + min(word_length, row_length - word);
+                if back[start..end] != self.shadow[start..end] {
+                    unsafe { self.target_lfb.buffer().add(start).copy_from(back.as_ptr().add(start), end - start); }
+                    self.shadow[start..end].copy_from_slice(&back[start..end]);
+                    written += end - start;
+                }
+            }
+        }
+
+        self.reset_dirty();
+        return written;
+    }
+
+    /// Copy only the rows and columns of the given rectangle to the framebuffer (clipped to the screen size).
+    pub fn flush_region(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let x_end = min(x.saturating_add(width), self.lfb.width());
+        let y_end = min(y.saturating_add(height), self.lfb.height());
+        if x >= x_end || y >= y_end {
+            return;
+        }
+
+        let bytes_per_pixel = ((self.lfb.bpp() + 7) / 8) as usize;
+        let pitch = self.lfb.pitch() as usize;
+        let row_offset = x as usize * bytes_per_pixel;
+        let row_length = (x_end - x) as usize * bytes_per_pixel;
+
+        for row in y as usize..y_end as usize {
+            let offset = row * pitch + row_offset;
+            unsafe { self.target_lfb.buffer().add(offset).copy_from(self.buffer.as_ptr().add(offset), row_length); }
+            if !self.shadow.is_empty() {
+                unsafe { self.shadow.as_mut_ptr().add(offset).copy_from(self.buffer.as_ptr().add(offset), row_length); }
+            }
+        }
+    }
+
+    /// Flush the area, that has been drawn to since the last flush, and reset it.
+    pub fn flush_dirty(&mut self) {
+        if self.dirty_x0 < self.dirty_x1 && self.dirty_y0 < self.dirty_y1 {
+            self.flush_region(self.dirty_x0, self.dirty_y0, self.dirty_x1 - self.dirty_x0, self.dirty_y1 - self.dirty_y0);
+        }
+
+        self.reset_dirty();
+    }
+
+    fn mark_dirty(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let x_end = min(x.saturating_add(width), self.lfb.width());
+        let y_end = min(y.saturating_add(height), self.lfb.height());
+        if x >= x_end || y >= y_end {
+            return;
+        }
+
+        if self.dirty_x0 >= self.dirty_x1 || self.dirty_y0 >= self.dirty_y1 {
+            (self.dirty_x0, self.dirty_y0, self.dirty_x1, self.dirty_y1) = (x, y, x_end, y_end);
+        } else {
+            self.dirty_x0 = min(self.dirty_x0, x);
+            self.dirty_y0 = min(self.dirty_y0, y);
+            self.dirty_x1 = max(self.dirty_x1, x_end);
+            self.dirty_y1 = max(self.dirty_y1, y_end);
+        }
+    }
+
+    fn reset_dirty(&mut self) {
+        (self.dirty_x0, self.dirty_y0, self.dirty_x1, self.dirty_y1) = (0, 0, 0, 0);
     }
 }
@@ -0,0 +1,392 @@
+use crate::color::Color;
+use crate::lfb::LFB;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_HEADER_SIZE: usize = 4;
+
+/// Bitmap font in the PSF1 format (8 pixels wide, one byte per row, most significant bit on the left).
+pub struct Font {
+    data: &'static [u8],
+    glyph_count: usize,
+    height: u32
+}
+
+pub const DEFAULT_FONT: Font = Font::new(&DEFAULT_FONT_DATA);
+
+impl Font {
+    pub const fn new(data: &'static [u8]) -> Self {
+        if data.len() < PSF1_HEADER_SIZE || data[0] != PSF1_MAGIC[0] || data[1] != PSF1_MAGIC[1] {
+            panic!("Font: Invalid PSF1 header!");
+        }
+
+        let glyph_count = if data[2] & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let height = data[3] as usize;
+        if data.len() < PSF1_HEADER_SIZE + glyph_count * height {
+            panic!("Font: Glyph data is truncated!");
+        }
+
+        Self { data, glyph_count, height: height as u32 }
+    }
+
+    pub const fn width(&self) -> u32 {
+        8
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Rows of the glyph for `c` (glyphs are indexed by code point). Characters without a glyph are shown as '?'.
+    fn glyph(&self, c: char) -> &[u8] {
+        let index = if (c as usize) < self.glyph_count { c as usize } else { '?' as usize };
+        let start = PSF1_HEADER_SIZE + index * self.height as usize;
+
+        &self.data[start..start + self.height as usize]
+    }
+}
+
+/// Draw `c` with its upper left corner at (`x`, `y`). Pixels outside the framebuffer are clipped.
+pub fn draw_char(lfb: &mut LFB, font: &Font, c: char, x: u32, y: u32, fg_color: &Color, bg_color: &Color) {
+    for (row, bits) in font.glyph(c).iter().enumerate() {
+        for col in 0..font.width() {
+            let color = match bits & (0x80 >> col) {
+                0 => bg_color,
+                _ => fg_color,
+            };
+
+            lfb.draw_pixel(x.saturating_add(col), y.saturating_add(row as u32), color);
+        }
+    }
+}
+
+/// Renders text into a rectangular area of the framebuffer, independently of the terminal.
+/// Lines are wrapped at the right border of the area. Text beyond the bottom border is discarded.
+pub struct TextRenderer {
+    font: &'static Font,
+    x: u32,
+    y: u32,
+    columns: u32,
+    rows: u32,
+
+    // Cursor position (in characters, relative to the area)
+    cursor_x: u32,
+    cursor_y: u32,
+    fg_color: Color,
+    bg_color: Color,
+}
+
+impl TextRenderer {
+    /// Create a renderer for the area with its upper left corner at (`x`, `y`) and the given size in pixels.
+    pub fn new(font: &'static Font, x: u32, y: u32, width: u32, height: u32, fg_color: Color, bg_color: Color) -> Self {
+        Self { font, x, y, columns: width / font.width(), rows: height / font.height(), cursor_x: 0, cursor_y: 0, fg_color, bg_color }
+    }
+
+    /// Set the cursor position (in characters).
+    pub fn set_cursor(&mut self, x: u32, y: u32) {
+        self.cursor_x = x;
+        self.cursor_y = y;
+    }
+
+    pub fn cursor(&self) -> (u32, u32) {
+        (self.cursor_x, self.cursor_y)
+    }
+
+    pub fn set_color(&mut self, fg_color: Color, bg_color: Color) {
+        self.fg_color = fg_color;
+        self.bg_color = bg_color;
+    }
+
+    pub fn draw_str(&mut self, lfb: &mut LFB, s: &str) {
+        for c in s.chars() {
+            self.draw_char(lfb, c);
+        }
+    }
+
+    pub fn draw_char(&mut self, lfb: &mut LFB, c: char) {
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.cursor_x = 0,
+            _ => {
+                if self.cursor_x >= self.columns {
+                    self.new_line();
+                }
+
+                if self.cursor_y < self.rows {
+                    let x = self.x + self.cursor_x * self.font.width();
+                    let y = self.y + self.cursor_y * self.font.height();
+                    draw_char(lfb, self.font, c, x, y, &self.fg_color, &self.bg_color);
+                }
+
+                self.cursor_x += 1;
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.cursor_x = 0;
+        self.cursor_y = self.cursor_y.saturating_add(1);
+    }
+}
+
+/// 8x16 font in the PSF1 format with 256 glyphs (ASCII and Latin-1),
+/// generated from the 8x8 glyphs of the 'font8x8' crate (public domain) by doubling each row.
+const DEFAULT_FONT_DATA: [u8; PSF1_HEADER_SIZE + 256 * 16] = [
+    0x36, 0x04, 0x00, 0x10, // Header (magic, mode, glyph height)
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x00
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x01
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x02
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x03
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x04
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x05
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x06
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x07
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x08
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x09
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0a
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0b
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0c
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0d
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0e
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x0f
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x10
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x11
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x12
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x13
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x14
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x15
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x16
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x17
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x18
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x19
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1a
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1b
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1c
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1d
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1e
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x1f
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x20
+    0x18, 0x18, 0x3c, 0x3c, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, // '!'
+    0x6c, 0x6c, 0x6c, 0x6c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '"'
+    0x6c, 0x6c, 0x6c, 0x6c, 0xfe, 0xfe, 0x6c, 0x6c, 0xfe, 0xfe, 0x6c, 0x6c, 0x6c, 0x6c, 0x00, 0x00, // '#'
+    0x30, 0x30, 0x7c, 0x7c, 0xc0, 0xc0, 0x78, 0x78, 0x0c, 0x0c, 0xf8, 0xf8, 0x30, 0x30, 0x00, 0x00, // '$'
+    0x00, 0x00, 0xc6, 0xc6, 0xcc, 0xcc, 0x18, 0x18, 0x30, 0x30, 0x66, 0x66, 0xc6, 0xc6, 0x00, 0x00, // '%'
+    0x38, 0x38, 0x6c, 0x6c, 0x38, 0x38, 0x76, 0x76, 0xdc, 0xdc, 0xcc, 0xcc, 0x76, 0x76, 0x00, 0x00, // '&'
+    0x60, 0x60, 0x60, 0x60, 0xc0, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // "'"
+    0x18, 0x18, 0x30, 0x30, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x30, 0x30, 0x18, 0x18, 0x00, 0x00, // '('
+    0x60, 0x60, 0x30, 0x30, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x30, 0x30, 0x60, 0x60, 0x00, 0x00, // ')'
+    0x00, 0x00, 0x66, 0x66, 0x3c, 0x3c, 0xff, 0xff, 0x3c, 0x3c, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00, // '*'
+    0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0xfc, 0xfc, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, // '+'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x60, 0x60, // ','
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfc, 0xfc, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '-'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, // '.'
+    0x06, 0x06, 0x0c, 0x0c, 0x18, 0x18, 0x30, 0x30, 0x60, 0x60, 0xc0, 0xc0, 0x80, 0x80, 0x00, 0x00, // '/'
+    0x7c, 0x7c, 0xc6, 0xc6, 0xce, 0xce, 0xde, 0xde, 0xf6, 0xf6, 0xe6, 0xe6, 0x7c, 0x7c, 0x00, 0x00, // '0'
+    0x30, 0x30, 0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0xfc, 0xfc, 0x00, 0x00, // '1'
+    0x78, 0x78, 0xcc, 0xcc, 0x0c, 0x0c, 0x38, 0x38, 0x60, 0x60, 0xcc, 0xcc, 0xfc, 0xfc, 0x00, 0x00, // '2'
+    0x78, 0x78, 0xcc, 0xcc, 0x0c, 0x0c, 0x38, 0x38, 0x0c, 0x0c, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // '3'
+    0x1c, 0x1c, 0x3c, 0x3c, 0x6c, 0x6c, 0xcc, 0xcc, 0xfe, 0xfe, 0x0c, 0x0c, 0x1e, 0x1e, 0x00, 0x00, // '4'
+    0xfc, 0xfc, 0xc0, 0xc0, 0xf8, 0xf8, 0x0c, 0x0c, 0x0c, 0x0c, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // '5'
+    0x38, 0x38, 0x60, 0x60, 0xc0, 0xc0, 0xf8, 0xf8, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // '6'
+    0xfc, 0xfc, 0xcc, 0xcc, 0x0c, 0x0c, 0x18, 0x18, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, // '7'
+    0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // '8'
+    0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0x18, 0x18, 0x70, 0x70, 0x00, 0x00, // '9'
+    0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, // ':'
+    0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x60, 0x60, // ';'
+    0x18, 0x18, 0x30, 0x30, 0x60, 0x60, 0xc0, 0xc0, 0x60, 0x60, 0x30, 0x30, 0x18, 0x18, 0x00, 0x00, // '<'
+    0x00, 0x00, 0x00, 0x00, 0xfc, 0xfc, 0x00, 0x00, 0x00, 0x00, 0xfc, 0xfc, 0x00, 0x00, 0x00, 0x00, // '='
+    0x60, 0x60, 0x30, 0x30, 0x18, 0x18, 0x0c, 0x0c, 0x18, 0x18, 0x30, 0x30, 0x60, 0x60, 0x00, 0x00, // '>'
+    0x78, 0x78, 0xcc, 0xcc, 0x0c, 0x0c, 0x18, 0x18, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x00, 0x00, // '?'
+    0x7c, 0x7c, 0xc6, 0xc6, 0xde, 0xde, 0xde, 0xde, 0xde, 0xde, 0xc0, 0xc0, 0x78, 0x78, 0x00, 0x00, // '@'
+    0x30, 0x30, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0xfc, 0xfc, 0xcc, 0xcc, 0xcc, 0xcc, 0x00, 0x00, // 'A'
+    0xfc, 0xfc, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x66, 0x66, 0x66, 0x66, 0xfc, 0xfc, 0x00, 0x00, // 'B'
+    0x3c, 0x3c, 0x66, 0x66, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0x66, 0x66, 0x3c, 0x3c, 0x00, 0x00, // 'C'
+    0xf8, 0xf8, 0x6c, 0x6c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x6c, 0x6c, 0xf8, 0xf8, 0x00, 0x00, // 'D'
+    0xfe, 0xfe, 0x62, 0x62, 0x68, 0x68, 0x78, 0x78, 0x68, 0x68, 0x62, 0x62, 0xfe, 0xfe, 0x00, 0x00, // 'E'
+    0xfe, 0xfe, 0x62, 0x62, 0x68, 0x68, 0x78, 0x78, 0x68, 0x68, 0x60, 0x60, 0xf0, 0xf0, 0x00, 0x00, // 'F'
+    0x3c, 0x3c, 0x66, 0x66, 0xc0, 0xc0, 0xc0, 0xc0, 0xce, 0xce, 0x66, 0x66, 0x3e, 0x3e, 0x00, 0x00, // 'G'
+    0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xfc, 0xfc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x00, 0x00, // 'H'
+    0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'I'
+    0x1e, 0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'J'
+    0xe6, 0xe6, 0x66, 0x66, 0x6c, 0x6c, 0x78, 0x78, 0x6c, 0x6c, 0x66, 0x66, 0xe6, 0xe6, 0x00, 0x00, // 'K'
+    0xf0, 0xf0, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x62, 0x62, 0x66, 0x66, 0xfe, 0xfe, 0x00, 0x00, // 'L'
+    0xc6, 0xc6, 0xee, 0xee, 0xfe, 0xfe, 0xfe, 0xfe, 0xd6, 0xd6, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, // 'M'
+    0xc6, 0xc6, 0xe6, 0xe6, 0xf6, 0xf6, 0xde, 0xde, 0xce, 0xce, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, // 'N'
+    0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x6c, 0x6c, 0x38, 0x38, 0x00, 0x00, // 'O'
+    0xfc, 0xfc, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x60, 0x60, 0x60, 0x60, 0xf0, 0xf0, 0x00, 0x00, // 'P'
+    0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xdc, 0xdc, 0x78, 0x78, 0x1c, 0x1c, 0x00, 0x00, // 'Q'
+    0xfc, 0xfc, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x6c, 0x6c, 0x66, 0x66, 0xe6, 0xe6, 0x00, 0x00, // 'R'
+    0x78, 0x78, 0xcc, 0xcc, 0xe0, 0xe0, 0x70, 0x70, 0x1c, 0x1c, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'S'
+    0xfc, 0xfc, 0xb4, 0xb4, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'T'
+    0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xfc, 0xfc, 0x00, 0x00, // 'U'
+    0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x30, 0x30, 0x00, 0x00, // 'V'
+    0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xd6, 0xd6, 0xfe, 0xfe, 0xee, 0xee, 0xc6, 0xc6, 0x00, 0x00, // 'W'
+    0xc6, 0xc6, 0xc6, 0xc6, 0x6c, 0x6c, 0x38, 0x38, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0x00, 0x00, // 'X'
+    0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'Y'
+    0xfe, 0xfe, 0xc6, 0xc6, 0x8c, 0x8c, 0x18, 0x18, 0x32, 0x32, 0x66, 0x66, 0xfe, 0xfe, 0x00, 0x00, // 'Z'
+    0x78, 0x78, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x78, 0x78, 0x00, 0x00, // '['
+    0xc0, 0xc0, 0x60, 0x60, 0x30, 0x30, 0x18, 0x18, 0x0c, 0x0c, 0x06, 0x06, 0x02, 0x02, 0x00, 0x00, // '\\'
+    0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x78, 0x78, 0x00, 0x00, // ']'
+    0x10, 0x10, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '^'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, // '_'
+    0x30, 0x30, 0x30, 0x30, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '`'
+    0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x76, 0x76, 0x00, 0x00, // 'a'
+    0xe0, 0xe0, 0x60, 0x60, 0x60, 0x60, 0x7c, 0x7c, 0x66, 0x66, 0x66, 0x66, 0xdc, 0xdc, 0x00, 0x00, // 'b'
+    0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xc0, 0xc0, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'c'
+    0x1c, 0x1c, 0x0c, 0x0c, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0xcc, 0xcc, 0x76, 0x76, 0x00, 0x00, // 'd'
+    0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xfc, 0xfc, 0xc0, 0xc0, 0x78, 0x78, 0x00, 0x00, // 'e'
+    0x38, 0x38, 0x6c, 0x6c, 0x60, 0x60, 0xf0, 0xf0, 0x60, 0x60, 0x60, 0x60, 0xf0, 0xf0, 0x00, 0x00, // 'f'
+    0x00, 0x00, 0x00, 0x00, 0x76, 0x76, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0xf8, 0xf8, // 'g'
+    0xe0, 0xe0, 0x60, 0x60, 0x6c, 0x6c, 0x76, 0x76, 0x66, 0x66, 0x66, 0x66, 0xe6, 0xe6, 0x00, 0x00, // 'h'
+    0x30, 0x30, 0x00, 0x00, 0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'i'
+    0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, // 'j'
+    0xe0, 0xe0, 0x60, 0x60, 0x66, 0x66, 0x6c, 0x6c, 0x78, 0x78, 0x6c, 0x6c, 0xe6, 0xe6, 0x00, 0x00, // 'k'
+    0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'l'
+    0x00, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xfe, 0xfe, 0xfe, 0xfe, 0xd6, 0xd6, 0xc6, 0xc6, 0x00, 0x00, // 'm'
+    0x00, 0x00, 0x00, 0x00, 0xf8, 0xf8, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x00, 0x00, // 'n'
+    0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'o'
+    0x00, 0x00, 0x00, 0x00, 0xdc, 0xdc, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x60, 0x60, 0xf0, 0xf0, // 'p'
+    0x00, 0x00, 0x00, 0x00, 0x76, 0x76, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0x1e, 0x1e, // 'q'
+    0x00, 0x00, 0x00, 0x00, 0xdc, 0xdc, 0x76, 0x76, 0x66, 0x66, 0x60, 0x60, 0xf0, 0xf0, 0x00, 0x00, // 'r'
+    0x00, 0x00, 0x00, 0x00, 0x7c, 0x7c, 0xc0, 0xc0, 0x78, 0x78, 0x0c, 0x0c, 0xf8, 0xf8, 0x00, 0x00, // 's'
+    0x10, 0x10, 0x30, 0x30, 0x7c, 0x7c, 0x30, 0x30, 0x30, 0x30, 0x34, 0x34, 0x18, 0x18, 0x00, 0x00, // 't'
+    0x00, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x76, 0x76, 0x00, 0x00, // 'u'
+    0x00, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x30, 0x30, 0x00, 0x00, // 'v'
+    0x00, 0x00, 0x00, 0x00, 0xc6, 0xc6, 0xd6, 0xd6, 0xfe, 0xfe, 0xfe, 0xfe, 0x6c, 0x6c, 0x00, 0x00, // 'w'
+    0x00, 0x00, 0x00, 0x00, 0xc6, 0xc6, 0x6c, 0x6c, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0x00, 0x00, // 'x'
+    0x00, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0xf8, 0xf8, // 'y'
+    0x00, 0x00, 0x00, 0x00, 0xfc, 0xfc, 0x98, 0x98, 0x30, 0x30, 0x64, 0x64, 0xfc, 0xfc, 0x00, 0x00, // 'z'
+    0x1c, 0x1c, 0x30, 0x30, 0x30, 0x30, 0xe0, 0xe0, 0x30, 0x30, 0x30, 0x30, 0x1c, 0x1c, 0x00, 0x00, // '{'
+    0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, // '|'
+    0xe0, 0xe0, 0x30, 0x30, 0x30, 0x30, 0x1c, 0x1c, 0x30, 0x30, 0x30, 0x30, 0xe0, 0xe0, 0x00, 0x00, // '}'
+    0x76, 0x76, 0xdc, 0xdc, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '~'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x7f
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x80
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x81
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x82
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x83
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x84
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x85
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x86
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x87
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x88
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x89
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8a
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8b
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8c
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8d
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8e
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x8f
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x90
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x91
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x92
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x93
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x94
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x95
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x96
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x97
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x98
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x99
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9a
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9b
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9c
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9d
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9e
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0x9f
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0xa0
+    0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, // '¡'
+    0x18, 0x18, 0x18, 0x18, 0x7e, 0x7e, 0xc0, 0xc0, 0xc0, 0xc0, 0x7e, 0x7e, 0x18, 0x18, 0x18, 0x18, // '¢'
+    0x38, 0x38, 0x6c, 0x6c, 0x64, 0x64, 0xf0, 0xf0, 0x60, 0x60, 0xe6, 0xe6, 0xfc, 0xfc, 0x00, 0x00, // '£'
+    0x00, 0x00, 0x00, 0x00, 0xc6, 0xc6, 0x7c, 0x7c, 0x6c, 0x6c, 0x7c, 0x7c, 0xc6, 0xc6, 0x00, 0x00, // '¤'
+    0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0xfc, 0xfc, 0x30, 0x30, 0xfc, 0xfc, 0x30, 0x30, 0x30, 0x30, // '¥'
+    0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, // '¦'
+    0x3e, 0x3e, 0x63, 0x63, 0x38, 0x38, 0x6c, 0x6c, 0x6c, 0x6c, 0x38, 0x38, 0xcc, 0xcc, 0x78, 0x78, // '§'
+    0xcc, 0xcc, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '¨'
+    0x3c, 0x3c, 0x42, 0x42, 0x99, 0x99, 0xa1, 0xa1, 0xa1, 0xa1, 0x99, 0x99, 0x42, 0x42, 0x3c, 0x3c, // '©'
+    0x3c, 0x3c, 0x6c, 0x6c, 0x6c, 0x6c, 0x3e, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 'ª'
+    0x00, 0x00, 0x33, 0x33, 0x66, 0x66, 0xcc, 0xcc, 0x66, 0x66, 0x33, 0x33, 0x00, 0x00, 0x00, 0x00, // '«'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfc, 0xfc, 0x0c, 0x0c, 0x0c, 0x0c, 0x00, 0x00, 0x00, 0x00, // '¬'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '\xad'
+    0x3c, 0x3c, 0x42, 0x42, 0xb9, 0xb9, 0xa5, 0xa5, 0xb9, 0xb9, 0xa5, 0xa5, 0x42, 0x42, 0x3c, 0x3c, // '®'
+    0x7e, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '¯'
+    0x38, 0x38, 0x6c, 0x6c, 0x6c, 0x6c, 0x38, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '°'
+    0x18, 0x18, 0x18, 0x18, 0x7e, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x7e, 0x7e, 0x00, 0x00, // '±'
+    0x38, 0x38, 0x0c, 0x0c, 0x18, 0x18, 0x30, 0x30, 0x3c, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '²'
+    0x38, 0x38, 0x0c, 0x0c, 0x18, 0x18, 0x0c, 0x0c, 0x38, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '³'
+    0x18, 0x18, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '´'
+    0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x60, 0x60, 0xc0, 0xc0, // 'µ'
+    0x7f, 0x7f, 0xdb, 0xdb, 0xdb, 0xdb, 0x7b, 0x7b, 0x1b, 0x1b, 0x1b, 0x1b, 0x1b, 0x1b, 0x00, 0x00, // '¶'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '·'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x0c, 0x0c, 0x78, 0x78, // '¸'
+    0x10, 0x10, 0x30, 0x30, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // '¹'
+    0x38, 0x38, 0x6c, 0x6c, 0x6c, 0x6c, 0x38, 0x38, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 'º'
+    0x00, 0x00, 0xcc, 0xcc, 0x66, 0x66, 0x33, 0x33, 0x66, 0x66, 0xcc, 0xcc, 0x00, 0x00, 0x00, 0x00, // '»'
+    0xc3, 0xc3, 0xc6, 0xc6, 0xcc, 0xcc, 0xbd, 0xbd, 0x37, 0x37, 0x6f, 0x6f, 0xcf, 0xcf, 0xc0, 0xc0, // '¼'
+    0xc3, 0xc3, 0xc6, 0xc6, 0xcc, 0xcc, 0xde, 0xde, 0x33, 0x33, 0x66, 0x66, 0xcc, 0xcc, 0x0f, 0x0f, // '½'
+    0xc0, 0xc0, 0x23, 0x23, 0xc6, 0xc6, 0x2d, 0x2d, 0xdb, 0xdb, 0x35, 0x35, 0x67, 0x67, 0x01, 0x01, // '¾'
+    0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x60, 0x60, 0xc0, 0xc0, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // '¿'
+    0xe0, 0xe0, 0x00, 0x00, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0xfe, 0xfe, 0xc6, 0xc6, 0x00, 0x00, // 'À'
+    0x0e, 0x0e, 0x00, 0x00, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0xfe, 0xfe, 0xc6, 0xc6, 0x00, 0x00, // 'Á'
+    0x38, 0x38, 0x6c, 0x6c, 0x00, 0x00, 0x7c, 0x7c, 0xc6, 0xc6, 0xfe, 0xfe, 0xc6, 0xc6, 0x00, 0x00, // 'Â'
+    0x76, 0x76, 0xdc, 0xdc, 0x00, 0x00, 0x7c, 0x7c, 0xc6, 0xc6, 0xfe, 0xfe, 0xc6, 0xc6, 0x00, 0x00, // 'Ã'
+    0xc6, 0xc6, 0x38, 0x38, 0x6c, 0x6c, 0xc6, 0xc6, 0xfe, 0xfe, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, // 'Ä'
+    0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xfc, 0xfc, 0xcc, 0xcc, 0x00, 0x00, // 'Å'
+    0x3e, 0x3e, 0x6c, 0x6c, 0xcc, 0xcc, 0xfe, 0xfe, 0xcc, 0xcc, 0xcc, 0xcc, 0xce, 0xce, 0x00, 0x00, // 'Æ'
+    0x78, 0x78, 0xcc, 0xcc, 0xc0, 0xc0, 0xcc, 0xcc, 0x78, 0x78, 0x18, 0x18, 0x0c, 0x0c, 0x78, 0x78, // 'Ç'
+    0xe0, 0xe0, 0x00, 0x00, 0xfc, 0xfc, 0x60, 0x60, 0x78, 0x78, 0x60, 0x60, 0xfc, 0xfc, 0x00, 0x00, // 'È'
+    0x1c, 0x1c, 0x00, 0x00, 0xfc, 0xfc, 0x60, 0x60, 0x78, 0x78, 0x60, 0x60, 0xfc, 0xfc, 0x00, 0x00, // 'É'
+    0x30, 0x30, 0x48, 0x48, 0xfc, 0xfc, 0x60, 0x60, 0x78, 0x78, 0x60, 0x60, 0xfc, 0xfc, 0x00, 0x00, // 'Ê'
+    0x6c, 0x6c, 0x00, 0x00, 0xfc, 0xfc, 0x60, 0x60, 0x78, 0x78, 0x60, 0x60, 0xfc, 0xfc, 0x00, 0x00, // 'Ë'
+    0xe0, 0xe0, 0x00, 0x00, 0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'Ì'
+    0x1c, 0x1c, 0x00, 0x00, 0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'Í'
+    0x30, 0x30, 0x48, 0x48, 0x00, 0x00, 0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'Î'
+    0xcc, 0xcc, 0x00, 0x00, 0x78, 0x78, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'Ï'
+    0xfc, 0xfc, 0x66, 0x66, 0xf6, 0xf6, 0xf6, 0xf6, 0x66, 0x66, 0x66, 0x66, 0xfc, 0xfc, 0x00, 0x00, // 'Ð'
+    0xfc, 0xfc, 0x00, 0x00, 0xcc, 0xcc, 0xec, 0xec, 0xfc, 0xfc, 0xdc, 0xdc, 0xcc, 0xcc, 0x00, 0x00, // 'Ñ'
+    0x70, 0x70, 0x00, 0x00, 0x18, 0x18, 0x3c, 0x3c, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, // 'Ò'
+    0x0e, 0x0e, 0x00, 0x00, 0x18, 0x18, 0x3c, 0x3c, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, // 'Ó'
+    0x3c, 0x3c, 0x66, 0x66, 0x18, 0x18, 0x3c, 0x3c, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, // 'Ô'
+    0x76, 0x76, 0xdc, 0xdc, 0x00, 0x00, 0x7c, 0x7c, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x7c, 0x00, 0x00, // 'Õ'
+    0xc3, 0xc3, 0x18, 0x18, 0x3c, 0x3c, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, // 'Ö'
+    0x00, 0x00, 0x6c, 0x6c, 0x38, 0x38, 0x10, 0x10, 0x38, 0x38, 0x6c, 0x6c, 0x00, 0x00, 0x00, 0x00, // '×'
+    0x3a, 0x3a, 0x6c, 0x6c, 0xce, 0xce, 0xde, 0xde, 0xf6, 0xf6, 0x6c, 0x6c, 0xb8, 0xb8, 0x00, 0x00, // 'Ø'
+    0x70, 0x70, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x3c, 0x00, 0x00, // 'Ù'
+    0x0e, 0x0e, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x3c, 0x00, 0x00, // 'Ú'
+    0x3c, 0x3c, 0x66, 0x66, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x3c, 0x00, 0x00, // 'Û'
+    0xcc, 0xcc, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'Ü'
+    0x0e, 0x0e, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, // 'Ý'
+    0xf0, 0xf0, 0x60, 0x60, 0x7c, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x7c, 0x60, 0x60, 0xf0, 0xf0, // 'Þ'
+    0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xf8, 0xf8, 0xcc, 0xcc, 0xf8, 0xf8, 0xc0, 0xc0, 0xc0, 0xc0, // 'ß'
+    0xe0, 0xe0, 0x00, 0x00, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'à'
+    0x1c, 0x1c, 0x00, 0x00, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'á'
+    0x7e, 0x7e, 0xc3, 0xc3, 0x3c, 0x3c, 0x06, 0x06, 0x3e, 0x3e, 0x66, 0x66, 0x3f, 0x3f, 0x00, 0x00, // 'â'
+    0x76, 0x76, 0xdc, 0xdc, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'ã'
+    0xcc, 0xcc, 0x00, 0x00, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'ä'
+    0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'å'
+    0x00, 0x00, 0x00, 0x00, 0x7f, 0x7f, 0x0c, 0x0c, 0x7f, 0x7f, 0xcc, 0xcc, 0x7f, 0x7f, 0x00, 0x00, // 'æ'
+    0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0xc0, 0xc0, 0xc0, 0xc0, 0x78, 0x78, 0x0c, 0x0c, 0x38, 0x38, // 'ç'
+    0xe0, 0xe0, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xfc, 0xfc, 0xc0, 0xc0, 0x78, 0x78, 0x00, 0x00, // 'è'
+    0x1c, 0x1c, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xfc, 0xfc, 0xc0, 0xc0, 0x78, 0x78, 0x00, 0x00, // 'é'
+    0x7e, 0x7e, 0xc3, 0xc3, 0x3c, 0x3c, 0x66, 0x66, 0x7e, 0x7e, 0x60, 0x60, 0x3c, 0x3c, 0x00, 0x00, // 'ê'
+    0xcc, 0xcc, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xfc, 0xfc, 0xc0, 0xc0, 0x78, 0x78, 0x00, 0x00, // 'ë'
+    0xe0, 0xe0, 0x00, 0x00, 0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'ì'
+    0x38, 0x38, 0x00, 0x00, 0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'í'
+    0x7c, 0x7c, 0xc6, 0xc6, 0x38, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x3c, 0x00, 0x00, // 'î'
+    0xcc, 0xcc, 0x00, 0x00, 0x70, 0x70, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x78, 0x78, 0x00, 0x00, // 'ï'
+    0xd8, 0xd8, 0x70, 0x70, 0xd8, 0xd8, 0x0c, 0x0c, 0x7c, 0x7c, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'ð'
+    0x00, 0x00, 0xf8, 0xf8, 0x00, 0x00, 0xf8, 0xf8, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x00, 0x00, // 'ñ'
+    0x00, 0x00, 0xe0, 0xe0, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'ò'
+    0x00, 0x00, 0x1c, 0x1c, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'ó'
+    0x78, 0x78, 0xcc, 0xcc, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'ô'
+    0x76, 0x76, 0xdc, 0xdc, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'õ'
+    0x00, 0x00, 0xcc, 0xcc, 0x00, 0x00, 0x78, 0x78, 0xcc, 0xcc, 0xcc, 0xcc, 0x78, 0x78, 0x00, 0x00, // 'ö'
+    0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x7e, 0x7e, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, // '÷'
+    0x00, 0x00, 0x06, 0x06, 0x3c, 0x3c, 0x6e, 0x6e, 0x7e, 0x7e, 0x76, 0x76, 0x3c, 0x3c, 0x60, 0x60, // 'ø'
+    0x00, 0x00, 0xe0, 0xe0, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'ù'
+    0x00, 0x00, 0x1c, 0x1c, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'ú'
+    0x78, 0x78, 0xcc, 0xcc, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'û'
+    0x00, 0x00, 0xcc, 0xcc, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7e, 0x7e, 0x00, 0x00, // 'ü'
+    0x00, 0x00, 0x1c, 0x1c, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0xf8, 0xf8, // 'ý'
+    0x00, 0x00, 0x00, 0x00, 0x60, 0x60, 0x7c, 0x7c, 0x66, 0x66, 0x7c, 0x7c, 0x60, 0x60, 0x00, 0x00, // 'þ'
+    0x00, 0x00, 0xcc, 0xcc, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x7c, 0x0c, 0x0c, 0xf8, 0xf8, // 'ÿ'
+];
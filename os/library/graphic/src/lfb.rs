@@ -1,4 +1,7 @@
+use crate::color;
 use crate::color::Color;
+use core::cmp::min;
+use core::fmt;
 use font8x8::{
     UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, HIRAGANA_FONTS, LATIN_FONTS,
     MISC_FONTS, SGA_FONTS,
@@ -12,6 +15,12 @@ pub struct LFB {
     bpp: u8,
 
     pixel_drawer: PixelDrawer,
+
+    // Text output state for 'core::fmt::Write' (position in characters)
+    cursor_x: u32,
+    cursor_y: u32,
+    fg_color: Color,
+    bg_color: Color,
 }
 
 unsafe impl Send for LFB {}
@@ -30,7 +39,7 @@ impl LFB {
             _ => draw_pixel_stub,
         };
 
-        Self { buffer, pitch, width, height, bpp, pixel_drawer }
+        Self { buffer, pitch, width, height, bpp, pixel_drawer, cursor_x: 0, cursor_y: 0, fg_color: color::WHITE, bg_color: color::BLACK }
     }
 
     pub const fn buffer(&self) -> *mut u8 {
@@ -62,9 +71,60 @@ impl LFB {
         unsafe { (self.pixel_drawer)(self.buffer, self.pitch, x, y, color) };
     }
 
+    /// Draw a line from (`x1`, `y1`) to (`x2`, `y2`) with Bresenham's algorithm.
+    /// The end points may be outside the framebuffer (pixels outside are skipped).
+    pub fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, color: &Color) {
+        // Skip lines, that are completely on one side outside the framebuffer
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x1, y1, x2, y2) = (x1 as i64, y1 as i64, x2 as i64, y2 as i64);
+        if (x1 < 0 && x2 < 0) || (y1 < 0 && y2 < 0) || (x1 >= width && x2 >= width) || (y1 >= height && y2 >= height) {
+            return;
+        }
+
+        let dx = (x2 - x1).abs();
+        let dy = -(y2 - y1).abs();
+        let step_x = if x1 < x2 { 1 } else { -1 };
+        let step_y = if y1 < y2 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x1, y1);
+
+        loop {
+            self.draw_pixel_signed(x, y, color);
+            if x == x2 && y == y2 {
+                break;
+            }
+
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draw the outline of a rectangle with its upper left corner at (`x`, `y`).
+    pub fn draw_rect(&self, x: u32, y: u32, width: u32, height: u32, color: &Color) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let end_x = x.saturating_add(width - 1);
+        let end_y = y.saturating_add(height - 1);
+
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, end_y, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(end_x, y, 1, height, color);
+    }
+
+    /// Fill a rectangle with its upper left corner at (`x`, `y`). Parts outside the framebuffer are clipped.
     pub fn fill_rect(&self, x: u32, y: u32, width: u32, height: u32, color: &Color) {
-        let end_x = x + width;
-        let end_y = y + height;
+        let end_x = min(x.saturating_add(width), self.width);
+        let end_y = min(y.saturating_add(height), self.height);
 
         for i in y..end_y {
             for j in x..end_x {
@@ -73,6 +133,35 @@ impl LFB {
         }
     }
 
+    /// Draw the outline of a circle around (`center_x`, `center_y`) with the midpoint variant of Bresenham's algorithm.
+    /// The center may be outside the framebuffer (pixels outside are skipped).
+    pub fn draw_circle(&self, center_x: i32, center_y: i32, radius: u32, color: &Color) {
+        let (center_x, center_y) = (center_x as i64, center_y as i64);
+        let mut x = radius as i64;
+        let mut y = 0;
+        let mut error = 1 - x;
+
+        while x >= y {
+            // Each computed point is mirrored into all eight octants
+            self.draw_pixel_signed(center_x + x, center_y + y, color);
+            self.draw_pixel_signed(center_x + y, center_y + x, color);
+            self.draw_pixel_signed(center_x - y, center_y + x, color);
+            self.draw_pixel_signed(center_x - x, center_y + y, color);
+            self.draw_pixel_signed(center_x - x, center_y - y, color);
+            self.draw_pixel_signed(center_x - y, center_y - x, color);
+            self.draw_pixel_signed(center_x + y, center_y - x, color);
+            self.draw_pixel_signed(center_x + x, center_y - y, color);
+
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
     pub fn draw_char(&self, x: u32, y: u32, fg_color: &Color, bg_color: &Color, c: char) -> bool {
         let mut glyph = BASIC_FONTS.get(c);
         if glyph.is_none() {
@@ -123,12 +212,29 @@ impl LFB {
         return false;
     }
 
+    fn draw_pixel_signed(&self, x: i64, y: i64, color: &Color) {
+        if x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64 {
+            self.draw_pixel(x as u32, y as u32, color);
+        }
+    }
+
     pub fn clear(&self) {
         unsafe {
             self.buffer.write_bytes(0, (self.pitch * self.height) as usize);
         }
     }
 
+    /// Set the position (in characters), at which the next character is written by `write!`.
+    pub fn set_cursor(&mut self, x: u32, y: u32) {
+        self.cursor_x = x;
+        self.cursor_y = y;
+    }
+
+    pub fn set_text_color(&mut self, fg_color: Color, bg_color: Color) {
+        self.fg_color = fg_color;
+        self.bg_color = bg_color;
+    }
+
     pub fn scroll_up(&self, lines: u32) {
         unsafe {
             // Move screen buffer upwards by the given amount of lines
@@ -140,6 +246,41 @@ impl LFB {
     }
 }
 
+// Allows writing formatted text directly to the framebuffer (e.g. for panics before the terminal is available).
+// Lines are wrapped at the right border and the screen is scrolled up, when the cursor reaches the bottom.
+impl fmt::Write for LFB {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let columns = self.width / CHAR_WIDTH;
+        let rows = self.height / CHAR_HEIGHT;
+        if columns == 0 || rows == 0 {
+            return Err(fmt::Error);
+        }
+
+        for c in s.chars() {
+            if c == '\n' || self.cursor_x >= columns {
+                self.cursor_x = 0;
+                self.cursor_y += 1;
+            }
+
+            if self.cursor_y >= rows {
+                self.scroll_up(CHAR_HEIGHT * (self.cursor_y - rows + 1));
+                self.cursor_y = rows - 1;
+            }
+
+            match c {
+                '\n' => {},
+                '\r' => self.cursor_x = 0,
+                _ => {
+                    self.draw_char(self.cursor_x * CHAR_WIDTH, self.cursor_y * CHAR_HEIGHT, &self.fg_color, &self.bg_color, c);
+                    self.cursor_x += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 type PixelDrawer = unsafe fn(addr: *mut u8, pitch: u32, x: u32, y: u32, color: &Color);
 
 fn draw_pixel_stub(addr: *mut u8, pitch: u32, x: u32, y: u32, color: &Color) {
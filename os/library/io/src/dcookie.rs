@@ -0,0 +1,7 @@
+use library_syscall::{syscall3, SystemCall};
+
+/// Copy the path of the binary registered under `cookie` (its load address) into `buffer`.
+/// Returns the length of the path, or a negative error code ('ResultOutOfRange', if `buffer` is too small).
+pub fn usr_lookup_dcookie(cookie: u64, buffer: &mut [u8]) -> isize {
+    syscall3(SystemCall::LookupDcookie as u64, cookie, buffer.as_mut_ptr() as u64, buffer.len() as u64) as isize
+}
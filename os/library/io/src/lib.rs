@@ -1,3 +1,6 @@
 #![no_std]
 
-pub mod stream;
\ No newline at end of file
+pub mod dcookie;
+pub mod random;
+pub mod stream;
+pub mod terminal;
@@ -0,0 +1,7 @@
+use library_syscall::{syscall3, SystemCall};
+
+/// Fill `buffer` with random bytes from the kernel's CSPRNG.
+/// Returns the number of bytes written, or a negative error code (e.g. 'TryAgain' with 'GRND_NONBLOCK').
+pub fn usr_get_random(buffer: &mut [u8], flags: u32) -> isize {
+    syscall3(SystemCall::GetRandom as u64, buffer.as_mut_ptr() as u64, buffer.len() as u64, flags as u64) as isize
+}
@@ -0,0 +1,15 @@
+use library_syscall::{syscall3, SystemCall, Termios, TCGETS, TCSETS};
+
+pub fn usr_ioctl(fd: i32, request: u64, arg: usize) -> i32 {
+    syscall3(SystemCall::Ioctl as u64, fd as u64, request, arg as u64) as i32
+}
+
+/// Read the line discipline settings of the terminal, referred to by `fd`.
+pub fn usr_tcgetattr(fd: i32, termios: &mut Termios) -> i32 {
+    usr_ioctl(fd, TCGETS, termios as *mut Termios as usize)
+}
+
+/// Change the line discipline settings (e.g. clear 'ICANON' and 'ECHO' for raw input).
+pub fn usr_tcsetattr(fd: i32, termios: &Termios) -> i32 {
+    usr_ioctl(fd, TCSETS, termios as *const Termios as usize)
+}
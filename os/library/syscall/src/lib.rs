@@ -1,7 +1,7 @@
 #![no_std]
 
 use core::arch::asm;
-use crate::SystemCall::ThreadExit;
+use crate::SystemCall::SetKeyboardLayout;
 
 #[repr(u8)]
 #[allow(dead_code)]
@@ -9,9 +9,408 @@ pub enum SystemCall {
     ThreadSwitch = 0,
     ThreadSleep = 1,
     ThreadExit = 2,
+    GetRusage = 3,
+    GetRandom = 4,
+    SchedGetAffinity = 5,
+    SchedSetAffinity = 6,
+    SchedYield = 7,
+    SetPgid = 8,
+    GetPgid = 9,
+    KillPg = 10,
+    TcSetPgrp = 11,
+    SetRlimit = 12,
+    GetRlimit = 13,
+    SetMempolicy = 14,
+    GetMempolicy = 15,
+    LookupDcookie = 16,
+    SigAction = 17,
+    SigReturn = 18,
+    Ioctl = 19,
+    Personality = 20,
+    Umask = 21,
+    Times = 22,
+    GetTimeOfDay = 23,
+    SchedSetScheduler = 24,
+    SchedGetScheduler = 25,
+    PkeyAlloc = 26,
+    PkeyMprotect = 27,
+    PkeyFree = 28,
+    SetPriority = 29,
+    Mmap = 30,
+    Munmap = 31,
+    ThreadJoin = 32,
+    GetErrno = 33,
+    ThreadYield = 34,
+    GetTid = 35,
+    GetPid = 36,
+    SetFsBase = 37,
+    GetMemInfo = 38,
+    SleepNs = 39,
+    KtraceEnable = 40,
+    ListModules = 41,
+    GetModule = 42,
+    Open = 43,
+    Read = 44,
+    Close = 45,
+    Write = 46,
+    SetWatchpoint = 47,
+    ClearWatchpoint = 48,
+    GetTime = 49,
+    Futex = 50,
+    ShmCreate = 51,
+    ShmAttach = 52,
+    ShmDetach = 53,
+    Pipe = 54,
+    ReadMouseEvent = 55,
+    Shutdown = 56,
+    Reboot = 57,
+    UefiGetVariable = 58,
+    UefiSetVariable = 59,
+    RequestIoPort = 60,
+    Exec = 61,
+    SetKeyboardLayout = 62,
 }
 
-pub const NUM_SYSCALLS: usize = ThreadExit as usize + 1;
+pub const NUM_SYSCALLS: usize = SetKeyboardLayout as usize + 1;
+
+/// Error codes, returned as negative values by system calls (values match Linux).
+#[repr(i32)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Errno {
+    OperationNotPermitted = 1,
+    NoSuchFile = 2,
+    NoSuchProcess = 3,
+    IoError = 5,
+    BadFileDescriptor = 9,
+    TryAgain = 11,
+    OutOfMemory = 12,
+    BadAddress = 14,
+    NoSuchDevice = 19,
+    NotADirectory = 20,
+    IsADirectory = 21,
+    InvalidArgument = 22,
+    TooManyOpenFiles = 24,
+    InappropriateIoctl = 25,
+    FileTooLarge = 27,
+    NoSpace = 28,
+    ReadOnlyFilesystem = 30,
+    BrokenPipe = 32,
+    ResultOutOfRange = 34,
+    Deadlock = 35,
+    TimedOut = 110,
+}
+
+/// Maximum length of a path, passed to the 'Open' system call (value matches Linux).
+pub const PATH_MAX: usize = 4096;
+
+/// Flag for the 'Open' system call: Create the file, if it does not exist (value matches Linux).
+pub const O_CREAT: u32 = 0o100;
+
+/// Conditions for the 'SetWatchpoint' system call (encoded like the debug register DR7).
+pub const WATCH_EXECUTE: u32 = 0b00;
+pub const WATCH_WRITE: u32 = 0b01;
+pub const WATCH_READ_WRITE: u32 = 0b11;
+
+/// Number of watchpoints per thread (hardware debug registers).
+pub const WATCHPOINT_COUNT: usize = 4;
+
+/// Operations for the 'Futex' system call (values match Linux).
+/// 'FUTEX_WAIT' blocks, while the 32-bit value at the given address equals the expected value,
+/// 'FUTEX_WAKE' wakes up to the given number of threads waiting on that address.
+pub const FUTEX_WAIT: u32 = 0;
+pub const FUTEX_WAKE: u32 = 1;
+
+/// Timeout for 'FUTEX_WAIT', that lets the thread wait until it is woken up.
+pub const FUTEX_WAIT_FOREVER: u64 = u64::MAX;
+
+/// Key for the 'ShmCreate' system call, that always creates a new region (value matches 'IPC_PRIVATE' on Linux).
+pub const SHM_PRIVATE: u64 = 0;
+
+/// Buttons of a 'MouseEvent' (encoded like the first byte of a PS/2 mouse packet).
+pub const MOUSE_BUTTON_LEFT: u8 = 0x01;
+pub const MOUSE_BUTTON_RIGHT: u8 = 0x02;
+pub const MOUSE_BUTTON_MIDDLE: u8 = 0x04;
+
+/// Maximum size of a UEFI variable, that can be read or written with the 'UefiGetVariable'/'UefiSetVariable' system calls.
+pub const UEFI_VARIABLE_MAX_SIZE: usize = 64 * 1024;
+
+/// Maximum length of a UEFI variable name (in UCS-2 characters, without terminator).
+pub const UEFI_VARIABLE_NAME_MAX: usize = 1024;
+
+/// Attributes of a UEFI variable (values match the UEFI specification).
+pub const UEFI_VARIABLE_NON_VOLATILE: u32 = 0x01;
+pub const UEFI_VARIABLE_BOOTSERVICE_ACCESS: u32 = 0x02;
+pub const UEFI_VARIABLE_RUNTIME_ACCESS: u32 = 0x04;
+pub const UEFI_VARIABLE_APPEND_WRITE: u32 = 0x40;
+
+/// Clock ticks per second, used by the 'Times' system call (value matches Linux).
+pub const CLK_TCK: u64 = 100;
+
+pub const RUSAGE_SELF: i32 = 0;
+pub const RUSAGE_CHILDREN: i32 = -1;
+
+/// Resources for the 'SetRlimit' and 'GetRlimit' system calls (values match Linux).
+pub const RLIMIT_CPU: u32 = 0;
+pub const RLIMIT_STACK: u32 = 3;
+pub const RLIMIT_NPROC: u32 = 6;
+pub const RLIMIT_NOFILE: u32 = 7;
+pub const RLIMIT_AS: u32 = 9;
+pub const RLIM_NLIMITS: usize = 10;
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Soft (`cur`) and hard (`max`) limit of a resource. CPU time is given in seconds, sizes in bytes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RLimit {
+    pub cur: u64,
+    pub max: u64,
+}
+
+/// Modes for the 'SetMempolicy' and 'GetMempolicy' system calls (values match Linux).
+pub const MPOL_DEFAULT: u32 = 0;
+pub const MPOL_BIND: u32 = 2;
+pub const MPOL_INTERLEAVE: u32 = 3;
+
+/// Flags for the 'GetMempolicy' system call.
+pub const MPOL_F_NODE: u32 = 0x01;
+pub const MPOL_F_ADDR: u32 = 0x02;
+pub const MPOL_F_MEMS_ALLOWED: u32 = 0x04;
+
+/// Priority levels of normal threads for the 'SetPriority' system call. Higher levels are scheduled first.
+pub const PRIORITY_LEVELS: usize = 8;
+pub const DEFAULT_PRIORITY: u8 = 4;
+
+/// Policies for the 'SchedSetScheduler' and 'SchedGetScheduler' system calls (values match Linux).
+/// Real-time threads ('SCHED_FIFO', 'SCHED_RR') have a priority between 'SCHED_PRIORITY_MIN' and 'SCHED_PRIORITY_MAX' and always run before normal threads.
+pub const SCHED_OTHER: i32 = 0;
+pub const SCHED_FIFO: i32 = 1;
+pub const SCHED_RR: i32 = 2;
+pub const SCHED_PRIORITY_MIN: i32 = 1;
+pub const SCHED_PRIORITY_MAX: i32 = 99;
+
+/// Kernel subsystems, whose tracing can be enabled with the 'KtraceEnable' system call.
+pub const KTRACE_SCHEDULER: u32 = 0;
+pub const KTRACE_MEMORY: u32 = 1;
+pub const KTRACE_SYSCALL: u32 = 2;
+pub const KTRACE_INTERRUPT: u32 = 3;
+
+/// Flags for the 'Mmap' system call (values match Linux). All mappings are private and anonymous.
+pub const MAP_PRIVATE: u32 = 0x02;
+pub const MAP_FIXED: u32 = 0x10;
+pub const MAP_ANONYMOUS: u32 = 0x20;
+
+/// Access rights for the 'PkeyAlloc' system call (values match Linux).
+pub const PKEY_DISABLE_ACCESS: u32 = 0x01;
+pub const PKEY_DISABLE_WRITE: u32 = 0x02;
+
+/// Page protection for the 'PkeyMprotect' system call (values match Linux).
+pub const PROT_READ: u32 = 0x01;
+pub const PROT_WRITE: u32 = 0x02;
+pub const PROT_EXEC: u32 = 0x04;
+
+/// Signals, raised by CPU exceptions in user mode (values match Linux).
+pub const SIGILL: u32 = 4;
+pub const SIGTRAP: u32 = 5;
+pub const SIGBUS: u32 = 7;
+pub const SIGFPE: u32 = 8;
+pub const SIGSEGV: u32 = 11;
+pub const NSIG: usize = 32;
+
+/// Handler value for the default action (the exception is fatal).
+pub const SIG_DFL: usize = 0;
+
+/// Flags for 'SigAction' (values match Linux).
+/// 'SA_NODEFER' does not block the signal while its handler runs, 'SA_RESETHAND' restores the default action on delivery.
+pub const SA_NODEFER: u32 = 0x40000000;
+pub const SA_RESETHAND: u32 = 0x80000000;
+
+/// Flags for the 'Personality' system call (values match Linux).
+/// Passing 'PER_QUERY' returns the current personality without changing it.
+pub const PER_LINUX: u32 = 0x0000000;
+pub const UNAME26: u32 = 0x0020000;
+pub const ADDR_NO_RANDOMIZE: u32 = 0x0040000;
+pub const PER_QUERY: u32 = 0xffffffff;
+
+/// Clocks for the vDSO function at 'VDSO_CLOCK_GETTIME' (values match Linux).
+pub const CLOCK_REALTIME: u32 = 0;
+pub const CLOCK_MONOTONIC: u32 = 1;
+
+/// The vDSO is mapped at 'VDSO_ADDRESS' into every user address space. Its functions can be called directly:
+/// 'VDSO_CLOCK_GETTIME' as `extern "C" fn(clock: u32, time: *mut Timespec) -> i32`
+/// and 'VDSO_GETTIMEOFDAY' as `extern "C" fn(time: *mut Timeval, timezone: *mut Timezone) -> i32`.
+pub const VDSO_ADDRESS: u64 = 0x7fffffffd000;
+pub const VDSO_CLOCK_GETTIME: u64 = VDSO_ADDRESS;
+pub const VDSO_GETTIMEOFDAY: u64 = VDSO_ADDRESS + 0x80;
+
+/// Requests for the 'Ioctl' system call on terminals (values match Linux).
+pub const TCGETS: u64 = 0x5401;
+pub const TCSETS: u64 = 0x5402;
+
+/// Local mode flags of 'Termios' (values match Linux).
+pub const ISIG: u32 = 0o0000001;
+pub const ICANON: u32 = 0o0000002;
+pub const ECHO: u32 = 0o0000010;
+pub const ECHOE: u32 = 0o0000020;
+
+/// Indices of control characters in 'Termios' (values match Linux).
+pub const VINTR: usize = 0;
+pub const VERASE: usize = 2;
+pub const NCCS: usize = 19;
+
+/// Flags for the 'GetRandom' system call.
+/// Without 'GRND_NONBLOCK', the call blocks until the kernel's entropy pool has been seeded.
+/// 'GRND_RANDOM' is accepted for compatibility, but has no effect.
+pub const GRND_NONBLOCK: u32 = 0x01;
+pub const GRND_RANDOM: u32 = 0x02;
+
+/// Layouts for the 'SetKeyboardLayout' system call.
+pub const KEYBOARD_LAYOUT_US: u32 = 0;
+pub const KEYBOARD_LAYOUT_DE: u32 = 1;
+pub const KEYBOARD_LAYOUT_UK: u32 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Timeval {
+    pub tv_sec: i64,
+    pub tv_usec: i64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Timespec {
+    pub tv_sec: i64,
+    pub tv_nsec: i64,
+}
+
+/// Relative motion of the PS/2 mouse, as reported by the 'ReadMouseEvent' system call.
+/// `dx` grows to the right and `dy` grows upwards (like in the PS/2 packet). `buttons` contains the 'MOUSE_BUTTON_*' flags of all pressed buttons.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub buttons: u8,
+}
+
+/// Wall clock time, as reported by the 'GetTime' system call.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KernelTime {
+    pub seconds_since_epoch: i64,
+    pub nanoseconds: u32,
+}
+
+/// Time zone, as reported by the 'GetTimeOfDay' system call (always UTC).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Timezone {
+    pub minuteswest: i32,
+    pub dsttime: i32,
+}
+
+/// Resource usage of a thread, as reported by the 'GetRusage' system call.
+/// 'maxrss' is given in KiB.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Rusage {
+    pub utime: Timeval,
+    pub stime: Timeval,
+    pub maxrss: u64,
+    pub majflt: u64,
+    pub minflt: u64,
+    pub nvcsw: u64,
+    pub nivcsw: u64,
+}
+
+/// Action for a signal, registered with the 'SigAction' system call.
+/// `handler` is called as `extern "C" fn(signum: u32, frame: &mut SignalFrame)` and must finish with the 'SigReturn' system call.
+/// `mask` contains additional signals, that are blocked while the handler runs.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SigAction {
+    pub handler: usize,
+    pub mask: u64,
+    pub flags: u32,
+}
+
+/// Saved user state, pushed on the user stack before a signal handler is called.
+/// The 'SigReturn' system call restores it (including any modifications done by the handler)
+/// and resumes execution at `rip`, which is the faulting instruction by default.
+/// `registers` is indexed by the register numbers used in instruction encoding (rax = 0, ..., r15 = 15).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SignalFrame {
+    pub signum: u64,
+    pub error_code: u64,
+    pub fault_address: u64,
+    pub registers: [u64; 16],
+    pub rip: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub mask: u64,
+}
+
+/// Terminal settings, read and written with the 'Ioctl' system call ('TCGETS' and 'TCSETS'). The layout matches Linux.
+/// Only the local flags ('ISIG', 'ICANON', 'ECHO' and 'ECHOE') and the control characters 'VINTR' and 'VERASE' are used.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// Canonical mode with echo, Ctrl+C as interrupt and backspace as erase character.
+    pub const fn new() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = 0x03;
+        cc[VERASE] = 0x08;
+
+        Self { iflag: 0, oflag: 0, cflag: 0, lflag: ISIG | ICANON | ECHO | ECHOE, line: 0, cc }
+    }
+}
+
+/// Process times, as reported by the 'Times' system call, in clock ticks ('CLK_TCK' per second).
+/// `cutime` and `cstime` contain the times of all terminated child threads.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tms {
+    pub utime: i64,
+    pub stime: i64,
+    pub cutime: i64,
+    pub cstime: i64,
+}
+
+/// Physical memory statistics, as reported by the 'GetMemInfo' system call (in page frames of `frame_size` bytes).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemInfo {
+    pub total_frames: u64,
+    pub free_frames: u64,
+    pub allocated_frames: u64,
+    pub frame_size: u64,
+}
+
+/// Scheduling parameters for 'SchedSetScheduler'. Normal threads ('SCHED_OTHER') must use priority 0.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SchedParam {
+    pub sched_priority: i32,
+}
+
+impl RLimit {
+    pub const INFINITY: RLimit = RLimit { cur: RLIM_INFINITY, max: RLIM_INFINITY };
+}
+
+impl Timeval {
+    pub const fn from_ns(ns: u64) -> Self {
+        Self { tv_sec: (ns / 1000000000) as i64, tv_usec: ((ns % 1000000000) / 1000) as i64 }
+    }
+}
 
 #[inline(always)]
 pub fn syscall0(arg0: u64) -> u64 {
@@ -88,3 +487,70 @@ pub fn syscall3(arg0: u64, arg1: u64, arg2: u64, arg3: u64) -> u64 {
 
     return ret;
 }
+
+/// System calls with more than three parameters pass the fourth one in r10 (instead of rcx, which is overwritten by 'syscall').
+#[inline(always)]
+pub fn syscall4(arg0: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> u64 {
+    let ret: u64;
+
+    unsafe {
+        asm!(
+        "syscall",
+        inlateout("rax") arg0 => ret,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        out("rcx") _,
+        out("r11") _,
+        options(preserves_flags, nostack)
+        );
+    }
+
+    return ret;
+}
+
+#[inline(always)]
+pub fn syscall5(arg0: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> u64 {
+    let ret: u64;
+
+    unsafe {
+        asm!(
+        "syscall",
+        inlateout("rax") arg0 => ret,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        in("r8") arg5,
+        out("rcx") _,
+        out("r11") _,
+        options(preserves_flags, nostack)
+        );
+    }
+
+    return ret;
+}
+
+#[inline(always)]
+pub fn syscall6(arg0: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, arg6: u64) -> u64 {
+    let ret: u64;
+
+    unsafe {
+        asm!(
+        "syscall",
+        inlateout("rax") arg0 => ret,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        in("r8") arg5,
+        in("r9") arg6,
+        out("rcx") _,
+        out("r11") _,
+        options(preserves_flags, nostack)
+        );
+    }
+
+    return ret;
+}
@@ -1,6 +1,10 @@
 #![no_std]
 
-use library_syscall::{syscall0, syscall1, SystemCall};
+pub mod sync;
+
+use core::{mem, ptr};
+use core::sync::atomic::AtomicU32;
+use library_syscall::{syscall0, syscall1, syscall2, syscall3, syscall4, syscall5, syscall6, KernelTime, FUTEX_WAIT, FUTEX_WAKE, MemInfo, MouseEvent, RLimit, Rusage, SchedParam, SigAction, SystemCall, Timespec, Timeval, Timezone, Tms, VDSO_CLOCK_GETTIME};
 
 #[allow(dead_code)]
 pub fn usr_thread_switch() {
@@ -12,6 +16,334 @@ pub fn usr_thread_sleep(ms: usize) {
     syscall1(SystemCall::ThreadSleep as u64, ms as u64);
 }
 
+/// Sleep with nanosecond precision (falls back to timer ticks, if the kernel has no one-shot timer).
+#[allow(dead_code)]
+pub fn usr_sleep_ns(ns: u64) -> isize {
+    syscall1(SystemCall::SleepNs as u64, ns) as isize
+}
+
+/// Enable or disable tracing of a kernel subsystem ('KTRACE_*').
+#[allow(dead_code)]
+pub fn usr_ktrace_enable(subsystem: u32, enable: bool) -> isize {
+    syscall2(SystemCall::KtraceEnable as u64, subsystem as u64, enable as u64) as isize
+}
+
+/// Number of modules, that have been loaded by the bootloader.
+#[allow(dead_code)]
+pub fn usr_list_modules() -> isize {
+    syscall0(SystemCall::ListModules as u64) as isize
+}
+
+/// Copy (up to `buffer.len()` bytes of) the module with the given index into `buffer` and return its full size.
+#[allow(dead_code)]
+pub fn usr_get_module(index: usize, buffer: &mut [u8]) -> isize {
+    syscall3(SystemCall::GetModule as u64, index as u64, buffer.as_mut_ptr() as u64, buffer.len() as u64) as isize
+}
+
+/// Replace the image of the calling process with the module with the given index. Only returns on errors.
+#[allow(dead_code)]
+pub fn usr_exec(index: usize) -> isize {
+    syscall1(SystemCall::Exec as u64, index as u64) as isize
+}
+
+/// Open the file at the absolute `path` and return its file descriptor ('O_CREAT' in `flags` creates missing files).
+#[allow(dead_code)]
+pub fn usr_open(path: &str, flags: u32) -> isize {
+    syscall3(SystemCall::Open as u64, path.as_ptr() as u64, path.len() as u64, flags as u64) as isize
+}
+
+/// Read from an open file and return the number of bytes read (0 at the end of the file).
+#[allow(dead_code)]
+pub fn usr_read(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall3(SystemCall::Read as u64, fd as u64, buffer.as_mut_ptr() as u64, buffer.len() as u64) as isize
+}
+
+/// Write to an open file at its current position and return the number of bytes written.
+#[allow(dead_code)]
+pub fn usr_write(fd: usize, buffer: &[u8]) -> isize {
+    syscall3(SystemCall::Write as u64, fd as u64, buffer.as_ptr() as u64, buffer.len() as u64) as isize
+}
+
+/// Create a pipe and store the file descriptors of its read end in `fds[0]` and of its write end in `fds[1]`.
+#[allow(dead_code)]
+pub fn usr_pipe(fds: &mut [i32; 2]) -> isize {
+    syscall1(SystemCall::Pipe as u64, fds as *mut [i32; 2] as u64) as isize
+}
+
+#[allow(dead_code)]
+pub fn usr_close(fd: usize) -> isize {
+    syscall1(SystemCall::Close as u64, fd as u64) as isize
+}
+
+/// Watch `size` bytes (1, 2, 4 or 8) at `addr` with one of the 'WATCHPOINT_COUNT' debug registers ('WATCH_*' conditions).
+/// Hitting the watchpoint raises 'SIGTRAP' in the calling thread.
+#[allow(dead_code)]
+pub fn usr_set_watchpoint(index: usize, addr: usize, condition: u32, size: usize) -> isize {
+    syscall4(SystemCall::SetWatchpoint as u64, index as u64, addr as u64, condition as u64, size as u64) as isize
+}
+
+#[allow(dead_code)]
+pub fn usr_clear_watchpoint(index: usize) -> isize {
+    syscall1(SystemCall::ClearWatchpoint as u64, index as u64) as isize
+}
+
 pub fn usr_thread_exit() {
     syscall0(SystemCall::ThreadExit as u64);
 }
+
+/// Block until thread `tid` has exited. The thread must share the calling thread's address space.
+pub fn usr_thread_join(tid: usize) -> isize {
+    syscall1(SystemCall::ThreadJoin as u64, tid as u64) as isize
+}
+
+/// Give up the CPU to the next ready thread with at least the same priority, without losing the rest of the current quantum.
+/// Returns immediately, if no such thread is ready.
+pub fn usr_thread_yield() -> isize {
+    syscall0(SystemCall::ThreadYield as u64) as isize
+}
+
+/// Id of the calling thread.
+pub fn usr_get_tid() -> usize {
+    syscall0(SystemCall::GetTid as u64) as usize
+}
+
+/// Id of the calling thread's address space, which is shared by all threads of a process.
+pub fn usr_get_pid() -> usize {
+    syscall0(SystemCall::GetPid as u64) as usize
+}
+
+/// Set the FS segment base of the calling thread (e.g. to install a thread-local storage block).
+pub fn usr_set_fs_base(addr: usize) -> isize {
+    syscall1(SystemCall::SetFsBase as u64, addr as u64) as isize
+}
+
+/// Error code ('Errno') of the calling thread's last failed system call.
+pub fn usr_get_errno() -> i32 {
+    syscall0(SystemCall::GetErrno as u64) as i32
+}
+
+/// Yield the CPU to another ready thread. Returns immediately, if no other thread is ready.
+pub fn usr_sched_yield() -> i32 {
+    syscall0(SystemCall::SchedYield as u64) as i32
+}
+
+pub fn usr_get_rusage(who: i32, usage: &mut Rusage) -> i32 {
+    syscall2(SystemCall::GetRusage as u64, who as u64, usage as *mut Rusage as u64) as i32
+}
+
+/// Read the CPU affinity mask of thread `tid` (0 = calling thread) into a 'cpu_set_t' compatible buffer.
+/// Returns the number of bytes written on success.
+pub fn usr_sched_getaffinity(tid: usize, mask: &mut [u8]) -> isize {
+    syscall3(SystemCall::SchedGetAffinity as u64, tid as u64, mask.len() as u64, mask.as_mut_ptr() as u64) as isize
+}
+
+/// Set the CPU affinity mask of thread `tid` (0 = calling thread), which must belong to the calling process.
+pub fn usr_sched_setaffinity(tid: usize, mask: &[u8]) -> i32 {
+    syscall3(SystemCall::SchedSetAffinity as u64, tid as u64, mask.len() as u64, mask.as_ptr() as u64) as i32
+}
+
+/// Set the priority level (0 to 'PRIORITY_LEVELS' - 1) of the calling thread.
+pub fn usr_set_priority(priority: u8) -> i32 {
+    syscall1(SystemCall::SetPriority as u64, priority as u64) as i32
+}
+
+/// Set the scheduling policy ('SCHED_*') and priority of thread `tid` (0 = calling thread), which must belong to the calling process.
+pub fn usr_sched_setscheduler(tid: usize, policy: i32, param: &SchedParam) -> i32 {
+    syscall3(SystemCall::SchedSetScheduler as u64, tid as u64, policy as u64, param as *const SchedParam as u64) as i32
+}
+
+pub fn usr_sched_getscheduler(tid: usize) -> i32 {
+    syscall1(SystemCall::SchedGetScheduler as u64, tid as u64) as i32
+}
+
+/// Move thread `pid` (0 = calling thread) into process group `pgid` (0 = new group named after the thread).
+/// The thread must belong to the calling process.
+pub fn usr_setpgid(pid: usize, pgid: usize) -> i32 {
+    syscall2(SystemCall::SetPgid as u64, pid as u64, pgid as u64) as i32
+}
+
+pub fn usr_getpgid(pid: usize) -> i64 {
+    syscall1(SystemCall::GetPgid as u64, pid as u64) as i64
+}
+
+/// Kill all threads in process group `pgid` (0 = group of the calling thread).
+/// Fails, if the group contains threads of other processes.
+pub fn usr_killpg(pgid: usize) -> i32 {
+    syscall1(SystemCall::KillPg as u64, pgid as u64) as i32
+}
+
+/// Make `pgid` the foreground process group of the terminal (receives Ctrl+C).
+/// The group must contain a thread of the calling process.
+pub fn usr_tcsetpgrp(fd: i32, pgid: usize) -> i32 {
+    syscall2(SystemCall::TcSetPgrp as u64, fd as u64, pgid as u64) as i32
+}
+
+pub fn usr_setrlimit(resource: u32, limit: &RLimit) -> i32 {
+    syscall2(SystemCall::SetRlimit as u64, resource as u64, limit as *const RLimit as u64) as i32
+}
+
+pub fn usr_getrlimit(resource: u32, limit: &mut RLimit) -> i32 {
+    syscall2(SystemCall::GetRlimit as u64, resource as u64, limit as *mut RLimit as u64) as i32
+}
+
+/// Set the NUMA memory policy of the calling thread. `nodemask` is a bitmap of node ids (ignored for 'MPOL_DEFAULT').
+pub fn usr_set_mempolicy(mode: u32, nodemask: &[u64]) -> i32 {
+    syscall3(SystemCall::SetMempolicy as u64, mode as u64, nodemask.as_ptr() as u64, (nodemask.len() * 64) as u64) as i32
+}
+
+pub fn usr_get_mempolicy(mode: &mut u32, nodemask: &mut [u64], addr: usize, flags: u32) -> i32 {
+    syscall5(SystemCall::GetMempolicy as u64, mode as *mut u32 as u64, nodemask.as_mut_ptr() as u64, (nodemask.len() * 64) as u64, addr as u64, flags as u64) as i32
+}
+
+/// Map `length` bytes of zeroed memory and return its address (or a negative error code).
+/// Without 'MAP_FIXED', `addr` is only a hint and may be 0.
+pub fn usr_mmap(addr: usize, length: usize, flags: u32) -> isize {
+    syscall3(SystemCall::Mmap as u64, addr as u64, length as u64, flags as u64) as isize
+}
+
+pub fn usr_munmap(addr: usize, length: usize) -> isize {
+    syscall2(SystemCall::Munmap as u64, addr as u64, length as u64) as isize
+}
+
+/// Allocate a protection key with initial `access_rights` ('PKEY_DISABLE_*') for the calling thread.
+pub fn usr_pkey_alloc(flags: u32, access_rights: u32) -> i32 {
+    syscall2(SystemCall::PkeyAlloc as u64, flags as u64, access_rights as u64) as i32
+}
+
+/// Set the protection ('PROT_*') and protection key of all pages in `[addr, addr + len)`.
+pub fn usr_pkey_mprotect(addr: usize, len: usize, prot: u32, pkey: i32) -> i32 {
+    syscall4(SystemCall::PkeyMprotect as u64, addr as u64, len as u64, prot as u64, pkey as u64) as i32
+}
+
+pub fn usr_pkey_free(pkey: i32) -> i32 {
+    syscall1(SystemCall::PkeyFree as u64, pkey as u64) as i32
+}
+
+/// Register `action` for `signum` and optionally return the previous action.
+pub fn usr_sigaction(signum: u32, action: Option<&SigAction>, old_action: Option<&mut SigAction>) -> i32 {
+    let action = action.map_or(ptr::null(), |action| action as *const SigAction);
+    let old_action = old_action.map_or(ptr::null_mut(), |old_action| old_action as *mut SigAction);
+    syscall3(SystemCall::SigAction as u64, signum as u64, action as u64, old_action as u64) as i32
+}
+
+/// Return from a signal handler and resume with the (possibly modified) state in the signal frame.
+pub fn usr_sigreturn() -> i32 {
+    syscall0(SystemCall::SigReturn as u64) as i32
+}
+
+/// Set the personality of the calling thread ('PER_QUERY' only reads it). Returns the previous personality.
+pub fn usr_personality(persona: u32) -> i64 {
+    syscall1(SystemCall::Personality as u64, persona as u64) as i64
+}
+
+/// Set the file creation mask of the calling thread and return the previous one.
+pub fn usr_umask(mask: u32) -> u32 {
+    syscall1(SystemCall::Umask as u64, mask as u64) as u32
+}
+
+/// Get the CPU times of the calling thread and its terminated children. Returns the elapsed clock ticks since boot.
+pub fn usr_times(times: &mut Tms) -> i64 {
+    syscall1(SystemCall::Times as u64, times as *mut Tms as u64) as i64
+}
+
+pub fn usr_mem_info(info: &mut MemInfo) -> i32 {
+    syscall1(SystemCall::GetMemInfo as u64, info as *mut MemInfo as u64) as i32
+}
+
+/// Get the current wall clock time (and time zone, which is always UTC).
+pub fn usr_gettimeofday(time: Option<&mut Timeval>, timezone: Option<&mut Timezone>) -> i32 {
+    let time = time.map_or(ptr::null_mut(), |time| time as *mut Timeval);
+    let timezone = timezone.map_or(ptr::null_mut(), |timezone| timezone as *mut Timezone);
+    syscall2(SystemCall::GetTimeOfDay as u64, time as u64, timezone as u64) as i32
+}
+
+/// Get the wall clock time from the firmware (EFI runtime services or real time clock).
+#[allow(dead_code)]
+pub fn usr_get_time(time: &mut KernelTime) -> isize {
+    syscall1(SystemCall::GetTime as u64, time as *mut KernelTime as u64) as isize
+}
+
+/// Block, while the value at `addr` equals `expected`, until another thread calls 'usr_futex_wake()' on `addr`
+/// or `timeout_ns` nanoseconds have passed ('FUTEX_WAIT_FOREVER' = no timeout).
+#[allow(dead_code)]
+pub fn usr_futex_wait(addr: &AtomicU32, expected: u32, timeout_ns: u64) -> isize {
+    syscall4(SystemCall::Futex as u64, addr.as_ptr() as u64, FUTEX_WAIT as u64, expected as u64, timeout_ns) as isize
+}
+
+/// Wake up to `count` threads waiting on `addr` and return how many threads have been woken up.
+#[allow(dead_code)]
+pub fn usr_futex_wake(addr: &AtomicU32, count: u32) -> isize {
+    syscall3(SystemCall::Futex as u64, addr.as_ptr() as u64, FUTEX_WAKE as u64, count as u64) as isize
+}
+
+/// Create a shared memory region of at least `size` bytes and return its handle.
+/// Regions with the same `key` are shared ('SHM_PRIVATE' always creates a new region).
+#[allow(dead_code)]
+pub fn usr_shm_create(size: usize, key: u64) -> isize {
+    syscall2(SystemCall::ShmCreate as u64, size as u64, key) as isize
+}
+
+/// Map a shared memory region at `addr` (0 = chosen by the kernel) and return its address.
+#[allow(dead_code)]
+pub fn usr_shm_attach(handle: isize, addr: usize) -> isize {
+    syscall2(SystemCall::ShmAttach as u64, handle as u64, addr as u64) as isize
+}
+
+/// Unmap a shared memory region. It is freed, when the last attachment has been detached.
+#[allow(dead_code)]
+pub fn usr_shm_detach(addr: usize) -> isize {
+    syscall1(SystemCall::ShmDetach as u64, addr as u64) as isize
+}
+
+/// Take the oldest event of the PS/2 mouse. Returns 1, if `event` has been filled, and 0, if no event is available.
+#[allow(dead_code)]
+pub fn usr_read_mouse_event(event: &mut MouseEvent) -> isize {
+    syscall1(SystemCall::ReadMouseEvent as u64, event as *mut MouseEvent as u64) as isize
+}
+
+/// Power off the system (does not return). `code` is logged by the kernel.
+#[allow(dead_code)]
+pub fn usr_shutdown(code: i32) -> ! {
+    syscall1(SystemCall::Shutdown as u64, code as u64);
+    unreachable!("Shutdown has returned!");
+}
+
+/// Restart the system (does not return).
+#[allow(dead_code)]
+pub fn usr_reboot() -> ! {
+    syscall0(SystemCall::Reboot as u64);
+    unreachable!("Reboot has returned!");
+}
+
+/// Read the UEFI variable `name` of the vendor `vendor_guid` into `buf`. Returns the number of bytes read.
+/// `size` receives the size of the variable (also, if `buf` is too small).
+#[allow(dead_code)]
+pub fn usr_uefi_get_variable(name: &[u16], vendor_guid: &[u8; 16], buf: &mut [u8], size: &mut usize) -> isize {
+    *size = buf.len();
+    syscall5(SystemCall::UefiGetVariable as u64, name.as_ptr() as u64, name.len() as u64, vendor_guid as *const [u8; 16] as u64, buf.as_mut_ptr() as u64, size as *mut usize as u64) as isize
+}
+
+/// Write `data` to the UEFI variable `name` of the vendor `vendor_guid` ('UEFI_VARIABLE_*' `attributes`).
+#[allow(dead_code)]
+pub fn usr_uefi_set_variable(name: &[u16], vendor_guid: &[u8; 16], attributes: u32, data: &[u8]) -> isize {
+    syscall6(SystemCall::UefiSetVariable as u64, name.as_ptr() as u64, name.len() as u64, vendor_guid as *const [u8; 16] as u64, attributes as u64, data.as_ptr() as u64, data.len() as u64) as isize
+}
+
+/// Allow direct access to the I/O port `port` (only for processes started by the kernel, see 'sys_request_ioport()').
+#[allow(dead_code)]
+pub fn usr_request_ioport(port: u16) -> isize {
+    syscall1(SystemCall::RequestIoPort as u64, port as u64) as isize
+}
+
+/// Switch the keyboard layout ('KEYBOARD_LAYOUT_*').
+#[allow(dead_code)]
+pub fn usr_set_keyboard_layout(id: u32) -> isize {
+    syscall1(SystemCall::SetKeyboardLayout as u64, id as u64) as isize
+}
+
+/// Read `clock` ('CLOCK_REALTIME' or 'CLOCK_MONOTONIC') via the vDSO, without entering the kernel.
+/// Only available in user threads, since the vDSO is not mapped into the kernel address space.
+pub fn usr_clock_gettime(clock: u32, time: &mut Timespec) -> i32 {
+    let clock_gettime: extern "C" fn(u32, *mut Timespec) -> i32 = unsafe { mem::transmute(VDSO_CLOCK_GETTIME as usize) };
+    clock_gettime(clock, time)
+}
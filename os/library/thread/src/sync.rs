@@ -0,0 +1,114 @@
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use library_syscall::{Errno, FUTEX_WAIT_FOREVER};
+use crate::{usr_futex_wait, usr_futex_wake};
+
+const UNLOCKED: u32 = 0;
+/// Locked without waiting threads, so unlocking does not need a system call
+const LOCKED: u32 = 1;
+/// Locked with (possibly) waiting threads, which must be woken up when unlocking
+const CONTENDED: u32 = 2;
+
+/// Mutual exclusion for user threads. Waiting threads block on a futex instead of spinning.
+pub struct Mutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+/// Unlocks the mutex, when dropped.
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self { state: AtomicU32::new(UNLOCKED), data: UnsafeCell::new(data) }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+            // Marking the mutex as contended makes the owner wake us up, before we go to sleep
+            while self.state.swap(CONTENDED, Acquire) != UNLOCKED {
+                usr_futex_wait(&self.state, CONTENDED, FUTEX_WAIT_FOREVER);
+            }
+        }
+
+        return MutexGuard { mutex: self };
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        return match self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed) {
+            Ok(_) => Some(MutexGuard { mutex: self }),
+            Err(_) => None
+        };
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Release) == CONTENDED {
+            usr_futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        return unsafe { &*self.mutex.data.get() };
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        return unsafe { &mut *self.mutex.data.get() };
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+/// Condition variable for user threads, used together with a 'Mutex'.
+/// Notifications increment a sequence number, so that a thread cannot miss a notification
+/// between unlocking the mutex and blocking on the futex. Spurious wakeups are possible, so conditions must be checked in a loop.
+pub struct Condvar {
+    sequence: AtomicU32
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self { sequence: AtomicU32::new(0) }
+    }
+
+    /// Unlock the mutex of `guard`, block until the condition variable is notified and lock the mutex again.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        return self.wait_timeout(guard, FUTEX_WAIT_FOREVER).0;
+    }
+
+    /// Like 'wait()', but gives up after `timeout_ns` nanoseconds. Returns the guard and whether the timeout has expired.
+    pub fn wait_timeout<'a, T>(&self, guard: MutexGuard<'a, T>, timeout_ns: u64) -> (MutexGuard<'a, T>, bool) {
+        let sequence = self.sequence.load(Relaxed);
+        let mutex = guard.mutex;
+        drop(guard);
+
+        let timed_out = usr_futex_wait(&self.sequence, sequence, timeout_ns) == -(Errno::TimedOut as isize);
+        return (mutex.lock(), timed_out);
+    }
+
+    pub fn notify_one(&self) {
+        self.sequence.fetch_add(1, Release);
+        usr_futex_wake(&self.sequence, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.sequence.fetch_add(1, Release);
+        usr_futex_wake(&self.sequence, u32::MAX);
+    }
+}